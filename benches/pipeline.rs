@@ -9,8 +9,9 @@ use std::hint::black_box;
 use tempfile::TempDir;
 
 use tool_executor::config::model::{
-    Config, Defaults, EnvVar, FeatureConfig, ProfileConfig, ProviderConfig, SearchMode, Snippet,
-    SnippetConfig, StdinMapping, StdinMode, WrapperConfig, WrapperMode,
+    Config, Defaults, EnvVar, FeatureConfig, HooksConfig, ProfileConfig, ProviderConfig, RagConfig,
+    RetentionConfig, SearchFormat, SearchMode, Snippet, SnippetConfig, StdinMapping, StdinMode,
+    WrapperConfig, WrapperMode,
 };
 use tool_executor::db::Database;
 use tool_executor::indexer::Indexer;
@@ -33,6 +34,8 @@ fn sample_config_with_root(root: PathBuf) -> Config {
                 key: "CODEX_TOKEN".into(),
                 value_template: "bench-token".into(),
             }],
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: vec![root],
             stdin: Some(StdinMapping {
                 args: vec!["--prompt".into()],
@@ -80,11 +83,18 @@ fn sample_config_with_root(root: PathBuf) -> Config {
         "default".into(),
         ProfileConfig {
             name: "default".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: Some("Default codex profile".into()),
             pre: vec!["lint".into()],
             post: vec!["notify".into()],
-            wrap: Some("shellwrap".into()),
+            wrap: vec!["shellwrap".into()],
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -95,6 +105,7 @@ fn sample_config_with_root(root: PathBuf) -> Config {
             provider: Some("codex".into()),
             profile: Some("default".into()),
             search_mode: SearchMode::FirstPrompt,
+            search_format: SearchFormat::Json,
             terminal_title: None,
         },
         providers,
@@ -103,7 +114,11 @@ fn sample_config_with_root(root: PathBuf) -> Config {
         profiles,
         features: FeatureConfig {
             prompt_assembler: None,
+            rag: RagConfig::default(),
         },
+        retention: RetentionConfig::default(),
+        secrets: IndexMap::new(),
+        hooks: HooksConfig::default(),
     }
 }
 
@@ -115,7 +130,7 @@ fn sample_request(config: &Config) -> PipelineRequest<'_> {
         additional_pre: vec![],
         additional_post: vec![],
         inline_pre: vec!["echo inline".into()],
-        wrap: None,
+        wrap: Vec::new(),
         provider_args: vec!["--color".into()],
         capture_prompt: false,
         prompt_assembler: None,
@@ -128,6 +143,7 @@ fn sample_request(config: &Config) -> PipelineRequest<'_> {
             label: Some("Demo Session".into()),
             path: Some("/tmp/sessions/sess-123.jsonl".into()),
             resume_token: Some("resume-token".into()),
+            thread_name: None,
         },
         cwd: PathBuf::from("/tmp/project"),
     }
@@ -142,7 +158,7 @@ fn bench_build_pipeline(c: &mut Criterion) {
     });
 
     let mut wrapped_request = sample_request(&config);
-    wrapped_request.wrap = Some("shellwrap");
+    wrapped_request.wrap = vec!["shellwrap".to_string()];
     wrapped_request.capture_prompt = true;
     c.bench_function("build_pipeline_wrapped_capture", |b| {
         b.iter(|| build_pipeline(black_box(&wrapped_request)).expect("pipeline"));
@@ -275,10 +291,133 @@ fn bench_indexer_runs(c: &mut Criterion) {
     });
 }
 
+const STORAGE_SESSIONS: usize = 200;
+
+/// A session shaped like a real agent run: a short prompt, a large tool output,
+/// and a short reply.
+fn storage_ingest(index: usize) -> SessionIngest {
+    let id = format!("codex/storage-{index}");
+    let summary = SessionSummary {
+        id: id.clone(),
+        provider: "codex".into(),
+        wrapper: None,
+        model: None,
+        label: Some(format!("Storage {index}")),
+        thread_name: None,
+        path: PathBuf::from(format!("/tmp/sessions/storage-{index}.jsonl")),
+        uuid: None,
+        first_prompt: Some(format!("Investigate flaky test {index}")),
+        actionable: true,
+        subagent: false,
+        created_at: Some(1),
+        started_at: Some(1),
+        last_active: Some(i64::try_from(index).unwrap_or(i64::MAX)),
+        size: 42,
+        mtime: 2,
+    };
+    let tool_output = (0..200)
+        .map(|line| {
+            format!(
+                "test suite::case_{line} ... ok ({index}.{line}ms) worker={}",
+                line % 8
+            )
+        })
+        .chain(std::iter::once(format!(
+            "error: flaky retry timeout in case {index}"
+        )))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let messages = vec![
+        MessageRecord::new(
+            &id,
+            0,
+            "user",
+            format!("Investigate flaky test {index}"),
+            None,
+            Some(1),
+        ),
+        MessageRecord::new(&id, 1, "tool", tool_output, None, Some(2)),
+        MessageRecord::new(
+            &id,
+            2,
+            "assistant",
+            "The retry timeout is too short.",
+            None,
+            Some(3),
+        ),
+    ];
+    SessionIngest::new(summary, messages)
+}
+
+fn build_storage_db() -> (TempDir, Database) {
+    let temp = TempDir::new().expect("temp dir");
+    let mut db = Database::open(&temp.path().join("tx.sqlite3")).expect("db open");
+    for index in 0..STORAGE_SESSIONS {
+        db.upsert_session(&storage_ingest(index)).expect("upsert");
+    }
+    (temp, db)
+}
+
+fn bench_storage(c: &mut Criterion) {
+    let (_temp, db) = build_storage_db();
+    let text_bytes: usize = (0..STORAGE_SESSIONS)
+        .flat_map(|index| storage_ingest(index).messages)
+        .map(|message| message.content.len())
+        .sum();
+    let stats = db.stats().expect("db stats");
+    let table_bytes = |name: &str| {
+        stats
+            .tables()
+            .into_iter()
+            .find(|table| table.name == name)
+            .map_or(0, |table| table.bytes)
+    };
+    // Criterion only measures time, so report the storage footprint alongside it.
+    println!(
+        "storage: {STORAGE_SESSIONS} sessions, {text_bytes} bytes of message text, \
+         database {} bytes (message_store {} bytes, messages_fts {} bytes)",
+        stats.page_size * stats.page_count,
+        table_bytes("message_store"),
+        table_bytes("messages_fts"),
+    );
+
+    c.bench_function("storage_ingest_sessions", |b| {
+        b.iter_batched(
+            || {
+                let temp = TempDir::new().expect("temp dir");
+                let db = Database::open(&temp.path().join("tx.sqlite3")).expect("db open");
+                (temp, db)
+            },
+            |(temp, mut db)| {
+                let _hold = temp;
+                for index in 0..20 {
+                    db.upsert_session(&storage_ingest(index)).expect("upsert");
+                }
+            },
+            BatchSize::SmallInput,
+        );
+    });
+
+    c.bench_function("storage_search_full_text", |b| {
+        b.iter(|| {
+            db.search_full_text(black_box("timeout"), None, false)
+                .expect("search")
+        });
+    });
+
+    c.bench_function("storage_fetch_transcript", |b| {
+        b.iter(|| {
+            db.fetch_transcript(black_box("codex/storage-7"))
+                .expect("fetch")
+        });
+    });
+}
+
 criterion_group!(
     pipeline,
     bench_build_pipeline,
     bench_db_upsert,
-    bench_indexer_runs
+    bench_indexer_runs,
+    bench_storage
 );
 criterion_main!(pipeline);
@@ -1,38 +1,48 @@
 use std::collections::HashMap;
 use std::ffi::OsString;
-#[cfg(any(test, not(coverage)))]
+use std::fs;
 use std::io::BufRead;
 use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitStatus};
 
 use color_eyre::Result;
 use color_eyre::eyre::{WrapErr, eyre};
 use regex::Regex;
 use serde_json::json;
 use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 use time::OffsetDateTime;
 use time::format_description::well_known::Rfc3339;
 use tracing::debug;
 use which::which;
 
 use crate::cli::{
-    Cli, ConfigCommand, ConfigDefaultCommand, ConfigSchemaCommand, ExportCommand,
-    InternalPromptAssemblerCommand, RagCommand, RagIndexCommand, RagSearchCommand, ResumeCommand,
-    SearchCommand, SelfUpdateCommand, StatsCommand,
+    Cli, ConfigCommand, ConfigDefaultCommand, ConfigDumpCommand, ConfigSchemaCommand, ExecCommand,
+    ExportCommand, HistoryCommand, InternalPromptAssemblerCommand, PruneCommand, RagCommand,
+    RagIndexCommand, RagModelsCommand, RagSearchCommand, RagStatusCommand, RagTopicsCommand,
+    RelatedCommand, RerunCommand, ResumeCommand, SearchCommand, SelfUpdateCommand, StatsCommand,
+};
+use crate::commands::search::{self, SearchColumn, SearchRenderOptions, SearchRow};
+use crate::commands::{exec, history, prune, stats};
+use crate::config::model::{
+    Config, DiagnosticLevel, ProfileVar, PromptAssemblerConfig, SecretConfig, SecretSource,
 };
-use crate::commands::stats;
-use crate::config::model::{DiagnosticLevel, PromptAssemblerConfig};
 use crate::config::{ConfigSourceKind, LoadedConfig};
-use crate::db::Database;
-use crate::indexer::{IndexError, IndexReport, Indexer};
+use crate::db::{Database, INDEX_LEASE, NewRun, RAG_AUTO_INDEX_LEASE, RagAutoIndexRun};
+use crate::indexer::{self, IndexError, IndexReport, Indexer};
 use crate::internal::assemble_prompt;
 use crate::pipeline::{
-    Invocation, PipelinePlan, PipelineRequest, PromptInvocation, SessionContext, build_pipeline,
+    Invocation, LaunchOptions, PLAN_SCHEMA_VERSION, PipelinePlan, PipelineRequest,
+    PromptInvocation, SessionContext, build_pipeline, launch_argv, missing_vars, write_env_file,
 };
 use crate::prompts::{PromptAssembler, PromptStatus};
 use crate::providers;
-use crate::rag::{OpenAIEmbeddingProvider, RagIndexOptions, index_history, search_history};
+use crate::rag::{
+    EmbeddingProvider, OpenAIEmbeddingProvider, RagIndexOptions, RagStatusCounts, configured_model,
+    format_context_block, index_history, index_status, related_sessions, search_history,
+    search_model, topic_clusters,
+};
 use crate::session::{SearchHit, SessionSummary, Transcript, is_subagent_job_session_texts};
 use crate::tui;
 use crate::util;
@@ -73,9 +83,11 @@ impl<'cli> App<'cli> {
         let mut db = Database::open(&db_path)?;
 
         if std::env::var_os("TX_SKIP_INDEX").is_none() {
-            let mut indexer = Indexer::new(&mut db, &loaded.config);
-            let index_report = indexer.run()?;
-            log_index_report(&index_report);
+            let updated = index_sessions(&mut db, &loaded.config)?;
+            if loaded.config.features.rag.auto_index {
+                let now_ms = util::unix_timestamp().saturating_mul(1000);
+                db.queue_rag_auto_index(&updated, now_ms)?;
+            }
         } else {
             tracing::debug!("skipping indexer run due to TX_SKIP_INDEX");
         }
@@ -95,12 +107,60 @@ impl<'cli> App<'cli> {
         })
     }
 
+    /// Embed the messages of sessions index runs added or changed.
+    ///
+    /// Called after a successful command when `[features.rag] auto_index` is
+    /// enabled; does nothing when no session is waiting. Sessions stay queued
+    /// until embedded, so work skipped here resumes on a later command.
+    /// Failures are recorded for `tx rag status` and never fail the command.
+    pub fn auto_index(&mut self) {
+        let rag = self.loaded.config.features.rag;
+        if !rag.auto_index
+            || self
+                .db
+                .rag_auto_index_queue()
+                .map_or(true, |queue| queue.is_empty())
+        {
+            return;
+        }
+        match self
+            .db
+            .try_acquire_lease(RAG_AUTO_INDEX_LEASE, &command_label(), LEASE_TTL)
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                debug!("another tx process is embedding messages; skipping semantic auto-index");
+                return;
+            }
+            Err(err) => {
+                debug!(error = ?err, "semantic auto-index could not take its lease");
+                return;
+            }
+        }
+        let now_ms = util::unix_timestamp().saturating_mul(1000);
+        let outcome =
+            crate::rag::auto_index(&mut self.db, rag, now_ms, || Self::rag_provider(None));
+        if let Err(err) = self.db.release_lease(RAG_AUTO_INDEX_LEASE) {
+            debug!(error = ?err, "semantic auto-index could not release its lease");
+        }
+        match outcome {
+            Ok(Some(run)) => debug!(
+                embedded = run.embedded,
+                deferred = run.deferred,
+                error = run.error.as_deref(),
+                "semantic auto-index complete"
+            ),
+            Ok(None) => {}
+            Err(err) => debug!(error = ?err, "semantic auto-index could not record its run"),
+        }
+    }
+
     /// Execute a sessions search (first prompt or full-text) depending on flags.
     ///
     /// # Errors
     ///
     /// Returns an error if the database search fails or the results cannot be
-    /// serialized to JSON.
+    /// rendered in the requested format.
     pub fn search(&self, cmd: &SearchCommand) -> Result<()> {
         let since_epoch = cmd
             .since
@@ -117,11 +177,23 @@ impl<'cli> App<'cli> {
             ));
         }
 
+        let columns = if cmd.columns.is_empty() {
+            SearchColumn::DEFAULTS.to_vec()
+        } else {
+            cmd.columns.clone()
+        };
+        let render_options = SearchRenderOptions {
+            format: cmd
+                .format
+                .unwrap_or(self.loaded.config.defaults.search_format),
+            columns: &columns,
+            width: search::output_width(),
+        };
+
         if term.is_none() {
-            let mut payload = Vec::new();
+            let mut rows = Vec::new();
             if cmd.limit == Some(0) {
-                println!("{}", serde_json::to_string_pretty(&payload)?);
-                return Ok(());
+                return search::print_results(&rows, &render_options);
             }
 
             self.db
@@ -131,17 +203,16 @@ impl<'cli> App<'cli> {
                     if !summary.subagent
                         && !is_subagent_job_session_texts(summary.first_prompt.as_deref(), None)
                     {
-                        payload.push(summary_to_json(
-                            &summary,
-                            summary.first_prompt.as_deref(),
-                            None,
-                        ));
+                        rows.push(SearchRow {
+                            snippet: summary.first_prompt.clone(),
+                            snippet_role: None,
+                            summary,
+                        });
                     }
-                    Ok(cmd.limit.is_none_or(|limit| payload.len() < limit))
+                    Ok(cmd.limit.is_none_or(|limit| rows.len() < limit))
                 })?;
 
-            println!("{}", serde_json::to_string_pretty(&payload)?);
-            return Ok(());
+            return search::print_results(&rows, &render_options);
         }
 
         let term = term.unwrap();
@@ -158,16 +229,15 @@ impl<'cli> App<'cli> {
         let detailed =
             self.collate_search_results_for_command(hits, since_epoch, role_filter, cmd.limit)?;
 
-        let payload: Vec<_> = detailed
-            .iter()
-            .map(|(hit, summary)| {
-                let snippet = hit.snippet.as_deref().or(summary.first_prompt.as_deref());
-                let snippet_role = hit.role.as_deref();
-                summary_to_json(summary, snippet, snippet_role)
+        let rows: Vec<_> = detailed
+            .into_iter()
+            .map(|(hit, summary)| SearchRow {
+                snippet: hit.snippet.or_else(|| summary.first_prompt.clone()),
+                snippet_role: hit.role,
+                summary,
             })
             .collect();
-        println!("{}", serde_json::to_string_pretty(&payload)?);
-        Ok(())
+        search::print_results(&rows, &render_options)
     }
 
     /// Execute semantic-index commands.
@@ -180,21 +250,53 @@ impl<'cli> App<'cli> {
         match cmd {
             RagCommand::Index(cmd) => self.rag_index(cmd),
             RagCommand::Search(cmd) => self.rag_search(cmd),
+            RagCommand::Models(cmd) => self.rag_models(cmd),
+            RagCommand::Status(cmd) => self.rag_status(cmd),
+            RagCommand::Topics(cmd) => self.rag_topics(cmd),
         }
     }
 
-    fn rag_index(&mut self, cmd: &RagIndexCommand) -> Result<()> {
+    fn rag_provider(model: Option<&str>) -> Result<OpenAIEmbeddingProvider> {
         let provider = OpenAIEmbeddingProvider::from_env()?;
+        match model {
+            Some(model) => provider.with_model(model),
+            None => Ok(provider),
+        }
+    }
+
+    /// Like [`Self::rag_provider`], but without an explicit model it searches the
+    /// [fallback model](search_model) while the configured one has no index.
+    fn rag_search_provider(&self, model: Option<&str>) -> Result<OpenAIEmbeddingProvider> {
+        let provider = Self::rag_provider(model)?;
+        if model.is_some() {
+            return Ok(provider);
+        }
+        let searched = search_model(&self.db, provider.model_name())?;
+        if searched == provider.model_name() {
+            return Ok(provider);
+        }
+        debug!(
+            configured = provider.model_name(),
+            searched = %searched,
+            "configured embedding model has no index yet; searching the latest indexed model"
+        );
+        provider.with_model(&searched)
+    }
+
+    fn rag_index(&mut self, cmd: &RagIndexCommand) -> Result<()> {
+        let provider = Self::rag_provider(cmd.model.as_deref())?;
         let options = RagIndexOptions {
             session_id: cmd.session.clone(),
             since_ts_ms: cmd.since,
             reindex: cmd.reindex,
             batch_size: cmd.batch_size,
+            chunking: self.loaded.config.features.rag.into(),
+            max_chunks: None,
         };
         let report = index_history(&mut self.db, &provider, &options)?;
         println!(
-            "rag index complete: scanned={}, embedded={}, skipped={}, deleted={}",
-            report.scanned, report.embedded, report.skipped, report.deleted
+            "rag index complete: scanned={}, embedded={}, reused={}, skipped={}, deleted={}",
+            report.scanned, report.embedded, report.reused, report.skipped, report.deleted
         );
         Ok(())
     }
@@ -206,9 +308,10 @@ impl<'cli> App<'cli> {
             return Err(eyre!("--since must be <= --until"));
         }
 
-        let provider = OpenAIEmbeddingProvider::from_env()?;
+        let provider = self.rag_search_provider(cmd.model.as_deref())?;
         let filters = crate::db::RagSearchFilters {
             session_id: cmd.session.clone(),
+            exclude_session_id: None,
             tool_name: cmd.tool.clone(),
             since_ts_ms: cmd.since,
             until_ts_ms: cmd.until,
@@ -229,6 +332,11 @@ impl<'cli> App<'cli> {
                         "tool_name": hit.tool_name,
                         "kind": hit.kind,
                         "chunk_id": hit.chunk_id,
+                        "chunk_ordinal": hit.chunk_ordinal,
+                        "span": {
+                            "start": hit.span_start,
+                            "end": hit.span_end,
+                        },
                         "source_event_id": hit.source_event_id,
                         "text": hit.text,
                     })
@@ -245,19 +353,268 @@ impl<'cli> App<'cli> {
 
         for (index, hit) in hits.iter().enumerate() {
             println!(
-                "#{rank:>2} dist={distance:.6} ts={timestamp} session={session} tool={tool} kind={kind}",
+                "#{rank:>2} dist={distance:.6} ts={timestamp} session={session} tool={tool} kind={kind} chunk={chunk} span={start}..{end}",
                 rank = index + 1,
                 distance = hit.distance,
                 timestamp = format_ts_ms(hit.ts_ms),
                 session = hit.session_id,
                 tool = hit.tool_name.as_deref().unwrap_or("-"),
                 kind = hit.kind,
+                chunk = hit.chunk_ordinal,
+                start = hit.span_start,
+                end = hit.span_end,
             );
             println!("    {}", trim_preview(&hit.text, 200));
         }
         Ok(())
     }
 
+    fn rag_models(&self, cmd: &RagModelsCommand) -> Result<()> {
+        let total_messages = self.db.rag_source_message_count()?;
+        let mut rows = Vec::new();
+        for model in self.db.rag_models()? {
+            let coverage = self.db.rag_model_coverage(&model)?;
+            rows.push((model, coverage));
+        }
+
+        let percent = |messages: usize| {
+            if total_messages == 0 {
+                0.0
+            } else {
+                #[allow(clippy::cast_precision_loss)]
+                let ratio = messages as f64 / total_messages as f64;
+                (ratio * 100.0).min(100.0)
+            }
+        };
+
+        if cmd.json {
+            let payload = rows
+                .iter()
+                .map(|(model, coverage)| {
+                    json!({
+                        "model": model.model,
+                        "dimensions": model.dim,
+                        "table": model.table_name,
+                        "chunks": coverage.chunks,
+                        "sessions": coverage.sessions,
+                        "messages": coverage.messages,
+                        "total_messages": total_messages,
+                        "coverage_percent": percent(coverage.messages),
+                        "last_indexed_ms": model.last_indexed_ms,
+                        "last_indexed": model.last_indexed_ms.map(format_ts_ms),
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&payload)?);
+            return Ok(());
+        }
+
+        if rows.is_empty() {
+            println!("No embedding models indexed yet. Run `tx rag index` first.");
+            return Ok(());
+        }
+
+        for (model, coverage) in &rows {
+            println!(
+                "{name} dim={dim} chunks={chunks} sessions={sessions} messages={messages}/{total} ({percent:.1}%) last_indexed={last}",
+                name = model.model,
+                dim = model.dim,
+                chunks = coverage.chunks,
+                sessions = coverage.sessions,
+                messages = coverage.messages,
+                total = total_messages,
+                percent = percent(coverage.messages),
+                last = model
+                    .last_indexed_ms
+                    .map_or_else(|| "-".to_string(), format_ts_ms),
+            );
+        }
+        Ok(())
+    }
+
+    fn rag_topics(&self, cmd: &RagTopicsCommand) -> Result<()> {
+        let model = match cmd.model.as_deref() {
+            Some(model) => model.trim().to_string(),
+            None => search_model(&self.db, &configured_model())?,
+        };
+        if model.is_empty() {
+            return Err(eyre!("embedding model name must not be empty"));
+        }
+
+        let report = topic_clusters(&self.db, &model, cmd.k, cmd.terms)?;
+
+        if cmd.json {
+            let topics = report
+                .clusters
+                .iter()
+                .enumerate()
+                .map(|(index, cluster)| {
+                    json!({
+                        "topic": index + 1,
+                        "label": cluster.label(),
+                        "terms": cluster.terms,
+                        "size": cluster.members.len(),
+                        "sessions": cluster
+                            .members
+                            .iter()
+                            .map(|member| json!({
+                                "session_id": member.session_id,
+                                "distance": member.distance,
+                            }))
+                            .collect::<Vec<_>>(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let payload = json!({
+                "model": report.model,
+                "sessions": report.sessions,
+                "topics": topics,
+            });
+            println!("{}", serde_json::to_string_pretty(&payload)?);
+            return Ok(());
+        }
+
+        if report.clusters.is_empty() {
+            println!("No indexed sessions to cluster for model {model}.");
+            return Ok(());
+        }
+
+        println!(
+            "{} topics across {} sessions (model {})",
+            report.clusters.len(),
+            report.sessions,
+            report.model
+        );
+        for (index, cluster) in report.clusters.iter().enumerate() {
+            println!();
+            println!(
+                "Topic {} ({} sessions): {}",
+                index + 1,
+                cluster.members.len(),
+                cluster.label()
+            );
+            for member in cluster.members.iter().take(cmd.sessions) {
+                let title = self
+                    .db
+                    .session_summary(&member.session_id)?
+                    .and_then(|summary| summary.thread_name.or(summary.first_prompt))
+                    .map(|title| trim_preview(&title, 80))
+                    .unwrap_or_default();
+                println!(
+                    "  {distance:.3}  {session}  {title}",
+                    distance = member.distance,
+                    session = member.session_id,
+                );
+            }
+            let hidden = cluster.members.len().saturating_sub(cmd.sessions);
+            if hidden > 0 {
+                println!("  … {hidden} more");
+            }
+        }
+        Ok(())
+    }
+
+    fn rag_status(&self, cmd: &RagStatusCommand) -> Result<()> {
+        let model = cmd
+            .model
+            .as_deref()
+            .map_or_else(configured_model, |model| model.trim().to_string());
+        if model.is_empty() {
+            return Err(eyre!("embedding model name must not be empty"));
+        }
+
+        let report = index_status(
+            &self.db,
+            &model,
+            self.loaded.config.features.rag.into(),
+            cmd.session.as_deref(),
+        )?;
+        let auto_enabled = self.loaded.config.features.rag.auto_index;
+        let auto_run = self.db.last_rag_auto_index_run()?;
+        if cmd.json {
+            let payload = json!({
+                "model": report.model,
+                "dimensions": report.dim,
+                "last_indexed_ms": report.last_indexed_ms,
+                "last_indexed": report.last_indexed_ms.map(format_ts_ms),
+                "totals": rag_counts_json(&report.totals),
+                "estimated_cost_usd": report.estimated_cost_usd(),
+                "auto_index": auto_index_json(auto_enabled, auto_run.as_ref()),
+                "providers": report
+                    .providers
+                    .iter()
+                    .map(|(provider, counts)| {
+                        let mut value = rag_counts_json(counts);
+                        value["provider"] = json!(provider);
+                        value
+                    })
+                    .collect::<Vec<_>>(),
+                "sessions": report
+                    .sessions
+                    .iter()
+                    .map(|session| {
+                        let mut value = rag_counts_json(&session.counts);
+                        value["session_id"] = json!(session.session_id);
+                        value["provider"] = json!(session.provider);
+                        value
+                    })
+                    .collect::<Vec<_>>(),
+            });
+            println!("{}", serde_json::to_string_pretty(&payload)?);
+            return Ok(());
+        }
+
+        println!(
+            "model: {}{}",
+            report.model,
+            report.dim.map_or_else(
+                || " (not indexed)".to_string(),
+                |dim| format!(" ({dim} dims)")
+            )
+        );
+        println!(
+            "last indexed: {}",
+            report
+                .last_indexed_ms
+                .map_or_else(|| "never".to_string(), format_ts_ms)
+        );
+        println!("chunks: {}", rag_counts_line(&report.totals));
+        let cost = report
+            .estimated_cost_usd()
+            .map_or_else(|| "unknown cost".to_string(), |cost| format!("~${cost:.4}"));
+        println!(
+            "backfill: ~{} tokens ({cost})",
+            report.totals.pending_tokens
+        );
+        println!(
+            "auto index: {}",
+            auto_index_summary(auto_enabled, auto_run.as_ref())
+        );
+
+        if !report.providers.is_empty() {
+            println!("providers:");
+            for (provider, counts) in &report.providers {
+                println!(
+                    "  {}: {}",
+                    provider.as_deref().unwrap_or("(removed)"),
+                    rag_counts_line(counts)
+                );
+            }
+        }
+
+        if cmd.sessions && !report.sessions.is_empty() {
+            println!("sessions:");
+            for session in &report.sessions {
+                println!(
+                    "  {}: {}",
+                    session.session_id,
+                    rag_counts_line(&session.counts)
+                );
+            }
+        }
+        Ok(())
+    }
+
     fn collate_search_results<F>(
         hits: Vec<SearchHit>,
         since_epoch: Option<i64>,
@@ -324,6 +681,24 @@ impl<'cli> App<'cli> {
             .ok_or_else(|| eyre!("{}", context.replace("{}", session_id)))
     }
 
+    /// Resolve any known session, imported ones included, from an identifier
+    /// or `last` for the most recently active one.
+    fn resolve_session_summary(&self, session_id: &str) -> Result<SessionSummary> {
+        if let Some(summary) = self.db.session_summary_for_identifier(session_id)? {
+            return Ok(summary);
+        }
+        if session_id.eq_ignore_ascii_case("last") {
+            let latest = self.db.list_sessions(None, true, None, Some(1))?;
+            return match latest.first() {
+                Some(session) => {
+                    self.session_summary_required(&session.id, "session '{}' disappeared")
+                }
+                None => Err(eyre!("no previous sessions available")),
+            };
+        }
+        Err(eyre!("session '{}' not found", session_id))
+    }
+
     /// Resolve a session summary from an explicit identifier or `last`.
     ///
     /// # Errors
@@ -331,6 +706,7 @@ impl<'cli> App<'cli> {
     /// Returns an error when no matching session can be found.
     fn resolve_resume_summary(&self, session_id: &str) -> Result<SessionSummary> {
         if let Some(summary) = self.db.session_summary_for_identifier(session_id)? {
+            ensure_resumable(&self.db, &summary)?;
             return Ok(summary);
         }
         if session_id.eq_ignore_ascii_case("last") {
@@ -340,6 +716,7 @@ impl<'cli> App<'cli> {
                     self.session_summary_required(&session.id, RESUME_SESSION_DISAPPEARED)?;
                 if summary.subagent
                     || is_subagent_job_session_texts(summary.first_prompt.as_deref(), None)
+                    || self.db.session_origin(&summary.id)?.is_some()
                 {
                     continue;
                 }
@@ -351,6 +728,43 @@ impl<'cli> App<'cli> {
         Err(eyre!("session '{}' not found", session_id))
     }
 
+    /// The profile and launch options to resume with: the recorded launch of
+    /// the session unless `--fresh` is set, overridden by explicit flags.
+    fn resume_launch(
+        &self,
+        cmd: &ResumeCommand,
+        summary: &SessionSummary,
+    ) -> Result<(Option<String>, LaunchOptions)> {
+        let vars = parse_vars(&cmd.vars)?;
+        let recorded = if cmd.fresh {
+            None
+        } else {
+            recorded_launch(&self.db, &self.loaded.config, summary)?
+        };
+        let Some(recorded) = recorded else {
+            let launch = LaunchOptions {
+                pre: cmd.pre_snippets.clone(),
+                post: cmd.post_snippets.clone(),
+                wrap: cmd.wrap.clone(),
+                vars: vars.into_iter().collect(),
+            };
+            return Ok((cmd.profile.clone(), launch));
+        };
+        for name in &recorded.dropped {
+            eprintln!(
+                "tx: run {} used {name}, which is no longer configured",
+                recorded.run_id
+            );
+        }
+        Ok(recorded.merge(
+            cmd.profile.as_deref(),
+            &cmd.pre_snippets,
+            &cmd.post_snippets,
+            &cmd.wrap,
+            vars,
+        ))
+    }
+
     fn resolve_resume_profile(
         &mut self,
         profile_name: Option<&str>,
@@ -396,10 +810,12 @@ impl<'cli> App<'cli> {
     pub fn resume(&mut self, cmd: &ResumeCommand) -> Result<()> {
         let summary = self.resolve_resume_summary(&cmd.session_id)?;
 
+        let (profile, launch) = self.resume_launch(cmd, &summary)?;
         let (prompt_invocation, profile_has_pre_snippets) =
-            self.resolve_resume_profile(cmd.profile.as_deref(), &summary.provider)?;
+            self.resolve_resume_profile(profile.as_deref(), &summary.provider)?;
 
-        let vars = parse_vars(&cmd.vars)?;
+        let mut vars = launch.vars.into_iter().collect();
+        prompt_for_missing_vars(&self.loaded.config, profile.as_deref(), &mut vars)?;
         let working_dir = summary.path.parent().map_or_else(
             || std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
             Path::to_path_buf,
@@ -414,20 +830,39 @@ impl<'cli> App<'cli> {
         }
         provider_args.extend(cmd.provider_args.clone());
 
+        let context = match cmd.with_context.as_deref() {
+            Some(query) => {
+                let context =
+                    self.retrieve_context(query, cmd.context_k, cmd.context_model.as_deref())?;
+                if cmd.preview_context {
+                    match context {
+                        Some(block) => print!("{block}"),
+                        None => println!("No semantic matches found."),
+                    }
+                    return Ok(());
+                }
+                if context.is_none() {
+                    eprintln!("tx: no past-session context matched; launching without it");
+                }
+                context
+            }
+            None => None,
+        };
+
         let capture_prompt = should_capture_prompt_for_resume(
             prompt_invocation.as_ref(),
             profile_has_pre_snippets,
-            &cmd.pre_snippets,
-        );
+            &launch.pre,
+        ) || context.is_some();
 
         let request = PipelineRequest {
             config: &self.loaded.config,
             provider_hint: Some(summary.provider.as_str()),
-            profile: cmd.profile.as_deref(),
-            additional_pre: cmd.pre_snippets.clone(),
-            additional_post: cmd.post_snippets.clone(),
+            profile: profile.as_deref(),
+            additional_pre: launch.pre,
+            additional_post: launch.post,
             inline_pre: Vec::new(),
-            wrap: cmd.wrap.as_deref(),
+            wrap: launch.wrap,
             provider_args,
             capture_prompt,
             prompt_assembler: prompt_invocation,
@@ -437,11 +872,19 @@ impl<'cli> App<'cli> {
                 label: summary.label.clone(),
                 path: Some(summary.path.to_string_lossy().to_string()),
                 resume_token,
+                thread_name: summary.thread_name.clone(),
             },
             cwd: working_dir,
         };
 
-        let plan = build_pipeline(&request)?;
+        let mut plan = build_pipeline(&request)?;
+        if context.is_some() && !plan.uses_capture_arg {
+            return Err(eyre!(
+                "provider '{}' does not capture prompts; --with-context requires stdin_mode = \"capture_arg\"",
+                plan.provider
+            ));
+        }
+        plan.context = context;
         if cmd.emit_json && !(cmd.dry_run || cmd.emit_command) {
             return Err(eyre!("--emit-json requires --dry-run or --emit-command"));
         }
@@ -459,7 +902,74 @@ impl<'cli> App<'cli> {
             return Ok(());
         }
 
-        execute_plan(&plan).wrap_err("failed to execute pipeline")
+        self.run_plan(&plan).wrap_err("failed to execute pipeline")
+    }
+
+    /// Execute `plan` and record the launch in the run history.
+    ///
+    /// Launches that never start the provider, such as a failing `before` hook,
+    /// are not recorded. A run that cannot be recorded is reported but does not
+    /// change the result.
+    fn run_plan(&mut self, plan: &PipelinePlan) -> Result<()> {
+        let provider = self.loaded.config.providers.get(&plan.provider);
+        let existing = provider.map(indexer::session_files).unwrap_or_default();
+        let started_ms = util::unix_timestamp().saturating_mul(1000);
+
+        let result = execute_plan(plan);
+        let outcome = match &result {
+            Ok(outcome) => Some(*outcome),
+            Err(err) => err.downcast_ref::<ProviderFailed>().map(|failed| failed.0),
+        };
+        if let Some(outcome) = outcome {
+            // A resumed session may continue in its own transcript rather than
+            // a new one, so fall back to the transcript being resumed.
+            let session_path = provider
+                .and_then(|provider| {
+                    indexer::session_files(provider)
+                        .into_iter()
+                        .filter(|path| !existing.contains(path))
+                        .max_by_key(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+                })
+                .or_else(|| {
+                    let id = plan.session_id.as_deref()?;
+                    self.db
+                        .session_summary(id)
+                        .ok()
+                        .flatten()
+                        .map(|summary| summary.path)
+                });
+            let run = NewRun {
+                started_ms,
+                plan,
+                exit_code: outcome.exit_code(),
+                duration_ms: i64::try_from(outcome.duration.as_millis()).unwrap_or(i64::MAX),
+                session_path: session_path.as_deref(),
+            };
+            if let Err(err) = self.db.record_run(&run) {
+                eprintln!("tx: failed to record run: {err:#}");
+            }
+        }
+        result.map(|_| ())
+    }
+
+    /// Retrieve past-session chunks for `--with-context`, sized to leave room for the
+    /// prompt itself within the capture limit.
+    fn retrieve_context(
+        &self,
+        query: &str,
+        k: usize,
+        model: Option<&str>,
+    ) -> Result<Option<String>> {
+        let provider = self.rag_search_provider(model)?;
+        let hits = search_history(
+            &self.db,
+            &provider,
+            query,
+            &crate::db::RagSearchFilters::default(),
+            k,
+        )
+        .wrap_err("failed to retrieve --with-context matches")?;
+        Ok(format_context_block(query, &hits, CONTEXT_LIMIT))
     }
 
     fn ensure_prompt_available(&mut self, prompt_name: &str) -> Result<()> {
@@ -518,6 +1028,81 @@ impl<'cli> App<'cli> {
         Ok(())
     }
 
+    /// List past sessions whose indexed chunks are semantically close to a session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session cannot be resolved or has not been indexed
+    /// for the selected embedding model.
+    pub fn related(&self, cmd: &RelatedCommand) -> Result<()> {
+        let summary = self.resolve_session_summary(&cmd.session_id)?;
+        let model = match cmd.model.as_deref() {
+            Some(model) => model.trim().to_string(),
+            None => search_model(&self.db, &configured_model())?,
+        };
+        if model.is_empty() {
+            return Err(eyre!("embedding model name must not be empty"));
+        }
+
+        let related = related_sessions(&self.db, &model, &summary.id, cmd.k)?;
+        let providers = self.db.rag_session_providers()?;
+
+        if cmd.json {
+            let payload = related
+                .iter()
+                .enumerate()
+                .map(|(index, session)| {
+                    let chunk = &session.best_chunk;
+                    json!({
+                        "rank": index + 1,
+                        "session_id": session.session_id,
+                        "provider": providers.get(&session.session_id),
+                        "distance": session.distance,
+                        "best_chunk": {
+                            "chunk_id": chunk.chunk_id,
+                            "chunk_ordinal": chunk.chunk_ordinal,
+                            "span": {
+                                "start": chunk.span_start,
+                                "end": chunk.span_end,
+                            },
+                            "timestamp_ms": chunk.ts_ms,
+                            "timestamp": format_ts_ms(chunk.ts_ms),
+                            "kind": chunk.kind,
+                            "tool_name": chunk.tool_name,
+                            "text": chunk.text,
+                        },
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&payload)?);
+            return Ok(());
+        }
+
+        if related.is_empty() {
+            println!("No related sessions found.");
+            return Ok(());
+        }
+
+        for (index, session) in related.iter().enumerate() {
+            let chunk = &session.best_chunk;
+            println!(
+                "#{rank:>2} dist={distance:.6} session={session} provider={provider} ts={timestamp} chunk={ordinal} span={start}..{end}",
+                rank = index + 1,
+                distance = session.distance,
+                session = session.session_id,
+                provider = providers
+                    .get(&session.session_id)
+                    .map_or("-", String::as_str),
+                timestamp = format_ts_ms(chunk.ts_ms),
+                ordinal = chunk.chunk_ordinal,
+                start = chunk.span_start,
+                end = chunk.span_end,
+            );
+            println!("    {}", trim_preview(&chunk.text, 200));
+        }
+        Ok(())
+    }
+
     /// Render usage statistics for the selected provider.
     ///
     /// # Errors
@@ -532,6 +1117,66 @@ impl<'cli> App<'cli> {
         }
     }
 
+    /// List recorded provider launches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the run history cannot be read.
+    pub fn history(&self, cmd: &HistoryCommand) -> Result<()> {
+        history::run(&self.db, cmd)
+    }
+
+    /// Launch a recorded run again with its stored plan. Secrets are resolved
+    /// afresh and the new launch is recorded as a run of its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the run is unknown, its plan cannot be decoded, or
+    /// the pipeline fails.
+    pub fn rerun(&mut self, cmd: &RerunCommand) -> Result<()> {
+        let run = history::resolve(&self.db, &cmd.run_id)?;
+        let plan = run.decode_plan()?;
+        if cmd.dry_run {
+            let mode = if cmd.emit_json {
+                EmitMode::Json
+            } else {
+                EmitMode::Plain {
+                    newline: true,
+                    friendly: false,
+                }
+            };
+            return emit_command(&plan, mode);
+        }
+        self.run_plan(&plan)
+            .wrap_err_with(|| format!("failed to rerun run {}", run.id))
+    }
+
+    /// Run a plan written by `--emit-json` through the normal launch path,
+    /// after checking its schema version and the programs it starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plan cannot be read or fails validation, or the
+    /// pipeline fails.
+    pub fn exec(&mut self, cmd: &ExecCommand) -> Result<()> {
+        let plan = exec::load_plan(&cmd.plan)?;
+        exec::check_plan(&plan)?;
+        if cmd.check {
+            println!("Plan OK: {}", plan.display);
+            return Ok(());
+        }
+        self.run_plan(&plan).wrap_err("failed to execute plan")
+    }
+
+    /// Prune index data for sessions outside the `[retention]` policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if candidates cannot be selected or deleted.
+    pub fn prune(&mut self, cmd: &PruneCommand) -> Result<()> {
+        prune::run(&mut self.db, &self.loaded.config.retention, cmd)
+    }
+
     /// Execute one of the configuration subcommands.
     ///
     /// # Errors
@@ -543,7 +1188,7 @@ impl<'cli> App<'cli> {
                 self.config_list();
                 Ok(())
             }
-            ConfigCommand::Dump => self.config_dump(),
+            ConfigCommand::Dump(cmd) => self.config_dump(cmd),
             ConfigCommand::Where => {
                 self.config_where();
                 Ok(())
@@ -589,7 +1234,10 @@ impl<'cli> App<'cli> {
             db: &mut self.db,
             prompt,
         };
-        tui::run(&mut ctx)
+        match tui::run(&mut ctx)? {
+            Some(plan) => self.run_plan(&plan),
+            None => Ok(()),
+        }
     }
 
     fn config_list(&self) {
@@ -621,9 +1269,16 @@ impl<'cli> App<'cli> {
                 profile.provider,
                 profile.pre.join(", "),
                 profile.post.join(", "),
-                profile.wrap.as_deref().unwrap_or("-"),
+                if profile.wrap.is_empty() {
+                    "-".to_string()
+                } else {
+                    profile.wrap.join(", ")
+                },
                 profile.description.as_deref().unwrap_or("-"),
             );
+            for var in profile.vars.values() {
+                println!("      var {}", describe_profile_var(var));
+            }
         }
     }
 
@@ -642,9 +1297,14 @@ impl<'cli> App<'cli> {
         write_config_schema(&mut stdout, cmd)
     }
 
-    fn config_dump(&self) -> Result<()> {
+    fn config_dump(&self, cmd: &ConfigDumpCommand) -> Result<()> {
         let mut stdout = io::stdout().lock();
-        write_config_dump(&mut stdout, &self.loaded.merged)
+        if cmd.resolved {
+            let resolved = Config::resolve(&self.loaded.merged)?;
+            write_config_dump(&mut stdout, &resolved)
+        } else {
+            write_config_dump(&mut stdout, &self.loaded.merged)
+        }
     }
 
     fn config_where(&self) {
@@ -697,7 +1357,130 @@ impl<'cli> App<'cli> {
     }
 }
 
-fn should_capture_prompt_for_resume(
+/// How long an index lease stays valid if its holder never releases it.
+const LEASE_TTL: Duration = Duration::from_mins(10);
+/// Byte limit applied to captured prompts, matching `tx internal capture-arg`.
+const DEFAULT_PROMPT_LIMIT: usize = 1_048_576;
+/// Share of the prompt limit that retrieved `--with-context` chunks may occupy.
+const CONTEXT_LIMIT: usize = DEFAULT_PROMPT_LIMIT / 2;
+
+/// Refuse to resume sessions imported from another machine; their logs live there.
+///
+/// # Errors
+///
+/// Returns an error naming the source machine when the session was imported.
+pub(crate) fn ensure_resumable(db: &Database, summary: &SessionSummary) -> Result<()> {
+    match db.session_origin(&summary.id)? {
+        Some(origin) => Err(eyre!(
+            "session '{}' was imported from {} and is read-only; resume it there as '{}'",
+            summary.id,
+            origin.machine,
+            origin.source_id
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Launch settings restored from the run that created a session.
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedLaunch {
+    pub run_id: i64,
+    pub profile: Option<String>,
+    pub launch: LaunchOptions,
+    /// Recorded names that are no longer configured and were dropped.
+    pub dropped: Vec<String>,
+}
+
+impl RecordedLaunch {
+    /// Combine the recorded settings with explicit resume flags. A flag replaces
+    /// the recorded value, and `--var` bindings override recorded ones by key.
+    pub fn merge(
+        self,
+        profile: Option<&str>,
+        pre: &[String],
+        post: &[String],
+        wrap: &[String],
+        vars: HashMap<String, String>,
+    ) -> (Option<String>, LaunchOptions) {
+        let mut launch = self.launch;
+        if !pre.is_empty() {
+            launch.pre = pre.to_vec();
+        }
+        if !post.is_empty() {
+            launch.post = post.to_vec();
+        }
+        if !wrap.is_empty() {
+            launch.wrap = wrap.to_vec();
+        }
+        launch.vars.extend(vars);
+        (profile.map(str::to_string).or(self.profile), launch)
+    }
+}
+
+/// Find the launch settings of the most recent run tied to `summary`'s transcript.
+///
+/// Profiles, snippets, and wrappers that are no longer configured (or whose
+/// provider no longer matches) are dropped and listed in
+/// [`RecordedLaunch::dropped`]. Runs whose plan cannot be decoded are ignored.
+///
+/// # Errors
+///
+/// Returns an error if the run history cannot be read.
+pub(crate) fn recorded_launch(
+    db: &Database,
+    config: &Config,
+    summary: &SessionSummary,
+) -> Result<Option<RecordedLaunch>> {
+    let Some(run) = db.latest_run_for_session(&summary.path)? else {
+        return Ok(None);
+    };
+    let Ok(plan) = run.decode_plan() else {
+        return Ok(None);
+    };
+
+    let mut dropped = Vec::new();
+    let profile = plan.profile.filter(|name| {
+        let kept = config
+            .profiles
+            .get(name)
+            .is_some_and(|profile| profile.provider == summary.provider);
+        if !kept {
+            dropped.push(format!("profile '{name}'"));
+        }
+        kept
+    });
+    let mut launch = plan.launch;
+    launch.pre.retain(|name| {
+        let kept = config.snippets.pre.contains_key(name);
+        if !kept {
+            dropped.push(format!("pre snippet '{name}'"));
+        }
+        kept
+    });
+    launch.post.retain(|name| {
+        let kept = config.snippets.post.contains_key(name);
+        if !kept {
+            dropped.push(format!("post snippet '{name}'"));
+        }
+        kept
+    });
+    launch.wrap.retain(|name| {
+        let kept = config.wrapper(name).is_some();
+        if !kept {
+            dropped.push(format!("wrapper '{name}'"));
+        }
+        kept
+    });
+
+    Ok(Some(RecordedLaunch {
+        run_id: run.id,
+        profile,
+        launch,
+        dropped,
+    }))
+}
+
+pub(crate) fn should_capture_prompt_for_resume(
     prompt_invocation: Option<&PromptInvocation>,
     profile_has_pre_snippets: bool,
     cmd_pre_snippets: &[String],
@@ -723,8 +1506,7 @@ fn emit_command_with_writer<W: Write>(
 ) -> Result<()> {
     match mode {
         EmitMode::Json => {
-            let payload = json!({ "command": plan.display, "env": plan.env });
-            let rendered = serde_json::to_string_pretty(&payload)?;
+            let rendered = serde_json::to_string_pretty(&plan_payload(plan))?;
             writer.write_all(rendered.as_bytes())?;
             writer.write_all(b"\n")?;
         }
@@ -746,6 +1528,28 @@ fn emit_command_with_writer<W: Write>(
     Ok(())
 }
 
+/// The JSON form of `plan` shared by `--emit-json` and `TX_PLAN`.
+fn plan_payload(plan: &PipelinePlan) -> serde_json::Value {
+    let origins: serde_json::Map<String, serde_json::Value> = plan
+        .env
+        .iter()
+        .zip(&plan.env_origins)
+        .map(|((key, _), origin)| (key.clone(), json!(origin)))
+        .collect();
+    let mut payload = json!({
+        "schema_version": PLAN_SCHEMA_VERSION,
+        "command": plan.display,
+        "cwd": plan.cwd,
+        "env": plan.env,
+        "env_origins": origins,
+        "plan": plan,
+    });
+    if let Some(context) = &plan.context {
+        payload["context"] = json!(context);
+    }
+    payload
+}
+
 fn write_config_default<W: Write>(
     writer: &mut W,
     cmd: &ConfigDefaultCommand,
@@ -769,6 +1573,50 @@ fn write_config_schema<W: Write>(writer: &mut W, cmd: &ConfigSchemaCommand) -> R
     Ok(())
 }
 
+/// Check a secret's source without fetching the value.
+fn describe_secret_health(secret: &SecretConfig) -> String {
+    let program = match &secret.source {
+        SecretSource::Command { argv } => argv[0].as_str(),
+        SecretSource::SecretTool { .. } => "secret-tool",
+        SecretSource::File { path } => {
+            return match crate::secrets::check_file_permissions(path) {
+                Ok(()) => format!("✔ secret {} file {}", secret.name, path.display()),
+                Err(err) => format!("✘ secret {}: {err}", secret.name),
+            };
+        }
+    };
+    match which(program) {
+        Ok(path) => format!(
+            "✔ secret {} uses {} ({program} found at {})",
+            secret.name,
+            secret.source.describe(),
+            path.display()
+        ),
+        Err(_) => format!(
+            "✘ secret {} uses {} but '{program}' is not on PATH",
+            secret.name,
+            secret.source.describe()
+        ),
+    }
+}
+
+fn describe_profile_var(var: &ProfileVar) -> String {
+    let mut parts = vec![var.kind.as_str().to_string()];
+    if var.required {
+        parts.push("required".to_string());
+    }
+    if let Some(default) = &var.default {
+        parts.push(format!("default: {default}"));
+    }
+    if !var.choices.is_empty() {
+        parts.push(format!("choices: [{}]", var.choices.join(", ")));
+    }
+    match &var.description {
+        Some(description) => format!("{}: {} — {description}", var.name, parts.join(", ")),
+        None => format!("{}: {}", var.name, parts.join(", ")),
+    }
+}
+
 fn write_config_dump<W: Write>(writer: &mut W, merged: &toml::Value) -> Result<()> {
     let toml_text = toml::to_string_pretty(merged)?;
     writer.write_all(toml_text.as_bytes())?;
@@ -790,18 +1638,36 @@ fn emit_terminal_title_with_writer<W: Write>(
     Ok(())
 }
 
+/// Launch `plan` and wait for the provider to exit.
+///
+/// A provider that exits unsuccessfully yields a [`ProviderFailed`] error, which
+/// still carries the [`LaunchOutcome`].
 #[cfg(not(coverage))]
-pub(crate) fn execute_plan(plan: &PipelinePlan) -> Result<()> {
-    execute_plan_with_stdin_prompt(plan, io::stdin().is_terminal(), |label| {
-        let stdin = io::stdin();
-        let mut handle = stdin.lock();
-        prompt_for_stdin_with_reader(label, &mut handle)
-    })
+pub(crate) fn execute_plan(plan: &PipelinePlan) -> Result<LaunchOutcome> {
+    execute_plan_with_stdin_prompt(
+        plan,
+        io::stdin().is_terminal(),
+        |label| {
+            let stdin = io::stdin();
+            let mut handle = stdin.lock();
+            prompt_for_stdin_with_reader(label, &mut handle)
+        },
+        read_piped_stdin,
+    )
 }
 
 #[cfg(coverage)]
-pub(crate) fn execute_plan(plan: &PipelinePlan) -> Result<()> {
-    execute_plan_with_stdin_prompt(plan, io::stdin().is_terminal(), |_label| Ok(String::new()))
+pub(crate) fn execute_plan(plan: &PipelinePlan) -> Result<LaunchOutcome> {
+    execute_plan_with_stdin_prompt(
+        plan,
+        io::stdin().is_terminal(),
+        |_label| Ok(String::new()),
+        read_piped_stdin,
+    )
+}
+
+fn read_piped_stdin() -> Result<String> {
+    io::read_to_string(io::stdin()).wrap_err("failed to read prompt from stdin")
 }
 
 fn emit_terminal_title(title: &str) -> Result<()> {
@@ -811,15 +1677,20 @@ fn emit_terminal_title(title: &str) -> Result<()> {
     emit_terminal_title_with_writer(title, is_terminal, &mut locked)
 }
 
-fn execute_plan_with_stdin_prompt<P>(
+/// Launch `plan`, collecting the captured prompt first when needed.
+///
+/// `read_prompt` asks for a prompt on a terminal; `read_piped` reads piped stdin
+/// to the end, which retrieved context is prepended to.
+fn execute_plan_with_stdin_prompt<P, S>(
     plan: &PipelinePlan,
     stdin_is_terminal: bool,
     mut read_prompt: P,
-) -> Result<()>
+    read_piped: S,
+) -> Result<LaunchOutcome>
 where
     P: FnMut(Option<&str>) -> Result<String>,
+    S: FnOnce() -> Result<String>,
 {
-    const DEFAULT_PROMPT_LIMIT: usize = 1_048_576;
     let assembled_prompt = if let Some(invocation) = &plan.prompt_assembler {
         let cmd = InternalPromptAssemblerCommand {
             prompt: invocation.name.clone(),
@@ -831,11 +1702,38 @@ where
         None
     };
 
-    execute_plan_with_prompt(plan, stdin_is_terminal, assembled_prompt, |label| {
+    let capture_input = match (plan.context.as_deref(), assembled_prompt) {
+        (Some(context), Some(prompt)) => Some(prepend_context(context, &prompt)?),
+        (Some(context), None) => {
+            let prompt = if stdin_is_terminal {
+                read_prompt(Some(&plan.provider))?
+            } else {
+                read_piped()?
+            };
+            Some(prepend_context(context, &prompt)?)
+        }
+        (None, prompt) => prompt,
+    };
+
+    execute_plan_with_prompt(plan, stdin_is_terminal, capture_input, |label| {
         read_prompt(label).map(Some)
     })
 }
 
+fn prepend_context(context: &str, prompt: &str) -> Result<String> {
+    let combined = if prompt.trim().is_empty() {
+        context.to_string()
+    } else {
+        format!("{context}\n{prompt}")
+    };
+    if combined.len() > DEFAULT_PROMPT_LIMIT {
+        return Err(eyre!(
+            "prompt with retrieved context exceeds configured limit of {DEFAULT_PROMPT_LIMIT} bytes"
+        ));
+    }
+    Ok(combined)
+}
+
 fn should_warn_capture(
     plan: &PipelinePlan,
     capture_input: Option<&str>,
@@ -852,7 +1750,7 @@ fn execute_plan_with_prompt<P>(
     stdin_is_terminal: bool,
     mut capture_input: Option<String>,
     mut prompt: P,
-) -> Result<()>
+) -> Result<LaunchOutcome>
 where
     P: FnMut(Option<&str>) -> Result<Option<String>>,
 {
@@ -866,57 +1764,170 @@ where
         );
     }
 
+    for command in &plan.hooks.before {
+        run_hook(plan, "before", command, None)?;
+    }
+
+    let mut env = plan.launch_env()?;
+    if let Some(input) = capture_input {
+        env.push(("TX_CAPTURE_STDIN_DATA".to_string(), input));
+    }
     emit_terminal_title(&plan.terminal_title)?;
 
-    match &plan.invocation {
-        Invocation::Shell { command } => {
-            let shell = default_shell();
-            let mut cmd = Command::new(&shell.path);
-            cmd.arg(shell.flag);
-            #[cfg(windows)]
-            {
-                use std::os::windows::process::CommandExt;
-                if matches!(shell.flag, "/C" | "/K") {
-                    cmd.raw_arg(command);
-                } else {
-                    cmd.arg(command);
-                }
-            }
-            #[cfg(not(windows))]
-            {
-                cmd.arg(command);
-            }
-            cmd.current_dir(&plan.cwd);
-            cmd.envs(plan.env.iter().map(|(k, v)| (k, v)));
-            if let Some(ref input) = capture_input {
-                cmd.env("TX_CAPTURE_STDIN_DATA", input);
-            }
-            let status = cmd.status()?;
-            if !status.success() {
-                return Err(eyre!("command exited with status {status}"));
-            }
-        }
-        Invocation::Exec { argv } => {
+    let mut env_file = None;
+    if let Invocation::Exec {
+        env_at: Some(_), ..
+    } = &plan.invocation
+    {
+        env_file = Some(write_env_file(&env)?);
+    }
+
+    let started = Instant::now();
+    let mut cmd = match &plan.invocation {
+        Invocation::Shell { command } => shell_command(command),
+        Invocation::Exec { argv, env_at } => {
+            let argv = launch_argv(argv, *env_at, env_file.as_deref());
             let program = argv
                 .first()
                 .ok_or_else(|| eyre!("wrapper produced empty argv"))?;
             let mut cmd = Command::new(program);
             cmd.args(&argv[1..]);
-            cmd.current_dir(&plan.cwd);
-            cmd.envs(plan.env.iter().map(|(k, v)| (k, v)));
-            if let Some(ref input) = capture_input {
-                cmd.env("TX_CAPTURE_STDIN_DATA", input);
-            }
-            let status = cmd.status()?;
-            if !status.success() {
-                return Err(eyre!("command exited with status {status}"));
-            }
+            cmd
+        }
+    };
+    cmd.current_dir(&plan.cwd);
+    cmd.envs(env.iter().map(|(k, v)| (k, v)));
+    let launched = cmd
+        .status()
+        .map(|status| LaunchOutcome {
+            status,
+            duration: started.elapsed(),
+        })
+        .wrap_err_with(|| format!("failed to launch '{}'", plan.display));
+    // A wrapper that started successfully deletes the env file itself once its
+    // window reads it, which may be after the wrapper has returned.
+    if let Some(env_file) = env_file
+        && launched
+            .as_ref()
+            .is_ok_and(|outcome| outcome.status.success())
+        && let Err(err) = env_file.keep()
+    {
+        debug!(error = ?err, "could not hand the env file over to the wrapper");
+    }
+
+    // Hooks after the launch only report failures; the provider's status, or
+    // the error that kept it from starting, wins.
+    let mut post_hooks: Vec<(&str, &String)> = Vec::new();
+    if !launched
+        .as_ref()
+        .is_ok_and(|outcome| outcome.status.success())
+    {
+        post_hooks.extend(
+            plan.hooks
+                .on_failure
+                .iter()
+                .map(|command| ("on_failure", command)),
+        );
+    }
+    post_hooks.extend(plan.hooks.after.iter().map(|command| ("after", command)));
+    for (stage, command) in post_hooks {
+        if let Err(err) = run_hook(plan, stage, command, launched.as_ref().ok()) {
+            eprintln!("tx: {err:#}");
         }
     }
 
+    let outcome = launched?;
+    if !outcome.status.success() {
+        return Err(ProviderFailed(outcome).into());
+    }
+    Ok(outcome)
+}
+
+/// How a provider launch ended.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LaunchOutcome {
+    pub status: ExitStatus,
+    pub duration: Duration,
+}
+
+impl LaunchOutcome {
+    /// `None` when the process was killed by a signal.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.status.code()
+    }
+}
+
+/// The provider ran but exited unsuccessfully.
+#[derive(Debug)]
+pub(crate) struct ProviderFailed(pub LaunchOutcome);
+
+impl std::fmt::Display for ProviderFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "command exited with status {}", self.0.status)
+    }
+}
+
+impl std::error::Error for ProviderFailed {}
+
+/// Run one hook command through the default shell in the plan's directory.
+///
+/// Hooks see `TX_HOOK`, `TX_PROVIDER`, `TX_SESSION_ID`, and `TX_PLAN`; once the
+/// provider has exited they also see `TX_EXIT_CODE` and `TX_DURATION_MS`, which
+/// stay unset when it could not be started.
+/// Secret values are never passed to hooks.
+fn run_hook(
+    plan: &PipelinePlan,
+    stage: &str,
+    command: &str,
+    outcome: Option<&LaunchOutcome>,
+) -> Result<()> {
+    let mut cmd = shell_command(command);
+    cmd.current_dir(&plan.cwd);
+    cmd.env("TX_HOOK", stage);
+    cmd.env("TX_PROVIDER", &plan.provider);
+    cmd.env(
+        "TX_SESSION_ID",
+        plan.session_id.as_deref().unwrap_or_default(),
+    );
+    cmd.env("TX_PLAN", plan_payload(plan).to_string());
+    if let Some(outcome) = outcome {
+        let exit_code = outcome
+            .exit_code()
+            .map_or_else(String::new, |code| code.to_string());
+        cmd.env("TX_EXIT_CODE", exit_code);
+        cmd.env("TX_DURATION_MS", outcome.duration.as_millis().to_string());
+    }
+    let status = cmd
+        .status()
+        .wrap_err_with(|| format!("failed to run {stage} hook '{command}'"))?;
+    if !status.success() {
+        return Err(eyre!(
+            "{stage} hook '{command}' exited with status {status}"
+        ));
+    }
     Ok(())
 }
 
+fn shell_command(command: &str) -> Command {
+    let shell = default_shell();
+    let mut cmd = Command::new(&shell.path);
+    cmd.arg(shell.flag);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        if matches!(shell.flag, "/C" | "/K") {
+            cmd.raw_arg(command);
+        } else {
+            cmd.arg(command);
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        cmd.arg(command);
+    }
+    cmd
+}
+
 fn parse_vars(vars: &[String]) -> Result<HashMap<String, String>> {
     let mut map = HashMap::new();
     for entry in vars {
@@ -928,6 +1939,65 @@ fn parse_vars(vars: &[String]) -> Result<HashMap<String, String>> {
     Ok(map)
 }
 
+/// Ask on the terminal for declared profile variables that are still missing.
+///
+/// Does nothing when stdin is not a terminal; `build_pipeline` then reports the
+/// first missing variable.
+fn prompt_for_missing_vars(
+    config: &Config,
+    profile: Option<&str>,
+    vars: &mut HashMap<String, String>,
+) -> Result<()> {
+    let missing = missing_vars(config, profile, vars);
+    let Some(profile) = profile else {
+        return Ok(());
+    };
+    if missing.is_empty() || !io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let stdin = io::stdin();
+    let mut handle = stdin.lock();
+    prompt_for_vars_with_io(profile, &missing, &mut handle, &mut io::stderr(), vars)
+}
+
+fn prompt_for_vars_with_io<R: BufRead, W: Write>(
+    profile: &str,
+    missing: &[&ProfileVar],
+    reader: &mut R,
+    writer: &mut W,
+    vars: &mut HashMap<String, String>,
+) -> Result<()> {
+    for var in missing {
+        loop {
+            writeln!(
+                writer,
+                "tx: profile '{profile}' needs {}",
+                var.prompt_label()
+            )?;
+            write!(writer, "› ")?;
+            writer.flush()?;
+
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(eyre!("no value entered for variable '{}'", var.name));
+            }
+            let value = line.trim_end_matches(['\n', '\r']);
+            if value.is_empty() {
+                writeln!(writer, "tx: a value is required")?;
+                continue;
+            }
+            match var.validate(value) {
+                Ok(value) => {
+                    vars.insert(var.name.clone(), value);
+                    break;
+                }
+                Err(err) => writeln!(writer, "tx: {err}")?,
+            }
+        }
+    }
+    Ok(())
+}
+
 struct ShellCommand {
     path: OsString,
     flag: &'static str,
@@ -1022,35 +2092,88 @@ fn trim_preview(text: &str, max_chars: usize) -> String {
     format!("{trimmed}...")
 }
 
-fn summary_to_json(
-    summary: &SessionSummary,
-    snippet: Option<&str>,
-    snippet_role: Option<&str>,
-) -> serde_json::Value {
+fn export_markdown(transcript: &Transcript) {
+    for line in transcript.markdown_lines(None) {
+        println!("{line}");
+    }
+}
+
+fn rag_counts_json(counts: &RagStatusCounts) -> serde_json::Value {
     json!({
-        "id": summary.id,
-        "provider": summary.provider,
-        "wrapper": summary.wrapper,
-        "label": summary.label,
-        "thread_name": summary.thread_name,
-        "path": summary.path.to_string_lossy(),
-        "uuid": summary.uuid,
-        "first_prompt": summary.first_prompt,
-        "actionable": summary.actionable,
-        "created_at": summary.created_at,
-        "started_at": summary.started_at,
-        "last_active": summary.last_active,
-        "size": summary.size,
-        "mtime": summary.mtime,
-        "snippet": snippet,
-        "snippet_role": snippet_role,
+        "total": counts.total,
+        "indexed": counts.indexed,
+        "stale": counts.stale,
+        "missing": counts.missing,
+        "orphaned": counts.orphaned,
+        "pending_tokens": counts.pending_tokens,
     })
 }
 
-fn export_markdown(transcript: &Transcript) {
-    for line in transcript.markdown_lines(None) {
-        println!("{line}");
+fn rag_counts_line(counts: &RagStatusCounts) -> String {
+    format!(
+        "total={} indexed={} stale={} missing={} orphaned={}",
+        counts.total, counts.indexed, counts.stale, counts.missing, counts.orphaned
+    )
+}
+
+fn auto_index_json(enabled: bool, run: Option<&RagAutoIndexRun>) -> serde_json::Value {
+    json!({
+        "enabled": enabled,
+        "last_run": run.map(|run| json!({
+            "started_ms": run.started_ms,
+            "started": format_ts_ms(run.started_ms),
+            "finished_ms": run.finished_ms,
+            "model": run.model,
+            "embedded": run.embedded,
+            "deferred": run.deferred,
+            "error": run.error,
+        })),
+    })
+}
+
+fn auto_index_summary(enabled: bool, run: Option<&RagAutoIndexRun>) -> String {
+    let state = if enabled { "enabled" } else { "disabled" };
+    match run {
+        None => format!("{state}, never run"),
+        Some(run) => {
+            let started = format_ts_ms(run.started_ms);
+            match &run.error {
+                Some(error) => format!("{state}, last run {started} failed: {error}"),
+                None => format!(
+                    "{state}, last run {started} embedded={} deferred={}",
+                    run.embedded, run.deferred
+                ),
+            }
+        }
+    }
+}
+
+/// Ingest session transcripts and return the ids of sessions the run added or
+/// changed. When another tx process is already indexing, this one works from
+/// the data in the database and reports no changes.
+fn index_sessions(db: &mut Database, config: &Config) -> Result<Vec<String>> {
+    if !db.try_acquire_lease(INDEX_LEASE, &command_label(), LEASE_TTL)? {
+        debug!("another tx process is indexing sessions; using existing data");
+        return Ok(Vec::new());
+    }
+    let result = Indexer::new(db, config).run();
+    db.release_lease(INDEX_LEASE)?;
+    let report = result?;
+    log_index_report(&report);
+    match db.checkpoint_wal() {
+        Ok((frames, checkpointed)) => debug!(frames, checkpointed, "WAL checkpoint complete"),
+        Err(err) => debug!(error = ?err, "WAL checkpoint skipped"),
     }
+    Ok(report.updated_sessions)
+}
+
+/// The invoked subcommand, recorded with leases so `tx doctor` can name holders.
+fn command_label() -> String {
+    let subcommand = std::env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .unwrap_or_default();
+    format!("tx {subcommand}").trim_end().to_string()
 }
 
 fn log_index_report(report: &IndexReport) {
@@ -1115,9 +2238,29 @@ fn run_doctor(loaded: &LoadedConfig, db: &Database) -> Result<()> {
         }
     }
 
+    for secret in loaded.config.secrets.values() {
+        println!("{}", describe_secret_health(secret));
+    }
+
     let db_path = loaded.directories.data_dir.join("tx.sqlite3");
     println!("\nDatabase: {}", db_path.display());
     println!("Known sessions: {}", db.count_sessions()?);
+    let leases = db.active_leases()?;
+    if leases.is_empty() {
+        println!("Index leases: none held");
+    } else {
+        println!("Index leases:");
+        for lease in &leases {
+            println!(
+                "  {} held by pid {} ({}) since {}, expires {}",
+                lease.name,
+                lease.pid,
+                lease.command.as_deref().unwrap_or("unknown command"),
+                format_ts_ms(lease.acquired_ms),
+                format_ts_ms(lease.expires_ms)
+            );
+        }
+    }
 
     if let Some(cfg) = loaded.config.features.prompt_assembler.clone() {
         check_prompt_assembler(&cfg);
@@ -2,17 +2,19 @@ use super::*;
 #[cfg(coverage)]
 use crate::cli::RagIndexCommand;
 use crate::cli::{
-    ConfigCommand, ConfigDefaultCommand, ExportCommand, RagCommand, RagSearchCommand,
-    ResumeCommand, SearchCommand, SelfUpdateCommand,
+    ConfigCommand, ConfigDefaultCommand, ConfigDumpCommand, ExportCommand, RagCommand,
+    RagSearchCommand, ResumeCommand, SearchCommand, SelfUpdateCommand,
 };
+use crate::commands::search::summary_to_json;
 use crate::config::model::{
-    Config, ConfigDiagnostic, Defaults, DiagnosticLevel, EnvVar, FeatureConfig, ProfileConfig,
-    ProviderConfig, SearchMode, Snippet, SnippetConfig, WrapperConfig, WrapperMode,
+    Config, ConfigDiagnostic, Defaults, DiagnosticLevel, EnvVar, FeatureConfig, HooksConfig,
+    ProfileConfig, ProviderConfig, RagConfig, RetentionConfig, SearchFormat, SearchMode, Snippet,
+    SnippetConfig, VarKind, WrapperConfig, WrapperMode,
 };
 use crate::config::{AppDirectories, ConfigSource, ConfigSourceKind, LoadedConfig};
 use crate::db::Database;
 use crate::indexer::IndexError;
-use crate::pipeline::{Invocation, PipelinePlan};
+use crate::pipeline::{Invocation, LaunchOptions, PipelinePlan};
 use crate::session::{MessageRecord, SearchHit, SessionIngest, SessionSummary, Transcript};
 use crate::test_support::{ENV_LOCK, EnvOverride, toml_path};
 use assert_fs::TempDir;
@@ -125,6 +127,42 @@ fn parse_vars_rejects_missing_equals() {
     assert!(err.to_string().contains("expected KEY=VALUE"));
 }
 
+#[test]
+fn prompt_for_vars_reprompts_until_value_is_valid() -> Result<()> {
+    let var = ProfileVar {
+        name: "ticket".into(),
+        kind: VarKind::Integer,
+        default: None,
+        description: Some("Tracker id".into()),
+        choices: Vec::new(),
+        required: true,
+    };
+    let mut input = Cursor::new("\nabc\n42\n");
+    let mut output = Vec::new();
+    let mut vars = HashMap::new();
+    prompt_for_vars_with_io("review", &[&var], &mut input, &mut output, &mut vars)?;
+    assert_eq!(vars.get("ticket").map(String::as_str), Some("42"));
+
+    let transcript = String::from_utf8(output)?;
+    assert!(transcript.contains("tx: profile 'review' needs ticket (Tracker id) [integer]"));
+    assert!(transcript.contains("tx: a value is required"));
+    assert!(transcript.contains("tx: variable 'ticket' expects an integer, got 'abc'"));
+
+    let err = prompt_for_vars_with_io(
+        "review",
+        &[&var],
+        &mut Cursor::new(""),
+        &mut Vec::new(),
+        &mut HashMap::new(),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("no value entered for variable 'ticket'")
+    );
+    Ok(())
+}
+
 #[test]
 fn resume_capture_prompt_detects_profile_and_cli_pre_snippets() {
     assert!(should_capture_prompt_for_resume(None, true, &[]));
@@ -331,6 +369,7 @@ fn log_index_report_emits_warnings_for_errors() {
     let mut report = IndexReport {
         scanned: 1,
         updated: 0,
+        updated_sessions: Vec::new(),
         skipped: 0,
         removed: 0,
         errors: vec![IndexError {
@@ -348,6 +387,7 @@ fn log_index_report_emits_debug_when_enabled() {
     let report = IndexReport {
         scanned: 3,
         updated: 2,
+        updated_sessions: vec!["a".to_string(), "b".to_string()],
         skipped: 1,
         removed: 0,
         errors: Vec::new(),
@@ -412,13 +452,21 @@ fn emit_command_covers_plain_and_json_modes() -> Result<()> {
         terminal_title: "echo".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     emit_command(
@@ -455,18 +503,27 @@ fn execute_plan_exec_invocation_sets_capture_env() -> Result<()> {
                 script.path().display().to_string(),
                 log_path.path().display().to_string(),
             ],
+            env_at: None,
         },
         provider: "demo".into(),
         terminal_title: "demo".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: true,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd: temp.path().to_path_buf(),
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     execute_plan_with_prompt(&plan, true, Some("payload".into()), |_| Ok(None))?;
@@ -492,13 +549,21 @@ fn execute_plan_coverage_wrapper_executes_without_prompt() -> Result<()> {
         terminal_title: "demo".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd: temp.path().to_path_buf(),
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     execute_plan(&plan)?;
@@ -565,13 +630,21 @@ fn execute_plan_shell_with_non_cmd_shell_uses_arg_path() -> Result<()> {
         terminal_title: "demo".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd: temp.path().to_path_buf(),
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     let err = execute_plan(&plan).expect_err("where.exe -c should fail");
@@ -610,6 +683,8 @@ fn fixture_config(sessions_dir: &Path) -> Config {
                 key: "TEST_PRESENT".into(),
                 value_template: "${env:TEST_PRESENT}".into(),
             }],
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: vec![sessions_dir.to_path_buf()],
             stdin: None,
         },
@@ -624,6 +699,8 @@ fn fixture_config(sessions_dir: &Path) -> Config {
                 key: "TEST_MISSING".into(),
                 value_template: "${env:TEST_MISSING}".into(),
             }],
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: vec![sessions_dir.to_path_buf()],
             stdin: None,
         },
@@ -648,11 +725,18 @@ fn fixture_config(sessions_dir: &Path) -> Config {
         "default".into(),
         ProfileConfig {
             name: "default".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: Some("Primary profile".into()),
             pre: vec!["pre".into()],
             post: Vec::new(),
-            wrap: Some("wrap".into()),
+            wrap: vec!["wrap".into()],
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -661,11 +745,18 @@ fn fixture_config(sessions_dir: &Path) -> Config {
         "mismatch".into(),
         ProfileConfig {
             name: "mismatch".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "alt".into(),
             description: None,
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -696,6 +787,7 @@ fn fixture_config(sessions_dir: &Path) -> Config {
             provider: Some("codex".into()),
             profile: Some("default".into()),
             search_mode: SearchMode::FirstPrompt,
+            search_format: SearchFormat::Json,
             terminal_title: None,
         },
         providers,
@@ -704,7 +796,11 @@ fn fixture_config(sessions_dir: &Path) -> Config {
         profiles,
         features: FeatureConfig {
             prompt_assembler: None,
+            rag: RagConfig::default(),
         },
+        retention: RetentionConfig::default(),
+        secrets: IndexMap::new(),
+        hooks: HooksConfig::default(),
     }
 }
 
@@ -836,6 +932,8 @@ fn app_search_and_export_paths() -> Result<()> {
         since: None,
         role: None,
         limit: None,
+        format: None,
+        columns: Vec::new(),
     };
     app.search(&search_cmd)?;
 
@@ -897,6 +995,8 @@ fn app_search_hides_subagent_sessions_before_applying_limit() -> Result<()> {
         since: None,
         role: None,
         limit: Some(1),
+        format: None,
+        columns: Vec::new(),
     };
     app.search(&search_cmd)?;
     Ok(())
@@ -957,6 +1057,8 @@ fn app_search_surfaces_db_error_when_sessions_table_missing() -> Result<()> {
         since: None,
         role: None,
         limit: None,
+        format: None,
+        columns: Vec::new(),
     };
     let err = app
         .search(&cmd)
@@ -984,6 +1086,8 @@ fn app_search_surfaces_db_error_when_first_prompt_query_fails() -> Result<()> {
         since: None,
         role: None,
         limit: None,
+        format: None,
+        columns: Vec::new(),
     };
     let err = app
         .search(&cmd)
@@ -1011,6 +1115,8 @@ fn app_search_surfaces_db_error_when_full_text_query_fails() -> Result<()> {
         since: None,
         role: None,
         limit: None,
+        format: None,
+        columns: Vec::new(),
     };
     let err = app
         .search(&cmd)
@@ -1029,7 +1135,7 @@ fn app_export_surfaces_db_error_when_messages_table_missing() -> Result<()> {
     let (_temp, app, summary) = build_app_fixture(Vec::new())?;
     let db_path = app.loaded.directories.data_dir.join("tx.sqlite3");
     let conn = rusqlite::Connection::open(db_path)?;
-    conn.execute("DROP TABLE messages", [])?;
+    conn.execute("DROP VIEW messages", [])?;
 
     let cmd = ExportCommand {
         session_id: summary.id,
@@ -1056,6 +1162,8 @@ fn app_search_rejects_role_without_full_text() -> Result<()> {
         since: None,
         role: Some("user".into()),
         limit: None,
+        format: None,
+        columns: Vec::new(),
     };
     let err = app.search(&cmd).expect_err("role requires full-text");
     assert!(err.to_string().contains("--role requires --full-text"));
@@ -1072,6 +1180,7 @@ fn app_rag_search_rejects_inverted_time_window() -> Result<()> {
         tool: None,
         since: Some(20),
         until: Some(10),
+        model: None,
         json: false,
     };
     let err = app
@@ -1093,18 +1202,21 @@ fn app_rag_index_and_search_paths() -> Result<()> {
         session: None,
         reindex: false,
         batch_size: 8,
+        model: None,
     }))?;
     app.rag(&RagCommand::Index(RagIndexCommand {
         since: None,
         session: Some(summary.id.clone()),
         reindex: false,
         batch_size: 8,
+        model: None,
     }))?;
     app.rag(&RagCommand::Index(RagIndexCommand {
         since: None,
         session: Some(summary.id.clone()),
         reindex: true,
         batch_size: 8,
+        model: None,
     }))?;
 
     app.rag(&RagCommand::Search(RagSearchCommand {
@@ -1114,6 +1226,7 @@ fn app_rag_index_and_search_paths() -> Result<()> {
         tool: Some("event_msg".to_string()),
         since: None,
         until: None,
+        model: None,
         json: true,
     }))?;
 
@@ -1124,6 +1237,7 @@ fn app_rag_index_and_search_paths() -> Result<()> {
         tool: None,
         since: None,
         until: None,
+        model: None,
         json: false,
     }))?;
     Ok(())
@@ -1141,6 +1255,7 @@ fn app_rag_search_plain_output_and_preview_trimming() -> Result<()> {
         session: Some(summary.id.clone()),
         reindex: true,
         batch_size: 8,
+        model: None,
     }))?;
 
     app.rag(&RagCommand::Search(RagSearchCommand {
@@ -1150,6 +1265,7 @@ fn app_rag_search_plain_output_and_preview_trimming() -> Result<()> {
         tool: Some("event_msg".to_string()),
         since: None,
         until: None,
+        model: None,
         json: false,
     }))?;
 
@@ -1168,17 +1284,93 @@ fn app_resume_accepts_plain_session_id() -> Result<()> {
         profile: Some("default".into()),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
+        fresh: false,
         emit_command: true,
         emit_json: false,
         vars: Vec::new(),
         dry_run: false,
+        with_context: None,
+        context_k: 5,
+        context_model: None,
+        preview_context: false,
         provider_args: Vec::new(),
     };
     app.resume(&cmd)?;
     Ok(())
 }
 
+#[test]
+fn resume_launch_restores_recorded_settings_unless_overridden() -> Result<()> {
+    let (temp, app, summary) = build_app_fixture(Vec::new())?;
+    let mut cmd = ResumeCommand {
+        session_id: summary.id.clone(),
+        profile: None,
+        pre_snippets: Vec::new(),
+        post_snippets: Vec::new(),
+        wrap: Vec::new(),
+        fresh: false,
+        emit_command: true,
+        emit_json: false,
+        vars: Vec::new(),
+        dry_run: false,
+        with_context: None,
+        context_k: 5,
+        context_model: None,
+        preview_context: false,
+        provider_args: Vec::new(),
+    };
+    let (profile, launch) = app.resume_launch(&cmd, &summary)?;
+    assert_eq!(profile, None);
+    assert!(launch.is_empty());
+
+    let mut plan = hook_plan(temp.path(), "codex", HooksConfig::default());
+    plan.profile = Some("default".into());
+    plan.launch = LaunchOptions {
+        pre: vec!["pre".into(), "gone".into()],
+        post: Vec::new(),
+        wrap: vec!["execwrap".into()],
+        vars: [("topic".to_string(), "retries".to_string())].into(),
+    };
+    app.db.record_run(&NewRun {
+        started_ms: 1_000,
+        plan: &plan,
+        exit_code: Some(0),
+        duration_ms: 10,
+        session_path: Some(&summary.path),
+    })?;
+
+    let recorded =
+        recorded_launch(&app.db, &app.loaded.config, &summary)?.expect("recorded launch");
+    assert_eq!(recorded.dropped, ["pre snippet 'gone'"]);
+
+    let (profile, launch) = app.resume_launch(&cmd, &summary)?;
+    assert_eq!(profile.as_deref(), Some("default"));
+    assert_eq!(launch.pre, ["pre"]);
+    assert_eq!(launch.wrap, ["execwrap"]);
+    assert_eq!(
+        launch.vars.get("topic").map(String::as_str),
+        Some("retries")
+    );
+
+    cmd.wrap = vec!["wrap".into()];
+    cmd.vars = vec!["topic=caching".into()];
+    let (_, launch) = app.resume_launch(&cmd, &summary)?;
+    assert_eq!(launch.pre, ["pre"]);
+    assert_eq!(launch.wrap, ["wrap"]);
+    assert_eq!(
+        launch.vars.get("topic").map(String::as_str),
+        Some("caching")
+    );
+
+    cmd.fresh = true;
+    let (profile, launch) = app.resume_launch(&cmd, &summary)?;
+    assert_eq!(profile, None);
+    assert!(launch.pre.is_empty());
+    assert_eq!(launch.wrap, ["wrap"]);
+    Ok(())
+}
+
 #[test]
 fn app_resume_handles_provider_without_resume_metadata() -> Result<()> {
     let (_temp, mut app, mut summary) = build_app_fixture(Vec::new())?;
@@ -1210,11 +1402,16 @@ fn app_resume_handles_provider_without_resume_metadata() -> Result<()> {
         profile: Some("mismatch".into()),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
+        fresh: false,
         emit_command: true,
         emit_json: false,
         vars: Vec::new(),
         dry_run: false,
+        with_context: None,
+        context_k: 5,
+        context_model: None,
+        preview_context: false,
         provider_args: Vec::new(),
     };
     app.resume(&cmd)?;
@@ -1229,11 +1426,16 @@ fn app_resume_rejects_invalid_vars() -> Result<()> {
         profile: Some("default".into()),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
+        fresh: false,
         emit_command: true,
         emit_json: false,
         vars: vec!["INVALID".into()],
         dry_run: false,
+        with_context: None,
+        context_k: 5,
+        context_model: None,
+        preview_context: false,
         provider_args: Vec::new(),
     };
 
@@ -1290,11 +1492,16 @@ fn resume_uses_current_dir_when_session_path_has_no_parent() -> Result<()> {
         profile: Some("default".into()),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
+        fresh: false,
         emit_command: true,
         emit_json: false,
         vars: Vec::new(),
         dry_run: false,
+        with_context: None,
+        context_k: 5,
+        context_model: None,
+        preview_context: false,
         provider_args: Vec::new(),
     };
 
@@ -1311,11 +1518,16 @@ fn resume_executes_pipeline_when_not_emitting() -> Result<()> {
         profile: None,
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
+        fresh: false,
         emit_command: false,
         emit_json: false,
         vars: Vec::new(),
         dry_run: false,
+        with_context: None,
+        context_k: 5,
+        context_model: None,
+        preview_context: false,
         provider_args: Vec::new(),
     };
 
@@ -1416,11 +1628,16 @@ fn app_resume_and_config_commands() -> Result<()> {
         profile: Some("default".into()),
         pre_snippets: vec!["pre".into()],
         post_snippets: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
+        fresh: false,
         emit_command: true,
         emit_json: true,
         vars: vec!["KEY=value".into()],
         dry_run: false,
+        with_context: None,
+        context_k: 5,
+        context_model: None,
+        preview_context: false,
         provider_args: vec!["--flag".into()],
     };
     app.resume(&resume_cmd)?;
@@ -1430,7 +1647,8 @@ fn app_resume_and_config_commands() -> Result<()> {
     assert!(err.to_string().contains("provider mismatch"));
 
     app.config(&ConfigCommand::List)?;
-    app.config(&ConfigCommand::Dump)?;
+    app.config(&ConfigCommand::Dump(ConfigDumpCommand { resolved: false }))?;
+    app.config(&ConfigCommand::Dump(ConfigDumpCommand { resolved: true }))?;
     app.config(&ConfigCommand::Where)?;
     app.config(&ConfigCommand::Default(ConfigDefaultCommand { raw: false }))?;
     Ok(())
@@ -1516,11 +1734,16 @@ fn app_resume_requires_emit_json_companion_flag() -> Result<()> {
         profile: Some("default".into()),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
+        fresh: false,
         emit_command: false,
         emit_json: true,
         vars: Vec::new(),
         dry_run: false,
+        with_context: None,
+        context_k: 5,
+        context_model: None,
+        preview_context: false,
         provider_args: Vec::new(),
     };
     let err = app
@@ -1562,13 +1785,21 @@ fn execute_plan_shell_succeeds() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     execute_plan(&plan)?;
@@ -1608,13 +1839,21 @@ fn execute_plan_shell_propagates_failure() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     let err = execute_plan(&plan).unwrap_err();
@@ -1636,6 +1875,137 @@ fn execute_plan_shell_propagates_failure() -> Result<()> {
     Ok(())
 }
 
+#[cfg(unix)]
+fn hook_plan(cwd: &Path, command: &str, hooks: HooksConfig) -> PipelinePlan {
+    PipelinePlan {
+        pipeline: command.into(),
+        display: command.into(),
+        friendly_display: command.into(),
+        env: vec![("TOKEN".into(), "***".into())],
+        invocation: Invocation::Shell {
+            command: command.into(),
+        },
+        provider: "codex".into(),
+        terminal_title: "codex".into(),
+        pre_snippets: Vec::new(),
+        post_snippets: Vec::new(),
+        wrappers: Vec::new(),
+        needs_stdin_prompt: false,
+        uses_capture_arg: false,
+        capture_has_pre_commands: false,
+        stdin_prompt_label: None,
+        cwd: cwd.to_path_buf(),
+        prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks,
+        session_id: Some("sess-42".into()),
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: vec!["provider codex".into()],
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn execute_plan_runs_hooks_without_masking_provider_status() -> Result<()> {
+    let _env = ENV_LOCK.lock().unwrap();
+    let _shell = EnvOverride::set_var("SHELL", "/bin/sh");
+    let temp = TempDir::new()?;
+    let hooks = HooksConfig {
+        before: vec![r#"echo "$TX_HOOK $TX_SESSION_ID $TX_PROVIDER" > before.txt"#.into()],
+        after: vec![
+            "exit 9".into(),
+            r#"echo "$TX_HOOK $TX_EXIT_CODE" > after.txt; printf %s "$TX_PLAN" > plan.json"#.into(),
+        ],
+        on_failure: vec![r#"echo "$TX_HOOK $TX_EXIT_CODE $TX_DURATION_MS" > failure.txt"#.into()],
+    };
+    let plan = hook_plan(temp.path(), "exit 3", hooks);
+
+    let err = execute_plan_with_prompt(&plan, false, None, |_| Ok(None)).unwrap_err();
+    assert_eq!(err.to_string(), "command exited with status exit status: 3");
+
+    let read = |name: &str| fs::read_to_string(temp.path().join(name));
+    assert_eq!(read("before.txt")?, "before sess-42 codex\n");
+    assert_eq!(read("after.txt")?, "after 3\n");
+    let failure = read("failure.txt")?;
+    let mut fields = failure.split_whitespace();
+    assert_eq!(fields.next(), Some("on_failure"));
+    assert_eq!(fields.next(), Some("3"));
+    assert!(fields.next().is_some_and(|ms| ms.parse::<u128>().is_ok()));
+
+    let payload: serde_json::Value = serde_json::from_str(&read("plan.json")?)?;
+    assert_eq!(payload["command"], "exit 3");
+    assert_eq!(payload["env"][0][1], "***");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn execute_plan_before_hook_failure_cancels_launch() -> Result<()> {
+    let _env = ENV_LOCK.lock().unwrap();
+    let _shell = EnvOverride::set_var("SHELL", "/bin/sh");
+    let temp = TempDir::new()?;
+    let hooks = HooksConfig {
+        before: vec!["exit 4".into()],
+        after: vec!["touch after.txt".into()],
+        on_failure: Vec::new(),
+    };
+    let plan = hook_plan(temp.path(), "touch launched.txt", hooks);
+
+    let err = execute_plan_with_prompt(&plan, false, None, |_| Ok(None)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "before hook 'exit 4' exited with status exit status: 4"
+    );
+    assert!(!temp.path().join("launched.txt").exists());
+    assert!(!temp.path().join("after.txt").exists());
+
+    let hooks = HooksConfig {
+        before: Vec::new(),
+        after: vec![r#"echo "$TX_EXIT_CODE" > after.txt"#.into()],
+        on_failure: vec!["touch failure.txt".into()],
+    };
+    execute_plan_with_prompt(
+        &hook_plan(temp.path(), "touch launched.txt", hooks),
+        false,
+        None,
+        |_| Ok(None),
+    )?;
+    assert!(temp.path().join("launched.txt").exists());
+    assert!(!temp.path().join("failure.txt").exists());
+    assert_eq!(fs::read_to_string(temp.path().join("after.txt"))?, "0\n");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn execute_plan_runs_failure_hooks_when_launch_fails() -> Result<()> {
+    let _env = ENV_LOCK.lock().unwrap();
+    let _shell = EnvOverride::set_var("SHELL", "/bin/sh");
+    let temp = TempDir::new()?;
+    let hooks = HooksConfig {
+        before: Vec::new(),
+        after: vec![r#"echo "$TX_HOOK ${TX_EXIT_CODE-unset}" > after.txt"#.into()],
+        on_failure: vec!["touch failure.txt".into()],
+    };
+    let mut plan = hook_plan(temp.path(), "missing-provider", hooks);
+    plan.invocation = Invocation::Exec {
+        argv: vec![temp.path().join("missing-provider").display().to_string()],
+        env_at: None,
+    };
+
+    let err = execute_plan_with_prompt(&plan, false, None, |_| Ok(None)).unwrap_err();
+    assert_eq!(err.to_string(), "failed to launch 'missing-provider'");
+    assert!(temp.path().join("failure.txt").exists());
+    assert_eq!(
+        fs::read_to_string(temp.path().join("after.txt"))?,
+        "after unset\n"
+    );
+    Ok(())
+}
+
 #[test]
 fn execute_plan_shell_captures_prompt_input() -> Result<()> {
     let _env = ENV_LOCK.lock().unwrap();
@@ -1671,13 +2041,21 @@ fn execute_plan_shell_captures_prompt_input() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: true,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: Some("Prompt".into()),
         cwd: temp.path().to_path_buf(),
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     execute_plan_with_prompt(&plan, true, None, |_| Ok(Some("payload".into())))?;
@@ -1719,20 +2097,123 @@ fn execute_plan_with_stdin_prompt_reads_input_for_terminal_stdin() -> Result<()>
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: true,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: Some("Prompt".into()),
         cwd: temp.path().to_path_buf(),
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
-    execute_plan_with_stdin_prompt(&plan, true, |_| Ok("payload-helper".into()))?;
+    execute_plan_with_stdin_prompt(
+        &plan,
+        true,
+        |_| Ok("payload-helper".into()),
+        || Err(eyre!("piped stdin should not be read for a terminal")),
+    )?;
     assert_eq!(std::fs::read_to_string(output.path())?, "payload-helper");
     Ok(())
 }
 
+#[cfg(unix)]
+#[test]
+fn execute_plan_with_stdin_prompt_prepends_retrieved_context() -> Result<()> {
+    let _env = ENV_LOCK.lock().unwrap();
+    let temp = TempDir::new()?;
+    let output = temp.child("captured-with-context.txt");
+    let script = temp.child("capture-context.sh");
+    script.write_str("#!/bin/sh\nprintf '%s' \"$TX_CAPTURE_STDIN_DATA\" > \"$1\"\n")?;
+    fs::set_permissions(script.path(), fs::Permissions::from_mode(0o755))?;
+
+    let command = format!("{} {}", script.path().display(), output.path().display());
+    let plan = PipelinePlan {
+        pipeline: command.clone(),
+        display: "capture-context".into(),
+        friendly_display: "capture-context".into(),
+        env: Vec::new(),
+        invocation: Invocation::Shell { command },
+        provider: "codex".into(),
+        terminal_title: "codex".into(),
+        pre_snippets: Vec::new(),
+        post_snippets: Vec::new(),
+        wrappers: Vec::new(),
+        needs_stdin_prompt: false,
+        uses_capture_arg: true,
+        capture_has_pre_commands: false,
+        stdin_prompt_label: None,
+        cwd: temp.path().to_path_buf(),
+        prompt_assembler: None,
+        context: Some("Context retrieved from past sessions:\n[1] session=s1\n".into()),
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
+    };
+
+    let mut labels = Vec::new();
+    execute_plan_with_stdin_prompt(
+        &plan,
+        true,
+        |label| {
+            labels.push(label.map(str::to_string));
+            Ok("fix the retry loop\n".into())
+        },
+        || Err(eyre!("piped stdin should not be read for a terminal")),
+    )?;
+    assert_eq!(labels, vec![Some("codex".to_string())]);
+    assert_eq!(
+        std::fs::read_to_string(output.path())?,
+        "Context retrieved from past sessions:\n[1] session=s1\n\nfix the retry loop\n"
+    );
+
+    execute_plan_with_stdin_prompt(
+        &plan,
+        false,
+        |_| {
+            Err(eyre!(
+                "stdin should not be prompted when it is not a terminal"
+            ))
+        },
+        || Ok("fix X\nfrom a pipe\n".into()),
+    )?;
+    assert_eq!(
+        std::fs::read_to_string(output.path())?,
+        "Context retrieved from past sessions:\n[1] session=s1\n\nfix X\nfrom a pipe\n"
+    );
+
+    execute_plan_with_stdin_prompt(
+        &plan,
+        false,
+        |_| Err(eyre!("stdin should not be prompted")),
+        || Ok(String::new()),
+    )?;
+    assert_eq!(
+        std::fs::read_to_string(output.path())?,
+        "Context retrieved from past sessions:\n[1] session=s1\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn prepend_context_enforces_prompt_limit() {
+    let context = "c".repeat(DEFAULT_PROMPT_LIMIT);
+    let err = prepend_context(&context, "more").expect_err("oversized prompt should fail");
+    assert!(err.to_string().contains("exceeds configured limit"));
+    assert_eq!(prepend_context("ctx\n", "  ").unwrap(), "ctx\n");
+}
+
 #[cfg(unix)]
 #[test]
 fn execute_plan_uses_prompt_assembler_output() -> Result<()> {
@@ -1775,7 +2256,7 @@ fn execute_plan_uses_prompt_assembler_output() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
@@ -1785,6 +2266,14 @@ fn execute_plan_uses_prompt_assembler_output() -> Result<()> {
             name: "demo".into(),
             args: vec!["value".into()],
         }),
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     execute_plan(&plan)?;
@@ -1829,13 +2318,21 @@ fn execute_plan_emits_capture_warning_for_internal_pipeline() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: true,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd: std::env::current_dir()?,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     execute_plan_with_prompt(&plan, true, None, |_| Ok(None))?;
@@ -1870,13 +2367,21 @@ fn should_warn_capture_respects_pre_pipeline() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: true,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd: cwd.clone(),
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     assert!(should_warn_capture(&base, None, true));
@@ -1901,24 +2406,34 @@ fn execute_plan_exec_succeeds_and_failures() -> Result<()> {
         env: Vec::new(),
         invocation: Invocation::Exec {
             argv: vec!["/bin/sh".into(), "-c".into(), "exit 0".into()],
+            env_at: None,
         },
         provider: "codex".into(),
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd: cwd.clone(),
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     execute_plan(&success_plan)?;
 
     success_plan.invocation = Invocation::Exec {
         argv: vec!["/bin/sh".into(), "-c".into(), "exit 5".into()],
+        env_at: None,
     };
     let err = execute_plan(&success_plan).unwrap_err();
     assert!(
@@ -1926,6 +2441,27 @@ fn execute_plan_exec_succeeds_and_failures() -> Result<()> {
         "unexpected error: {err:?}"
     );
 
+    // The env file reaches the wrapper by path; a failed wrapper leaves it
+    // for tx to delete.
+    let seen = TempDir::new()?;
+    let seen_path = seen.path().join("env-file");
+    success_plan.env = vec![("TX_WRAPPED".into(), "yes".into())];
+    success_plan.invocation = Invocation::Exec {
+        argv: vec![
+            "/bin/sh".into(),
+            "-c".into(),
+            r#"grep -qx 'export TX_WRAPPED=yes' "$1" && printf %s "$1" > "$2" && exit 7"#.into(),
+            "sh".into(),
+            seen_path.display().to_string(),
+        ],
+        env_at: Some(4),
+    };
+    let err = execute_plan(&success_plan).unwrap_err();
+    assert!(err.to_string().contains("exited with status"), "{err:?}");
+    let env_file = fs::read_to_string(&seen_path)?;
+    assert!(!env_file.contains("yes"));
+    assert!(!Path::new(&env_file).exists());
+
     Ok(())
 }
 
@@ -1937,18 +2473,29 @@ fn execute_plan_exec_rejects_empty_argv() -> Result<()> {
         display: "exec empty".into(),
         friendly_display: "exec empty".into(),
         env: Vec::new(),
-        invocation: Invocation::Exec { argv: Vec::new() },
+        invocation: Invocation::Exec {
+            argv: Vec::new(),
+            env_at: None,
+        },
         provider: "codex".into(),
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     let err = execute_plan(&plan).expect_err("empty argv should error");
@@ -1975,13 +2522,21 @@ fn execute_plan_with_prompt_surfaces_prompt_callback_error() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: true,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: Some("Prompt".into()),
         cwd,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     let err = execute_plan_with_prompt(&plan, true, None, |_label| Err(eyre!("prompt failed")))
@@ -2021,13 +2576,21 @@ fn emit_command_covers_all_modes() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     emit_command(&plan, EmitMode::Json)?;
@@ -2098,13 +2661,21 @@ fn emit_command_with_writer_propagates_flush_errors() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
     let mut failing_flush = FailingWriter {
@@ -2145,6 +2716,8 @@ fn search_requires_full_text_for_role() -> Result<()> {
         since: None,
         role: Some("user".into()),
         limit: None,
+        format: None,
+        columns: Vec::new(),
     };
     let err = app
         .search(&cmd)
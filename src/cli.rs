@@ -2,6 +2,10 @@ use std::path::PathBuf;
 
 use clap::{ArgAction, Args, Parser, Subcommand};
 
+use crate::commands::db::QueryFormat;
+use crate::commands::search::SearchColumn;
+use crate::config::model::SearchFormat;
+
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None, name = "tx", bin_name = "tx")]
 pub struct Cli {
@@ -26,6 +30,14 @@ pub enum Command {
     Resume(ResumeCommand),
     /// Export a session transcript.
     Export(ExportCommand),
+    /// Find past sessions semantically similar to a session.
+    Related(RelatedCommand),
+    /// List recorded provider launches, most recent first.
+    History(HistoryCommand),
+    /// Launch a recorded run again with the same plan.
+    Rerun(RerunCommand),
+    /// Run a plan written by `--emit-json`.
+    Exec(ExecCommand),
     /// Show usage statistics.
     #[command(subcommand)]
     Stats(StatsCommand),
@@ -35,6 +47,8 @@ pub enum Command {
     /// Manage the local database.
     #[command(subcommand)]
     Db(DbCommand),
+    /// Delete index data for sessions outside the `[retention]` policy.
+    Prune(PruneCommand),
     /// Experimental semantic retrieval over indexed session history.
     #[command(subcommand)]
     Rag(RagCommand),
@@ -66,24 +80,34 @@ pub struct SearchCommand {
     /// Maximum number of sessions to return.
     #[arg(long)]
     pub limit: Option<usize>,
+    /// Output format: json, ndjson, table, tsv, or ids (defaults to `search_format`).
+    #[arg(long, value_parser = parse_search_format)]
+    pub format: Option<SearchFormat>,
+    /// Comma-separated columns for table and tsv output.
+    #[arg(long, value_delimiter = ',', value_parser = parse_search_column)]
+    pub columns: Vec<SearchColumn>,
 }
 
 #[derive(Debug, Args)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ResumeCommand {
     /// Session identifier to resume (use 'last' for the most recent session).
     pub session_id: String,
-    /// Optional profile to merge.
+    /// Optional profile to merge (defaults to the profile the session was launched with).
     #[arg(long)]
     pub profile: Option<String>,
-    /// Append an additional pre snippet by name (repeatable).
+    /// Append an additional pre snippet by name (repeatable; replaces the recorded ones).
     #[arg(long = "pre", action = ArgAction::Append)]
     pub pre_snippets: Vec<String>,
-    /// Append an additional post snippet by name (repeatable).
+    /// Append an additional post snippet by name (repeatable; replaces the recorded ones).
     #[arg(long = "post", action = ArgAction::Append)]
     pub post_snippets: Vec<String>,
-    /// Override the wrapper by name.
-    #[arg(long)]
-    pub wrap: Option<String>,
+    /// Override the wrapper by name (repeatable to chain wrappers, outermost first).
+    #[arg(long, action = ArgAction::Append)]
+    pub wrap: Vec<String>,
+    /// Ignore the profile, snippets, wrapper, and vars recorded when the session was launched.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub fresh: bool,
     /// Print the fully-resolved command instead of executing it.
     #[arg(long, action = ArgAction::SetTrue)]
     pub emit_command: bool,
@@ -96,6 +120,18 @@ pub struct ResumeCommand {
     /// Print the final command and exit without running it.
     #[arg(long, action = ArgAction::SetTrue)]
     pub dry_run: bool,
+    /// Semantic query whose top matches from past sessions are prepended to the prompt.
+    #[arg(long, value_name = "QUERY")]
+    pub with_context: Option<String>,
+    /// Number of chunks to retrieve for --with-context.
+    #[arg(long, default_value_t = 5, value_parser = parse_positive_usize)]
+    pub context_k: usize,
+    /// Embedding model to query for --with-context (defaults to `TX_RAG_EMBED_MODEL`).
+    #[arg(long)]
+    pub context_model: Option<String>,
+    /// Print the retrieved context block and exit without launching the provider.
+    #[arg(long, action = ArgAction::SetTrue, requires = "with_context")]
+    pub preview_context: bool,
     /// Arguments forwarded to the provider after `--`.
     #[arg(last = true)]
     pub provider_args: Vec<String>,
@@ -107,6 +143,63 @@ pub struct ExportCommand {
     pub session_id: String,
 }
 
+#[derive(Debug, Args)]
+pub struct RelatedCommand {
+    /// Session identifier to compare against (use 'last' for the most recent session).
+    pub session_id: String,
+    /// Number of related sessions to return.
+    #[arg(long, default_value_t = 10, value_parser = parse_positive_usize)]
+    pub k: usize,
+    /// Embedding model whose vectors to compare (defaults to `TX_RAG_EMBED_MODEL`).
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Emit structured JSON instead of the default text output.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryCommand {
+    /// Maximum number of runs to list.
+    #[arg(long, default_value_t = 20, value_parser = parse_positive_usize)]
+    pub limit: usize,
+    /// Emit structured JSON, including each run's plan, instead of text.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RerunCommand {
+    /// Run identifier from `tx history` (use 'last' for the most recent run).
+    pub run_id: String,
+    /// Print the recorded command and exit without running it.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub dry_run: bool,
+    /// Emit pipeline details as JSON when combined with --dry-run.
+    #[arg(long, action = ArgAction::SetTrue, requires = "dry_run")]
+    pub emit_json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExecCommand {
+    /// Plan file written by `--emit-json`, or `-` to read it from stdin.
+    #[arg(long, value_name = "FILE")]
+    pub plan: PathBuf,
+    /// Validate the plan and the programs it needs without running it.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub check: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PruneCommand {
+    /// List the sessions that would be pruned without deleting anything.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub dry_run: bool,
+    /// Emit structured JSON instead of the default text output.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum StatsCommand {
     /// Show Codex usage statistics.
@@ -117,6 +210,20 @@ pub enum StatsCommand {
 pub enum DbCommand {
     /// Delete the indexed session database.
     Reset(DbResetCommand),
+    /// Copy the database to a file with the `SQLite` online backup API.
+    Backup(DbBackupCommand),
+    /// Rebuild the database file to reclaim free space.
+    Vacuum,
+    /// Verify database integrity and full-text/vector consistency with messages.
+    Check(DbCheckCommand),
+    /// Show table and index sizes, row counts, and per-provider totals.
+    Stats(DbStatsCommand),
+    /// Write locally indexed sessions to a bundle for merging on another machine.
+    ExportBundle(DbExportBundleCommand),
+    /// Merge a bundle exported on another machine; imported sessions are read-only.
+    ImportBundle(DbImportBundleCommand),
+    /// Run a read-only SQL query against the database and print the rows.
+    Query(DbQueryCommand),
 }
 
 #[derive(Debug, Subcommand)]
@@ -125,6 +232,12 @@ pub enum RagCommand {
     Index(RagIndexCommand),
     /// Experimental: run semantic KNN search over indexed history chunks.
     Search(RagSearchCommand),
+    /// Experimental: list embedding models with indexed vectors and their coverage.
+    Models(RagModelsCommand),
+    /// Experimental: report indexed, stale, missing, and orphaned chunks for a model.
+    Status(RagStatusCommand),
+    /// Experimental: cluster indexed sessions into topics labelled by full-text terms.
+    Topics(RagTopicsCommand),
 }
 
 #[derive(Debug, Args)]
@@ -141,6 +254,9 @@ pub struct RagIndexCommand {
     /// Number of chunks to process per embeddings batch.
     #[arg(long, default_value_t = 64, value_parser = parse_positive_usize)]
     pub batch_size: usize,
+    /// Embedding model to index with (defaults to `TX_RAG_EMBED_MODEL`).
+    #[arg(long)]
+    pub model: Option<String>,
 }
 
 #[derive(Debug, Args)]
@@ -163,6 +279,51 @@ pub struct RagSearchCommand {
     /// Only include results at or before this unix timestamp in milliseconds.
     #[arg(long)]
     pub until: Option<i64>,
+    /// Embedding model whose vectors to search (defaults to `TX_RAG_EMBED_MODEL`).
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Emit structured JSON instead of the default text output.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RagStatusCommand {
+    /// Embedding model to report on (defaults to `TX_RAG_EMBED_MODEL`).
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Restrict the report to a single session id.
+    #[arg(long)]
+    pub session: Option<String>,
+    /// List per-session counts in the text output.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub sessions: bool,
+    /// Emit structured JSON instead of the default text output.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RagTopicsCommand {
+    /// Number of topics (defaults to sqrt(sessions / 2), at most 12).
+    #[arg(long, value_parser = parse_positive_usize)]
+    pub k: Option<usize>,
+    /// Embedding model whose vectors to cluster (defaults to `TX_RAG_EMBED_MODEL`).
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Number of label terms per topic.
+    #[arg(long, default_value_t = 5, value_parser = parse_positive_usize)]
+    pub terms: usize,
+    /// Number of sessions listed per topic in the text output.
+    #[arg(long, default_value_t = 5)]
+    pub sessions: usize,
+    /// Emit structured JSON instead of the default text output.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RagModelsCommand {
     /// Emit structured JSON instead of the default text output.
     #[arg(long, action = ArgAction::SetTrue)]
     pub json: bool,
@@ -175,6 +336,57 @@ pub struct DbResetCommand {
     pub yes: bool,
 }
 
+#[derive(Debug, Args)]
+pub struct DbBackupCommand {
+    /// Destination file for the backup.
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+    /// Overwrite the destination if it already exists.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DbCheckCommand {
+    /// Emit structured JSON instead of the default text output.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DbStatsCommand {
+    /// Emit structured JSON instead of the default text output.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DbExportBundleCommand {
+    /// Write the bundle to this file instead of stdout.
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Machine name recorded as the bundle's provenance (defaults to `TX_MACHINE_NAME` or the hostname).
+    #[arg(long, value_name = "NAME")]
+    pub machine: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct DbImportBundleCommand {
+    /// Bundle file to import, or `-` to read it from stdin.
+    #[arg(value_name = "FILE")]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct DbQueryCommand {
+    /// A single read-only SQL statement, e.g. `SELECT * FROM v_sessions LIMIT 5`.
+    #[arg(value_name = "SQL")]
+    pub sql: String,
+    /// Output format: table, json, or csv.
+    #[arg(long, default_value = "table", value_parser = parse_query_format)]
+    pub format: QueryFormat,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum InternalCommand {
     /// Run a provider after capturing stdin as a positional prompt argument.
@@ -222,7 +434,7 @@ pub enum ConfigCommand {
     /// List configured providers, profiles, and wrappers.
     List,
     /// Dump the merged configuration TOML.
-    Dump,
+    Dump(ConfigDumpCommand),
     /// Show configuration search paths.
     Where,
     /// Validate configuration references.
@@ -233,6 +445,13 @@ pub enum ConfigCommand {
     Schema(ConfigSchemaCommand),
 }
 
+#[derive(Debug, Args)]
+pub struct ConfigDumpCommand {
+    /// Show profiles with `extends` flattened and `+` appends applied.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub resolved: bool,
+}
+
 #[derive(Debug, Args)]
 pub struct ConfigDefaultCommand {
     /// Show the raw bundled template without resolving runtime paths.
@@ -262,6 +481,33 @@ fn parse_role(raw: &str) -> Result<String, String> {
     }
 }
 
+fn parse_search_format(raw: &str) -> Result<SearchFormat, String> {
+    SearchFormat::parse(raw).ok_or_else(|| {
+        format!(
+            "invalid format '{raw}', expected one of: {}",
+            SearchFormat::VARIANTS.join(", ")
+        )
+    })
+}
+
+fn parse_query_format(raw: &str) -> Result<QueryFormat, String> {
+    QueryFormat::parse(raw).ok_or_else(|| {
+        format!(
+            "invalid format '{raw}', expected one of: {}",
+            QueryFormat::VARIANTS.join(", ")
+        )
+    })
+}
+
+fn parse_search_column(raw: &str) -> Result<SearchColumn, String> {
+    SearchColumn::parse(raw).ok_or_else(|| {
+        format!(
+            "invalid column '{raw}', expected one of: {}",
+            SearchColumn::VARIANTS.join(", ")
+        )
+    })
+}
+
 fn parse_positive_usize(raw: &str) -> Result<usize, String> {
     let value = raw
         .parse::<usize>()
@@ -355,6 +601,41 @@ mod tests {
         assert!(message.contains("invalid duration 'later'"));
     }
 
+    #[test]
+    fn parse_search_format_and_columns() {
+        let cli = Cli::try_parse_from([
+            "tx",
+            "search",
+            "--format",
+            "TSV",
+            "--columns",
+            "id,last_active,snippet",
+        ])
+        .expect("parse search command");
+
+        let cmd = cli.command.and_then(into_search).expect("search command");
+        assert_eq!(cmd.format, Some(SearchFormat::Tsv));
+        assert_eq!(
+            cmd.columns,
+            vec![
+                SearchColumn::Id,
+                SearchColumn::LastActive,
+                SearchColumn::Snippet
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_search_rejects_unknown_format_and_column() {
+        let err = Cli::try_parse_from(["tx", "search", "--format", "yaml"])
+            .expect_err("unknown format should fail");
+        assert!(err.to_string().contains("invalid format 'yaml'"));
+
+        let err = Cli::try_parse_from(["tx", "search", "--columns", "id,cost"])
+            .expect_err("unknown column should fail");
+        assert!(err.to_string().contains("invalid column 'cost'"));
+    }
+
     #[test]
     fn parse_role_accepts_assistant() {
         let role = parse_role("assistant").expect("assistant is valid");
@@ -380,6 +661,45 @@ mod tests {
         assert_eq!(cmd.provider_args, vec!["--flag", "value"]);
     }
 
+    #[test]
+    fn parse_resume_with_context_flags() {
+        let cli = Cli::try_parse_from([
+            "tx",
+            "resume",
+            "sess-1",
+            "--with-context",
+            "retry logic",
+            "--context-k",
+            "3",
+            "--preview-context",
+        ])
+        .expect("parse resume with context");
+
+        let cmd = cli.command.and_then(into_resume).expect("resume command");
+        assert_eq!(cmd.with_context.as_deref(), Some("retry logic"));
+        assert_eq!(cmd.context_k, 3);
+        assert!(cmd.preview_context);
+
+        let err = Cli::try_parse_from(["tx", "resume", "sess-1", "--preview-context"])
+            .expect_err("preview requires a query");
+        assert!(err.to_string().contains("--with-context"));
+    }
+
+    #[test]
+    fn parse_related_command() {
+        let cli = Cli::try_parse_from(["tx", "related", "sess-1", "--k", "3", "--json"])
+            .expect("parse related");
+        assert!(cli.command.is_some_and(|command| matches!(
+            command,
+            Command::Related(RelatedCommand {
+                ref session_id,
+                k: 3,
+                model: None,
+                json: true,
+            }) if session_id == "sess-1"
+        )));
+    }
+
     #[test]
     fn parse_config_default_raw_flag() {
         let cli = Cli::try_parse_from(["tx", "config", "default", "--raw"])
@@ -413,6 +733,8 @@ mod tests {
             "--reindex",
             "--batch-size",
             "32",
+            "--model",
+            "text-embedding-3-large",
         ])
         .expect("parse rag index");
 
@@ -422,8 +744,9 @@ mod tests {
                 since: Some(1000),
                 session: Some(_),
                 reindex: true,
-                batch_size: 32
-            }))
+                batch_size: 32,
+                model: Some(ref model),
+            })) if model == "text-embedding-3-large"
         )));
     }
 
@@ -462,6 +785,37 @@ mod tests {
         assert!(cmd.json);
     }
 
+    #[test]
+    fn parse_rag_models_command() {
+        let cli = Cli::try_parse_from(["tx", "rag", "models", "--json"]).expect("parse rag models");
+        assert!(matches!(
+            cli.command,
+            Some(Command::Rag(RagCommand::Models(RagModelsCommand {
+                json: true
+            })))
+        ));
+    }
+
+    #[test]
+    fn parse_rag_status_command() {
+        let cli = Cli::try_parse_from([
+            "tx",
+            "rag",
+            "status",
+            "--model",
+            "text-embedding-3-large",
+            "--sessions",
+        ])
+        .expect("parse rag status");
+        let Some(Command::Rag(RagCommand::Status(cmd))) = cli.command else {
+            panic!("expected rag status command");
+        };
+        assert_eq!(cmd.model.as_deref(), Some("text-embedding-3-large"));
+        assert!(cmd.session.is_none());
+        assert!(cmd.sessions);
+        assert!(!cmd.json);
+    }
+
     #[test]
     fn parse_rag_search_rejects_zero_k() {
         let err = Cli::try_parse_from([
@@ -1,23 +1,524 @@
+use std::fmt::Write as _;
 use std::fs;
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use color_eyre::Result;
 use color_eyre::eyre::{Context, eyre};
+use rusqlite::types::Value as SqlValue;
+use serde_json::{Value, json};
+use unicode_width::UnicodeWidthStr;
 
-use crate::cli::{DbCommand, DbResetCommand};
+use crate::cli::{
+    DbBackupCommand, DbCheckCommand, DbCommand, DbExportBundleCommand, DbImportBundleCommand,
+    DbQueryCommand, DbResetCommand, DbStatsCommand,
+};
+use crate::commands::search::truncate_to_width;
 use crate::config;
+use crate::db::{Database, QueryResult};
+use crate::rag::prune_embedding_cache;
 
 const DB_FILENAME: &str = "tx.sqlite3";
+const COLUMN_GAP: &str = "  ";
+/// Widest a `tx db query` table cell gets before it is truncated.
+const MAX_CELL_WIDTH: usize = 60;
+
+/// Output formats for `tx db query`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl QueryFormat {
+    pub const VARIANTS: [&'static str; 3] = ["table", "json", "csv"];
+
+    #[must_use]
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "table" => Some(QueryFormat::Table),
+            "json" => Some(QueryFormat::Json),
+            "csv" => Some(QueryFormat::Csv),
+            _ => None,
+        }
+    }
+}
 
 /// Execute database maintenance commands.
 ///
 /// # Errors
 ///
-/// Returns an error if configuration loading or file removal fails.
+/// Returns an error if configuration loading, file removal, or a maintenance
+/// operation fails, or if `check` finds problems.
 pub fn run(cmd: &DbCommand, config_dir: Option<&Path>, quiet: bool) -> Result<()> {
     match cmd {
         DbCommand::Reset(cmd) => reset(config_dir, quiet, cmd),
+        DbCommand::Backup(cmd) => backup(config_dir, quiet, cmd),
+        DbCommand::Vacuum => vacuum(config_dir, quiet),
+        DbCommand::Check(cmd) => check(config_dir, cmd),
+        DbCommand::Stats(cmd) => stats(config_dir, cmd),
+        DbCommand::ExportBundle(cmd) => export_bundle(config_dir, quiet, cmd),
+        DbCommand::ImportBundle(cmd) => import_bundle(config_dir, quiet, cmd),
+        DbCommand::Query(cmd) => query(config_dir, cmd),
+    }
+}
+
+fn database_path(config_dir: Option<&Path>) -> Result<PathBuf> {
+    let loaded = config::load(config_dir)?;
+    Ok(loaded.directories.data_dir.join(DB_FILENAME))
+}
+
+fn open_existing(config_dir: Option<&Path>) -> Result<(PathBuf, Database)> {
+    let db_path = database_path(config_dir)?;
+    if !db_path.exists() {
+        return Err(eyre!("database not found at {}", db_path.display()));
+    }
+    let db = Database::open(&db_path)?;
+    Ok((db_path, db))
+}
+
+/// Size of the database file plus its write-ahead log.
+fn on_disk_size(db_path: &Path) -> u64 {
+    [db_path.to_path_buf(), db_path.with_extension("sqlite3-wal")]
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+/// Name recorded as the provenance of exported sessions.
+fn machine_name(explicit: Option<&str>) -> String {
+    [
+        explicit.map(ToString::to_string),
+        std::env::var("TX_MACHINE_NAME").ok(),
+        std::env::var("HOSTNAME").ok(),
+        fs::read_to_string("/etc/hostname").ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|name| name.trim().to_string())
+    .find(|name| !name.is_empty())
+    .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn backup(config_dir: Option<&Path>, quiet: bool, cmd: &DbBackupCommand) -> Result<()> {
+    let (db_path, db) = open_existing(config_dir)?;
+    ensure_not_live_database(&db_path, &cmd.path)?;
+    if cmd.path.exists() {
+        if !cmd.force {
+            return Err(eyre!(
+                "{} already exists; pass --force to overwrite it",
+                cmd.path.display()
+            ));
+        }
+        fs::remove_file(&cmd.path)
+            .with_context(|| format!("failed to remove {}", cmd.path.display()))?;
+    }
+    if let Some(parent) = cmd
+        .path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    db.backup_to(&cmd.path)?;
+
+    if !quiet {
+        println!(
+            "Backed up {} to {} ({})",
+            db_path.display(),
+            cmd.path.display(),
+            format_bytes(on_disk_size(&cmd.path))
+        );
+    }
+    Ok(())
+}
+
+/// Refuse backup targets that resolve to the live database or its WAL and
+/// shared-memory files, which `--force` would otherwise delete.
+fn ensure_not_live_database(db_path: &Path, target: &Path) -> Result<()> {
+    let live = canonical_path(db_path);
+    let target = canonical_path(target);
+    let is_live = ["", "-wal", "-shm"].iter().any(|suffix| {
+        let mut file = live.clone().into_os_string();
+        file.push(suffix);
+        file == target.as_os_str()
+    });
+    if is_live {
+        return Err(eyre!(
+            "{} is the live database or one of its files; choose another backup path",
+            target.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Canonical form of `path`, resolving only the parent directory when the file
+/// does not exist yet.
+fn canonical_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    parent
+        .canonicalize()
+        .map_or_else(|_| path.to_path_buf(), |parent| parent.join(name))
+}
+
+fn vacuum(config_dir: Option<&Path>, quiet: bool) -> Result<()> {
+    let (db_path, mut db) = open_existing(config_dir)?;
+    let before = on_disk_size(&db_path);
+    let cached = prune_embedding_cache(&mut db)?;
+    db.vacuum()?;
+    let after = on_disk_size(&db_path);
+
+    if !quiet {
+        if cached > 0 {
+            println!("Dropped {cached} unused cached embedding(s).");
+        }
+        println!(
+            "Vacuumed {}: {} -> {}",
+            db_path.display(),
+            format_bytes(before),
+            format_bytes(after)
+        );
+    }
+    Ok(())
+}
+
+fn check(config_dir: Option<&Path>, cmd: &DbCheckCommand) -> Result<()> {
+    let (_db_path, db) = open_existing(config_dir)?;
+    let report = db.check()?;
+
+    if cmd.json {
+        let payload = json!({
+            "ok": report.problem_count() == 0,
+            "checks": report
+                .checks
+                .iter()
+                .map(|check| json!({ "name": check.name, "problems": check.problems }))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+    } else {
+        for check in &report.checks {
+            if check.problems.is_empty() {
+                println!("{}: ok", check.name);
+            } else {
+                println!("{}: {} problem(s)", check.name, check.problems.len());
+                for problem in &check.problems {
+                    println!("  {problem}");
+                }
+            }
+        }
+    }
+
+    match report.problem_count() {
+        0 => Ok(()),
+        count => Err(eyre!("database check found {count} problem(s)")),
+    }
+}
+
+fn stats(config_dir: Option<&Path>, cmd: &DbStatsCommand) -> Result<()> {
+    let (db_path, db) = open_existing(config_dir)?;
+    let stats = db.stats()?;
+    let file_bytes = fs::metadata(&db_path).map_or(0, |meta| meta.len());
+    let wal_bytes =
+        fs::metadata(db_path.with_extension("sqlite3-wal")).map_or(0, |meta| meta.len());
+
+    if cmd.json {
+        let payload = json!({
+            "path": db_path.display().to_string(),
+            "file_bytes": file_bytes,
+            "wal_bytes": wal_bytes,
+            "page_size": stats.page_size,
+            "page_count": stats.page_count,
+            "freelist_count": stats.freelist_count,
+            "objects": stats
+                .objects
+                .iter()
+                .map(|object| json!({
+                    "name": object.name,
+                    "kind": object.kind,
+                    "table": object.table,
+                    "bytes": object.bytes,
+                    "rows": object.rows,
+                }))
+                .collect::<Vec<_>>(),
+            "providers": stats
+                .providers
+                .iter()
+                .map(|provider| json!({
+                    "provider": provider.provider,
+                    "sessions": provider.sessions,
+                    "messages": provider.messages,
+                    "content_bytes": provider.content_bytes,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    println!(
+        "database: {} ({}, wal {})",
+        db_path.display(),
+        format_bytes(file_bytes),
+        format_bytes(wal_bytes)
+    );
+    println!(
+        "pages: {} x {} ({} free)",
+        stats.page_count,
+        format_bytes(stats.page_size),
+        stats.freelist_count
+    );
+    println!("tables (including indexes and shadow tables):");
+    let tables = stats.tables();
+    let width = tables
+        .iter()
+        .map(|table| table.name.len())
+        .max()
+        .unwrap_or(0);
+    for table in &tables {
+        let line = format!(
+            "  {:>10}  {:<width$}",
+            format_bytes(table.bytes),
+            table.name
+        );
+        match table.rows {
+            Some(rows) => println!("{line}  rows={rows}"),
+            None => println!("{}", line.trim_end()),
+        }
+    }
+    if !stats.providers.is_empty() {
+        println!("providers:");
+        for provider in &stats.providers {
+            println!(
+                "  {}: sessions={} messages={} content={}",
+                provider.provider,
+                provider.sessions,
+                provider.messages,
+                format_bytes(provider.content_bytes)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn export_bundle(
+    config_dir: Option<&Path>,
+    quiet: bool,
+    cmd: &DbExportBundleCommand,
+) -> Result<()> {
+    let (_db_path, db) = open_existing(config_dir)?;
+    let machine = machine_name(cmd.machine.as_deref());
+    let report = if let Some(path) = cmd.output.as_deref() {
+        let file = fs::File::create(path)
+            .with_context(|| format!("failed to create {}", path.display()))?;
+        db.export_bundle(&mut BufWriter::new(file), &machine)?
+    } else {
+        db.export_bundle(&mut io::stdout().lock(), &machine)?
+    };
+
+    if !quiet {
+        let message = format!(
+            "Exported {} session(s), {} message(s), {} token usage record(s) from {machine}",
+            report.sessions, report.messages, report.token_usage
+        );
+        // Keep stdout clean when it carries the bundle itself.
+        match cmd.output.as_deref() {
+            Some(path) => println!("{message} to {}", path.display()),
+            None => eprintln!("{message}"),
+        }
+    }
+    Ok(())
+}
+
+fn import_bundle(
+    config_dir: Option<&Path>,
+    quiet: bool,
+    cmd: &DbImportBundleCommand,
+) -> Result<()> {
+    let db_path = database_path(config_dir)?;
+    if let Some(parent) = db_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut db = Database::open(&db_path)?;
+    let report = if cmd.path.as_os_str() == "-" {
+        db.import_bundle(io::stdin().lock())?
+    } else {
+        let file = fs::File::open(&cmd.path)
+            .with_context(|| format!("failed to open bundle {}", cmd.path.display()))?;
+        db.import_bundle(BufReader::new(file))
+            .with_context(|| format!("failed to import {}", cmd.path.display()))?
+    };
+
+    if !quiet {
+        println!(
+            "Imported bundle from {}: {} added, {} updated, {} unchanged, {} skipped",
+            report.machine, report.added, report.updated, report.unchanged, report.skipped
+        );
+    }
+    Ok(())
+}
+
+fn query(config_dir: Option<&Path>, cmd: &DbQueryCommand) -> Result<()> {
+    let db_path = database_path(config_dir)?;
+    if !db_path.exists() {
+        return Err(eyre!("database not found at {}", db_path.display()));
     }
+    let result = Database::open_read_only(&db_path)?.query(&cmd.sql)?;
+    let mut out = io::stdout().lock();
+    match cmd.format {
+        QueryFormat::Table => write_query_table(&mut out, &result)?,
+        QueryFormat::Json => {
+            let rows: Vec<Value> = result
+                .rows
+                .iter()
+                .map(|row| {
+                    let object = result
+                        .columns
+                        .iter()
+                        .zip(row)
+                        .map(|(column, value)| (column.clone(), json_value(value)))
+                        .collect::<serde_json::Map<_, _>>();
+                    Value::Object(object)
+                })
+                .collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&rows)?)?;
+        }
+        QueryFormat::Csv => write_query_csv(&mut out, &result)?,
+    }
+    Ok(())
+}
+
+fn write_query_table<W: Write>(writer: &mut W, result: &QueryResult) -> Result<()> {
+    let cells: Vec<Vec<String>> = result
+        .rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| {
+                    let text = text_value(value);
+                    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    truncate_to_width(&flat, MAX_CELL_WIDTH)
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = result
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            cells
+                .iter()
+                .map(|row| row[index].width())
+                .chain(std::iter::once(column.width()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let write_line = |writer: &mut W, line: &[String]| -> Result<()> {
+        let mut text = String::new();
+        for (index, (cell, width)) in line.iter().zip(&widths).enumerate() {
+            if index > 0 {
+                text.push_str(COLUMN_GAP);
+            }
+            text.push_str(cell);
+            text.push_str(&" ".repeat(width.saturating_sub(cell.width())));
+        }
+        writeln!(writer, "{}", text.trim_end())?;
+        Ok(())
+    };
+    write_line(writer, &result.columns)?;
+    for row in &cells {
+        write_line(writer, row)?;
+    }
+    Ok(())
+}
+
+fn write_query_csv<W: Write>(writer: &mut W, result: &QueryResult) -> Result<()> {
+    let header: Vec<String> = result
+        .columns
+        .iter()
+        .map(|column| csv_field(column))
+        .collect();
+    writeln!(writer, "{}", header.join(","))?;
+    for row in &result.rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                SqlValue::Null => String::new(),
+                value => csv_field(&text_value(value)),
+            })
+            .collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field when it contains a separator, quote, or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn text_value(value: &SqlValue) -> String {
+    match value {
+        SqlValue::Null => "NULL".to_string(),
+        SqlValue::Integer(value) => value.to_string(),
+        SqlValue::Real(value) => value.to_string(),
+        SqlValue::Text(value) => value.clone(),
+        SqlValue::Blob(bytes) => hex(bytes),
+    }
+}
+
+fn json_value(value: &SqlValue) -> Value {
+    match value {
+        SqlValue::Null => Value::Null,
+        SqlValue::Integer(value) => json!(value),
+        SqlValue::Real(value) => json!(value),
+        SqlValue::Text(value) => json!(value),
+        SqlValue::Blob(bytes) => json!(hex(bytes)),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
 }
 
 fn reset(config_dir: Option<&Path>, quiet: bool, cmd: &DbResetCommand) -> Result<()> {
@@ -27,8 +528,7 @@ fn reset(config_dir: Option<&Path>, quiet: bool, cmd: &DbResetCommand) -> Result
         ));
     }
 
-    let loaded = config::load(config_dir)?;
-    let db_path = loaded.directories.data_dir.join(DB_FILENAME);
+    let db_path = database_path(config_dir)?;
     let candidates = [
         db_path.clone(),
         db_path.with_extension("sqlite3-wal"),
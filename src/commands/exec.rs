@@ -0,0 +1,190 @@
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use color_eyre::Result;
+use color_eyre::eyre::{WrapErr, eyre};
+use serde_json::Value;
+
+use crate::pipeline::{PLAN_SCHEMA_VERSION, PipelinePlan, command_program};
+
+/// Read an `--emit-json` document from `path` (`-` for stdin) and decode its plan.
+///
+/// # Errors
+///
+/// Returns an error if the document cannot be read, is not JSON, has a
+/// different schema version, or carries no readable plan.
+pub fn load_plan(path: &Path) -> Result<PipelinePlan> {
+    let raw = if path.as_os_str() == "-" {
+        let mut raw = String::new();
+        io::stdin()
+            .read_to_string(&mut raw)
+            .wrap_err("failed to read plan from stdin")?;
+        raw
+    } else {
+        fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read plan {}", path.display()))?
+    };
+    parse_plan(&raw)
+}
+
+/// Decode the plan in an `--emit-json` document.
+///
+/// # Errors
+///
+/// Returns an error if the document is not JSON, has a different schema
+/// version, or carries no readable plan.
+pub fn parse_plan(raw: &str) -> Result<PipelinePlan> {
+    let mut document: Value = serde_json::from_str(raw).wrap_err("plan is not valid JSON")?;
+    let version = document
+        .get("schema_version")
+        .ok_or_else(|| eyre!("plan has no schema_version; regenerate it with --emit-json"))?;
+    if version.as_u64() != Some(u64::from(PLAN_SCHEMA_VERSION)) {
+        return Err(eyre!(
+            "plan schema_version {version} is not supported; expected {PLAN_SCHEMA_VERSION}"
+        ));
+    }
+    let plan = document
+        .get_mut("plan")
+        .map(Value::take)
+        .ok_or_else(|| eyre!("plan document has no 'plan' entry"))?;
+    serde_json::from_value(plan).wrap_err("plan does not match the expected schema")
+}
+
+/// Check that the plan's directory, the programs it starts, and the programs
+/// its hooks run still exist, and that its masked env entries are in range.
+///
+/// Relative program paths resolve against the plan's directory, as they would
+/// at launch.
+///
+/// # Errors
+///
+/// Returns an error naming the first missing directory or program, or a
+/// masked env entry the plan does not have.
+pub fn check_plan(plan: &PipelinePlan) -> Result<()> {
+    if !plan.cwd.is_dir() {
+        return Err(eyre!(
+            "plan directory {} does not exist",
+            plan.cwd.display()
+        ));
+    }
+    if let Some(pending) = plan
+        .secret_env
+        .iter()
+        .find(|pending| pending.index >= plan.env.len())
+    {
+        return Err(eyre!(
+            "plan masks env entry {} but has only {} entries",
+            pending.index,
+            plan.env.len()
+        ));
+    }
+    for program in &plan.programs {
+        find_program(plan, program)
+            .map_err(|_| eyre!("program '{program}' required by the plan was not found"))?;
+    }
+    let hooks = &plan.hooks;
+    for command in hooks
+        .before
+        .iter()
+        .chain(&hooks.after)
+        .chain(&hooks.on_failure)
+    {
+        if let Some(program) = command_program(command) {
+            find_program(plan, &program).map_err(|_| {
+                eyre!("program '{program}' required by hook '{command}' was not found")
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn find_program(plan: &PipelinePlan, program: &str) -> which::Result<std::path::PathBuf> {
+    which::which_in(program, env::var_os("PATH"), &plan.cwd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::SecretEnv;
+    use serde_json::json;
+
+    fn document(version: u32) -> Value {
+        json!({
+            "schema_version": version,
+            "command": "sh",
+            "plan": {
+                "pipeline": "sh",
+                "display": "sh",
+                "friendly_display": "sh",
+                "env": [],
+                "env_origins": [],
+                "invocation": { "shell": { "command": "sh" } },
+                "provider": "shell",
+                "profile": null,
+                "terminal_title": "shell",
+                "pre_snippets": [],
+                "post_snippets": [],
+                "wrappers": [],
+                "needs_stdin_prompt": false,
+                "uses_capture_arg": false,
+                "capture_has_pre_commands": false,
+                "stdin_prompt_label": null,
+                "cwd": "/",
+                "prompt_assembler": null,
+                "context": null,
+                "secret_env": [],
+                "hooks": { "before": [], "after": [], "on_failure": [] },
+                "session_id": null,
+                "programs": ["sh"],
+            },
+        })
+    }
+
+    #[test]
+    fn parse_plan_checks_the_schema_version() -> Result<()> {
+        let plan = parse_plan(&document(PLAN_SCHEMA_VERSION).to_string())?;
+        assert_eq!(plan.display, "sh");
+        assert!(plan.launch.is_empty());
+
+        let err = parse_plan(&document(PLAN_SCHEMA_VERSION + 1).to_string()).unwrap_err();
+        assert!(err.to_string().contains("is not supported"));
+        let err = parse_plan(r#"{"command": "sh"}"#).unwrap_err();
+        assert!(err.to_string().contains("no schema_version"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_plan_reports_missing_programs_and_directories() -> Result<()> {
+        let mut plan = parse_plan(&document(PLAN_SCHEMA_VERSION).to_string())?;
+        check_plan(&plan)?;
+
+        plan.programs.push("tx-missing-program".into());
+        let err = check_plan(&plan).unwrap_err();
+        assert!(err.to_string().contains("'tx-missing-program'"));
+
+        plan.programs.pop();
+        plan.hooks.before = vec!["exit 0".into(), "FOO=1 tx-missing-hook --flag".into()];
+        let err = check_plan(&plan).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'tx-missing-hook' required by hook")
+        );
+
+        plan.hooks.before.pop();
+        plan.secret_env.push(SecretEnv {
+            index: 0,
+            parts: Vec::new(),
+        });
+        let err = check_plan(&plan).unwrap_err();
+        assert!(err.to_string().contains("env entry 0"));
+        assert!(plan.launch_env().is_err());
+
+        plan.cwd = "/tx/missing/dir".into();
+        let err = check_plan(&plan).unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::cli::HistoryCommand;
+use crate::db::{Database, RunRecord};
+
+/// List recorded launches, most recent first.
+///
+/// # Errors
+///
+/// Returns an error if the runs cannot be read or output fails.
+pub fn run(db: &Database, cmd: &HistoryCommand) -> Result<()> {
+    let runs = db.recent_runs(cmd.limit)?;
+
+    if cmd.json {
+        let payload = runs.iter().map(run_json).collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    if runs.is_empty() {
+        println!("No runs recorded yet.");
+        return Ok(());
+    }
+    for run in &runs {
+        println!("{}", run_line(run));
+    }
+    Ok(())
+}
+
+/// Find the run `selector` names: a run id or `last`.
+///
+/// # Errors
+///
+/// Returns an error if the selector is not an id, or no such run exists.
+pub fn resolve(db: &Database, selector: &str) -> Result<RunRecord> {
+    let selector = selector.trim();
+    if selector.eq_ignore_ascii_case("last") {
+        return db
+            .recent_runs(1)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre!("no runs recorded yet"));
+    }
+    let id: i64 = selector
+        .parse()
+        .map_err(|_| eyre!("invalid run id '{selector}'; expected a number or 'last'"))?;
+    db.run_by_id(id)?.ok_or_else(|| eyre!("run {id} not found"))
+}
+
+/// One-line summary shared by `tx history` and the TUI launch list.
+#[must_use]
+pub fn run_line(run: &RunRecord) -> String {
+    let target = match &run.profile {
+        Some(profile) => format!("{}/{profile}", run.provider),
+        None => run.provider.clone(),
+    };
+    let command = run
+        .decode_plan()
+        .map_or_else(|_| "<unreadable plan>".to_string(), |plan| plan.display);
+    format!(
+        "{:>4}  {}  {:<9}  {:>7}  {target}  {command}",
+        run.id,
+        format_ts_ms(run.started_ms),
+        exit_label(run),
+        format_duration(run.duration_ms),
+    )
+}
+
+/// `exit 0`, `exit 2`, or `signal` for a run killed by a signal.
+#[must_use]
+pub fn exit_label(run: &RunRecord) -> String {
+    run.exit_code
+        .map_or_else(|| "signal".to_string(), |code| format!("exit {code}"))
+}
+
+/// Compact duration such as `850ms`, `42s`, or `3m05s`.
+#[must_use]
+pub fn format_duration(ms: i64) -> String {
+    let duration = Duration::from_millis(u64::try_from(ms).unwrap_or_default());
+    let secs = duration.as_secs();
+    if secs == 0 {
+        format!("{}ms", duration.as_millis())
+    } else if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+fn run_json(run: &RunRecord) -> Value {
+    json!({
+        "id": run.id,
+        "started_at": format_ts_ms(run.started_ms),
+        "profile": run.profile,
+        "provider": run.provider,
+        "cwd": run.cwd,
+        "exit_code": run.exit_code,
+        "duration_ms": run.duration_ms,
+        "session_path": run.session_path,
+        "plan": serde_json::from_str::<Value>(&run.plan).unwrap_or(Value::Null),
+    })
+}
+
+fn format_ts_ms(ts_ms: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(ts_ms / 1000)
+        .ok()
+        .and_then(|dt| dt.format(&Rfc3339).ok())
+        .unwrap_or_else(|| ts_ms.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_duration_picks_a_compact_unit() {
+        assert_eq!(format_duration(850), "850ms");
+        assert_eq!(format_duration(42_300), "42s");
+        assert_eq!(format_duration(185_000), "3m05s");
+        assert_eq!(format_duration(7_380_000), "2h03m");
+        assert_eq!(format_duration(-5), "0ms");
+    }
+}
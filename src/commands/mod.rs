@@ -1,2 +1,6 @@
 pub mod db;
+pub mod exec;
+pub mod history;
+pub mod prune;
+pub mod search;
 pub mod stats;
@@ -0,0 +1,108 @@
+use color_eyre::Result;
+use serde_json::json;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::cli::PruneCommand;
+use crate::config::model::RetentionConfig;
+use crate::db::{Database, PruneCandidate};
+use crate::rag::prune_embedding_cache;
+use crate::util::unix_timestamp;
+
+/// Select sessions outside the retention policy and delete their index rows
+/// unless `--dry-run` is set, then drop cached embeddings no chunk uses any
+/// more. Transcript files are never touched.
+///
+/// # Errors
+///
+/// Returns an error if candidates cannot be selected or deleted, or output fails.
+pub fn run(db: &mut Database, policy: &RetentionConfig, cmd: &PruneCommand) -> Result<()> {
+    let candidates = db.prune_candidates(policy, unix_timestamp())?;
+    let report = if cmd.dry_run || candidates.is_empty() {
+        None
+    } else {
+        Some(db.prune_sessions(&candidates)?)
+    };
+    let cached = if cmd.dry_run {
+        0
+    } else {
+        prune_embedding_cache(db)?
+    };
+
+    if cmd.json {
+        let payload = json!({
+            "dry_run": cmd.dry_run,
+            "configured": policy.has_limits(),
+            "sessions": candidates.iter().map(candidate_json).collect::<Vec<_>>(),
+            "deleted": report.map(|report| json!({
+                "sessions": report.sessions,
+                "messages": report.messages,
+                "token_usage": report.token_usage,
+                "vectors": report.vectors,
+            })),
+            "cached_embeddings": cached,
+        });
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    if !policy.has_limits() {
+        println!(
+            "No retention limits configured; set max_age_days or max_sessions_per_provider under [retention]."
+        );
+        return Ok(());
+    }
+    if candidates.is_empty() {
+        println!("Nothing to prune.");
+        print_cache_pruned(cached);
+        return Ok(());
+    }
+
+    let verb = if cmd.dry_run {
+        "Would prune"
+    } else {
+        "Pruning"
+    };
+    println!("{verb} {} session(s):", candidates.len());
+    for candidate in &candidates {
+        println!(
+            "  {}  last active {}  {} message(s)  ({})",
+            candidate.id,
+            format_ts(candidate.last_active),
+            candidate.messages,
+            candidate.reason.as_str()
+        );
+    }
+    if let Some(report) = report {
+        println!(
+            "Deleted {} session(s), {} message(s), {} token usage row(s), and {} vector(s); transcript files were left in place.",
+            report.sessions, report.messages, report.token_usage, report.vectors
+        );
+    }
+    print_cache_pruned(cached);
+    Ok(())
+}
+
+fn print_cache_pruned(cached: usize) {
+    if cached > 0 {
+        println!("Dropped {cached} unused cached embedding(s).");
+    }
+}
+
+fn candidate_json(candidate: &PruneCandidate) -> serde_json::Value {
+    json!({
+        "id": candidate.id,
+        "provider": candidate.provider,
+        "path": candidate.path.to_string_lossy(),
+        "last_active": candidate.last_active,
+        "messages": candidate.messages,
+        "reason": candidate.reason.as_str(),
+    })
+}
+
+fn format_ts(ts: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(ts)
+        .ok()
+        .and_then(|dt| dt.format(&Rfc3339).ok())
+        .unwrap_or_else(|| ts.to_string())
+}
@@ -0,0 +1,480 @@
+use std::io::{self, IsTerminal, Write};
+
+use color_eyre::Result;
+use serde_json::json;
+use time::OffsetDateTime;
+use time::format_description::FormatItem;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::config::model::SearchFormat;
+use crate::session::SessionSummary;
+
+const COLUMN_GAP: &str = "  ";
+const MIN_FLEX_WIDTH: usize = 8;
+const ELLIPSIS: char = '…';
+
+static TABLE_TIMESTAMP_FORMAT: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchColumn {
+    Id,
+    Provider,
+    Model,
+    LastActive,
+    ThreadName,
+    Snippet,
+}
+
+impl SearchColumn {
+    pub const DEFAULTS: [SearchColumn; 6] = [
+        SearchColumn::Id,
+        SearchColumn::Provider,
+        SearchColumn::Model,
+        SearchColumn::LastActive,
+        SearchColumn::ThreadName,
+        SearchColumn::Snippet,
+    ];
+
+    pub const VARIANTS: [&'static str; 6] = [
+        "id",
+        "provider",
+        "model",
+        "last_active",
+        "thread_name",
+        "snippet",
+    ];
+
+    #[must_use]
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "id" => Some(SearchColumn::Id),
+            "provider" => Some(SearchColumn::Provider),
+            "model" => Some(SearchColumn::Model),
+            "last_active" => Some(SearchColumn::LastActive),
+            "thread_name" | "thread" => Some(SearchColumn::ThreadName),
+            "snippet" => Some(SearchColumn::Snippet),
+            _ => None,
+        }
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            SearchColumn::Id => "ID",
+            SearchColumn::Provider => "PROVIDER",
+            SearchColumn::Model => "MODEL",
+            SearchColumn::LastActive => "LAST ACTIVE",
+            SearchColumn::ThreadName => "THREAD",
+            SearchColumn::Snippet => "SNIPPET",
+        }
+    }
+
+    /// Free-text columns absorb whatever width the fixed columns leave over.
+    fn is_flexible(self) -> bool {
+        matches!(self, SearchColumn::ThreadName | SearchColumn::Snippet)
+    }
+}
+
+/// One session row produced by `tx search`, together with the text that matched.
+#[derive(Debug, Clone)]
+pub struct SearchRow {
+    pub summary: SessionSummary,
+    pub snippet: Option<String>,
+    pub snippet_role: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchRenderOptions<'a> {
+    pub format: SearchFormat,
+    pub columns: &'a [SearchColumn],
+    /// Maximum table width in terminal cells; `None` disables truncation.
+    pub width: Option<usize>,
+}
+
+/// Write search results to stdout using the requested format.
+///
+/// # Errors
+///
+/// Returns an error if serialization or writing to stdout fails.
+pub fn print_results(rows: &[SearchRow], options: &SearchRenderOptions<'_>) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    write_results(&mut stdout, rows, options)
+}
+
+/// Write search results to `writer` using the requested format.
+///
+/// # Errors
+///
+/// Returns an error if serialization or writing fails.
+pub fn write_results<W: Write>(
+    writer: &mut W,
+    rows: &[SearchRow],
+    options: &SearchRenderOptions<'_>,
+) -> Result<()> {
+    match options.format {
+        SearchFormat::Json => {
+            let payload: Vec<_> = rows.iter().map(row_to_json).collect();
+            writeln!(writer, "{}", serde_json::to_string_pretty(&payload)?)?;
+        }
+        SearchFormat::Ndjson => {
+            for row in rows {
+                writeln!(writer, "{}", serde_json::to_string(&row_to_json(row))?)?;
+            }
+        }
+        SearchFormat::Ids => {
+            for row in rows {
+                writeln!(writer, "{}", row.summary.id)?;
+            }
+        }
+        SearchFormat::Tsv => {
+            for row in rows {
+                let cells: Vec<_> = options
+                    .columns
+                    .iter()
+                    .map(|column| tsv_cell(row, *column))
+                    .collect();
+                writeln!(writer, "{}", cells.join("\t"))?;
+            }
+        }
+        SearchFormat::Table => write_table(writer, rows, options.columns, options.width)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Resolve the width available for table output.
+///
+/// Uses the terminal size when stdout is a TTY and falls back to `$COLUMNS`.
+#[must_use]
+pub fn output_width() -> Option<usize> {
+    if io::stdout().is_terminal()
+        && let Ok((columns, _)) = crossterm::terminal::size()
+        && columns > 0
+    {
+        return Some(usize::from(columns));
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|raw| raw.trim().parse::<usize>().ok())
+        .filter(|width| *width > 0)
+}
+
+pub(crate) fn summary_to_json(
+    summary: &SessionSummary,
+    snippet: Option<&str>,
+    snippet_role: Option<&str>,
+) -> serde_json::Value {
+    json!({
+        "id": summary.id,
+        "provider": summary.provider,
+        "wrapper": summary.wrapper,
+        "label": summary.label,
+        "thread_name": summary.thread_name,
+        "path": summary.path.to_string_lossy(),
+        "uuid": summary.uuid,
+        "first_prompt": summary.first_prompt,
+        "actionable": summary.actionable,
+        "created_at": summary.created_at,
+        "started_at": summary.started_at,
+        "last_active": summary.last_active,
+        "size": summary.size,
+        "mtime": summary.mtime,
+        "snippet": snippet,
+        "snippet_role": snippet_role,
+    })
+}
+
+fn row_to_json(row: &SearchRow) -> serde_json::Value {
+    summary_to_json(
+        &row.summary,
+        row.snippet.as_deref(),
+        row.snippet_role.as_deref(),
+    )
+}
+
+fn write_table<W: Write>(
+    writer: &mut W,
+    rows: &[SearchRow],
+    columns: &[SearchColumn],
+    width: Option<usize>,
+) -> Result<()> {
+    if columns.is_empty() {
+        return Ok(());
+    }
+
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| table_cell(row, *column))
+                .collect()
+        })
+        .collect();
+
+    let natural: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            cells
+                .iter()
+                .map(|row| row[index].width())
+                .chain(std::iter::once(column.header().width()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let widths = fit_widths(columns, &natural, width);
+
+    let header: Vec<String> = columns
+        .iter()
+        .map(|column| column.header().into())
+        .collect();
+    write_table_line(writer, &header, &widths)?;
+    for row in &cells {
+        write_table_line(writer, row, &widths)?;
+    }
+    Ok(())
+}
+
+fn write_table_line<W: Write>(writer: &mut W, cells: &[String], widths: &[usize]) -> Result<()> {
+    let last = cells.len().saturating_sub(1);
+    let mut line = String::new();
+    for (index, (cell, width)) in cells.iter().zip(widths).enumerate() {
+        let fitted = truncate_to_width(cell, *width);
+        line.push_str(&fitted);
+        if index != last {
+            line.push_str(&" ".repeat(width.saturating_sub(fitted.width())));
+            line.push_str(COLUMN_GAP);
+        }
+    }
+    writeln!(writer, "{line}")?;
+    Ok(())
+}
+
+/// Shrink flexible columns so the table fits into `available` cells.
+///
+/// Fixed columns keep their natural width; if they alone overflow, the table is
+/// left untruncated rather than mangling identifiers.
+fn fit_widths(columns: &[SearchColumn], natural: &[usize], available: Option<usize>) -> Vec<usize> {
+    let mut widths = natural.to_vec();
+    let Some(available) = available else {
+        return widths;
+    };
+
+    let gaps = COLUMN_GAP.len() * columns.len().saturating_sub(1);
+    let total: usize = widths.iter().sum::<usize>() + gaps;
+    if total <= available {
+        return widths;
+    }
+
+    let fixed: usize = columns
+        .iter()
+        .zip(natural)
+        .filter(|(column, _)| !column.is_flexible())
+        .map(|(_, width)| *width)
+        .sum();
+    let flex_indices: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| column.is_flexible())
+        .map(|(index, _)| index)
+        .collect();
+    if flex_indices.is_empty() {
+        return widths;
+    }
+
+    let mut budget = available.saturating_sub(fixed + gaps);
+    let mut remaining = flex_indices.len();
+    let mut order = flex_indices;
+    order.sort_by_key(|index| natural[*index]);
+    for index in order {
+        let share = (budget / remaining).max(MIN_FLEX_WIDTH);
+        let assigned = natural[index].min(share);
+        widths[index] = assigned;
+        budget = budget.saturating_sub(assigned);
+        remaining -= 1;
+    }
+    widths
+}
+
+pub(crate) fn truncate_to_width(text: &str, max: usize) -> String {
+    if text.width() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+
+    let mut out = String::new();
+    let mut used = 0;
+    for ch in text.chars() {
+        let ch_width = ch.width().unwrap_or(0);
+        if used + ch_width + 1 > max {
+            break;
+        }
+        out.push(ch);
+        used += ch_width;
+    }
+    out.push(ELLIPSIS);
+    out
+}
+
+fn table_cell(row: &SearchRow, column: SearchColumn) -> String {
+    let value = match column {
+        SearchColumn::LastActive => row
+            .summary
+            .last_active
+            .and_then(|ts| OffsetDateTime::from_unix_timestamp(ts).ok())
+            .and_then(|dt| dt.format(TABLE_TIMESTAMP_FORMAT).ok()),
+        other => raw_cell(row, other),
+    };
+    value
+        .map(|text| normalize_whitespace(&text))
+        .filter(|text| !text.is_empty())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn tsv_cell(row: &SearchRow, column: SearchColumn) -> String {
+    let value = match column {
+        SearchColumn::LastActive => row
+            .summary
+            .last_active
+            .and_then(|ts| OffsetDateTime::from_unix_timestamp(ts).ok())
+            .and_then(|dt| dt.format(&Rfc3339).ok()),
+        other => raw_cell(row, other),
+    };
+    value
+        .map(|text| normalize_whitespace(&text))
+        .unwrap_or_default()
+}
+
+fn raw_cell(row: &SearchRow, column: SearchColumn) -> Option<String> {
+    let summary = &row.summary;
+    match column {
+        SearchColumn::Id => Some(summary.id.clone()),
+        SearchColumn::Provider => Some(summary.provider.clone()),
+        SearchColumn::Model => summary.model.clone(),
+        SearchColumn::LastActive => summary.last_active.map(|ts| ts.to_string()),
+        SearchColumn::ThreadName => summary.thread_name.clone(),
+        SearchColumn::Snippet => row.snippet.clone().or_else(|| summary.first_prompt.clone()),
+    }
+}
+
+fn normalize_whitespace(input: &str) -> String {
+    input.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn row(id: &str, snippet: &str) -> SearchRow {
+        SearchRow {
+            summary: SessionSummary {
+                id: id.into(),
+                provider: "codex".into(),
+                wrapper: None,
+                model: Some("gpt-5".into()),
+                label: None,
+                thread_name: Some("Fix\tflaky tests".into()),
+                path: PathBuf::from(format!("{id}.jsonl")),
+                uuid: None,
+                first_prompt: Some("first prompt".into()),
+                actionable: true,
+                subagent: false,
+                created_at: Some(0),
+                started_at: Some(0),
+                last_active: Some(1_700_000_000),
+                size: 1,
+                mtime: 0,
+            },
+            snippet: Some(snippet.into()),
+            snippet_role: Some("user".into()),
+        }
+    }
+
+    fn render(rows: &[SearchRow], format: SearchFormat, width: Option<usize>) -> String {
+        let options = SearchRenderOptions {
+            format,
+            columns: &SearchColumn::DEFAULTS,
+            width,
+        };
+        let mut out = Vec::new();
+        write_results(&mut out, rows, &options).expect("render results");
+        String::from_utf8(out).expect("utf8 output")
+    }
+
+    #[test]
+    fn search_column_parse_accepts_aliases() {
+        assert_eq!(
+            SearchColumn::parse("Last-Active"),
+            Some(SearchColumn::LastActive)
+        );
+        assert_eq!(
+            SearchColumn::parse("thread"),
+            Some(SearchColumn::ThreadName)
+        );
+        assert_eq!(SearchColumn::parse("cost"), None);
+    }
+
+    #[test]
+    fn ndjson_and_ids_emit_one_line_per_row() {
+        let rows = [row("sess-1", "alpha"), row("sess-2", "beta")];
+        let ndjson = render(&rows, SearchFormat::Ndjson, None);
+        let lines: Vec<_> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let parsed: serde_json::Value = serde_json::from_str(lines[1]).expect("json line");
+        assert_eq!(parsed["id"], "sess-2");
+        assert_eq!(parsed["snippet_role"], "user");
+
+        assert_eq!(render(&rows, SearchFormat::Ids, None), "sess-1\nsess-2\n");
+    }
+
+    #[test]
+    fn tsv_flattens_whitespace_inside_cells() {
+        let rows = [row("sess-1", "multi\nline\tsnippet")];
+        let tsv = render(&rows, SearchFormat::Tsv, None);
+        let fields: Vec<_> = tsv.trim_end().split('\t').collect();
+        assert_eq!(
+            fields,
+            vec![
+                "sess-1",
+                "codex",
+                "gpt-5",
+                "2023-11-14T22:13:20Z",
+                "Fix flaky tests",
+                "multi line snippet",
+            ]
+        );
+    }
+
+    #[test]
+    fn table_truncates_flexible_columns_to_width() {
+        let rows = [row("sess-1", &"漢字 snippet ".repeat(20))];
+        let table = render(&rows, SearchFormat::Table, Some(80));
+        let lines: Vec<_> = table.lines().collect();
+        assert!(lines[0].starts_with("ID"));
+        assert!(lines.iter().all(|line| line.width() <= 80), "{table}");
+        assert!(lines[1].ends_with(ELLIPSIS), "{table}");
+        assert!(lines[1].contains("2023-11-14 22:13"));
+    }
+
+    #[test]
+    fn table_without_width_keeps_full_text() {
+        let snippet = "word ".repeat(40);
+        let rows = [row("sess-1", &snippet)];
+        let table = render(&rows, SearchFormat::Table, None);
+        assert!(table.contains(snippet.trim_end()));
+    }
+
+    #[test]
+    fn truncate_to_width_respects_wide_characters() {
+        assert_eq!(truncate_to_width("漢字漢字", 5), "漢字…");
+        assert_eq!(truncate_to_width("short", 10), "short");
+        assert_eq!(truncate_to_width("abc", 0), "");
+    }
+}
@@ -14,14 +14,22 @@ pub fn merge_tables(
         if raw_key.ends_with('+') {
             let key = raw_key.trim_end_matches('+');
             let addition_array = expect_array(value, key, source_path)?;
-            match target.entry(key.to_string()) {
+            if let Some(existing) = target.get_mut(key) {
+                let Value::Array(existing_values) = existing else {
+                    return Err(eyre!("cannot append to non-array key '{key}'")
+                        .wrap_err(format!("the key is defined earlier in {key}")));
+                };
+                existing_values.extend(addition_array);
+                continue;
+            }
+            // Nothing to append to yet. Keep the suffix so a base that appears
+            // later, such as an inherited profile, still receives the items;
+            // `resolve_appends` turns leftovers into plain arrays.
+            match target.entry(raw_key) {
                 Entry::Occupied(mut occ) => {
-                    let existing = occ.get_mut();
-                    let Value::Array(existing_values) = existing else {
-                        return Err(eyre!("cannot append to non-array key '{key}'")
-                            .wrap_err(format!("the key is defined earlier in {}", occ.key())));
-                    };
-                    existing_values.extend(addition_array);
+                    if let Value::Array(pending) = occ.get_mut() {
+                        pending.extend(addition_array);
+                    }
                 }
                 Entry::Vacant(vac) => {
                     vac.insert(Value::Array(addition_array));
@@ -44,6 +52,7 @@ pub fn merge_tables(
                 }
             },
             other => {
+                target.remove(&format!("{raw_key}+"));
                 if matches_null(&other) {
                     target.remove(&raw_key);
                 } else {
@@ -56,6 +65,94 @@ pub fn merge_tables(
     Ok(())
 }
 
+/// Apply `key+` appends that never found a base value, recursively.
+///
+/// # Errors
+///
+/// Returns an error if an append targets a key that holds a non-array value.
+pub fn resolve_appends(table: &mut toml::map::Map<String, Value>) -> Result<()> {
+    let pending: Vec<String> = table
+        .keys()
+        .filter(|key| key.ends_with('+'))
+        .cloned()
+        .collect();
+    for raw_key in pending {
+        let Some(Value::Array(items)) = table.remove(&raw_key) else {
+            continue;
+        };
+        let key = raw_key.trim_end_matches('+');
+        match table.get_mut(key) {
+            Some(Value::Array(existing)) => existing.extend(items),
+            Some(_) => return Err(eyre!("cannot append to non-array key '{key}'")),
+            None => {
+                table.insert(key.to_string(), Value::Array(items));
+            }
+        }
+    }
+    for (_, value) in table.iter_mut() {
+        if let Value::Table(nested) = value {
+            resolve_appends(nested)?;
+        }
+    }
+    Ok(())
+}
+
+/// Flatten `extends` chains in a `[profiles]` table.
+///
+/// Each profile starts from a copy of its resolved parent and is merged on top
+/// with [`merge_tables`], so plain keys override and `pre+`/`post+` append. The
+/// `extends` key is kept so callers can still see the direct parent. Missing
+/// parents and cycles are left for [`Config::lint`](super::model::Config::lint)
+/// to report; the affected profile keeps whatever part of its chain resolved.
+///
+/// # Errors
+///
+/// Returns an error if merging a profile over its parent fails.
+pub fn resolve_profiles(
+    profiles: &toml::map::Map<String, Value>,
+) -> Result<toml::map::Map<String, Value>> {
+    let mut cache = toml::map::Map::new();
+    let mut resolved = toml::map::Map::new();
+    for (name, value) in profiles {
+        let flattened = resolve_profile(name, profiles, &mut cache, &mut Vec::new())?
+            .map_or_else(|| value.clone(), Value::Table);
+        // Malformed (non-table) entries pass through for decoding to report.
+        resolved.insert(name.clone(), flattened);
+    }
+    Ok(resolved)
+}
+
+fn resolve_profile(
+    name: &str,
+    profiles: &toml::map::Map<String, Value>,
+    resolved: &mut toml::map::Map<String, Value>,
+    visiting: &mut Vec<String>,
+) -> Result<Option<toml::map::Map<String, Value>>> {
+    if let Some(Value::Table(done)) = resolved.get(name) {
+        return Ok(Some(done.clone()));
+    }
+    let Some(Value::Table(own)) = profiles.get(name) else {
+        return Ok(None);
+    };
+    if visiting.iter().any(|seen| seen == name) {
+        return Ok(None);
+    }
+
+    visiting.push(name.to_string());
+    let parent = match own.get("extends").and_then(Value::as_str) {
+        Some(parent) => resolve_profile(parent, profiles, resolved, visiting)?,
+        None => None,
+    };
+    visiting.pop();
+
+    let mut table = parent.unwrap_or_default();
+    table.remove("description");
+    merge_tables(&mut table, own.clone(), None)?;
+    resolve_appends(&mut table)?;
+    resolved.insert(name.to_string(), Value::Table(table.clone()));
+    Ok(Some(table))
+}
+
 fn expect_array(value: Value, key: &str, source: Option<&Path>) -> Result<Vec<Value>> {
     match value {
         Value::Array(items) => Ok(items),
@@ -158,11 +255,104 @@ mod tests {
             Value::Array(vec![Value::Integer(5)]),
         )]);
         merge_tables(&mut target, addition, None)?;
+        assert!(
+            !target.contains_key("items"),
+            "append waits for a base value"
+        );
+        resolve_appends(&mut target)?;
         let values = target
             .get("items")
             .and_then(Value::as_array)
             .expect("expected array");
         assert_eq!(values, &vec![Value::Integer(5)]);
+        assert!(!target.contains_key("items+"));
+        Ok(())
+    }
+
+    #[test]
+    fn merge_tables_plain_value_discards_pending_append() -> Result<()> {
+        let mut target = table(vec![("items+", Value::Array(vec![Value::Integer(1)]))]);
+        let addition = table(vec![("items", Value::Array(vec![Value::Integer(2)]))]);
+        merge_tables(&mut target, addition, None)?;
+        assert_eq!(
+            target.get("items"),
+            Some(&Value::Array(vec![Value::Integer(2)]))
+        );
+        assert!(!target.contains_key("items+"));
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_profiles_flattens_multi_level_extends() -> Result<()> {
+        let profiles: toml::map::Map<String, Value> = toml::from_str(
+            r#"
+            [base]
+            provider = "codex"
+            description = "Shared setup"
+            pre = ["lint"]
+            wrap = "tmux"
+
+            [review]
+            extends = "base"
+            "pre+" = ["fetch"]
+            post = ["notify"]
+
+            [deep]
+            extends = "review"
+            "pre+" = ["context"]
+            wrap = "null"
+            "#,
+        )?;
+        let resolved = resolve_profiles(&profiles)?;
+        let deep = resolved["deep"].as_table().expect("deep profile");
+        assert_eq!(deep["provider"].as_str(), Some("codex"));
+        assert_eq!(deep["extends"].as_str(), Some("review"));
+        assert_eq!(
+            deep["pre"],
+            Value::Array(vec!["lint".into(), "fetch".into(), "context".into()])
+        );
+        assert_eq!(deep["post"], Value::Array(vec!["notify".into()]));
+        assert!(
+            !deep.contains_key("wrap"),
+            "null clears the inherited wrapper"
+        );
+        assert!(
+            !deep.contains_key("description"),
+            "descriptions are not inherited"
+        );
+        assert!(!deep.contains_key("pre+"));
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_profiles_tolerates_cycles_and_missing_parents() -> Result<()> {
+        let profiles: toml::map::Map<String, Value> = toml::from_str(
+            r#"
+            [a]
+            extends = "b"
+            provider = "codex"
+            "pre+" = ["one"]
+
+            [b]
+            extends = "a"
+            "pre+" = ["two"]
+
+            [orphan]
+            extends = "missing"
+            "pre+" = ["three"]
+            "#,
+        )?;
+        let resolved = resolve_profiles(&profiles)?;
+        assert_eq!(
+            resolved["a"]["pre"],
+            Value::Array(vec!["two".into(), "one".into()])
+        );
+        assert_eq!(resolved["b"]["pre"], Value::Array(vec!["two".into()]));
+        assert_eq!(
+            resolved["orphan"]["pre"],
+            Value::Array(vec!["three".into()])
+        );
+        assert_eq!(resolved["orphan"]["extends"].as_str(), Some("missing"));
         Ok(())
     }
 
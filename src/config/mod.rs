@@ -114,7 +114,8 @@ pub fn load(dir_override: Option<&Path>) -> Result<LoadedConfig> {
             "Ignoring configuration key `preview_filter`; the preview filter feature has been removed."
         );
     }
-    let config = Config::from_value(&merged_value)?;
+    let mut config = Config::from_value(&merged_value)?;
+    config.anchor_secret_files(&dirs.config_dir);
     let mut diagnostics = config.lint();
     if saw_preview_filter {
         diagnostics.push(ConfigDiagnostic {
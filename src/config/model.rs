@@ -3,14 +3,19 @@ use color_eyre::eyre::{WrapErr, eyre};
 use directories::BaseDirs;
 use indexmap::IndexMap;
 use schemars::{JsonSchema, Schema, generate::SchemaGenerator, json_schema};
-use serde::Deserialize;
 use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
 use shellexpand::full;
 use std::borrow::Cow;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use toml::Value;
 
+use super::merge;
+use crate::secrets;
+use crate::template::Template;
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub defaults: Defaults,
@@ -19,6 +24,10 @@ pub struct Config {
     pub wrappers: IndexMap<String, WrapperConfig>,
     pub profiles: IndexMap<String, ProfileConfig>,
     pub features: FeatureConfig,
+    pub retention: RetentionConfig,
+    pub secrets: IndexMap<String, SecretConfig>,
+    /// Global hooks; provider and profile hooks run after these.
+    pub hooks: HooksConfig,
 }
 
 #[derive(Debug, Clone)]
@@ -26,6 +35,7 @@ pub struct Defaults {
     pub provider: Option<String>,
     pub profile: Option<String>,
     pub search_mode: SearchMode,
+    pub search_format: SearchFormat,
     pub terminal_title: Option<String>,
 }
 
@@ -45,12 +55,51 @@ impl SearchMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFormat {
+    Json,
+    Ndjson,
+    Table,
+    Tsv,
+    Ids,
+}
+
+impl SearchFormat {
+    pub const VARIANTS: [&'static str; 5] = ["json", "ndjson", "table", "tsv", "ids"];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchFormat::Json => "json",
+            SearchFormat::Ndjson => "ndjson",
+            SearchFormat::Table => "table",
+            SearchFormat::Tsv => "tsv",
+            SearchFormat::Ids => "ids",
+        }
+    }
+
+    #[must_use]
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(SearchFormat::Json),
+            "ndjson" => Some(SearchFormat::Ndjson),
+            "table" => Some(SearchFormat::Table),
+            "tsv" => Some(SearchFormat::Tsv),
+            "ids" => Some(SearchFormat::Ids),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ProviderConfig {
     pub name: String,
     pub bin: String,
     pub flags: Vec<String>,
     pub env: Vec<EnvVar>,
+    /// Dotenv files loaded before `env`. Relative paths start at the directory tx runs in.
+    pub env_file: Vec<PathBuf>,
+    pub hooks: HooksConfig,
     pub session_roots: Vec<PathBuf>,
     pub stdin: Option<StdinMapping>,
 }
@@ -61,6 +110,46 @@ pub struct EnvVar {
     pub value_template: String,
 }
 
+/// A named secret that `${secret:NAME}` references in provider `env` entries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretConfig {
+    pub name: String,
+    pub source: SecretSource,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretSource {
+    /// Run a command and use its standard output.
+    Command { argv: Vec<String> },
+    /// Read a file that only its owner can access. Relative paths start at the
+    /// configuration directory.
+    File { path: PathBuf },
+    /// Look up the attributes with `secret-tool lookup`.
+    SecretTool {
+        attributes: IndexMap<String, String>,
+    },
+}
+
+impl SecretSource {
+    /// Short description for diagnostics; never includes the secret.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self {
+            SecretSource::Command { argv } => format!("command '{}'", argv.join(" ")),
+            SecretSource::File { path } => format!("file {}", path.display()),
+            SecretSource::SecretTool { attributes } => format!(
+                "secret-tool {}",
+                attributes
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StdinMapping {
     pub args: Vec<String>,
@@ -97,21 +186,273 @@ pub enum WrapperMode {
     Exec { argv: Vec<String> },
 }
 
+/// Exec-wrapper argument that expands at launch to the path of a private file
+/// exporting the launch environment, for commands that do not inherit it.
+pub const ENV_FILE_ARG: &str = "{{ENV_FILE}}";
+
+/// `sh -c` script used by the built-in wrappers: load and delete the env file
+/// named by `$1`, then run the command line in `$2`.
+pub const SOURCE_ENV_FILE: &str = r#"env=$(cat -- "$1"); status=$?; rm -f -- "$1"; [ "$status" -eq 0 ] || exit "$status"; eval "$env" && exec sh -c "$2""#;
+
+/// Wrappers available without configuration. A configured wrapper with the
+/// same name takes precedence.
+static BUILTIN_WRAPPERS: LazyLock<IndexMap<String, WrapperConfig>> = LazyLock::new(|| {
+    let exec = |name: &str, argv: &[&str]| {
+        let wrapper = WrapperConfig {
+            name: name.to_string(),
+            mode: WrapperMode::Exec {
+                argv: argv.iter().map(ToString::to_string).collect(),
+            },
+        };
+        (name.to_string(), wrapper)
+    };
+    IndexMap::from([
+        exec(
+            "tmux",
+            &[
+                "tmux",
+                "new-window",
+                "-n",
+                "{{title}}",
+                "-c",
+                "{{cwd}}",
+                "sh",
+                "-c",
+                SOURCE_ENV_FILE,
+                "tx-env",
+                ENV_FILE_ARG,
+                "{{CMD}}",
+            ],
+        ),
+        exec(
+            "zellij",
+            &[
+                "zellij",
+                "run",
+                "--name",
+                "{{title}}",
+                "--cwd",
+                "{{cwd}}",
+                "--",
+                "sh",
+                "-c",
+                SOURCE_ENV_FILE,
+                "tx-env",
+                ENV_FILE_ARG,
+                "{{CMD}}",
+            ],
+        ),
+    ])
+});
+
+/// The built-in `tmux` and `zellij` wrappers, which open the launch in a new
+/// window or pane named by `terminal_title`. The multiplexer's server starts
+/// the window, so both hand it the launch environment in an env file.
+#[must_use]
+pub fn builtin_wrappers() -> &'static IndexMap<String, WrapperConfig> {
+    &BUILTIN_WRAPPERS
+}
+
 #[derive(Debug, Clone)]
 pub struct ProfileConfig {
     pub name: String,
+    /// Direct parent named by `extends`; the other fields are already flattened.
+    pub extends: Option<String>,
     pub provider: String,
     pub description: Option<String>,
     pub pre: Vec<String>,
     pub post: Vec<String>,
-    pub wrap: Option<String>,
+    /// Wrappers applied around the pipeline, outermost first.
+    pub wrap: Vec<String>,
     pub prompt_assembler: Option<String>,
     pub prompt_assembler_args: Vec<String>,
+    /// Variables the profile's templates expect, in declaration order.
+    pub vars: IndexMap<String, ProfileVar>,
+    /// Replaces the provider's `flags` when set.
+    pub flags: Option<Vec<String>>,
+    /// Layered over the provider's environment, after `env_file`.
+    pub env: Vec<EnvVar>,
+    pub env_file: Vec<PathBuf>,
+    /// Launch directory. Relative paths start at the directory tx runs in.
+    pub cwd: Option<PathBuf>,
+    pub hooks: HooksConfig,
+}
+
+/// Shell commands run around a provider launch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HooksConfig {
+    /// Run before the provider starts; a failure cancels the launch.
+    pub before: Vec<String>,
+    /// Run after the provider exits, whatever its status.
+    pub after: Vec<String>,
+    /// Run when the provider exits with a failure, before `after`.
+    pub on_failure: Vec<String>,
+}
+
+/// A `[profiles.<name>.vars.<var>]` declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileVar {
+    pub name: String,
+    pub kind: VarKind,
+    pub default: Option<String>,
+    pub description: Option<String>,
+    pub choices: Vec<String>,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarKind {
+    String,
+    Integer,
+    Boolean,
+}
+
+impl VarKind {
+    pub const VARIANTS: [&'static str; 3] = ["string", "integer", "boolean"];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VarKind::String => "string",
+            VarKind::Integer => "integer",
+            VarKind::Boolean => "boolean",
+        }
+    }
+
+    #[must_use]
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "string" => Some(VarKind::String),
+            "integer" | "int" => Some(VarKind::Integer),
+            "boolean" | "bool" => Some(VarKind::Boolean),
+            _ => None,
+        }
+    }
+}
+
+impl ProfileVar {
+    /// Check a value against the declared type and choices.
+    ///
+    /// Returns the normalized value: integers and booleans are rewritten in
+    /// canonical form so templates see `true` rather than `yes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the value does not parse as the declared type or is
+    /// not one of the declared choices.
+    pub fn validate(&self, value: &str) -> Result<String> {
+        let normalized = match self.kind {
+            VarKind::String => value.to_string(),
+            VarKind::Integer => value
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| eyre!("variable '{}' expects an integer, got '{value}'", self.name))?
+                .to_string(),
+            VarKind::Boolean => match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => "true".to_string(),
+                "false" | "no" | "off" | "0" => "false".to_string(),
+                _ => {
+                    return Err(eyre!(
+                        "variable '{}' expects true or false, got '{value}'",
+                        self.name
+                    ));
+                }
+            },
+        };
+        if !self.choices.is_empty() && !self.choices.contains(&normalized) {
+            return Err(eyre!(
+                "variable '{}' must be one of {}, got '{value}'",
+                self.name,
+                self.choices.join(", ")
+            ));
+        }
+        Ok(normalized)
+    }
+
+    /// Whether a value must be supplied because nothing else fills it in.
+    #[must_use]
+    pub fn needs_value(&self) -> bool {
+        self.required && self.default.is_none()
+    }
+
+    /// One-line description used when asking for a value interactively.
+    #[must_use]
+    pub fn prompt_label(&self) -> String {
+        let mut parts = vec![self.name.clone()];
+        if let Some(description) = &self.description {
+            parts.push(format!("({description})"));
+        }
+        if !self.choices.is_empty() {
+            parts.push(format!("[{}]", self.choices.join("|")));
+        } else if self.kind != VarKind::String {
+            parts.push(format!("[{}]", self.kind.as_str()));
+        }
+        parts.join(" ")
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct FeatureConfig {
     pub prompt_assembler: Option<PromptAssemblerConfig>,
+    pub rag: RagConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RagConfig {
+    pub chunk_tokens: usize,
+    pub chunk_overlap: usize,
+    pub auto_index: bool,
+    pub auto_index_interval_secs: u64,
+    pub auto_index_max_chunks: usize,
+}
+
+impl RagConfig {
+    pub const DEFAULT_CHUNK_TOKENS: usize = 512;
+    pub const DEFAULT_CHUNK_OVERLAP: usize = 64;
+    pub const DEFAULT_AUTO_INDEX_INTERVAL_SECS: u64 = 300;
+    pub const DEFAULT_AUTO_INDEX_MAX_CHUNKS: usize = 256;
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        Self {
+            chunk_tokens: Self::DEFAULT_CHUNK_TOKENS,
+            chunk_overlap: Self::DEFAULT_CHUNK_OVERLAP,
+            auto_index: false,
+            auto_index_interval_secs: Self::DEFAULT_AUTO_INDEX_INTERVAL_SECS,
+            auto_index_max_chunks: Self::DEFAULT_AUTO_INDEX_MAX_CHUNKS,
+        }
+    }
+}
+
+/// Limits applied by `tx prune` to indexed sessions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionConfig {
+    pub max_age_days: Option<u64>,
+    pub max_sessions_per_provider: Option<usize>,
+    /// Keep sessions the user gave a thread name.
+    pub keep_named: bool,
+    /// Session ids or uuids that are never pruned.
+    pub keep: Vec<String>,
+}
+
+impl RetentionConfig {
+    /// Whether any limit is configured; without one nothing is ever pruned.
+    #[must_use]
+    pub fn has_limits(&self) -> bool {
+        self.max_age_days.is_some() || self.max_sessions_per_provider.is_some()
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            max_sessions_per_provider: None,
+            keep_named: true,
+            keep: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -132,6 +473,14 @@ pub enum DiagnosticLevel {
 }
 
 impl Config {
+    /// Look up a wrapper by name, falling back to the [built-in presets](builtin_wrappers).
+    #[must_use]
+    pub fn wrapper(&self, name: &str) -> Option<&WrapperConfig> {
+        self.wrappers
+            .get(name)
+            .or_else(|| BUILTIN_WRAPPERS.get(name))
+    }
+
     /// Parse a configuration [`Value`] into a [`Config`].
     ///
     /// # Errors
@@ -139,13 +488,33 @@ impl Config {
     /// Returns an error when the input TOML fails schema validation or cannot
     /// be decoded into the strongly typed configuration.
     pub fn from_value(value: &Value) -> Result<Self> {
-        let raw: RawConfig = value
-            .clone()
+        let raw: RawConfig = Self::resolve(value)?
             .try_into()
             .map_err(|err: toml::de::Error| eyre!("failed to decode configuration: {err}"))?;
         raw.into_config()
     }
 
+    /// Flatten profile `extends` chains and apply pending `key+` appends.
+    ///
+    /// This is the configuration [`Config::from_value`] decodes, and what
+    /// `tx config dump --resolved` prints.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when an append targets a non-array value.
+    pub fn resolve(value: &Value) -> Result<Value> {
+        let Value::Table(table) = value else {
+            return Ok(value.clone());
+        };
+        let mut table = table.clone();
+        if let Some(Value::Table(profiles)) = table.get("profiles") {
+            let profiles = merge::resolve_profiles(profiles)?;
+            table.insert("profiles".into(), Value::Table(profiles));
+        }
+        merge::resolve_appends(&mut table)?;
+        Ok(Value::Table(table))
+    }
+
     #[must_use]
     pub fn lint(&self) -> Vec<ConfigDiagnostic> {
         let mut diags = Vec::new();
@@ -171,8 +540,31 @@ impl Config {
             });
         }
 
+        diags.extend(self.template_diagnostics());
+        diags.extend(self.secret_diagnostics());
+
         for profile in self.profiles.values() {
-            if !self.providers.contains_key(&profile.provider) {
+            let extends_problem = self.extends_problem(profile);
+            let broken_chain = extends_problem.is_some();
+            if let Some(message) = extends_problem {
+                diags.push(ConfigDiagnostic {
+                    level: DiagnosticLevel::Error,
+                    message,
+                });
+            }
+
+            // A broken chain already explains a missing inherited provider.
+            if profile.provider.is_empty() {
+                if !broken_chain {
+                    diags.push(ConfigDiagnostic {
+                        level: DiagnosticLevel::Error,
+                        message: format!(
+                            "profile '{}' does not set a provider and does not inherit one",
+                            profile.name
+                        ),
+                    });
+                }
+            } else if !self.providers.contains_key(&profile.provider) {
                 diags.push(ConfigDiagnostic {
                     level: DiagnosticLevel::Error,
                     message: format!(
@@ -206,9 +598,10 @@ impl Config {
                 }
             }
 
-            if let Some(wrapper) = &profile.wrap
-                && !self.wrappers.contains_key(wrapper)
-            {
+            for wrapper in &profile.wrap {
+                if self.wrapper(wrapper).is_some() {
+                    continue;
+                }
                 diags.push(ConfigDiagnostic {
                     level: DiagnosticLevel::Warning,
                     message: format!(
@@ -217,10 +610,126 @@ impl Config {
                     ),
                 });
             }
+
+            for var in profile.vars.values() {
+                if var.required && var.default.is_some() {
+                    diags.push(ConfigDiagnostic {
+                        level: DiagnosticLevel::Warning,
+                        message: format!(
+                            "profile '{}' variable '{}' is required but has a default, so it is never prompted for",
+                            profile.name, var.name
+                        ),
+                    });
+                }
+            }
         }
 
         diags
     }
+
+    /// Resolve relative secret `file` paths against the configuration directory.
+    pub fn anchor_secret_files(&mut self, config_dir: &Path) {
+        for secret in self.secrets.values_mut() {
+            if let SecretSource::File { path } = &mut secret.source
+                && path.is_relative()
+            {
+                *path = config_dir.join(&*path);
+            }
+        }
+    }
+
+    /// Report provider env entries that reference undefined secrets.
+    fn secret_diagnostics(&self) -> Vec<ConfigDiagnostic> {
+        let mut diags = Vec::new();
+        for provider in self.providers.values() {
+            for entry in &provider.env {
+                for name in secrets::references(&entry.value_template) {
+                    if !self.secrets.contains_key(name) {
+                        diags.push(ConfigDiagnostic {
+                            level: DiagnosticLevel::Error,
+                            message: format!(
+                                "provider '{}' env {} references unknown secret '{name}'",
+                                provider.name, entry.key
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        diags
+    }
+
+    /// Report templates in wrappers, snippets, and titles that fail to parse.
+    fn template_diagnostics(&self) -> Vec<ConfigDiagnostic> {
+        let mut templates: Vec<(String, &str)> = Vec::new();
+        for wrapper in self.wrappers.values() {
+            let owner = format!("wrapper '{}'", wrapper.name);
+            match &wrapper.mode {
+                WrapperMode::Shell { command } => templates.push((owner, command)),
+                WrapperMode::Exec { argv } => {
+                    templates.extend(
+                        argv.iter()
+                            .filter(|arg| *arg != ENV_FILE_ARG)
+                            .map(|arg| (owner.clone(), arg.as_str())),
+                    );
+                }
+            }
+        }
+        for (kind, snippets) in [("pre", &self.snippets.pre), ("post", &self.snippets.post)] {
+            for snippet in snippets.values() {
+                templates.push((
+                    format!("{kind} snippet '{}'", snippet.name),
+                    &snippet.command,
+                ));
+            }
+        }
+        if let Some(title) = &self.defaults.terminal_title {
+            templates.push(("terminal_title".into(), title));
+        }
+
+        templates
+            .into_iter()
+            .filter_map(|(owner, source)| {
+                let err = Template::parse(source).err()?;
+                Some(ConfigDiagnostic {
+                    level: DiagnosticLevel::Error,
+                    message: format!("{owner}: {err}"),
+                })
+            })
+            .collect()
+    }
+
+    /// Describe a missing parent or cycle in the profile's `extends` chain.
+    fn extends_problem(&self, profile: &ProfileConfig) -> Option<String> {
+        let parent = profile.extends.as_deref()?;
+        if !self.profiles.contains_key(parent) {
+            return Some(format!(
+                "profile '{}' extends unknown profile '{parent}'",
+                profile.name
+            ));
+        }
+
+        let mut chain = vec![profile.name.as_str()];
+        let mut next = Some(parent);
+        while let Some(name) = next {
+            let seen = chain.contains(&name);
+            chain.push(name);
+            if seen {
+                return (name == profile.name).then(|| {
+                    format!(
+                        "profile '{}' is part of an extends cycle: {}",
+                        profile.name,
+                        chain.join(" -> ")
+                    )
+                });
+            }
+            next = self
+                .profiles
+                .get(name)
+                .and_then(|parent| parent.extends.as_deref());
+        }
+        None
+    }
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -240,6 +749,13 @@ pub(crate) struct RawConfig {
     profiles: IndexMap<String, RawProfile>,
     #[serde(default)]
     features: RawFeatures,
+    #[serde(default)]
+    retention: RawRetention,
+    #[serde(default)]
+    #[schemars(with = "std::collections::BTreeMap<String, RawSecret>")]
+    secrets: IndexMap<String, RawSecret>,
+    #[serde(default)]
+    hooks: RawHooks,
 }
 
 impl RawConfig {
@@ -259,10 +775,16 @@ impl RawConfig {
 
         let mut profiles = IndexMap::new();
         for (name, profile) in self.profiles {
-            profiles.insert(name.clone(), profile.into_profile(name));
+            profiles.insert(name.clone(), profile.into_profile(name)?);
         }
 
         let features = self.features.into_features()?;
+        let retention = self.retention.into_config()?;
+
+        let mut secrets = IndexMap::new();
+        for (name, secret) in self.secrets {
+            secrets.insert(name.clone(), secret.into_secret(name)?);
+        }
 
         Ok(Config {
             defaults,
@@ -271,6 +793,9 @@ impl RawConfig {
             wrappers,
             profiles,
             features,
+            retention,
+            secrets,
+            hooks: self.hooks.into_hooks(),
         })
     }
 }
@@ -281,6 +806,10 @@ pub(crate) struct RawDefaults {
     profile: Option<String>,
     #[serde(default = "RawDefaults::default_search_mode")]
     search_mode: String,
+    #[schemars(
+        description = "Default output format for `tx search`: json, ndjson, table, tsv, or ids."
+    )]
+    search_format: Option<String>,
     terminal_title: Option<String>,
 }
 
@@ -299,10 +828,18 @@ impl RawDefaults {
             }
         };
 
+        let search_format = match self.search_format.as_deref().map(str::trim) {
+            None | Some("") => SearchFormat::Json,
+            Some(raw) => {
+                SearchFormat::parse(raw).ok_or_else(|| eyre!("unknown search_format '{raw}'"))?
+            }
+        };
+
         Ok(Defaults {
             provider: self.provider,
             profile: self.profile,
             search_mode,
+            search_format,
             terminal_title: self.terminal_title,
         })
     }
@@ -371,6 +908,13 @@ pub(crate) struct RawProvider {
     #[serde(default)]
     env: Vec<String>,
     #[serde(default)]
+    #[schemars(
+        description = "Dotenv files loaded before `env`, in order. Relative paths start at the directory tx runs in; missing files are skipped."
+    )]
+    env_file: Vec<String>,
+    #[serde(default)]
+    hooks: RawHooks,
+    #[serde(default)]
     stdin_to: Option<String>,
     #[serde(default)]
     stdin_mode: RawStdinMode,
@@ -403,17 +947,26 @@ impl RawProvider {
             name,
             bin,
             flags: self.flags,
-            env: self
-                .env
-                .into_iter()
-                .map(|entry| parse_env_var(&entry))
-                .collect::<Result<Vec<_>>>()?,
+            env: parse_env_vars(self.env)?,
+            env_file: expand_paths(&self.env_file)?,
+            hooks: self.hooks.into_hooks(),
             session_roots,
             stdin,
         })
     }
 }
 
+fn parse_env_vars(entries: Vec<String>) -> Result<Vec<EnvVar>> {
+    entries
+        .into_iter()
+        .map(|entry| parse_env_var(&entry))
+        .collect()
+}
+
+fn expand_paths(raw: &[String]) -> Result<Vec<PathBuf>> {
+    raw.iter().map(|path| expand_path(path.trim())).collect()
+}
+
 fn parse_env_var(raw: &str) -> Result<EnvVar> {
     let (key, value) = raw
         .split_once('=')
@@ -487,6 +1040,13 @@ pub(crate) enum WrapperCommandSpec {
     List(Vec<String>),
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub(crate) enum RawWrapList {
+    One(String),
+    Chain(Vec<String>),
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub(crate) struct RawWrapper {
     #[serde(default)]
@@ -523,8 +1083,69 @@ impl RawWrapper {
     }
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(crate) struct RawSecret {
+    #[serde(default)]
+    #[schemars(
+        description = "Command whose standard output is the secret, e.g. `pass show api/openai`."
+    )]
+    command: Option<WrapperCommandSpec>,
+    #[serde(default)]
+    #[schemars(
+        description = "File holding the secret. Relative paths start at the configuration directory; the file must not be readable by group or others."
+    )]
+    file: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        with = "Option<std::collections::BTreeMap<String, String>>",
+        description = "Attributes passed to `secret-tool lookup`."
+    )]
+    secret_tool: Option<IndexMap<String, String>>,
+}
+
+impl RawSecret {
+    fn into_secret(self, name: String) -> Result<SecretConfig> {
+        let source = match (self.command, self.file, self.secret_tool) {
+            (Some(command), None, None) => {
+                let argv = match command {
+                    WrapperCommandSpec::String(raw) => parse_command_args(&raw)?,
+                    WrapperCommandSpec::List(argv) => argv,
+                };
+                if argv.is_empty() {
+                    return Err(eyre!("secret '{name}' has an empty command"));
+                }
+                SecretSource::Command { argv }
+            }
+            (None, Some(file), None) => SecretSource::File {
+                path: expand_path(file.trim())?,
+            },
+            (None, None, Some(attributes)) => {
+                if attributes.is_empty() {
+                    return Err(eyre!(
+                        "secret '{name}' needs at least one secret_tool attribute"
+                    ));
+                }
+                SecretSource::SecretTool { attributes }
+            }
+            _ => {
+                return Err(eyre!(
+                    "secret '{name}' must set exactly one of command, file, or secret_tool"
+                ));
+            }
+        };
+        Ok(SecretConfig { name, source })
+    }
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub(crate) struct RawProfile {
+    #[serde(default)]
+    #[schemars(
+        description = "Profile to inherit from. Keys set here override the parent's; `pre+` and `post+` append to its snippet lists."
+    )]
+    extends: Option<String>,
+    #[serde(default)]
+    #[schemars(description = "Provider to launch. Required unless inherited through `extends`.")]
     provider: String,
     #[serde(default)]
     description: Option<String>,
@@ -533,7 +1154,8 @@ pub(crate) struct RawProfile {
     #[serde(default)]
     post: Vec<String>,
     #[serde(default)]
-    wrap: Option<String>,
+    #[schemars(description = "Wrapper name, or a list of wrapper names applied outermost first.")]
+    wrap: Option<RawWrapList>,
     #[serde(default)]
     #[schemars(
         description = "Prompt name to render via the prompt-assembler helper before launching the provider."
@@ -541,12 +1163,79 @@ pub(crate) struct RawProfile {
     prompt_assembler: Option<String>,
     #[serde(default)]
     prompt_assembler_args: Vec<String>,
+    #[serde(default)]
+    #[schemars(
+        with = "std::collections::BTreeMap<String, RawProfileVar>",
+        description = "Variables the profile's templates use, supplied with `--var KEY=VALUE` or prompted for."
+    )]
+    vars: IndexMap<String, RawProfileVar>,
+    #[serde(default)]
+    #[schemars(description = "Provider arguments used instead of the provider's `flags`.")]
+    flags: Option<Vec<String>>,
+    #[serde(default)]
+    #[schemars(
+        description = "Environment entries (`KEY=value`) that override the provider's and the env files'."
+    )]
+    env: Vec<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Dotenv files loaded after the provider's environment and before `env`."
+    )]
+    env_file: Vec<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Directory to launch the provider in. Relative paths start at the directory tx runs in."
+    )]
+    cwd: Option<String>,
+    #[serde(default)]
+    hooks: RawHooks,
+}
+
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub(crate) struct RawHooks {
+    #[serde(default)]
+    #[schemars(
+        description = "Shell commands run before the provider starts. A failure cancels the launch."
+    )]
+    before: Vec<String>,
+    #[serde(default)]
+    #[schemars(description = "Shell commands run after the provider exits, whatever its status.")]
+    after: Vec<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Shell commands run when the provider exits with a failure, before `after`."
+    )]
+    on_failure: Vec<String>,
+}
+
+impl RawHooks {
+    fn into_hooks(self) -> HooksConfig {
+        HooksConfig {
+            before: self.before,
+            after: self.after,
+            on_failure: self.on_failure,
+        }
+    }
 }
 
 impl RawProfile {
-    fn into_profile(self, name: String) -> ProfileConfig {
-        ProfileConfig {
+    fn into_profile(self, name: String) -> Result<ProfileConfig> {
+        let mut vars = IndexMap::new();
+        for (var_name, raw) in self.vars {
+            let var = raw
+                .into_var(var_name.clone())
+                .wrap_err_with(|| format!("invalid variable in profile '{name}'"))?;
+            vars.insert(var_name, var);
+        }
+        let env = parse_env_vars(self.env)
+            .wrap_err_with(|| format!("invalid env entry in profile '{name}'"))?;
+
+        Ok(ProfileConfig {
             name,
+            extends: self
+                .extends
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty()),
             provider: self.provider,
             description: self
                 .description
@@ -554,20 +1243,114 @@ impl RawProfile {
                 .filter(|value| !value.is_empty()),
             pre: self.pre,
             post: self.post,
-            wrap: self.wrap,
+            wrap: match self.wrap {
+                None => Vec::new(),
+                Some(RawWrapList::One(name)) => vec![name],
+                Some(RawWrapList::Chain(names)) => names,
+            },
             prompt_assembler: self
                 .prompt_assembler
                 .map(|value| value.trim().to_string())
                 .filter(|value| !value.is_empty()),
             prompt_assembler_args: self.prompt_assembler_args,
+            vars,
+            flags: self.flags,
+            env,
+            env_file: expand_paths(&self.env_file)?,
+            cwd: self
+                .cwd
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(expand_path)
+                .transpose()?,
+            hooks: self.hooks.into_hooks(),
+        })
+    }
+}
+
+/// A TOML scalar accepted for variable defaults and choices.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub(crate) enum RawScalar {
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+}
+
+impl RawScalar {
+    fn into_string(self) -> String {
+        match self {
+            RawScalar::Boolean(value) => value.to_string(),
+            RawScalar::Integer(value) => value.to_string(),
+            RawScalar::String(value) => value,
         }
     }
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(crate) struct RawProfileVar {
+    #[serde(default, rename = "type")]
+    #[schemars(description = "Value type: string (default), integer, or boolean.")]
+    kind: Option<String>,
+    #[serde(default)]
+    #[schemars(description = "Value used when none is supplied.")]
+    default: Option<RawScalar>,
+    #[serde(default)]
+    #[schemars(description = "Shown when prompting for a value.")]
+    description: Option<String>,
+    #[serde(default)]
+    #[schemars(description = "Allowed values. Empty allows any value of the declared type.")]
+    choices: Vec<RawScalar>,
+    #[serde(default)]
+    #[schemars(
+        description = "Refuse to launch (or prompt on a terminal) when no value or default is available."
+    )]
+    required: bool,
+}
+
+impl RawProfileVar {
+    fn into_var(self, name: String) -> Result<ProfileVar> {
+        let kind = match self.kind.as_deref() {
+            None => VarKind::String,
+            Some(raw) => VarKind::parse(raw).ok_or_else(|| {
+                eyre!(
+                    "variable '{name}' has unknown type '{raw}'; expected one of {}",
+                    VarKind::VARIANTS.join(", ")
+                )
+            })?,
+        };
+        let mut var = ProfileVar {
+            name,
+            kind,
+            default: None,
+            description: self
+                .description
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty()),
+            choices: Vec::new(),
+            required: self.required,
+        };
+        var.choices = self
+            .choices
+            .into_iter()
+            .map(|choice| var.validate(&choice.into_string()))
+            .collect::<Result<_>>()?;
+        var.default = self
+            .default
+            .map(|default| var.validate(&default.into_string()))
+            .transpose()
+            .wrap_err_with(|| format!("default for variable '{}' is invalid", var.name))?;
+        Ok(var)
+    }
+}
+
 #[derive(Debug, Deserialize, Default, JsonSchema)]
 pub(crate) struct RawFeatures {
     #[serde(default)]
     pa: Option<RawPromptAssembler>,
+    #[serde(default)]
+    rag: Option<RawRag>,
 }
 
 impl RawFeatures {
@@ -577,7 +1360,94 @@ impl RawFeatures {
             _ => None,
         };
 
-        Ok(FeatureConfig { prompt_assembler })
+        let rag = self
+            .rag
+            .map_or_else(|| Ok(RagConfig::default()), RawRag::into_config)?;
+
+        Ok(FeatureConfig {
+            prompt_assembler,
+            rag,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub(crate) struct RawRag {
+    /// Maximum estimated tokens per semantic chunk.
+    chunk_tokens: Option<usize>,
+    /// Estimated tokens shared between consecutive chunks of the same message.
+    chunk_overlap: Option<usize>,
+    /// Embed messages of sessions that indexing added or changed after a successful command.
+    auto_index: Option<bool>,
+    /// Minimum seconds between automatic embedding runs.
+    auto_index_interval_secs: Option<u64>,
+    /// Maximum chunks embedded by a single automatic run.
+    auto_index_max_chunks: Option<usize>,
+}
+
+impl RawRag {
+    fn into_config(self) -> Result<RagConfig> {
+        let chunk_tokens = self.chunk_tokens.unwrap_or(RagConfig::DEFAULT_CHUNK_TOKENS);
+        let chunk_overlap = self
+            .chunk_overlap
+            .unwrap_or(RagConfig::DEFAULT_CHUNK_OVERLAP);
+        if chunk_tokens == 0 {
+            return Err(eyre!("features.rag.chunk_tokens must be greater than zero"));
+        }
+        if chunk_overlap >= chunk_tokens {
+            return Err(eyre!(
+                "features.rag.chunk_overlap ({chunk_overlap}) must be smaller than chunk_tokens ({chunk_tokens})"
+            ));
+        }
+        let auto_index_max_chunks = self
+            .auto_index_max_chunks
+            .unwrap_or(RagConfig::DEFAULT_AUTO_INDEX_MAX_CHUNKS);
+        if auto_index_max_chunks == 0 {
+            return Err(eyre!(
+                "features.rag.auto_index_max_chunks must be greater than zero"
+            ));
+        }
+        Ok(RagConfig {
+            chunk_tokens,
+            chunk_overlap,
+            auto_index: self.auto_index.unwrap_or(false),
+            auto_index_interval_secs: self
+                .auto_index_interval_secs
+                .unwrap_or(RagConfig::DEFAULT_AUTO_INDEX_INTERVAL_SECS),
+            auto_index_max_chunks,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub(crate) struct RawRetention {
+    /// Prune sessions whose last activity is older than this many days.
+    max_age_days: Option<u64>,
+    /// Keep at most this many of each provider's most recently active sessions.
+    max_sessions_per_provider: Option<usize>,
+    /// Never prune sessions that have a thread name (defaults to true).
+    keep_named: Option<bool>,
+    /// Session ids or uuids that are never pruned.
+    #[serde(default)]
+    keep: Vec<String>,
+}
+
+impl RawRetention {
+    fn into_config(self) -> Result<RetentionConfig> {
+        if self.max_age_days == Some(0) {
+            return Err(eyre!("retention.max_age_days must be greater than zero"));
+        }
+        if self.max_sessions_per_provider == Some(0) {
+            return Err(eyre!(
+                "retention.max_sessions_per_provider must be greater than zero"
+            ));
+        }
+        Ok(RetentionConfig {
+            max_age_days: self.max_age_days,
+            max_sessions_per_provider: self.max_sessions_per_provider,
+            keep_named: self.keep_named.unwrap_or(true),
+            keep: self.keep,
+        })
     }
 }
 
@@ -690,6 +1560,7 @@ mod tests {
             provider: Some("codex".into()),
             profile: Some("demo".into()),
             search_mode: SearchMode::FirstPrompt,
+            search_format: SearchFormat::Json,
             terminal_title: None,
         };
 
@@ -705,11 +1576,18 @@ mod tests {
             "demo".into(),
             ProfileConfig {
                 name: "demo".into(),
+                extends: None,
+                vars: IndexMap::new(),
+                flags: None,
+                env: Vec::new(),
+                env_file: Vec::new(),
+                cwd: None,
+                hooks: HooksConfig::default(),
                 provider: "missing-provider".into(),
                 description: None,
                 pre: vec!["prep".into()],
                 post: vec!["cleanup".into()],
-                wrap: Some("shellwrap".into()),
+                wrap: vec!["shellwrap".into()],
                 prompt_assembler: None,
                 prompt_assembler_args: Vec::new(),
             },
@@ -723,7 +1601,11 @@ mod tests {
             profiles,
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let diagnostics = config.lint();
@@ -761,6 +1643,7 @@ mod tests {
             provider: None,
             profile: None,
             search_mode: "invalid".into(),
+            search_format: None,
             terminal_title: None,
         };
         let err = defaults
@@ -769,6 +1652,338 @@ mod tests {
         assert!(err.to_string().contains("unknown search_mode 'invalid'"));
     }
 
+    #[test]
+    fn raw_defaults_parses_search_format() {
+        let defaults = RawDefaults {
+            provider: None,
+            profile: None,
+            search_mode: "first_prompt".into(),
+            search_format: Some(" TSV ".into()),
+            terminal_title: None,
+        };
+        let parsed = defaults.into_defaults().expect("tsv is a known format");
+        assert_eq!(parsed.search_format, SearchFormat::Tsv);
+
+        let defaults = RawDefaults {
+            provider: None,
+            profile: None,
+            search_mode: "first_prompt".into(),
+            search_format: Some("yaml".into()),
+            terminal_title: None,
+        };
+        let err = defaults
+            .into_defaults()
+            .expect_err("unknown search format should fail");
+        assert!(err.to_string().contains("unknown search_format 'yaml'"));
+    }
+
+    #[test]
+    fn config_from_value_parses_rag_chunking() {
+        let value: Value = toml::from_str(
+            r"
+            [features.rag]
+            chunk_tokens = 256
+            ",
+        )
+        .expect("parse toml");
+        let config = Config::from_value(&value).expect("rag config should parse");
+        assert_eq!(config.features.rag.chunk_tokens, 256);
+        assert_eq!(
+            config.features.rag.chunk_overlap,
+            RagConfig::DEFAULT_CHUNK_OVERLAP
+        );
+
+        let value: Value = toml::from_str(
+            r"
+            [features.rag]
+            chunk_tokens = 64
+            chunk_overlap = 64
+            ",
+        )
+        .expect("parse toml");
+        let err = Config::from_value(&value).expect_err("overlap must be below chunk size");
+        assert!(
+            format!("{err:?}")
+                .contains("chunk_overlap (64) must be smaller than chunk_tokens (64)")
+        );
+    }
+
+    #[test]
+    fn config_from_value_parses_rag_auto_index() {
+        let value: Value = toml::from_str(
+            r"
+            [features.rag]
+            auto_index = true
+            auto_index_max_chunks = 32
+            ",
+        )
+        .expect("parse toml");
+        let config = Config::from_value(&value).expect("rag config should parse");
+        assert!(config.features.rag.auto_index);
+        assert_eq!(config.features.rag.auto_index_max_chunks, 32);
+        assert_eq!(
+            config.features.rag.auto_index_interval_secs,
+            RagConfig::DEFAULT_AUTO_INDEX_INTERVAL_SECS
+        );
+
+        let value: Value = toml::from_str(
+            r"
+            [features.rag]
+            auto_index_max_chunks = 0
+            ",
+        )
+        .expect("parse toml");
+        let err = Config::from_value(&value).expect_err("zero batch cap should fail");
+        assert!(format!("{err:?}").contains("auto_index_max_chunks must be greater than zero"));
+    }
+
+    #[test]
+    fn config_from_value_parses_retention() {
+        let value: Value = toml::from_str(
+            r#"
+            [retention]
+            max_age_days = 90
+            keep = ["codex/pinned.jsonl"]
+            "#,
+        )
+        .expect("parse toml");
+        let config = Config::from_value(&value).expect("retention config should parse");
+        assert_eq!(config.retention.max_age_days, Some(90));
+        assert_eq!(config.retention.max_sessions_per_provider, None);
+        assert!(config.retention.keep_named);
+        assert_eq!(
+            config.retention.keep,
+            vec!["codex/pinned.jsonl".to_string()]
+        );
+        assert!(config.retention.has_limits());
+        assert!(!RetentionConfig::default().has_limits());
+
+        let value: Value = toml::from_str(
+            r"
+            [retention]
+            max_sessions_per_provider = 0
+            ",
+        )
+        .expect("parse toml");
+        let err = Config::from_value(&value).expect_err("zero cap should fail");
+        assert!(format!("{err:?}").contains("max_sessions_per_provider must be greater than zero"));
+    }
+
+    #[test]
+    fn config_from_value_resolves_profile_extends() {
+        let value: Value = toml::from_str(
+            r#"
+            [providers.codex]
+            bin = "codex"
+
+            [snippets.pre]
+            lint = "cargo fmt --check"
+            fetch = "git fetch"
+
+            [profiles.base]
+            provider = "codex"
+            pre = ["lint"]
+
+            [profiles.review]
+            extends = "base"
+            "pre+" = ["fetch"]
+            "#,
+        )
+        .expect("parse toml");
+        let config = Config::from_value(&value).expect("profiles should resolve");
+        let review = &config.profiles["review"];
+        assert_eq!(review.extends.as_deref(), Some("base"));
+        assert_eq!(review.provider, "codex");
+        assert_eq!(review.pre, vec!["lint".to_string(), "fetch".to_string()]);
+        assert!(config.lint().is_empty());
+    }
+
+    #[test]
+    fn config_from_value_decodes_hooks_at_every_level() {
+        let value: Value = toml::from_str(
+            r#"
+            [hooks]
+            before = ["date > .tx-started"]
+
+            [providers.codex]
+            bin = "codex"
+            hooks.on_failure = ["notify-send failed"]
+
+            [profiles.base]
+            provider = "codex"
+            hooks.after = ["git status --short"]
+
+            [profiles.review]
+            extends = "base"
+            hooks."after+" = ["echo done"]
+            "#,
+        )
+        .expect("parse toml");
+        let config = Config::from_value(&value).expect("hooks should decode");
+        assert_eq!(config.hooks.before, vec!["date > .tx-started".to_string()]);
+        assert_eq!(
+            config.providers["codex"].hooks.on_failure,
+            vec!["notify-send failed".to_string()]
+        );
+        let review = &config.profiles["review"].hooks;
+        assert_eq!(
+            review.after,
+            vec!["git status --short".to_string(), "echo done".to_string()]
+        );
+        assert!(review.before.is_empty());
+    }
+
+    #[test]
+    fn config_from_value_decodes_secrets_and_lints_references() {
+        let value: Value = toml::from_str(
+            r#"
+            [providers.codex]
+            bin = "codex"
+            env = ["OPENAI_API_KEY=${secret:openai}", "GH_TOKEN=${secret:github}"]
+
+            [secrets.openai]
+            command = "pass show 'api/open ai'"
+
+            [secrets.keyring]
+            secret_tool = { service = "tx", account = "me" }
+
+            [secrets.local]
+            file = "secrets/token"
+            "#,
+        )
+        .expect("parse toml");
+        let mut config = Config::from_value(&value).expect("secrets should parse");
+        assert!(matches!(
+            &config.secrets["openai"].source,
+            SecretSource::Command { argv } if argv == &["pass", "show", "api/open ai"]
+        ));
+        assert_eq!(
+            config.secrets["keyring"].source.describe(),
+            "secret-tool service=tx account=me"
+        );
+        config.anchor_secret_files(Path::new("/etc/tx"));
+        assert!(matches!(
+            &config.secrets["local"].source,
+            SecretSource::File { path } if path == Path::new("/etc/tx/secrets/token")
+        ));
+
+        let messages: Vec<_> = config.lint().into_iter().map(|diag| diag.message).collect();
+        assert_eq!(
+            messages,
+            ["provider 'codex' env GH_TOKEN references unknown secret 'github'"]
+        );
+
+        let value: Value = toml::from_str(
+            r#"
+            [secrets.both]
+            command = "pass show x"
+            file = "x"
+            "#,
+        )
+        .expect("parse toml");
+        let err = Config::from_value(&value).expect_err("two sources should fail");
+        assert!(
+            err.to_string()
+                .contains("secret 'both' must set exactly one of command, file, or secret_tool")
+        );
+    }
+
+    #[test]
+    fn config_from_value_decodes_profile_vars() {
+        let value: Value = toml::from_str(
+            r#"
+            [providers.codex]
+            bin = "codex"
+
+            [profiles.base.vars.ticket]
+            type = "integer"
+            description = "Tracker id"
+            required = true
+
+            [profiles.base]
+            provider = "codex"
+
+            [profiles.review]
+            extends = "base"
+
+            [profiles.review.vars.depth]
+            type = "integer"
+            choices = [1, 2, 3]
+            default = 2
+            required = true
+            "#,
+        )
+        .expect("parse toml");
+        let config = Config::from_value(&value).expect("vars should decode");
+        let review = &config.profiles["review"];
+        assert_eq!(review.vars.keys().collect::<Vec<_>>(), ["ticket", "depth"]);
+        let ticket = &review.vars["ticket"];
+        assert_eq!(ticket.kind, VarKind::Integer);
+        assert!(ticket.needs_value());
+        assert_eq!(ticket.prompt_label(), "ticket (Tracker id) [integer]");
+        let depth = &review.vars["depth"];
+        assert_eq!(depth.choices, ["1", "2", "3"]);
+        assert_eq!(depth.default.as_deref(), Some("2"));
+        assert_eq!(depth.prompt_label(), "depth [1|2|3]");
+        assert_eq!(
+            config
+                .lint()
+                .into_iter()
+                .map(|diag| diag.message)
+                .collect::<Vec<_>>(),
+            vec![
+                "profile 'review' variable 'depth' is required but has a default, so it is never prompted for"
+            ]
+        );
+
+        let bad: Value = toml::from_str(
+            r#"
+            [profiles.base]
+            provider = "codex"
+            vars.mode = { choices = ["fast"], default = "slow" }
+            "#,
+        )
+        .expect("parse toml");
+        let err = Config::from_value(&bad).expect_err("default outside choices");
+        let message = format!("{err:#}");
+        assert!(message.contains("default for variable 'mode' is invalid"));
+        assert!(message.contains("must be one of fast"));
+    }
+
+    #[test]
+    fn config_lint_reports_extends_cycles_and_missing_parents() {
+        let value: Value = toml::from_str(
+            r#"
+            [providers.codex]
+            bin = "codex"
+
+            [profiles.a]
+            extends = "b"
+            provider = "codex"
+
+            [profiles.b]
+            extends = "a"
+
+            [profiles.c]
+            extends = "a"
+
+            [profiles.orphan]
+            extends = "missing"
+            "#,
+        )
+        .expect("parse toml");
+        let config = Config::from_value(&value).expect("lint reports inheritance problems");
+        let messages: Vec<_> = config.lint().into_iter().map(|diag| diag.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "profile 'a' is part of an extends cycle: a -> b -> a".to_string(),
+                "profile 'b' is part of an extends cycle: b -> a -> b".to_string(),
+                "profile 'orphan' extends unknown profile 'missing'".to_string(),
+            ]
+        );
+    }
+
     #[test]
     fn config_from_value_rejects_malformed_env_entry() {
         let value: Value = toml::from_str(
@@ -832,6 +2047,45 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn config_from_value_accepts_wrapper_chains_and_builtin_wrappers() {
+        let value: Value = toml::from_str(
+            r#"
+            [providers.codex]
+            bin = "codex"
+
+            [wrappers.logged]
+            shell = true
+            cmd = "script -q /tmp/log -c {{CMD}}"
+
+            [profiles.single]
+            provider = "codex"
+            wrap = "tmux"
+
+            [profiles.chained]
+            provider = "codex"
+            wrap = ["zellij", "logged"]
+
+            [profiles.broken]
+            provider = "codex"
+            wrap = ["tmux", "missing"]
+            "#,
+        )
+        .expect("parse toml");
+        let config = Config::from_value(&value).expect("profiles should parse");
+        assert_eq!(config.profiles["single"].wrap, ["tmux"]);
+        assert_eq!(config.profiles["chained"].wrap, ["zellij", "logged"]);
+        assert!(config.wrapper("tmux").is_some());
+        assert!(config.wrapper("logged").is_some());
+        assert!(!config.wrappers.contains_key("tmux"));
+
+        let messages: Vec<_> = config.lint().into_iter().map(|diag| diag.message).collect();
+        assert_eq!(
+            messages,
+            ["profile 'broken' references unknown wrapper 'missing'"]
+        );
+    }
+
     #[test]
     fn config_lint_reports_unknown_provider_reference() {
         let mut config = lint_fixture_config();
@@ -844,6 +2098,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn config_lint_reports_template_errors_with_positions() {
+        let mut config = lint_fixture_config();
+        config.wrappers.insert(
+            "tmux".into(),
+            WrapperConfig {
+                name: "tmux".into(),
+                mode: WrapperMode::Exec {
+                    argv: vec![
+                        "tmux".into(),
+                        "new-window".into(),
+                        "{{thread_name | kebab}}".into(),
+                        "{{CMD | shout}}".into(),
+                    ],
+                },
+            },
+        );
+        config.snippets.pre.insert(
+            "setup".into(),
+            Snippet {
+                name: "setup".into(),
+                command: "{{#if var:ticket}}echo {{var:ticket}}".into(),
+            },
+        );
+        config.defaults.terminal_title = Some("tx {{session.nope}}".into());
+
+        let messages: Vec<_> = config
+            .lint()
+            .into_iter()
+            .filter(|diag| diag.level == DiagnosticLevel::Error)
+            .map(|diag| diag.message)
+            .collect();
+        for expected in [
+            "wrapper 'tmux': line 1, column 9: unknown filter 'shout'",
+            "pre snippet 'setup': line 1, column 1: unclosed {{#if}}",
+            "terminal_title: line 1, column 6: unknown template placeholder '{{session.nope}}'",
+        ] {
+            assert!(
+                messages.iter().any(|msg| msg == expected),
+                "missing {expected:?} in {messages:?}"
+            );
+        }
+        assert!(!messages.iter().any(|msg| msg.contains("kebab")));
+    }
+
     #[test]
     fn expand_optional_path_returns_none_for_blank_input() {
         assert_eq!(expand_optional_path("   "), None);
@@ -1009,6 +2308,8 @@ mod tests {
                 bin: "codex".into(),
                 flags: Vec::new(),
                 env: Vec::new(),
+                env_file: Vec::new(),
+                hooks: HooksConfig::default(),
                 session_roots: Vec::new(),
                 stdin: None,
             },
@@ -1019,11 +2320,18 @@ mod tests {
             "default".into(),
             ProfileConfig {
                 name: "default".into(),
+                extends: None,
+                vars: IndexMap::new(),
+                flags: None,
+                env: Vec::new(),
+                env_file: Vec::new(),
+                cwd: None,
+                hooks: HooksConfig::default(),
                 provider: "codex".into(),
                 description: None,
                 pre: vec!["setup".into()],
                 post: vec!["teardown".into()],
-                wrap: Some("missing-wrap".into()),
+                wrap: vec!["missing-wrap".into()],
                 prompt_assembler: None,
                 prompt_assembler_args: Vec::new(),
             },
@@ -1034,6 +2342,7 @@ mod tests {
                 provider: Some("missing".into()),
                 profile: Some("absent-profile".into()),
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -1045,7 +2354,11 @@ mod tests {
             profiles,
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         }
     }
 }
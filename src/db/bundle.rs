@@ -0,0 +1,606 @@
+use std::cmp::Ordering;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use color_eyre::eyre::{Context, eyre};
+use rusqlite::{OptionalExtension, Transaction, params};
+use serde_json::{Value, json};
+
+use super::{Database, map_summary, map_token_usage, write_session};
+use crate::session::{MessageRecord, SessionIngest, SessionSummary, TokenUsageRecord};
+use crate::util::unix_timestamp;
+
+/// Format tag written on the first line of every bundle.
+pub const BUNDLE_FORMAT: &str = "tx-bundle";
+/// Bundle layout version written by this binary and the newest one it reads.
+pub const BUNDLE_VERSION: i64 = 1;
+
+/// Provenance of a session imported from another machine's bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionOrigin {
+    pub machine: String,
+    pub source_id: String,
+    pub imported_ms: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BundleExportReport {
+    pub sessions: usize,
+    pub messages: usize,
+    pub token_usage: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BundleImportReport {
+    pub machine: String,
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    /// Sessions left alone because a local or newer copy with the same uuid exists,
+    /// or because they were pruned.
+    pub skipped: usize,
+}
+
+enum ImportAction {
+    Insert,
+    Replace(String),
+    Unchanged,
+    Skip,
+}
+
+/// Identifier an imported session is stored under, so it never collides with a
+/// session indexed locally.
+#[must_use]
+pub fn imported_session_id(machine: &str, source_id: &str) -> String {
+    format!("{source_id}@{machine}")
+}
+
+impl Database {
+    /// Write every locally indexed session, with its messages and token usage, as a
+    /// newline-delimited JSON bundle tagged with `machine`.
+    ///
+    /// Sessions imported from other machines are not exported again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a query fails or the bundle cannot be written.
+    pub fn export_bundle<W: Write>(
+        &self,
+        writer: &mut W,
+        machine: &str,
+    ) -> Result<BundleExportReport> {
+        let header = json!({
+            "format": BUNDLE_FORMAT,
+            "version": BUNDLE_VERSION,
+            "machine": machine,
+            "exported_ms": unix_timestamp().saturating_mul(1000),
+        });
+        writeln!(writer, "{header}")?;
+
+        let mut report = BundleExportReport::default();
+        for summary in self.local_sessions()? {
+            let messages = self.session_messages(&summary.id)?;
+            let token_usage = self.session_token_usage(&summary.id)?;
+            report.sessions += 1;
+            report.messages += messages.len();
+            report.token_usage += token_usage.len();
+            writeln!(
+                writer,
+                "{}",
+                session_to_json(&summary, &messages, &token_usage)
+            )?;
+        }
+        writer.flush()?;
+        Ok(report)
+    }
+
+    /// Merge a bundle produced by [`Database::export_bundle`] on another machine.
+    ///
+    /// Sessions are matched by uuid (falling back to their imported identifier):
+    /// local sessions always win, an imported copy is replaced only by a newer
+    /// one, and importing the same bundle twice changes nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bundle is malformed or a write fails; nothing is
+    /// imported in that case.
+    pub fn import_bundle<R: BufRead>(&mut self, reader: R) -> Result<BundleImportReport> {
+        let mut lines = reader
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()));
+        let header = match lines.next() {
+            Some((_, line)) => line.context("failed to read bundle")?,
+            None => return Err(eyre!("bundle is empty")),
+        };
+        let machine = parse_header(&header)?;
+        let imported_ms = unix_timestamp().saturating_mul(1000);
+        let mut report = BundleImportReport {
+            machine: machine.clone(),
+            ..BundleImportReport::default()
+        };
+
+        let tx = self.conn.transaction()?;
+        for (index, line) in lines {
+            let line_number = index + 1;
+            let line = line.context("failed to read bundle")?;
+            let value: Value = serde_json::from_str(&line)
+                .with_context(|| format!("bundle line {line_number} is not valid JSON"))?;
+            let (source_id, ingest) = ingest_from_json(&value, &machine)
+                .with_context(|| format!("bundle line {line_number} is not a session"))?;
+
+            match import_action(&tx, &ingest.summary)? {
+                ImportAction::Insert => report.added += 1,
+                ImportAction::Replace(existing) => {
+                    if existing != ingest.summary.id {
+                        tx.execute("DELETE FROM sessions WHERE id = ?1", [&existing])?;
+                    }
+                    report.updated += 1;
+                }
+                ImportAction::Unchanged => {
+                    report.unchanged += 1;
+                    continue;
+                }
+                ImportAction::Skip => {
+                    report.skipped += 1;
+                    continue;
+                }
+            }
+            write_session(&tx, &ingest)?;
+            tx.execute(
+                r"
+                INSERT INTO session_origins (session_id, machine, source_id, imported_ms)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(session_id) DO UPDATE SET
+                    machine = excluded.machine,
+                    source_id = excluded.source_id,
+                    imported_ms = excluded.imported_ms
+                ",
+                params![ingest.summary.id, machine, source_id, imported_ms],
+            )?;
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Look up where a session was imported from; `None` for local sessions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn session_origin(&self, session_id: &str) -> Result<Option<SessionOrigin>> {
+        self.conn
+            .query_row(
+                "SELECT machine, source_id, imported_ms FROM session_origins WHERE session_id = ?1",
+                [session_id],
+                |row| {
+                    Ok(SessionOrigin {
+                        machine: row.get(0)?,
+                        source_id: row.get(1)?,
+                        imported_ms: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|err| eyre!("failed to query origin of session {session_id}: {err}"))
+    }
+
+    fn local_sessions(&self) -> Result<Vec<SessionSummary>> {
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT
+                id,
+                provider,
+                wrapper,
+                model,
+                label,
+                thread_name,
+                path,
+                uuid,
+                first_prompt,
+                actionable,
+                subagent,
+                created_at,
+                started_at,
+                last_active,
+                size,
+                mtime
+            FROM sessions
+            WHERE id NOT IN (SELECT session_id FROM session_origins)
+            ORDER BY id
+            ",
+        )?;
+        let rows = stmt.query_map([], map_summary)?;
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(row?);
+        }
+        Ok(out)
+    }
+
+    fn session_token_usage(&self, session_id: &str) -> Result<Vec<TokenUsageRecord>> {
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT
+                session_id,
+                timestamp,
+                input_tokens,
+                cached_input_tokens,
+                output_tokens,
+                reasoning_output_tokens,
+                total_tokens,
+                model,
+                rate_limits
+            FROM token_usage
+            WHERE session_id = ?1
+            ORDER BY timestamp
+            ",
+        )?;
+        let rows = stmt.query_map([session_id], map_token_usage)?;
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(row?);
+        }
+        Ok(out)
+    }
+}
+
+fn parse_header(line: &str) -> Result<String> {
+    let header: Value = serde_json::from_str(line).context("bundle header is not valid JSON")?;
+    if header.get("format").and_then(Value::as_str) != Some(BUNDLE_FORMAT) {
+        return Err(eyre!(
+            "not a tx bundle (missing \"format\": \"{BUNDLE_FORMAT}\")"
+        ));
+    }
+    let version = header.get("version").and_then(Value::as_i64).unwrap_or(0);
+    if !(1..=BUNDLE_VERSION).contains(&version) {
+        return Err(eyre!(
+            "unsupported bundle version {version} (this binary reads up to {BUNDLE_VERSION})"
+        ));
+    }
+    header
+        .get("machine")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|machine| !machine.is_empty())
+        .map(ToString::to_string)
+        .ok_or_else(|| eyre!("bundle header does not name the machine it came from"))
+}
+
+/// Decide what importing `incoming` should do given what the database already holds.
+fn import_action(tx: &Transaction<'_>, incoming: &SessionSummary) -> Result<ImportAction> {
+    let pruned = tx
+        .query_row(
+            "SELECT 1 FROM pruned_sessions WHERE id = ?1",
+            [&incoming.id],
+            |_| Ok(()),
+        )
+        .optional()?;
+    if pruned.is_some() {
+        return Ok(ImportAction::Skip);
+    }
+
+    // (id, imported, last_active, size) of the copy already present, local copies first.
+    let select = r"
+        SELECT s.id, o.session_id IS NOT NULL, s.last_active, s.size
+        FROM sessions s
+        LEFT JOIN session_origins o ON o.session_id = s.id
+    ";
+    let map = |row: &rusqlite::Row<'_>| -> rusqlite::Result<(String, bool, Option<i64>, i64)> {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    };
+    let by_uuid = match incoming.uuid.as_deref() {
+        Some(uuid) => tx
+            .query_row(
+                &format!("{select} WHERE s.uuid = ?1 ORDER BY 2 LIMIT 1"),
+                [uuid],
+                map,
+            )
+            .optional()?,
+        None => None,
+    };
+    let existing = match by_uuid {
+        Some(existing) => Some(existing),
+        None => tx
+            .query_row(&format!("{select} WHERE s.id = ?1"), [&incoming.id], map)
+            .optional()?,
+    };
+
+    let Some((id, imported, last_active, size)) = existing else {
+        return Ok(ImportAction::Insert);
+    };
+    if !imported {
+        return Ok(ImportAction::Skip);
+    }
+    Ok(
+        match (incoming.last_active, incoming.size).cmp(&(last_active, size)) {
+            Ordering::Greater => ImportAction::Replace(id),
+            Ordering::Equal if id == incoming.id => ImportAction::Unchanged,
+            _ => ImportAction::Skip,
+        },
+    )
+}
+
+fn session_to_json(
+    summary: &SessionSummary,
+    messages: &[MessageRecord],
+    token_usage: &[TokenUsageRecord],
+) -> Value {
+    json!({
+        "id": summary.id,
+        "provider": summary.provider,
+        "wrapper": summary.wrapper,
+        "model": summary.model,
+        "label": summary.label,
+        "thread_name": summary.thread_name,
+        "path": summary.path.to_string_lossy(),
+        "uuid": summary.uuid,
+        "first_prompt": summary.first_prompt,
+        "actionable": summary.actionable,
+        "subagent": summary.subagent,
+        "created_at": summary.created_at,
+        "started_at": summary.started_at,
+        "last_active": summary.last_active,
+        "size": summary.size,
+        "mtime": summary.mtime,
+        "messages": messages
+            .iter()
+            .map(|message| {
+                json!({
+                    "index": message.index,
+                    "source_event_id": message.source_event_id,
+                    "role": message.role,
+                    "content": message.content,
+                    "source": message.source,
+                    "timestamp": message.timestamp,
+                    "is_first": message.is_first,
+                })
+            })
+            .collect::<Vec<_>>(),
+        "token_usage": token_usage
+            .iter()
+            .map(|usage| {
+                json!({
+                    "timestamp": usage.timestamp,
+                    "input_tokens": usage.input_tokens,
+                    "cached_input_tokens": usage.cached_input_tokens,
+                    "output_tokens": usage.output_tokens,
+                    "reasoning_output_tokens": usage.reasoning_output_tokens,
+                    "total_tokens": usage.total_tokens,
+                    "model": usage.model,
+                    "rate_limits": usage.rate_limits,
+                })
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Rebuild a session from its bundle record under its imported identifier.
+/// Returns the identifier it had on the source machine alongside it.
+fn ingest_from_json(value: &Value, machine: &str) -> Result<(String, SessionIngest)> {
+    let source_id = required_str(value, "id")?;
+    let id = imported_session_id(machine, &source_id);
+    let summary = SessionSummary {
+        id: id.clone(),
+        provider: required_str(value, "provider")?,
+        wrapper: optional_str(value, "wrapper"),
+        model: optional_str(value, "model"),
+        label: optional_str(value, "label"),
+        thread_name: optional_str(value, "thread_name"),
+        path: PathBuf::from(required_str(value, "path")?),
+        uuid: optional_str(value, "uuid"),
+        first_prompt: optional_str(value, "first_prompt"),
+        actionable: value
+            .get("actionable")
+            .and_then(Value::as_bool)
+            .unwrap_or(true),
+        subagent: value
+            .get("subagent")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        created_at: optional_i64(value, "created_at"),
+        started_at: optional_i64(value, "started_at"),
+        last_active: optional_i64(value, "last_active"),
+        size: optional_i64(value, "size").unwrap_or(0),
+        mtime: optional_i64(value, "mtime").unwrap_or(0),
+    };
+
+    let mut messages = Vec::new();
+    for message in array_field(value, "messages") {
+        messages.push(MessageRecord {
+            session_id: id.clone(),
+            index: optional_i64(message, "index")
+                .ok_or_else(|| eyre!("message is missing \"index\""))?,
+            source_event_id: optional_i64(message, "source_event_id"),
+            role: required_str(message, "role")?,
+            content: required_str(message, "content")?,
+            source: optional_str(message, "source"),
+            timestamp: optional_i64(message, "timestamp"),
+            is_first: message
+                .get("is_first")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        });
+    }
+
+    let mut token_usage = Vec::new();
+    for usage in array_field(value, "token_usage") {
+        token_usage.push(TokenUsageRecord {
+            session_id: id.clone(),
+            timestamp: optional_i64(usage, "timestamp")
+                .ok_or_else(|| eyre!("token usage is missing \"timestamp\""))?,
+            input_tokens: optional_i64(usage, "input_tokens").unwrap_or(0),
+            cached_input_tokens: optional_i64(usage, "cached_input_tokens").unwrap_or(0),
+            output_tokens: optional_i64(usage, "output_tokens").unwrap_or(0),
+            reasoning_output_tokens: optional_i64(usage, "reasoning_output_tokens").unwrap_or(0),
+            total_tokens: optional_i64(usage, "total_tokens").unwrap_or(0),
+            model: optional_str(usage, "model"),
+            rate_limits: optional_str(usage, "rate_limits"),
+        });
+    }
+
+    Ok((
+        source_id,
+        SessionIngest {
+            summary,
+            messages,
+            token_usage,
+        },
+    ))
+}
+
+fn required_str(value: &Value, key: &str) -> Result<String> {
+    optional_str(value, key).ok_or_else(|| eyre!("missing string field \"{key}\""))
+}
+
+fn optional_str(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(ToString::to_string)
+}
+
+fn optional_i64(value: &Value, key: &str) -> Option<i64> {
+    value.get(key).and_then(Value::as_i64)
+}
+
+fn array_field<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::session_summary;
+    use tempfile::TempDir;
+
+    fn ingest(id: &str, uuid: Option<&str>, last_active: i64, text: &str) -> SessionIngest {
+        let summary = SessionSummary {
+            model: Some("gpt-5".into()),
+            label: Some("release notes".into()),
+            thread_name: Some("Draft notes".into()),
+            path: PathBuf::from(format!("/remote/{id}.jsonl")),
+            uuid: uuid.map(ToString::to_string),
+            first_prompt: Some(text.to_string()),
+            size: 10,
+            ..session_summary(id, last_active)
+        };
+        let message = MessageRecord::new(id, 0, "user", text, None, Some(last_active));
+        let usage = TokenUsageRecord {
+            session_id: id.to_string(),
+            timestamp: last_active,
+            input_tokens: 5,
+            cached_input_tokens: 0,
+            output_tokens: 7,
+            reasoning_output_tokens: 0,
+            total_tokens: 12,
+            model: Some("gpt-5".into()),
+            rate_limits: None,
+        };
+        SessionIngest::new(summary, vec![message]).with_token_usage(vec![usage])
+    }
+
+    fn export(db: &Database, machine: &str) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        db.export_bundle(&mut out, machine)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn bundle_roundtrip_records_provenance_and_reimports_idempotently() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut remote = Database::open(&temp.path().join("remote.sqlite3"))?;
+        remote.upsert_session(&ingest("codex/a.jsonl", Some("uuid-a"), 100, "ship it"))?;
+        let bundle = export(&remote, "devbox")?;
+
+        let mut local = Database::open(&temp.path().join("local.sqlite3"))?;
+        let report = local.import_bundle(bundle.as_slice())?;
+        assert_eq!(report.machine, "devbox");
+        assert_eq!(report.added, 1);
+
+        let id = imported_session_id("devbox", "codex/a.jsonl");
+        let transcript = local.fetch_transcript(&id)?.expect("imported transcript");
+        assert_eq!(transcript.session.label.as_deref(), Some("release notes"));
+        assert_eq!(transcript.messages[0].content, "ship it");
+        assert_eq!(local.token_usage_for_provider("codex")?.len(), 1);
+        assert_eq!(local.search_full_text("ship", None, false)?.len(), 1);
+        let origin = local.session_origin(&id)?.expect("origin recorded");
+        assert_eq!(origin.machine, "devbox");
+        assert_eq!(origin.source_id, "codex/a.jsonl");
+        assert!(local.indexed_sessions_for_provider("codex")?.is_empty());
+
+        let again = local.import_bundle(bundle.as_slice())?;
+        assert_eq!((again.added, again.updated, again.unchanged), (0, 0, 1));
+        assert_eq!(local.count_sessions()?, 1);
+        assert!(export(&local, "laptop")?.ends_with(b"}\n"));
+        assert_eq!(local.export_bundle(&mut Vec::new(), "laptop")?.sessions, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_conflicts_resolve_by_uuid() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut remote = Database::open(&temp.path().join("remote.sqlite3"))?;
+        remote.upsert_session(&ingest("codex/a.jsonl", Some("uuid-a"), 100, "old"))?;
+        remote.upsert_session(&ingest("codex/b.jsonl", Some("uuid-b"), 100, "mine"))?;
+        let old = export(&remote, "devbox")?;
+        remote.upsert_session(&ingest("codex/a.jsonl", Some("uuid-a"), 200, "new"))?;
+        let new = export(&remote, "devbox")?;
+
+        let mut local = Database::open(&temp.path().join("local.sqlite3"))?;
+        local.upsert_session(&ingest("codex/b.jsonl", Some("uuid-b"), 50, "local"))?;
+
+        let first = local.import_bundle(new.as_slice())?;
+        assert_eq!((first.added, first.skipped), (1, 1), "local uuid-b wins");
+        let stale = local.import_bundle(old.as_slice())?;
+        assert_eq!((stale.updated, stale.skipped), (0, 2));
+        let id = imported_session_id("devbox", "codex/a.jsonl");
+        let transcript = local.fetch_transcript(&id)?.expect("imported");
+        assert_eq!(transcript.messages[0].content, "new");
+
+        // The same session relayed by another machine replaces the older copy.
+        let relayed = String::from_utf8(new)?.replace("\"devbox\"", "\"laptop\"");
+        let mut newer = String::new();
+        for line in relayed.lines() {
+            newer.push_str(&line.replace("\"last_active\":200", "\"last_active\":300"));
+            newer.push('\n');
+        }
+        let report = local.import_bundle(newer.as_bytes())?;
+        assert_eq!(report.updated, 1);
+        assert!(local.session_summary(&id)?.is_none());
+        let relayed_id = imported_session_id("laptop", "codex/a.jsonl");
+        assert!(local.session_summary(&relayed_id)?.is_some());
+        assert_eq!(
+            local
+                .session_summary_for_identifier("uuid-b")?
+                .map(|s| s.id),
+            Some("codex/b.jsonl".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn import_bundle_rejects_malformed_input_without_partial_writes() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        assert!(db.import_bundle(&b""[..]).is_err());
+        assert!(db.import_bundle(&b"{\"format\":\"other\"}\n"[..]).is_err());
+        let future =
+            format!("{{\"format\":\"{BUNDLE_FORMAT}\",\"version\":99,\"machine\":\"m\"}}\n");
+        assert!(db.import_bundle(future.as_bytes()).is_err());
+
+        let mut remote = Database::open(&temp.path().join("remote.sqlite3"))?;
+        remote.upsert_session(&ingest("codex/a.jsonl", None, 100, "hello"))?;
+        let mut bundle = export(&remote, "devbox")?;
+        bundle.extend_from_slice(b"{\"id\":\"codex/broken.jsonl\"}\n");
+        let err = db.import_bundle(bundle.as_slice()).unwrap_err();
+        assert!(format!("{err:#}").contains("bundle line 3"), "{err:#}");
+        assert_eq!(db.count_sessions()?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use color_eyre::Result;
+use rusqlite::params;
+
+use super::Database;
+use crate::util::unix_timestamp;
+
+/// Lease held while a process ingests session transcripts.
+pub const INDEX_LEASE: &str = "index";
+/// Lease held while a process embeds new messages for semantic search.
+pub const RAG_AUTO_INDEX_LEASE: &str = "rag-auto-index";
+
+/// A named lease row: which process holds it and until when.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub name: String,
+    pub pid: u32,
+    pub command: Option<String>,
+    pub acquired_ms: i64,
+    pub expires_ms: i64,
+}
+
+impl Database {
+    /// Take the lease `name` for this process unless another process holds an
+    /// unexpired one. Returns whether the lease was acquired.
+    ///
+    /// Leases expire after `ttl`, so a crashed holder never blocks others for longer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lease row cannot be written.
+    pub fn try_acquire_lease(&self, name: &str, command: &str, ttl: Duration) -> Result<bool> {
+        let now_ms = unix_timestamp().saturating_mul(1000);
+        let ttl_ms = i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX);
+        let changed = self.conn.execute(
+            r"
+            INSERT INTO leases(name, pid, command, acquired_ms, expires_ms)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(name) DO UPDATE SET
+                pid = excluded.pid,
+                command = excluded.command,
+                acquired_ms = excluded.acquired_ms,
+                expires_ms = excluded.expires_ms
+            WHERE leases.expires_ms <= excluded.acquired_ms OR leases.pid = excluded.pid
+            ",
+            params![
+                name,
+                std::process::id(),
+                command,
+                now_ms,
+                now_ms.saturating_add(ttl_ms)
+            ],
+        )?;
+        Ok(changed == 1)
+    }
+
+    /// Release the lease `name` if this process holds it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lease row cannot be deleted.
+    pub fn release_lease(&self, name: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM leases WHERE name = ?1 AND pid = ?2",
+            params![name, std::process::id()],
+        )?;
+        Ok(())
+    }
+
+    /// List unexpired leases, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn active_leases(&self) -> Result<Vec<Lease>> {
+        let now_ms = unix_timestamp().saturating_mul(1000);
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT name, pid, command, acquired_ms, expires_ms
+            FROM leases
+            WHERE expires_ms > ?1
+            ORDER BY acquired_ms ASC, name ASC
+            ",
+        )?;
+        let rows = stmt.query_map([now_ms], |row| {
+            Ok(Lease {
+                name: row.get(0)?,
+                pid: row.get(1)?,
+                command: row.get(2)?,
+                acquired_ms: row.get(3)?,
+                expires_ms: row.get(4)?,
+            })
+        })?;
+        let mut leases = Vec::new();
+        for row in rows {
+            leases.push(row?);
+        }
+        Ok(leases)
+    }
+
+    /// Copy committed WAL frames into the database file without waiting for readers.
+    ///
+    /// Returns the number of frames in the WAL and how many were checkpointed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint cannot run.
+    pub fn checkpoint_wal(&self) -> Result<(i64, i64)> {
+        let frames = self
+            .conn
+            .query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |row| {
+                Ok((row.get(1)?, row.get(2)?))
+            })?;
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn lease_is_exclusive_until_released_or_expired() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let ttl = Duration::from_mins(1);
+
+        assert!(db.try_acquire_lease(INDEX_LEASE, "tx search", ttl)?);
+        // The holder may renew its own lease.
+        assert!(db.try_acquire_lease(INDEX_LEASE, "tx search", ttl)?);
+        let leases = db.active_leases()?;
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].pid, std::process::id());
+        assert_eq!(leases[0].command.as_deref(), Some("tx search"));
+
+        db.conn.execute(
+            "UPDATE leases SET pid = pid + 1 WHERE name = ?1",
+            [INDEX_LEASE],
+        )?;
+        assert!(!db.try_acquire_lease(INDEX_LEASE, "tx", ttl)?);
+        db.release_lease(INDEX_LEASE)?;
+        assert_eq!(db.active_leases()?.len(), 1, "only the holder can release");
+
+        db.conn.execute(
+            "UPDATE leases SET expires_ms = 0 WHERE name = ?1",
+            [INDEX_LEASE],
+        )?;
+        assert!(db.active_leases()?.is_empty());
+        assert!(db.try_acquire_lease(INDEX_LEASE, "tx", ttl)?);
+        db.release_lease(INDEX_LEASE)?;
+        assert!(db.active_leases()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_opens_migrate_once() -> Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().join("tx.sqlite3");
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || Database::open(&path).map(|_| ()))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().expect("open thread")?;
+        }
+        let db = Database::open(&path)?;
+        assert_eq!(db.check()?.problem_count(), 0);
+        Ok(())
+    }
+}
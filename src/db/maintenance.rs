@@ -0,0 +1,532 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use rusqlite::Connection;
+use rusqlite::backup::Backup;
+
+use super::Database;
+
+/// Pages copied per step of an online backup before yielding to other writers.
+const BACKUP_PAGES_PER_STEP: i32 = 1024;
+
+/// Storage used by one table or index, as reported by `dbstat`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbObjectSize {
+    pub name: String,
+    /// `table`, `virtual`, `shadow`, or `index`.
+    pub kind: String,
+    /// Table the object belongs to; shadow tables belong to their virtual table.
+    pub table: String,
+    pub bytes: u64,
+    /// Row count for tables and virtual tables; `None` for indexes and shadow tables.
+    pub rows: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbProviderCounts {
+    pub provider: String,
+    pub sessions: u64,
+    pub messages: u64,
+    pub content_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbStats {
+    pub page_size: u64,
+    pub page_count: u64,
+    pub freelist_count: u64,
+    /// Tables and indexes ordered by size, largest first.
+    pub objects: Vec<DbObjectSize>,
+    pub providers: Vec<DbProviderCounts>,
+}
+
+/// Total storage of a table together with its indexes and shadow tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbTableSize {
+    pub name: String,
+    pub bytes: u64,
+    pub rows: Option<u64>,
+}
+
+impl DbStats {
+    /// Roll object sizes up to the tables they belong to, largest first.
+    #[must_use]
+    pub fn tables(&self) -> Vec<DbTableSize> {
+        let mut totals: HashMap<&str, DbTableSize> = HashMap::new();
+        for object in &self.objects {
+            let table = totals
+                .entry(object.table.as_str())
+                .or_insert_with(|| DbTableSize {
+                    name: object.table.clone(),
+                    bytes: 0,
+                    rows: None,
+                });
+            table.bytes += object.bytes;
+            if object.name == object.table {
+                table.rows = object.rows;
+            }
+        }
+        let mut tables: Vec<DbTableSize> = totals.into_values().collect();
+        tables.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+        tables
+    }
+}
+
+/// One consistency check and the problems it found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbCheck {
+    pub name: String,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbCheckReport {
+    pub checks: Vec<DbCheck>,
+}
+
+impl DbCheckReport {
+    #[must_use]
+    pub fn problem_count(&self) -> usize {
+        self.checks.iter().map(|check| check.problems.len()).sum()
+    }
+}
+
+impl Database {
+    /// Copy the database to `dest` with the `SQLite` online backup API.
+    ///
+    /// The copy is consistent even while other processes keep writing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the destination cannot be opened or the backup fails.
+    pub fn backup_to(&self, dest: &Path) -> Result<()> {
+        let mut target = Connection::open(dest)
+            .with_context(|| format!("failed to open backup target {}", dest.display()))?;
+        let backup = Backup::new(&self.conn, &mut target)?;
+        backup
+            .run_to_completion(BACKUP_PAGES_PER_STEP, Duration::from_millis(10), None)
+            .with_context(|| format!("failed to back up database to {}", dest.display()))?;
+        Ok(())
+    }
+
+    /// Rebuild the database file to reclaim free pages, then truncate the WAL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `VACUUM` or the checkpoint fails.
+    pub fn vacuum(&self) -> Result<()> {
+        self.conn
+            .execute_batch("VACUUM;")
+            .context("VACUUM failed")?;
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .context("WAL checkpoint failed")?;
+        Ok(())
+    }
+
+    /// Run `PRAGMA integrity_check` and verify that the full-text and vector
+    /// indexes agree with `messages`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a check query cannot be executed. Problems found by
+    /// the checks are reported in the result instead.
+    pub fn check(&self) -> Result<DbCheckReport> {
+        let mut report = DbCheckReport::default();
+
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let integrity = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|line| line != "ok")
+            .collect();
+        report.checks.push(DbCheck {
+            name: "integrity".to_string(),
+            problems: integrity,
+        });
+
+        report.checks.push(DbCheck {
+            name: "fts".to_string(),
+            problems: self.fts_problems()?,
+        });
+
+        let sources = self.message_sources()?;
+        for model in self.rag_models()? {
+            let problems = match self.rag_indexed_chunks(&model, None, None) {
+                Ok(chunks) => {
+                    let orphaned = chunks
+                        .iter()
+                        .filter(|chunk| {
+                            !sources.contains(&(chunk.session_id.clone(), chunk.source_event_id))
+                        })
+                        .count();
+                    if orphaned == 0 {
+                        Vec::new()
+                    } else {
+                        vec![format!(
+                            "{orphaned} of {} vectors reference messages that no longer exist",
+                            chunks.len()
+                        )]
+                    }
+                }
+                Err(err) => vec![format!(
+                    "vector table {} unreadable: {err}",
+                    model.table_name
+                )],
+            };
+            report.checks.push(DbCheck {
+                name: format!("vectors {} ({} dims)", model.model, model.dim),
+                problems,
+            });
+        }
+
+        Ok(report)
+    }
+
+    fn fts_problems(&self) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+        if let Err(err) = self.conn.execute(
+            "INSERT INTO messages_fts(messages_fts) VALUES('integrity-check')",
+            [],
+        ) {
+            problems.push(format!("full-text index integrity-check failed: {err}"));
+        }
+
+        // The index reads message text from `messages`, so compare the rows it
+        // has documents for (`messages_fts_docsize`) against the stored messages.
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT m.session_id, COUNT(*), COUNT(d.id)
+            FROM messages AS m
+            LEFT JOIN messages_fts_docsize AS d ON d.id = m.id
+            GROUP BY m.session_id
+            HAVING COUNT(*) != COUNT(d.id)
+            ORDER BY m.session_id
+            ",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (session_id, messages, fts) = row?;
+            problems.push(format!(
+                "session {session_id}: {messages} messages but {fts} full-text rows"
+            ));
+        }
+
+        let orphaned: i64 = self.conn.query_row(
+            r"
+            SELECT COUNT(*)
+            FROM messages_fts_docsize AS d
+            WHERE NOT EXISTS (SELECT 1 FROM messages AS m WHERE m.id = d.id)
+            ",
+            [],
+            |row| row.get(0),
+        )?;
+        if orphaned > 0 {
+            problems.push(format!(
+                "{orphaned} full-text rows reference messages that no longer exist"
+            ));
+        }
+        Ok(problems)
+    }
+
+    fn message_sources(&self) -> Result<HashSet<(String, i64)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT session_id, COALESCE(source_event_id, idx) FROM messages")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut sources = HashSet::new();
+        for row in rows {
+            sources.insert(row?);
+        }
+        Ok(sources)
+    }
+
+    /// Report page usage, per-object sizes and row counts, and per-provider totals.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any statistics query fails.
+    pub fn stats(&self) -> Result<DbStats> {
+        let pragma = |name: &str| -> Result<u64> {
+            let value: i64 = self
+                .conn
+                .query_row(&format!("PRAGMA {name}"), [], |row| row.get(0))?;
+            Ok(u64::try_from(value).unwrap_or(0))
+        };
+        let mut stats = DbStats {
+            page_size: pragma("page_size")?,
+            page_count: pragma("page_count")?,
+            freelist_count: pragma("freelist_count")?,
+            ..DbStats::default()
+        };
+
+        let mut kinds: HashMap<String, String> = HashMap::new();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, type FROM pragma_table_list WHERE schema = 'main'")?;
+        for row in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+            let (name, kind) = row?;
+            kinds.insert(name, kind);
+        }
+        let mut virtual_tables: Vec<String> = kinds
+            .iter()
+            .filter(|(_, kind)| *kind == "virtual")
+            .map(|(name, _)| name.clone())
+            .collect();
+        virtual_tables.sort();
+        // Virtual tables keep their data in shadow tables named `<table>_<suffix>`.
+        let virtual_owner = |name: &str| {
+            virtual_tables
+                .iter()
+                .filter(|table| {
+                    name.strip_prefix(table.as_str())
+                        .is_some_and(|rest| rest.starts_with('_'))
+                })
+                .max_by_key(|table| table.len())
+                .cloned()
+        };
+
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT d.name, COALESCE(s.type, 'table'), COALESCE(s.tbl_name, d.name), SUM(d.pgsize)
+            FROM dbstat AS d
+            LEFT JOIN sqlite_schema AS s ON s.name = d.name
+            GROUP BY d.name
+            ORDER BY SUM(d.pgsize) DESC, d.name ASC
+            ",
+        )?;
+        let sizes = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (name, schema_type, table, bytes) in sizes {
+            let owner = virtual_owner(&table);
+            let kind = match (schema_type.as_str(), &owner) {
+                ("index", _) => schema_type,
+                (_, Some(_)) => "shadow".to_string(),
+                _ => kinds.get(&name).cloned().unwrap_or(schema_type),
+            };
+            let table = owner.unwrap_or(table);
+            stats
+                .objects
+                .push(self.object_size(name, kind, table, bytes)?);
+        }
+        // Virtual tables own no pages themselves; list them for their row counts.
+        for name in &virtual_tables {
+            stats.objects.push(self.object_size(
+                name.clone(),
+                "virtual".to_string(),
+                name.clone(),
+                0,
+            )?);
+        }
+
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT s.provider,
+                   COUNT(DISTINCT s.id),
+                   COUNT(m.session_id),
+                   COALESCE(SUM(LENGTH(CAST(m.content AS BLOB))), 0)
+            FROM sessions AS s
+            LEFT JOIN messages AS m ON m.session_id = s.id
+            GROUP BY s.provider
+            ORDER BY s.provider
+            ",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(DbProviderCounts {
+                provider: row.get(0)?,
+                sessions: u64::try_from(row.get::<_, i64>(1)?).unwrap_or(0),
+                messages: u64::try_from(row.get::<_, i64>(2)?).unwrap_or(0),
+                content_bytes: u64::try_from(row.get::<_, i64>(3)?).unwrap_or(0),
+            })
+        })?;
+        for row in rows {
+            stats.providers.push(row?);
+        }
+
+        Ok(stats)
+    }
+
+    fn object_size(
+        &self,
+        name: String,
+        kind: String,
+        table: String,
+        bytes: i64,
+    ) -> Result<DbObjectSize> {
+        let rows = if matches!(kind.as_str(), "table" | "virtual") && !name.starts_with("sqlite_") {
+            let quoted = name.replace('"', "\"\"");
+            let count: i64 =
+                self.conn
+                    .query_row(&format!("SELECT COUNT(*) FROM \"{quoted}\""), [], |row| {
+                        row.get(0)
+                    })?;
+            Some(u64::try_from(count).unwrap_or(0))
+        } else {
+            None
+        };
+        Ok(DbObjectSize {
+            name,
+            kind,
+            table,
+            bytes: u64::try_from(bytes).unwrap_or(0),
+            rows,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{MessageRecord, SessionIngest, SessionSummary};
+    use crate::test_support::session_summary;
+    use tempfile::TempDir;
+
+    fn seeded_db(temp: &TempDir) -> Result<Database> {
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let summary = SessionSummary {
+            path: temp.path().join("sess-1.jsonl"),
+            ..session_summary("sess-1", 1)
+        };
+        let messages = vec![
+            MessageRecord::new("sess-1", 0, "user", "hello there", None, Some(1)),
+            MessageRecord::new("sess-1", 1, "assistant", "general kenobi", None, Some(2)),
+        ];
+        db.upsert_session(&SessionIngest::new(summary, messages))?;
+        Ok(db)
+    }
+
+    #[test]
+    fn check_reports_fts_rows_out_of_sync_with_messages() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db = seeded_db(&temp)?;
+        let clean = db.check()?;
+        assert_eq!(clean.problem_count(), 0);
+        assert_eq!(clean.checks[0].name, "integrity");
+
+        db.conn.execute(
+            "DELETE FROM messages_fts WHERE rowid = (SELECT MIN(rowid) FROM messages_fts)",
+            [],
+        )?;
+        let report = db.check()?;
+        assert_eq!(report.problem_count(), 1);
+        assert_eq!(
+            report.checks[1].problems,
+            vec!["session sess-1: 2 messages but 1 full-text rows".to_string()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_reports_vectors_without_source_messages() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = seeded_db(&temp)?;
+        let record = |chunk_id: i64, source_event_id: i64| crate::db::RagChunkRecord {
+            chunk_id,
+            embedding: vec![0.5; 4],
+            session_id: "sess-1".into(),
+            ts_ms: 1,
+            tool_name: None,
+            kind: "user".into(),
+            model: "mock-embed".into(),
+            content_hash: format!("hash-{chunk_id}"),
+            text: "hello there".into(),
+            source_event_id,
+            chunk_ordinal: 0,
+            span_start: 0,
+            span_end: 11,
+        };
+        db.upsert_rag_chunks(&[record(1, 0), record(2, 7)])?;
+
+        let report = db.check()?;
+        let vectors = report
+            .checks
+            .iter()
+            .find(|check| check.name == "vectors mock-embed (4 dims)")
+            .expect("vector check runs per model");
+        assert_eq!(
+            vectors.problems,
+            vec!["1 of 2 vectors reference messages that no longer exist".to_string()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stats_reports_object_sizes_and_provider_counts() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db = seeded_db(&temp)?;
+        let stats = db.stats()?;
+        assert!(stats.page_size > 0);
+        let messages = stats
+            .objects
+            .iter()
+            .find(|object| object.name == "message_store")
+            .expect("message table listed");
+        assert_eq!(messages.kind, "table");
+        assert_eq!(messages.rows, Some(2));
+        let fts = stats
+            .objects
+            .iter()
+            .find(|object| object.name == "messages_fts")
+            .expect("fts table listed");
+        assert_eq!((fts.kind.as_str(), fts.rows), ("virtual", Some(2)));
+        let shadow = stats
+            .objects
+            .iter()
+            .find(|object| object.name == "messages_fts_data")
+            .expect("fts shadow table listed");
+        assert_eq!(
+            (shadow.kind.as_str(), shadow.table.as_str()),
+            ("shadow", "messages_fts")
+        );
+        let tables = stats.tables();
+        let fts_total = tables
+            .iter()
+            .find(|table| table.name == "messages_fts")
+            .expect("fts rolled up");
+        assert!(fts_total.bytes > 0);
+        assert_eq!(fts_total.rows, Some(2));
+        assert!(!tables.iter().any(|table| table.name == "messages_fts_data"));
+        assert_eq!(
+            stats.providers,
+            vec![DbProviderCounts {
+                provider: "codex".into(),
+                sessions: 1,
+                messages: 2,
+                content_bytes: 25,
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn backup_to_copies_a_readable_database() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db = seeded_db(&temp)?;
+        let dest = temp.path().join("backup.sqlite3");
+        db.backup_to(&dest)?;
+        db.vacuum()?;
+
+        let copy = Database::open(&dest)?;
+        assert_eq!(copy.check()?.problem_count(), 0);
+        assert_eq!(copy.stats()?.providers[0].messages, 2);
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt::Write;
 use std::io::BufRead;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use color_eyre::Result;
 use color_eyre::eyre::{self, Context, eyre};
@@ -14,23 +15,134 @@ use crate::session::{
 };
 use crate::sqlite_ext;
 
+mod bundle;
+mod lease;
+mod maintenance;
+mod query;
 mod rag;
-
+mod retention;
+mod runs;
+mod storage;
+mod views;
+
+pub use bundle::*;
+pub use lease::*;
+pub use maintenance::*;
+pub use query::*;
 pub use rag::*;
+pub use retention::*;
+pub use runs::*;
+pub use views::*;
+
+pub(crate) use storage::register_functions;
 
-const SCHEMA_VERSION: i32 = 11;
+const SCHEMA_VERSION: i32 = 23;
 const SCHEMA_VERSION_V5: i32 = 5;
 const SCHEMA_VERSION_V6: i32 = 6;
 const SCHEMA_VERSION_V7: i32 = 7;
 const SCHEMA_VERSION_V8: i32 = 8;
 const SCHEMA_VERSION_V9: i32 = 9;
 const SCHEMA_VERSION_V10: i32 = 10;
+const SCHEMA_VERSION_V11: i32 = 11;
+const SCHEMA_VERSION_V12: i32 = 12;
+const SCHEMA_VERSION_V13: i32 = 13;
+const SCHEMA_VERSION_V14: i32 = 14;
+const SCHEMA_VERSION_V15: i32 = 15;
+const SCHEMA_VERSION_V16: i32 = 16;
+const SCHEMA_VERSION_V17: i32 = 17;
+const SCHEMA_VERSION_V18: i32 = 18;
+const SCHEMA_VERSION_V19: i32 = 19;
+const SCHEMA_VERSION_V20: i32 = 20;
+const SCHEMA_VERSION_V21: i32 = 21;
+const SCHEMA_VERSION_V22: i32 = 22;
 const V5_INDEXES_SQL: &str = r"
     CREATE INDEX IF NOT EXISTS idx_sessions_provider_last_active ON sessions(provider, last_active);
     CREATE INDEX IF NOT EXISTS idx_sessions_path ON sessions(path);
     CREATE INDEX IF NOT EXISTS idx_sessions_uuid ON sessions(uuid);
     CREATE INDEX IF NOT EXISTS idx_messages_session_timestamp ON messages(session_id, timestamp);
 ";
+const RAG_MODELS_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS rag_models (
+        model TEXT NOT NULL,
+        dim INTEGER NOT NULL,
+        table_name TEXT NOT NULL,
+        created_ms INTEGER NOT NULL,
+        last_indexed_ms INTEGER,
+        PRIMARY KEY (model, dim)
+    );
+";
+const RAG_EMBEDDING_CACHE_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS rag_embedding_cache (
+        model TEXT NOT NULL,
+        input_hash TEXT NOT NULL,
+        dim INTEGER NOT NULL,
+        embedding BLOB NOT NULL,
+        created_ms INTEGER NOT NULL,
+        PRIMARY KEY (model, input_hash)
+    );
+";
+const RAG_AUTO_INDEX_RUNS_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS rag_auto_index_runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        started_ms INTEGER NOT NULL,
+        finished_ms INTEGER NOT NULL,
+        model TEXT,
+        embedded INTEGER NOT NULL DEFAULT 0,
+        deferred INTEGER NOT NULL DEFAULT 0,
+        error TEXT
+    );
+";
+const RAG_AUTO_INDEX_PENDING_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS rag_auto_index_pending (
+        session_id TEXT PRIMARY KEY,
+        queued_ms INTEGER NOT NULL
+    );
+";
+const LEASES_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS leases (
+        name TEXT PRIMARY KEY,
+        pid INTEGER NOT NULL,
+        command TEXT,
+        acquired_ms INTEGER NOT NULL,
+        expires_ms INTEGER NOT NULL
+    );
+";
+const SESSION_ORIGINS_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS session_origins (
+        session_id TEXT PRIMARY KEY,
+        machine TEXT NOT NULL,
+        source_id TEXT NOT NULL,
+        imported_ms INTEGER NOT NULL,
+        FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+    );
+";
+const PRUNED_SESSIONS_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS pruned_sessions (
+        id TEXT PRIMARY KEY,
+        provider TEXT NOT NULL,
+        path TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        mtime INTEGER NOT NULL,
+        pruned_ms INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_pruned_sessions_provider ON pruned_sessions(provider);
+";
+const RUNS_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        started_ms INTEGER NOT NULL,
+        profile TEXT,
+        provider TEXT NOT NULL,
+        plan TEXT NOT NULL,
+        cwd TEXT NOT NULL,
+        exit_code INTEGER,
+        duration_ms INTEGER NOT NULL,
+        session_path TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_runs_started ON runs(started_ms);
+";
+/// How long a connection waits for another process's write lock before failing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
 type SessionBackfillRow = (String, String, Option<String>, bool);
 
@@ -137,9 +249,14 @@ impl Database {
                 PRAGMA synchronous = NORMAL;
                 PRAGMA temp_store = MEMORY;
                 PRAGMA mmap_size = 134217728;
+                PRAGMA wal_autocheckpoint = 1000;
+                PRAGMA journal_size_limit = 67108864;
                 ",
             )
             .context("failed to configure database pragmas")?;
+        self.conn
+            .busy_timeout(BUSY_TIMEOUT)
+            .context("failed to set database busy timeout")?;
         Ok(())
     }
 
@@ -160,6 +277,28 @@ impl Database {
             ));
         }
 
+        // Several tx processes may open an old database at once; the write lock
+        // makes the first one migrate while the others wait and then see the
+        // new version.
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let result = self.migrate_locked();
+        match result {
+            Ok(()) => self.conn.execute_batch("COMMIT")?,
+            Err(_) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+            }
+        }
+        result
+    }
+
+    fn migrate_locked(&self) -> Result<()> {
+        let current: i32 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if current == SCHEMA_VERSION {
+            return Ok(());
+        }
+
         if current == 0 {
             self.create_schema()?;
             return Ok(());
@@ -189,10 +328,58 @@ impl Database {
             .then(|| self.migrate_to_v10())
             .transpose()?;
 
-        (current < SCHEMA_VERSION)
+        (current < SCHEMA_VERSION_V11)
             .then(|| self.migrate_to_v11())
             .transpose()?;
 
+        (current < SCHEMA_VERSION_V12)
+            .then(|| self.migrate_to_v12())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V13)
+            .then(|| self.migrate_to_v13())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V14)
+            .then(|| self.migrate_to_v14())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V15)
+            .then(|| self.migrate_to_v15())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V16)
+            .then(|| self.migrate_to_v16())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V17)
+            .then(|| self.migrate_to_v17())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V18)
+            .then(|| self.migrate_to_v18())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V19)
+            .then(|| self.migrate_to_v19())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V20)
+            .then(|| self.migrate_to_v20())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V21)
+            .then(|| self.migrate_to_v21())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION_V22)
+            .then(|| self.migrate_to_v22())
+            .transpose()?;
+
+        (current < SCHEMA_VERSION)
+            .then(|| self.migrate_to_v23())
+            .transpose()?;
+
         Ok(())
     }
 
@@ -329,6 +516,171 @@ impl Database {
             stmt.execute(params![id, thread_name])?;
         }
 
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V11}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v12(&self) -> Result<()> {
+        // vec0 tables cannot gain columns in place, so rebuild the chunk table with
+        // the chunk ordinal and span columns. Existing rows were always produced as
+        // a single chunk covering the whole message.
+        self.conn.execute_batch(
+            r"
+            CREATE TEMP TABLE vec_session_chunks_v11 AS
+                SELECT
+                    chunk_id,
+                    embedding,
+                    session_id,
+                    ts_ms,
+                    tool_name,
+                    kind,
+                    model,
+                    content_hash,
+                    text,
+                    source_event_id
+                FROM vec_session_chunks;
+            DROP TABLE vec_session_chunks;
+            ",
+        )?;
+        self.conn
+            .execute_batch(&vec_chunks_sql(LEGACY_RAG_TABLE, LEGACY_RAG_DIM))?;
+        self.conn.execute_batch(
+            r"
+            INSERT INTO vec_session_chunks(
+                chunk_id,
+                embedding,
+                session_id,
+                ts_ms,
+                tool_name,
+                kind,
+                model,
+                content_hash,
+                text,
+                source_event_id,
+                chunk_ordinal,
+                span_start,
+                span_end
+            )
+            SELECT
+                chunk_id,
+                embedding,
+                session_id,
+                ts_ms,
+                tool_name,
+                kind,
+                model,
+                content_hash,
+                text,
+                source_event_id,
+                0,
+                0,
+                length(text)
+            FROM vec_session_chunks_v11;
+            DROP TABLE vec_session_chunks_v11;
+            ",
+        )?;
+
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V12}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v13(&self) -> Result<()> {
+        // Keep the existing fixed-dimension table for the default model and move any
+        // other model's vectors to a table of its own, so they stay searchable
+        // without sharing chunk ids; new models get their own tables lazily.
+        self.conn.execute_batch(RAG_MODELS_SQL)?;
+        rag::split_legacy_rag_models(&self.conn)?;
+        self.conn.execute(
+            r"
+            INSERT OR IGNORE INTO rag_models(model, dim, table_name, created_ms)
+            SELECT DISTINCT model, ?1, ?2, ?3 FROM vec_session_chunks
+            ",
+            params![
+                i64::try_from(LEGACY_RAG_DIM).unwrap_or(i64::MAX),
+                LEGACY_RAG_TABLE,
+                crate::util::unix_timestamp().saturating_mul(1000)
+            ],
+        )?;
+
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V13}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v14(&self) -> Result<()> {
+        self.conn.execute_batch(RAG_EMBEDDING_CACHE_SQL)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V14}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v15(&self) -> Result<()> {
+        self.conn.execute_batch(RAG_AUTO_INDEX_RUNS_SQL)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V15}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v16(&self) -> Result<()> {
+        self.conn.execute_batch(LEASES_SQL)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V16}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v17(&self) -> Result<()> {
+        self.conn.execute_batch(SESSION_ORIGINS_SQL)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V17}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v18(&self) -> Result<()> {
+        self.conn.execute_batch(PRUNED_SESSIONS_SQL)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V18}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v19(&self) -> Result<()> {
+        views::create_views(&self.conn)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V19}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v20(&self) -> Result<()> {
+        // `messages` gains an integer id and its standalone full-text copy
+        // becomes an external-content index; the `v_*` views are recreated on
+        // top of the rebuilt table.
+        storage::migrate_messages(&self.conn)?;
+        views::create_views(&self.conn)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V20}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v21(&self) -> Result<()> {
+        self.conn.execute_batch(RUNS_SQL)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V21}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v22(&self) -> Result<()> {
+        self.conn.execute_batch(RAG_AUTO_INDEX_PENDING_SQL)?;
+        self.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V22}"), [])?;
+        Ok(())
+    }
+
+    fn migrate_to_v23(&self) -> Result<()> {
+        // `messages` becomes a view over `message_store`, which keeps large
+        // bodies zstd-compressed; the `v_*` views are recreated on top of it.
+        storage::migrate_message_store(&self.conn)?;
+        views::create_views(&self.conn)?;
         self.conn
             .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION}"), [])?;
         Ok(())
@@ -389,25 +741,6 @@ impl Database {
                 mtime INTEGER NOT NULL DEFAULT 0
             );
 
-            CREATE TABLE IF NOT EXISTS messages (
-                session_id TEXT NOT NULL,
-                idx INTEGER NOT NULL,
-                role TEXT NOT NULL,
-                content TEXT NOT NULL,
-                source TEXT,
-                timestamp INTEGER,
-                is_first INTEGER NOT NULL DEFAULT 0,
-                source_event_id INTEGER,
-                PRIMARY KEY (session_id, idx),
-                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
-            );
-
-            CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
-                session_id UNINDEXED,
-                role UNINDEXED,
-                content
-            );
-
             CREATE TABLE IF NOT EXISTS token_usage (
                 session_id TEXT NOT NULL,
                 timestamp INTEGER NOT NULL,
@@ -433,24 +766,22 @@ impl Database {
             CREATE INDEX IF NOT EXISTS idx_sessions_provider_last_active ON sessions(provider, last_active);
             CREATE INDEX IF NOT EXISTS idx_sessions_path ON sessions(path);
             CREATE INDEX IF NOT EXISTS idx_sessions_uuid ON sessions(uuid);
-            CREATE INDEX IF NOT EXISTS idx_messages_session_timestamp ON messages(session_id, timestamp);
             CREATE INDEX IF NOT EXISTS idx_token_usage_timestamp ON token_usage(timestamp);
             CREATE INDEX IF NOT EXISTS idx_token_usage_session ON token_usage(session_id);
-
-            CREATE VIRTUAL TABLE IF NOT EXISTS vec_session_chunks USING vec0(
-                chunk_id INTEGER PRIMARY KEY,
-                embedding FLOAT[1536],
-                session_id TEXT PARTITION KEY,
-                ts_ms INTEGER,
-                tool_name TEXT,
-                kind TEXT,
-                model TEXT,
-                content_hash TEXT,
-                +text TEXT,
-                +source_event_id INTEGER
-            );
             ",
         )?;
+        storage::create_message_store(&self.conn)?;
+        self.conn
+            .execute_batch(&vec_chunks_sql(LEGACY_RAG_TABLE, LEGACY_RAG_DIM))?;
+        self.conn.execute_batch(RAG_MODELS_SQL)?;
+        self.conn.execute_batch(RAG_EMBEDDING_CACHE_SQL)?;
+        self.conn.execute_batch(RAG_AUTO_INDEX_RUNS_SQL)?;
+        self.conn.execute_batch(RAG_AUTO_INDEX_PENDING_SQL)?;
+        self.conn.execute_batch(LEASES_SQL)?;
+        self.conn.execute_batch(SESSION_ORIGINS_SQL)?;
+        self.conn.execute_batch(PRUNED_SESSIONS_SQL)?;
+        self.conn.execute_batch(RUNS_SQL)?;
+        views::create_views(&self.conn)?;
 
         let pragma = format!("PRAGMA user_version = {SCHEMA_VERSION}");
         self.conn.execute(&pragma, [])?;
@@ -499,71 +830,7 @@ impl Database {
     /// Returns an error if any insert or delete statement fails.
     pub fn upsert_session(&mut self, ingest: &SessionIngest) -> Result<()> {
         let tx = self.conn.transaction()?;
-
-        let s = &ingest.summary;
-        tx.execute(
-            r"
-            INSERT INTO sessions (
-                id,
-                provider,
-                wrapper,
-                model,
-                label,
-                thread_name,
-                path,
-                uuid,
-                first_prompt,
-                actionable,
-                subagent,
-                created_at,
-                started_at,
-                last_active,
-                size,
-                mtime
-            )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
-            ON CONFLICT(id) DO UPDATE SET
-                provider = excluded.provider,
-                wrapper = excluded.wrapper,
-                model = excluded.model,
-                label = excluded.label,
-                thread_name = excluded.thread_name,
-                path = excluded.path,
-                uuid = excluded.uuid,
-                first_prompt = excluded.first_prompt,
-                actionable = excluded.actionable,
-                subagent = excluded.subagent,
-                created_at = excluded.created_at,
-                started_at = excluded.started_at,
-                last_active = excluded.last_active,
-                size = excluded.size,
-                mtime = excluded.mtime
-            ",
-            params![
-                s.id,
-                s.provider,
-                s.wrapper.as_deref(),
-                s.model.as_deref(),
-                s.label.as_deref(),
-                s.thread_name.as_deref(),
-                s.path.to_string_lossy(),
-                s.uuid.as_deref(),
-                s.first_prompt.as_deref(),
-                i64::from(s.actionable),
-                i64::from(s.subagent),
-                s.created_at,
-                s.started_at,
-                s.last_active,
-                s.size,
-                s.mtime,
-            ],
-        )?;
-
-        clear_session_data(&tx, &s.id)?;
-        insert_messages(&tx, ingest)?;
-        insert_message_fts(&tx, ingest)?;
-        insert_token_usage(&tx, ingest)?;
-
+        write_session(&tx, ingest)?;
         tx.commit()?;
         Ok(())
     }
@@ -607,6 +874,8 @@ impl Database {
 
     /// List the session fields needed to decide whether provider files changed.
     ///
+    /// Imported sessions are left out: their logs live on another machine.
+    ///
     /// # Errors
     ///
     /// Returns an error if the query cannot be executed.
@@ -616,6 +885,7 @@ impl Database {
             SELECT id, path, size, mtime
             FROM sessions
             WHERE provider = ?1
+              AND id NOT IN (SELECT session_id FROM session_origins)
             ",
         )?;
         let rows = stmt.query_map([provider], |row| {
@@ -909,14 +1179,26 @@ impl Database {
             };
             summary
         };
-        let session_id = summary.id.clone();
+        let messages = self.session_messages(&summary.id)?;
 
-        let mut messages_stmt = self.conn.prepare(
+        Ok(Some(Transcript {
+            session: summary,
+            messages,
+        }))
+    }
+
+    /// Load a session's messages in transcript order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn session_messages(&self, session_id: &str) -> Result<Vec<MessageRecord>> {
+        let mut stmt = self.conn.prepare(
             "SELECT idx, source_event_id, role, content, source, timestamp, is_first FROM messages WHERE session_id = ?1 ORDER BY idx",
         )?;
-        let message_rows = messages_stmt.query_map([session_id.clone()], |row| {
+        let rows = stmt.query_map([session_id], |row| {
             Ok(MessageRecord {
-                session_id: session_id.clone(),
+                session_id: session_id.to_string(),
                 index: row.get(0)?,
                 source_event_id: row.get(1)?,
                 role: row.get(2)?,
@@ -927,14 +1209,10 @@ impl Database {
             })
         })?;
         let mut messages = Vec::new();
-        for row in message_rows {
+        for row in rows {
             messages.push(row?);
         }
-
-        Ok(Some(Transcript {
-            session: summary,
-            messages,
-        }))
+        Ok(messages)
     }
 
     fn session_summary_by_uuid(&self, uuid: &str) -> Result<Option<SessionSummary>> {
@@ -959,6 +1237,8 @@ impl Database {
                 mtime
             FROM sessions
             WHERE uuid = ?1
+            ORDER BY id IN (SELECT session_id FROM session_origins)
+            LIMIT 1
             ",
         )?;
         stmt.query_row([uuid], map_summary)
@@ -1068,13 +1348,76 @@ impl Database {
     }
 }
 
-fn clear_session_data(tx: &Transaction<'_>, session_id: &str) -> Result<()> {
+fn write_session(tx: &Transaction<'_>, ingest: &SessionIngest) -> Result<()> {
+    let s = &ingest.summary;
     tx.execute(
-        "DELETE FROM messages WHERE session_id = ?1",
-        params![session_id],
+        r"
+        INSERT INTO sessions (
+            id,
+            provider,
+            wrapper,
+            model,
+            label,
+            thread_name,
+            path,
+            uuid,
+            first_prompt,
+            actionable,
+            subagent,
+            created_at,
+            started_at,
+            last_active,
+            size,
+            mtime
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        ON CONFLICT(id) DO UPDATE SET
+            provider = excluded.provider,
+            wrapper = excluded.wrapper,
+            model = excluded.model,
+            label = excluded.label,
+            thread_name = excluded.thread_name,
+            path = excluded.path,
+            uuid = excluded.uuid,
+            first_prompt = excluded.first_prompt,
+            actionable = excluded.actionable,
+            subagent = excluded.subagent,
+            created_at = excluded.created_at,
+            started_at = excluded.started_at,
+            last_active = excluded.last_active,
+            size = excluded.size,
+            mtime = excluded.mtime
+        ",
+        params![
+            s.id,
+            s.provider,
+            s.wrapper.as_deref(),
+            s.model.as_deref(),
+            s.label.as_deref(),
+            s.thread_name.as_deref(),
+            s.path.to_string_lossy(),
+            s.uuid.as_deref(),
+            s.first_prompt.as_deref(),
+            i64::from(s.actionable),
+            i64::from(s.subagent),
+            s.created_at,
+            s.started_at,
+            s.last_active,
+            s.size,
+            s.mtime,
+        ],
     )?;
+
+    clear_session_data(tx, &s.id)?;
+    insert_messages(tx, ingest)?;
+    insert_token_usage(tx, ingest)?;
+    Ok(())
+}
+
+fn clear_session_data(tx: &Transaction<'_>, session_id: &str) -> Result<()> {
+    // Triggers on `message_store` keep the full-text index in sync.
     tx.execute(
-        "DELETE FROM messages_fts WHERE session_id = ?1",
+        "DELETE FROM message_store WHERE session_id = ?1",
         params![session_id],
     )?;
     tx.execute(
@@ -1085,29 +1428,9 @@ fn clear_session_data(tx: &Transaction<'_>, session_id: &str) -> Result<()> {
 }
 
 fn insert_messages(tx: &Transaction<'_>, ingest: &SessionIngest) -> Result<()> {
-    let mut stmt = tx.prepare(
-        "INSERT INTO messages (session_id, idx, role, content, source, timestamp, is_first, source_event_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-    )?;
-    for message in &ingest.messages {
-        stmt.execute(params![
-            message.session_id,
-            message.index,
-            message.role,
-            message.content,
-            message.source.as_deref(),
-            message.timestamp,
-            i64::from(message.is_first),
-            message.source_event_id.unwrap_or(message.index),
-        ])?;
-    }
-    Ok(())
-}
-
-fn insert_message_fts(tx: &Transaction<'_>, ingest: &SessionIngest) -> Result<()> {
-    let mut stmt =
-        tx.prepare("INSERT INTO messages_fts (session_id, role, content) VALUES (?1, ?2, ?3)")?;
+    let mut stmt = tx.prepare(storage::INSERT_MESSAGE_SQL)?;
     for message in &ingest.messages {
-        stmt.execute(params![message.session_id, message.role, message.content])?;
+        storage::insert_message(&mut stmt, message)?;
     }
     Ok(())
 }
@@ -1260,7 +1583,7 @@ fn session_log_thread_name(path: &Path) -> Result<Option<String>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_support::{ENV_LOCK, EnvOverride};
+    use crate::test_support::{ENV_LOCK, EnvOverride, session_summary};
     use assert_fs::TempDir;
     use assert_fs::prelude::*;
     use rusqlite::{Connection, OpenFlags};
@@ -2024,6 +2347,67 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn migrate_v20_schema_adds_runs_table() -> Result<()> {
+        let db = create_db()?;
+        db.conn.execute_batch("DROP TABLE runs")?;
+        db.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V20}"), [])?;
+        db.migrate()?;
+        assert!(db.has_column("runs", "session_path")?);
+        assert!(db.recent_runs(10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_v22_schema_compresses_large_message_bodies() -> Result<()> {
+        let mut db = create_db()?;
+        let long = "stack frame at retry loop\n".repeat(500);
+        db.upsert_session(&SessionIngest::new(
+            session_summary("sess-v22", 1),
+            vec![
+                MessageRecord::new("sess-v22", 0, "user", "Hello legacy", None, Some(1)),
+                MessageRecord::new("sess-v22", 1, "tool", long.clone(), None, Some(2)),
+            ],
+        ))?;
+        // Rebuild the v22 layout: a plain `messages` table with the same ids.
+        db.conn.execute_batch(
+            r"
+            DROP VIEW v_sessions;
+            DROP VIEW v_messages;
+            DROP VIEW messages;
+            DROP TRIGGER message_store_fts_insert;
+            DROP TRIGGER message_store_fts_delete;
+            DROP TRIGGER message_store_fts_update;
+            DROP INDEX idx_messages_session_timestamp;
+            ALTER TABLE message_store RENAME TO messages;
+            UPDATE messages SET content = tx_unzstd(content_zstd) WHERE content IS NULL;
+            ALTER TABLE messages DROP COLUMN content_zstd;
+            ",
+        )?;
+        db.conn
+            .execute(&format!("PRAGMA user_version = {SCHEMA_VERSION_V22}"), [])?;
+        db.migrate()?;
+
+        let compressed: i64 = db.conn.query_row(
+            "SELECT COUNT(*) FROM message_store WHERE content IS NULL AND content_zstd IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(compressed, 1);
+        let transcript = db.fetch_transcript("sess-v22")?.expect("transcript");
+        assert_eq!(transcript.messages[1].content, long);
+        assert_eq!(db.search_full_text("retry", None, false)?.len(), 1);
+        assert_eq!(db.check()?.problem_count(), 0);
+        let content: String = db.conn.query_row(
+            "SELECT content FROM v_messages WHERE message_index = 1",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(content, long);
+        Ok(())
+    }
+
     #[test]
     fn migrate_handles_intermediate_schema_versions() -> Result<()> {
         let db = create_db()?;
@@ -2064,6 +2448,168 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn migrate_v8_schema_preserves_chunks_with_whole_message_span() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db_path = temp.child("v8-chunks.sqlite3");
+        sqlite_ext::init_sqlite_extensions()?;
+        let mut embedding = vec![0.0_f32; 1536];
+        embedding[0] = 1.0;
+        {
+            let conn = Connection::open(db_path.path())?;
+            conn.execute_batch(V8_SCHEMA)?;
+            conn.execute(
+                r"
+                INSERT INTO vec_session_chunks(
+                    chunk_id, embedding, session_id, ts_ms, tool_name, kind, model,
+                    content_hash, text, source_event_id
+                ) VALUES (7, ?1, 'sess-v8', 100, '', 'user', 'text-embedding-3-small', 'hash', 'hello world', 3)
+                ",
+                [f32s_to_blob(&embedding)],
+            )?;
+        }
+
+        let db = Database::open(db_path.path())?;
+        let model = db
+            .rag_model("text-embedding-3-small", 1536)?
+            .expect("legacy model registered");
+        assert_eq!(model.table_name, LEGACY_RAG_TABLE);
+        let hits = db.search_similar_chunks(&model, &embedding, &RagSearchFilters::default(), 5)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk_id, 7);
+        assert_eq!(hits[0].text, "hello world");
+        assert_eq!(hits[0].chunk_ordinal, 0);
+        assert_eq!((hits[0].span_start, hits[0].span_end), (0, 11));
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_v8_schema_moves_other_models_out_of_the_legacy_table() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db_path = temp.child("v8-models.sqlite3");
+        sqlite_ext::init_sqlite_extensions()?;
+        let mut embedding = vec![0.0_f32; 1536];
+        embedding[0] = 1.0;
+        {
+            let conn = Connection::open(db_path.path())?;
+            conn.execute_batch(V8_SCHEMA)?;
+            for (chunk_id, model) in [(7, "text-embedding-3-small"), (8, "other-model")] {
+                conn.execute(
+                    r"
+                    INSERT INTO vec_session_chunks(
+                        chunk_id, embedding, session_id, ts_ms, tool_name, kind, model,
+                        content_hash, text, source_event_id
+                    ) VALUES (?1, ?2, 'sess-v8', 100, '', 'user', ?3, 'hash', 'hello', ?1)
+                    ",
+                    params![chunk_id, f32s_to_blob(&embedding), model],
+                )?;
+            }
+        }
+
+        let mut db = Database::open(db_path.path())?;
+        let legacy = db
+            .rag_model("text-embedding-3-small", 1536)?
+            .expect("legacy model registered");
+        let other = db
+            .rag_model("other-model", 1536)?
+            .expect("other model registered");
+        assert_eq!(legacy.table_name, LEGACY_RAG_TABLE);
+        assert_ne!(other.table_name, LEGACY_RAG_TABLE);
+        let filters = RagSearchFilters::default();
+        let legacy_hits = db.search_similar_chunks(&legacy, &embedding, &filters, 5)?;
+        let other_hits = db.search_similar_chunks(&other, &embedding, &filters, 5)?;
+        assert_eq!(
+            legacy_hits
+                .iter()
+                .map(|hit| hit.chunk_id)
+                .collect::<Vec<_>>(),
+            [7]
+        );
+        assert_eq!(
+            other_hits
+                .iter()
+                .map(|hit| hit.chunk_id)
+                .collect::<Vec<_>>(),
+            [8]
+        );
+
+        // Re-indexing the legacy chunk with the other model leaves the original vector.
+        db.upsert_rag_chunks(&[RagChunkRecord {
+            chunk_id: 7,
+            embedding: embedding.clone(),
+            session_id: "sess-v8".to_string(),
+            ts_ms: 100,
+            tool_name: None,
+            kind: "user".to_string(),
+            model: "other-model".to_string(),
+            content_hash: "hash".to_string(),
+            text: "hello".to_string(),
+            source_event_id: 7,
+            chunk_ordinal: 0,
+            span_start: 0,
+            span_end: 5,
+        }])?;
+        assert_eq!(
+            db.rag_chunk_content_hash(&legacy, 7)?.as_deref(),
+            Some("hash")
+        );
+        assert_eq!(
+            db.rag_chunk_content_hash(&other, 7)?.as_deref(),
+            Some("hash")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_v8_schema_indexes_messages_without_a_second_copy() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db_path = temp.child("v8-messages.sqlite3");
+        sqlite_ext::init_sqlite_extensions()?;
+        let long = "stack frame at retry loop\n".repeat(500);
+        {
+            let conn = Connection::open(db_path.path())?;
+            conn.execute_batch(V8_SCHEMA)?;
+            conn.execute(
+                "INSERT INTO sessions (id, provider, label, path, first_prompt, last_active) VALUES ('sess-v8', 'codex', 'Legacy', 'sess-v8.jsonl', 'Hello', 1)",
+                [],
+            )?;
+            for (idx, role, content) in [(0, "user", "Hello legacy"), (1, "tool", long.as_str())] {
+                conn.execute(
+                    "INSERT INTO messages (session_id, idx, role, content) VALUES ('sess-v8', ?1, ?2, ?3)",
+                    params![idx, role, content],
+                )?;
+                conn.execute(
+                    "INSERT INTO messages_fts (session_id, role, content) VALUES ('sess-v8', ?1, ?2)",
+                    params![role, content],
+                )?;
+            }
+        }
+
+        let db = Database::open(db_path.path())?;
+        let (ids, fts_copies): (i64, i64) = db.conn.query_row(
+            "SELECT (SELECT COUNT(DISTINCT id) FROM messages), (SELECT COUNT(*) FROM sqlite_schema WHERE name = 'messages_fts_content')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!((ids, fts_copies), (2, 0));
+        let transcript = db.fetch_transcript("sess-v8")?.expect("legacy transcript");
+        assert_eq!(transcript.messages.len(), 2);
+        assert_eq!(transcript.messages[1].content, long);
+        assert_eq!(db.search_full_text("legacy", None, false)?.len(), 1);
+        assert_eq!(db.search_full_text("retry", None, false)?.len(), 1);
+        assert_eq!(db.check()?.problem_count(), 0);
+
+        // The documented views decompress bodies through the function tx registers
+        // on every connection it opens.
+        let content: String = Connection::open(db_path.path())?.query_row(
+            "SELECT content FROM v_messages WHERE message_index = 1",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(content, long);
+        Ok(())
+    }
+
     #[test]
     fn migrate_v9_schema_backfills_subagent_flag_and_hides_session() -> Result<()> {
         let temp = TempDir::new()?;
@@ -2326,7 +2872,7 @@ mod tests {
         let now = OffsetDateTime::now_utc().unix_timestamp();
         insert_session(&mut db, "sess-1", "codex", "Prompt", true, now)?;
         db.conn
-            .execute_batch("PRAGMA foreign_keys = OFF; DROP TABLE messages;")?;
+            .execute_batch("PRAGMA foreign_keys = OFF; DROP VIEW messages;")?;
 
         assert!(db.fetch_transcript("sess-1").is_err());
         Ok(())
@@ -2475,6 +3021,9 @@ mod tests {
                 content_hash: "hash-a".to_string(),
                 text: "exact".to_string(),
                 source_event_id: 1,
+                chunk_ordinal: 0,
+                span_start: 0,
+                span_end: 5,
             },
             RagChunkRecord {
                 chunk_id: 2,
@@ -2487,10 +3036,14 @@ mod tests {
                 content_hash: "hash-b".to_string(),
                 text: "nearby".to_string(),
                 source_event_id: 2,
+                chunk_ordinal: 0,
+                span_start: 0,
+                span_end: 6,
             },
         ])?;
 
-        let nearest = db.search_similar_chunks(&exact, &RagSearchFilters::default(), 1)?;
+        let model = db.ensure_rag_model("text-embedding-3-small", 1536)?;
+        let nearest = db.search_similar_chunks(&model, &exact, &RagSearchFilters::default(), 1)?;
         assert_eq!(nearest.len(), 1);
         assert_eq!(nearest[0].chunk_id, 1);
         Ok(())
@@ -2514,6 +3067,9 @@ mod tests {
                 content_hash: "hash-a".to_string(),
                 text: "alpha".to_string(),
                 source_event_id: 1,
+                chunk_ordinal: 0,
+                span_start: 0,
+                span_end: 5,
             },
             RagChunkRecord {
                 chunk_id: 22,
@@ -2526,16 +3082,21 @@ mod tests {
                 content_hash: "hash-b".to_string(),
                 text: "beta".to_string(),
                 source_event_id: 2,
+                chunk_ordinal: 0,
+                span_start: 0,
+                span_end: 4,
             },
         ])?;
 
         let filters = RagSearchFilters {
             session_id: Some("sess-b".to_string()),
+            exclude_session_id: None,
             tool_name: None,
             since_ts_ms: None,
             until_ts_ms: None,
         };
-        let hits = db.search_similar_chunks(&exact, &filters, 10)?;
+        let model = db.ensure_rag_model("text-embedding-3-small", 1536)?;
+        let hits = db.search_similar_chunks(&model, &exact, &filters, 10)?;
         assert_eq!(hits.len(), 1);
         assert_eq!(hits[0].session_id, "sess-b");
         Ok(())
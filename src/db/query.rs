@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use color_eyre::Result;
+use color_eyre::eyre::{Context, eyre};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OpenFlags};
+
+use super::{BUSY_TIMEOUT, Database, SCHEMA_VERSION, assert_isolated_test_db_path};
+use crate::sqlite_ext;
+
+/// Column names and rows returned by an ad-hoc query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<SqlValue>>,
+}
+
+impl Database {
+    /// Open an existing database without write access and without migrating it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or its schema is not the one
+    /// this binary writes.
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        assert_isolated_test_db_path(path)?;
+        sqlite_ext::init_sqlite_extensions()?;
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .with_context(|| format!("failed to open database at {}", path.display()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .context("failed to set database busy timeout")?;
+        conn.execute_batch("PRAGMA query_only = ON")?;
+
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            return Err(eyre!(
+                "database schema version {version} does not match this binary ({SCHEMA_VERSION}); \
+                 run any other tx command to migrate it first"
+            ));
+        }
+        Ok(Self { conn })
+    }
+
+    /// Run a single read-only statement and collect every row.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SQL does not parse, contains more than one
+    /// statement, or would modify the database.
+    pub fn query(&self, sql: &str) -> Result<QueryResult> {
+        let mut stmt = self.conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(eyre!(
+                "only read-only statements can be run with tx db query"
+            ));
+        }
+        let columns: Vec<String> = stmt
+            .column_names()
+            .into_iter()
+            .map(ToString::to_string)
+            .collect();
+        let width = columns.len();
+        let mut rows = Vec::new();
+        let mut query = stmt.query([])?;
+        while let Some(row) = query.next()? {
+            let mut values = Vec::with_capacity(width);
+            for index in 0..width {
+                values.push(row.get::<_, SqlValue>(index)?);
+            }
+            rows.push(values);
+        }
+        Ok(QueryResult { columns, rows })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_only_query_returns_rows_and_rejects_writes() -> Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().join("tx.sqlite3");
+        drop(Database::open(&path)?);
+
+        let db = Database::open_read_only(&path)?;
+        let result =
+            db.query("SELECT views_version, 'x' AS label, NULL AS empty FROM v_version")?;
+        assert_eq!(result.columns, vec!["views_version", "label", "empty"]);
+        assert_eq!(
+            result.rows,
+            vec![vec![
+                SqlValue::Integer(1),
+                SqlValue::Text("x".into()),
+                SqlValue::Null
+            ]]
+        );
+
+        let err = db
+            .query("DELETE FROM sessions")
+            .expect_err("writes are rejected");
+        assert!(err.to_string().contains("read-only"), "{err}");
+        assert!(db.query("SELECT 1; SELECT 2").is_err());
+        assert!(
+            Database::open_read_only(&temp.path().join("missing.sqlite3")).is_err(),
+            "read-only open never creates a database"
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,18 @@
+use std::collections::{HashMap, HashSet};
+
 use color_eyre::Result;
+use color_eyre::eyre::eyre;
 use rusqlite::types::Value as SqlValue;
-use rusqlite::{OptionalExtension, params_from_iter};
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
 
 use super::{Database, f32s_to_blob};
+use crate::util::unix_timestamp;
+
+/// Table that held every embedding before per-model tables existed.
+pub const LEGACY_RAG_TABLE: &str = "vec_session_chunks";
+/// Model and dimension that keep using [`LEGACY_RAG_TABLE`].
+pub const LEGACY_RAG_MODEL: &str = "text-embedding-3-small";
+pub const LEGACY_RAG_DIM: usize = 1536;
 
 #[derive(Debug, Clone)]
 pub struct RagSourceMessage {
@@ -26,11 +36,52 @@ pub struct RagChunkRecord {
     pub content_hash: String,
     pub text: String,
     pub source_event_id: i64,
+    pub chunk_ordinal: i64,
+    pub span_start: i64,
+    pub span_end: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RagIndexedChunk {
+    pub chunk_id: i64,
+    pub session_id: String,
+    pub source_event_id: i64,
+    pub content_hash: String,
+}
+
+/// A registered embedding model and the vector table that stores its chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RagModel {
+    pub model: String,
+    pub dim: usize,
+    pub table_name: String,
+    pub last_indexed_ms: Option<i64>,
+}
+
+/// Outcome of one automatic embedding run after session indexing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RagAutoIndexRun {
+    pub started_ms: i64,
+    pub finished_ms: i64,
+    pub model: Option<String>,
+    pub embedded: usize,
+    /// Chunks left for a later run because of the per-run cap.
+    pub deferred: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RagModelCoverage {
+    pub chunks: usize,
+    pub sessions: usize,
+    pub messages: usize,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct RagSearchFilters {
     pub session_id: Option<String>,
+    /// Leave out chunks of this session, such as the source of a related search.
+    pub exclude_session_id: Option<String>,
     pub tool_name: Option<String>,
     pub since_ts_ms: Option<i64>,
     pub until_ts_ms: Option<i64>,
@@ -46,6 +97,9 @@ pub struct RagSearchHit {
     pub kind: String,
     pub text: String,
     pub source_event_id: Option<i64>,
+    pub chunk_ordinal: i64,
+    pub span_start: i64,
+    pub span_end: i64,
 }
 
 impl Database {
@@ -112,34 +166,141 @@ impl Database {
         Ok(out)
     }
 
+    /// Count transcript messages that carry indexable text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query cannot be executed.
+    pub fn rag_source_message_count(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM messages WHERE trim(content) <> ''",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(usize::try_from(count).unwrap_or(0))
+    }
+
+    /// Map every indexed session id to its provider.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query cannot be executed.
+    pub fn rag_session_providers(&self) -> Result<HashMap<String, String>> {
+        let mut stmt = self.conn.prepare("SELECT id, provider FROM sessions")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut out = HashMap::new();
+        for row in rows {
+            let (id, provider) = row?;
+            out.insert(id, provider);
+        }
+        Ok(out)
+    }
+
+    /// List registered embedding models, most recently indexed first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the registry cannot be read.
+    pub fn rag_models(&self) -> Result<Vec<RagModel>> {
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT model, dim, table_name, last_indexed_ms
+            FROM rag_models
+            ORDER BY last_indexed_ms IS NULL, last_indexed_ms DESC, model ASC, dim ASC
+            ",
+        )?;
+        let rows = stmt.query_map([], rag_model_from_row)?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(row?);
+        }
+        Ok(out)
+    }
+
+    /// Look up the registered vector table for a model and dimension.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the registry cannot be read.
+    pub fn rag_model(&self, model: &str, dim: usize) -> Result<Option<RagModel>> {
+        find_rag_model(&self.conn, model, dim)
+    }
+
+    /// Return the vector table for a model and dimension, creating it on first use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table or its registry row cannot be created.
+    pub fn ensure_rag_model(&self, model: &str, dim: usize) -> Result<RagModel> {
+        ensure_rag_model(&self.conn, model, dim)
+    }
+
+    /// Count chunks, sessions, and source messages stored for a model.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vector table cannot be scanned.
+    pub fn rag_model_coverage(&self, model: &RagModel) -> Result<RagModelCoverage> {
+        let sql = format!(
+            r"
+            SELECT
+                COUNT(*),
+                COUNT(DISTINCT session_id),
+                COUNT(DISTINCT session_id || ':' || source_event_id)
+            FROM {table}
+            WHERE model = ?1
+            ",
+            table = model.table_name
+        );
+        let (chunks, sessions, messages): (i64, i64, i64) =
+            self.conn.query_row(&sql, [model.model.as_str()], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+        Ok(RagModelCoverage {
+            chunks: usize::try_from(chunks).unwrap_or(0),
+            sessions: usize::try_from(sessions).unwrap_or(0),
+            messages: usize::try_from(messages).unwrap_or(0),
+        })
+    }
+
     /// Fetch the currently indexed content hash for a chunk id.
     ///
     /// # Errors
     ///
     /// Returns an error if the lookup query fails.
-    pub fn rag_chunk_content_hash(&self, chunk_id: i64) -> Result<Option<String>> {
+    pub fn rag_chunk_content_hash(
+        &self,
+        model: &RagModel,
+        chunk_id: i64,
+    ) -> Result<Option<String>> {
+        let sql = format!(
+            "SELECT content_hash FROM {} WHERE chunk_id = ?1 AND model = ?2",
+            model.table_name
+        );
         self.conn
-            .query_row(
-                "SELECT content_hash FROM vec_session_chunks WHERE chunk_id = ?1",
-                [chunk_id],
-                |row| row.get(0),
-            )
+            .query_row(&sql, params![chunk_id, model.model], |row| row.get(0))
             .optional()
             .map_err(Into::into)
     }
 
-    /// Delete semantic chunks by optional scope filters.
+    /// List indexed chunk identities and content hashes within an optional scope.
     ///
     /// # Errors
     ///
-    /// Returns an error if the delete statement fails.
-    pub fn delete_rag_chunks(
+    /// Returns an error if the query cannot be executed.
+    pub fn rag_indexed_chunks(
         &self,
+        model: &RagModel,
         session_id: Option<&str>,
         since_ts_ms: Option<i64>,
-    ) -> Result<usize> {
-        let mut query = String::from("DELETE FROM vec_session_chunks WHERE 1 = 1");
-        let mut params: Vec<SqlValue> = Vec::new();
+    ) -> Result<Vec<RagIndexedChunk>> {
+        let mut query = format!(
+            "SELECT chunk_id, session_id, source_event_id, content_hash FROM {} WHERE model = ?",
+            model.table_name
+        );
+        let mut params: Vec<SqlValue> = vec![SqlValue::from(model.model.clone())];
 
         if let Some(id) = session_id {
             query.push_str(" AND session_id = ?");
@@ -151,71 +312,527 @@ impl Database {
             params.push(SqlValue::from(since));
         }
 
-        let deleted = self.conn.execute(&query, params_from_iter(params.iter()))?;
+        let mut stmt = self.conn.prepare(&query)?;
+        let rows = stmt.query_map(params_from_iter(params.iter()), |row| {
+            Ok(RagIndexedChunk {
+                chunk_id: row.get(0)?,
+                session_id: row.get(1)?,
+                source_event_id: row.get(2)?,
+                content_hash: row.get(3)?,
+            })
+        })?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(row?);
+        }
+        Ok(out)
+    }
+
+    /// Delete semantic chunks by id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any delete statement fails.
+    pub fn delete_rag_chunk_ids(&mut self, model: &RagModel, chunk_ids: &[i64]) -> Result<usize> {
+        if chunk_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let tx = self.conn.transaction()?;
+        let mut deleted = 0usize;
+        {
+            let sql = format!(
+                "DELETE FROM {} WHERE chunk_id = ?1 AND model = ?2",
+                model.table_name
+            );
+            let mut stmt = tx.prepare(&sql)?;
+            for chunk_id in chunk_ids {
+                deleted += stmt.execute(params![chunk_id, model.model])?;
+            }
+        }
+        tx.commit()?;
         Ok(deleted)
     }
 
-    /// Insert or replace semantic chunks in a single transaction.
+    /// Delete a model's semantic chunks by optional scope filters.
+    ///
+    /// Every vector table registered for `model` is cleared, whatever its dimension.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete statement fails.
+    pub fn delete_rag_chunks(
+        &self,
+        model: &str,
+        session_id: Option<&str>,
+        since_ts_ms: Option<i64>,
+    ) -> Result<usize> {
+        let mut deleted = 0usize;
+        for registered in self.rag_models()? {
+            if registered.model != model {
+                continue;
+            }
+
+            let mut query = format!("DELETE FROM {} WHERE model = ?", registered.table_name);
+            let mut params: Vec<SqlValue> = vec![SqlValue::from(model.to_string())];
+
+            if let Some(id) = session_id {
+                query.push_str(" AND session_id = ?");
+                params.push(SqlValue::from(id.to_string()));
+            }
+
+            if let Some(since) = since_ts_ms {
+                query.push_str(" AND ts_ms >= ?");
+                params.push(SqlValue::from(since));
+            }
+
+            deleted += self.conn.execute(&query, params_from_iter(params.iter()))?;
+        }
+        Ok(deleted)
+    }
+
+    /// Look up cached embeddings for a model by input hash.
+    ///
+    /// Hashes without a cached vector are absent from the returned map.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache cannot be queried.
+    pub fn rag_cached_embeddings(
+        &self,
+        model: &str,
+        input_hashes: &[String],
+    ) -> Result<HashMap<String, Vec<f32>>> {
+        let mut cached = HashMap::new();
+        for batch in input_hashes.chunks(500) {
+            let placeholders = vec!["?"; batch.len()].join(", ");
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT input_hash, embedding FROM rag_embedding_cache
+                 WHERE model = ? AND input_hash IN ({placeholders})"
+            ))?;
+            let mut params: Vec<SqlValue> = Vec::with_capacity(batch.len() + 1);
+            params.push(SqlValue::from(model.to_string()));
+            params.extend(batch.iter().cloned().map(SqlValue::from));
+            let rows = stmt.query_map(params_from_iter(params.iter()), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })?;
+            for row in rows {
+                let (hash, blob) = row?;
+                cached.insert(hash, blob_to_f32s(&blob));
+            }
+        }
+        Ok(cached)
+    }
+
+    /// Store freshly computed embeddings in the cache, replacing older vectors
+    /// for the same model and input hash.
     ///
     /// # Errors
     ///
     /// Returns an error if any statement fails.
-    pub fn upsert_rag_chunks(&mut self, chunks: &[RagChunkRecord]) -> Result<usize> {
-        if chunks.is_empty() {
+    pub fn store_rag_cached_embeddings(
+        &mut self,
+        model: &str,
+        entries: &[(String, Vec<f32>)],
+    ) -> Result<usize> {
+        if entries.is_empty() {
             return Ok(0);
         }
+        let now_ms = unix_timestamp().saturating_mul(1000);
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                r"
+                INSERT OR REPLACE INTO rag_embedding_cache(model, input_hash, dim, embedding, created_ms)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ",
+            )?;
+            for (hash, embedding) in entries {
+                stmt.execute(params![
+                    model,
+                    hash,
+                    i64::try_from(embedding.len()).unwrap_or(i64::MAX),
+                    f32s_to_blob(embedding),
+                    now_ms
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(entries.len())
+    }
+
+    /// Delete cached embeddings whose input hash is not listed for their model
+    /// in `live`. Models missing from `live` lose all of their cached vectors.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache cannot be read or updated.
+    pub fn retain_rag_cached_embeddings(
+        &mut self,
+        live: &HashMap<String, HashSet<String>>,
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let stale = {
+            let mut stmt = tx.prepare("SELECT model, input_hash FROM rag_embedding_cache")?;
+            stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .filter(|row| {
+                row.as_ref().map_or(true, |(model, hash)| {
+                    !live.get(model).is_some_and(|hashes| hashes.contains(hash))
+                })
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        {
+            let mut delete =
+                tx.prepare("DELETE FROM rag_embedding_cache WHERE model = ?1 AND input_hash = ?2")?;
+            for (model, hash) in &stale {
+                delete.execute(params![model, hash])?;
+            }
+        }
+        tx.commit()?;
+        Ok(stale.len())
+    }
 
+    /// Record an automatic embedding run, keeping only the most recent runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the run cannot be written.
+    pub fn record_rag_auto_index_run(&mut self, run: &RagAutoIndexRun) -> Result<()> {
+        const KEEP_RUNS: i64 = 50;
         let tx = self.conn.transaction()?;
-        let mut delete_stmt = tx.prepare("DELETE FROM vec_session_chunks WHERE chunk_id = ?1")?;
-        let mut insert_stmt = tx.prepare(
+        tx.execute(
             r"
-            INSERT OR IGNORE INTO vec_session_chunks(
-                chunk_id,
-                embedding,
-                session_id,
-                ts_ms,
-                tool_name,
-                kind,
-                model,
-                content_hash,
-                text,
-                source_event_id
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            INSERT INTO rag_auto_index_runs(started_ms, finished_ms, model, embedded, deferred, error)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
             ",
+            params![
+                run.started_ms,
+                run.finished_ms,
+                run.model,
+                i64::try_from(run.embedded).unwrap_or(i64::MAX),
+                i64::try_from(run.deferred).unwrap_or(i64::MAX),
+                run.error
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM rag_auto_index_runs WHERE id <= (SELECT MAX(id) FROM rag_auto_index_runs) - ?1",
+            params![KEEP_RUNS],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Return the most recent automatic embedding run, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn last_rag_auto_index_run(&self) -> Result<Option<RagAutoIndexRun>> {
+        let run = self
+            .conn
+            .query_row(
+                r"
+                SELECT started_ms, finished_ms, model, embedded, deferred, error
+                FROM rag_auto_index_runs
+                ORDER BY id DESC
+                LIMIT 1
+                ",
+                [],
+                |row| {
+                    Ok(RagAutoIndexRun {
+                        started_ms: row.get(0)?,
+                        finished_ms: row.get(1)?,
+                        model: row.get(2)?,
+                        embedded: usize::try_from(row.get::<_, i64>(3)?).unwrap_or(0),
+                        deferred: usize::try_from(row.get::<_, i64>(4)?).unwrap_or(0),
+                        error: row.get(5)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(run)
+    }
+
+    /// Queue sessions for the next automatic embedding run. Sessions already
+    /// queued keep their place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sessions cannot be written.
+    pub fn queue_rag_auto_index(&mut self, session_ids: &[String], now_ms: i64) -> Result<()> {
+        if session_ids.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR IGNORE INTO rag_auto_index_pending(session_id, queued_ms) VALUES (?1, ?2)",
+            )?;
+            for session_id in session_ids {
+                insert.execute(params![session_id, now_ms])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Return the sessions waiting for an automatic embedding run, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn rag_auto_index_queue(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT session_id FROM rag_auto_index_pending ORDER BY queued_ms, session_id",
         )?;
+        let sessions = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(sessions)
+    }
+
+    /// Remove sessions whose messages are fully embedded from the queue.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sessions cannot be removed.
+    pub fn dequeue_rag_auto_index(&mut self, session_ids: &[String]) -> Result<()> {
+        if session_ids.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.transaction()?;
+        {
+            let mut delete =
+                tx.prepare("DELETE FROM rag_auto_index_pending WHERE session_id = ?1")?;
+            for session_id in session_ids {
+                delete.execute(params![session_id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Insert or replace semantic chunks in a single transaction.
+    ///
+    /// Each record is written to the vector table for its model and embedding
+    /// length, which is created and registered on first use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any statement fails.
+    pub fn upsert_rag_chunks(&mut self, chunks: &[RagChunkRecord]) -> Result<usize> {
+        if chunks.is_empty() {
+            return Ok(0);
+        }
+
+        let tx = self.conn.transaction()?;
+        let mut tables: HashMap<(String, usize), RagModel> = HashMap::new();
         let mut inserted = 0usize;
 
         for chunk in chunks {
-            delete_stmt.execute([chunk.chunk_id])?;
+            let key = (chunk.model.clone(), chunk.embedding.len());
+            let registered = if let Some(registered) = tables.get(&key) {
+                registered.clone()
+            } else {
+                let registered = ensure_rag_model(&tx, &chunk.model, chunk.embedding.len())?;
+                tables.insert(key, registered.clone());
+                registered
+            };
+
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE chunk_id = ?1 AND model = ?2",
+                    registered.table_name
+                ),
+                params![chunk.chunk_id, chunk.model],
+            )?;
             let embedding_blob = f32s_to_blob(&chunk.embedding);
-            let changed = insert_stmt.execute((
-                chunk.chunk_id,
-                embedding_blob,
-                chunk.session_id.as_str(),
-                chunk.ts_ms,
-                chunk.tool_name.as_deref().unwrap_or(""),
-                chunk.kind.as_str(),
-                chunk.model.as_str(),
-                chunk.content_hash.as_str(),
-                chunk.text.as_str(),
-                chunk.source_event_id,
-            ))?;
+            let changed = tx.execute(
+                &format!(
+                    r"
+                    INSERT OR IGNORE INTO {}(
+                        chunk_id,
+                        embedding,
+                        session_id,
+                        ts_ms,
+                        tool_name,
+                        kind,
+                        model,
+                        content_hash,
+                        text,
+                        source_event_id,
+                        chunk_ordinal,
+                        span_start,
+                        span_end
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                    ",
+                    registered.table_name
+                ),
+                (
+                    chunk.chunk_id,
+                    embedding_blob,
+                    chunk.session_id.as_str(),
+                    chunk.ts_ms,
+                    chunk.tool_name.as_deref().unwrap_or(""),
+                    chunk.kind.as_str(),
+                    chunk.model.as_str(),
+                    chunk.content_hash.as_str(),
+                    chunk.text.as_str(),
+                    chunk.source_event_id,
+                    chunk.chunk_ordinal,
+                    chunk.span_start,
+                    chunk.span_end,
+                ),
+            )?;
             inserted += changed;
         }
 
-        drop(insert_stmt);
-        drop(delete_stmt);
+        let now_ms = unix_timestamp().saturating_mul(1000);
+        for registered in tables.values() {
+            tx.execute(
+                "UPDATE rag_models SET last_indexed_ms = ?3 WHERE model = ?1 AND dim = ?2",
+                params![
+                    registered.model,
+                    i64::try_from(registered.dim).unwrap_or(i64::MAX),
+                    now_ms
+                ],
+            )?;
+        }
+
         tx.commit()?;
         Ok(inserted)
     }
 
-    /// Run KNN search against semantic chunks with optional metadata filters.
+    /// Load the stored embeddings for every chunk of one session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vector table cannot be queried.
+    pub fn rag_session_embeddings(
+        &self,
+        model: &RagModel,
+        session_id: &str,
+    ) -> Result<Vec<Vec<f32>>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT embedding FROM {} WHERE session_id = ?1 AND model = ?2 ORDER BY chunk_id",
+            model.table_name
+        ))?;
+        let rows = stmt.query_map(params![session_id, model.model], |row| {
+            row.get::<_, Vec<u8>>(0)
+        })?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(blob_to_f32s(&row?));
+        }
+        Ok(out)
+    }
+
+    /// Stream the kind, tool name, and text of every stored chunk of a model to `visit`.
     ///
     /// # Errors
     ///
-    /// Returns an error if query preparation or execution fails.
+    /// Returns an error if the vector table cannot be queried.
+    pub fn visit_rag_chunk_inputs<F>(&self, model: &RagModel, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&str, Option<&str>, &str),
+    {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT kind, tool_name, text FROM {} WHERE model = ?1",
+            model.table_name
+        ))?;
+        let mut rows = stmt.query([model.model.as_str()])?;
+        while let Some(row) = rows.next()? {
+            let kind: String = row.get(0)?;
+            let tool_name: Option<String> = row.get(1)?;
+            let text: String = row.get(2)?;
+            visit(
+                &kind,
+                tool_name.as_deref().filter(|name| !name.trim().is_empty()),
+                &text,
+            );
+        }
+        Ok(())
+    }
+
+    /// Stream every stored embedding of a model, with its session id, to `visit`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vector table cannot be queried.
+    pub fn visit_rag_embeddings<F>(&self, model: &RagModel, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&str, &[f32]),
+    {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT session_id, embedding FROM {} WHERE model = ?1",
+            model.table_name
+        ))?;
+        let mut rows = stmt.query(params![model.model])?;
+        while let Some(row) = rows.next()? {
+            let session_id: String = row.get(0)?;
+            let blob: Vec<u8> = row.get(1)?;
+            visit(&session_id, &blob_to_f32s(&blob));
+        }
+        Ok(())
+    }
+
+    /// Count, for every full-text term, how many of `session_ids` contain it.
+    ///
+    /// Terms come from the `messages_fts` index, so they match the tokens that
+    /// `tx search --full-text` uses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the FTS vocabulary table cannot be created or queried.
+    pub fn fts_term_session_counts(
+        &self,
+        session_ids: &[String],
+    ) -> Result<HashMap<String, usize>> {
+        let mut counts = HashMap::new();
+        if session_ids.is_empty() {
+            return Ok(counts);
+        }
+        self.conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS temp.messages_fts_instances
+                USING fts5vocab(main, messages_fts, instance);",
+        )?;
+
+        // One JSON array parameter, so long histories stay under SQLite's
+        // bound-variable limit.
+        let ids = serde_json::to_string(session_ids)?;
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT v.term, COUNT(DISTINCT f.session_id)
+            FROM temp.messages_fts_instances v
+            JOIN messages_fts f ON f.rowid = v.doc
+            WHERE f.session_id IN (SELECT value FROM json_each(?1))
+            GROUP BY v.term
+            ",
+        )?;
+        let rows = stmt.query_map([ids], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (term, sessions) = row?;
+            counts.insert(term, usize::try_from(sessions).unwrap_or(0));
+        }
+        Ok(counts)
+    }
+
+    /// Run KNN search against a model's semantic chunks with optional metadata filters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query embedding does not match the model's
+    /// dimension, or if query preparation or execution fails.
     pub fn search_similar_chunks(
         &self,
+        model: &RagModel,
         query_embedding: &[f32],
         filters: &RagSearchFilters,
         k: usize,
@@ -224,7 +841,16 @@ impl Database {
             return Ok(Vec::new());
         }
 
-        let mut query = String::from(
+        if query_embedding.len() != model.dim {
+            return Err(eyre!(
+                "query embedding dimension mismatch for model {}: expected {}, got {}",
+                model.model,
+                model.dim,
+                query_embedding.len()
+            ));
+        }
+
+        let mut query = format!(
             r"
             SELECT
                 chunk_id,
@@ -234,18 +860,30 @@ impl Database {
                 tool_name,
                 kind,
                 text,
-                source_event_id
-            FROM vec_session_chunks
+                source_event_id,
+                chunk_ordinal,
+                span_start,
+                span_end
+            FROM {}
             WHERE embedding MATCH ?
+                AND model = ?
             ",
+            model.table_name
         );
-        let mut params: Vec<SqlValue> = vec![SqlValue::Blob(f32s_to_blob(query_embedding))];
-
+        let mut params: Vec<SqlValue> = vec![
+            SqlValue::Blob(f32s_to_blob(query_embedding)),
+            SqlValue::from(model.model.clone()),
+        ];
         if let Some(session_id) = filters.session_id.as_deref() {
             query.push_str(" AND session_id = ?");
             params.push(SqlValue::from(session_id.to_string()));
         }
 
+        if let Some(session_id) = filters.exclude_session_id.as_deref() {
+            query.push_str(" AND session_id != ?");
+            params.push(SqlValue::from(session_id.to_string()));
+        }
+
         if let Some(tool_name) = filters.tool_name.as_deref() {
             query.push_str(" AND tool_name = ?");
             params.push(SqlValue::from(tool_name.to_string()));
@@ -279,6 +917,9 @@ impl Database {
                 kind: row.get(5)?,
                 text: row.get(6)?,
                 source_event_id: row.get(7)?,
+                chunk_ordinal: row.get(8)?,
+                span_start: row.get(9)?,
+                span_end: row.get(10)?,
             })
         })?;
 
@@ -290,6 +931,151 @@ impl Database {
     }
 }
 
+fn rag_model_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RagModel> {
+    let dim: i64 = row.get(1)?;
+    Ok(RagModel {
+        model: row.get(0)?,
+        dim: usize::try_from(dim).unwrap_or(0),
+        table_name: row.get(2)?,
+        last_indexed_ms: row.get(3)?,
+    })
+}
+
+fn find_rag_model(conn: &Connection, model: &str, dim: usize) -> Result<Option<RagModel>> {
+    conn.query_row(
+        "SELECT model, dim, table_name, last_indexed_ms FROM rag_models WHERE model = ?1 AND dim = ?2",
+        params![model, i64::try_from(dim).unwrap_or(i64::MAX)],
+        rag_model_from_row,
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+fn ensure_rag_model(conn: &Connection, model: &str, dim: usize) -> Result<RagModel> {
+    if let Some(existing) = find_rag_model(conn, model, dim)? {
+        return Ok(existing);
+    }
+
+    if model.trim().is_empty() {
+        return Err(eyre!("embedding model name must not be empty"));
+    }
+    if dim == 0 {
+        return Err(eyre!("embedding dimension must be greater than zero"));
+    }
+
+    // The default model keeps the original table so databases created before and
+    // after per-model tables share one layout.
+    let table_name = if model == LEGACY_RAG_MODEL && dim == LEGACY_RAG_DIM {
+        LEGACY_RAG_TABLE.to_string()
+    } else {
+        rag_table_name(model, dim)
+    };
+    conn.execute_batch(&vec_chunks_sql(&table_name, dim))?;
+    conn.execute(
+        "INSERT INTO rag_models(model, dim, table_name, created_ms) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            model,
+            i64::try_from(dim).unwrap_or(i64::MAX),
+            table_name,
+            unix_timestamp().saturating_mul(1000)
+        ],
+    )?;
+    super::views::refresh_rag_chunks_view(conn)?;
+
+    Ok(RagModel {
+        model: model.to_string(),
+        dim,
+        table_name,
+        last_indexed_ms: None,
+    })
+}
+
+/// Move vectors of models other than [`LEGACY_RAG_MODEL`] out of
+/// [`LEGACY_RAG_TABLE`] into tables of their own and register them.
+///
+/// `chunk_id` is the table's primary key, so two models sharing one table would
+/// overwrite each other's vector for the same chunk.
+pub(super) fn split_legacy_rag_models(conn: &Connection) -> Result<()> {
+    let models = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT DISTINCT model FROM {LEGACY_RAG_TABLE} WHERE model <> ?1"
+        ))?;
+        stmt.query_map([LEGACY_RAG_MODEL], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    let columns = "chunk_id, embedding, session_id, ts_ms, tool_name, kind, model, \
+                   content_hash, text, source_event_id, chunk_ordinal, span_start, span_end";
+    for model in models {
+        let registered = ensure_rag_model(conn, &model, LEGACY_RAG_DIM)?;
+        conn.execute(
+            &format!(
+                "INSERT INTO {table}({columns}) SELECT {columns} FROM {LEGACY_RAG_TABLE} WHERE model = ?1",
+                table = registered.table_name
+            ),
+            [&model],
+        )?;
+        let chunk_ids = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT chunk_id FROM {LEGACY_RAG_TABLE} WHERE model = ?1"
+            ))?;
+            stmt.query_map([&model], |row| row.get::<_, i64>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        let mut delete = conn.prepare(&format!(
+            "DELETE FROM {LEGACY_RAG_TABLE} WHERE chunk_id = ?1 AND model = ?2"
+        ))?;
+        for chunk_id in chunk_ids {
+            delete.execute(params![chunk_id, model])?;
+        }
+    }
+    Ok(())
+}
+
+/// Derive a safe, stable table name for a model and dimension.
+fn rag_table_name(model: &str, dim: usize) -> String {
+    let slug: String = model
+        .chars()
+        .take(40)
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash = blake3::hash(model.as_bytes()).to_hex();
+    format!("vec_chunks_{slug}_{dim}_{}", &hash[..8])
+}
+
+fn blob_to_f32s(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+pub(super) fn vec_chunks_sql(table_name: &str, dim: usize) -> String {
+    format!(
+        r"
+        CREATE VIRTUAL TABLE IF NOT EXISTS {table_name} USING vec0(
+            chunk_id INTEGER PRIMARY KEY,
+            embedding FLOAT[{dim}],
+            session_id TEXT PARTITION KEY,
+            ts_ms INTEGER,
+            tool_name TEXT,
+            kind TEXT,
+            model TEXT,
+            content_hash TEXT,
+            +text TEXT,
+            +source_event_id INTEGER,
+            +chunk_ordinal INTEGER,
+            +span_start INTEGER,
+            +span_end INTEGER
+        );
+        "
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -309,6 +1095,10 @@ mod tests {
         Ok(db)
     }
 
+    fn small_model(db: &Database) -> Result<RagModel> {
+        db.ensure_rag_model("text-embedding-3-small", 1536)
+    }
+
     fn insert_message(
         db: &mut Database,
         session_id: &str,
@@ -392,6 +1182,9 @@ mod tests {
                 content_hash: "hash-1".to_string(),
                 text: "alpha".to_string(),
                 source_event_id: 1,
+                chunk_ordinal: 0,
+                span_start: 0,
+                span_end: 5,
             },
             RagChunkRecord {
                 chunk_id: 2,
@@ -404,13 +1197,47 @@ mod tests {
                 content_hash: "hash-2".to_string(),
                 text: "beta".to_string(),
                 source_event_id: 2,
+                chunk_ordinal: 0,
+                span_start: 0,
+                span_end: 4,
             },
         ])?;
 
-        let deleted = db.delete_rag_chunks(Some("sess-a"), Some(150))?;
+        let deleted = db.delete_rag_chunks("text-embedding-3-small", Some("sess-a"), Some(150))?;
         assert_eq!(deleted, 1);
-        assert_eq!(db.rag_chunk_content_hash(1)?, Some("hash-1".to_string()));
-        assert_eq!(db.rag_chunk_content_hash(2)?, None);
+        assert_eq!(
+            db.rag_chunk_content_hash(&small_model(&db)?, 1)?,
+            Some("hash-1".to_string())
+        );
+        assert_eq!(db.rag_chunk_content_hash(&small_model(&db)?, 2)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn ensure_rag_model_registers_tables_per_model_and_dimension() -> Result<()> {
+        let db = create_db()?;
+        assert!(db.rag_models()?.is_empty());
+
+        let default = db.ensure_rag_model(LEGACY_RAG_MODEL, LEGACY_RAG_DIM)?;
+        assert_eq!(default.table_name, LEGACY_RAG_TABLE);
+        let large = db.ensure_rag_model("text-embedding-3-large", 3072)?;
+        assert_eq!(
+            large.table_name,
+            rag_table_name("text-embedding-3-large", 3072)
+        );
+        assert!(
+            large
+                .table_name
+                .starts_with("vec_chunks_text_embedding_3_large_3072_")
+        );
+        assert_eq!(db.ensure_rag_model("text-embedding-3-large", 3072)?, large);
+        assert_eq!(db.rag_models()?.len(), 2);
+        assert_eq!(db.rag_model_coverage(&large)?, RagModelCoverage::default());
+
+        let err = db
+            .ensure_rag_model("  ", 8)
+            .expect_err("blank model names are rejected");
+        assert!(err.to_string().contains("must not be empty"));
         Ok(())
     }
 
@@ -438,6 +1265,9 @@ mod tests {
             content_hash: "hash-no-tool".to_string(),
             text: "no tool metadata".to_string(),
             source_event_id: 3,
+            chunk_ordinal: 0,
+            span_start: 0,
+            span_end: 16,
         }])?;
         assert_eq!(inserted, 1);
 
@@ -445,7 +1275,7 @@ mod tests {
             session_id: Some("sess-no-tool".to_string()),
             ..RagSearchFilters::default()
         };
-        let hits = db.search_similar_chunks(&exact, &filters, 5)?;
+        let hits = db.search_similar_chunks(&small_model(&db)?, &exact, &filters, 5)?;
         assert_eq!(hits.len(), 1);
         assert_eq!(hits[0].chunk_id, 31);
         assert_eq!(hits[0].tool_name, None);
@@ -470,6 +1300,9 @@ mod tests {
                 content_hash: "hash-a".to_string(),
                 text: "alpha".to_string(),
                 source_event_id: 1,
+                chunk_ordinal: 0,
+                span_start: 0,
+                span_end: 5,
             },
             RagChunkRecord {
                 chunk_id: 22,
@@ -482,19 +1315,24 @@ mod tests {
                 content_hash: "hash-b".to_string(),
                 text: "beta".to_string(),
                 source_event_id: 2,
+                chunk_ordinal: 0,
+                span_start: 0,
+                span_end: 4,
             },
         ])?;
 
-        let none = db.search_similar_chunks(&exact, &RagSearchFilters::default(), 0)?;
+        let none =
+            db.search_similar_chunks(&small_model(&db)?, &exact, &RagSearchFilters::default(), 0)?;
         assert!(none.is_empty());
 
         let filters = RagSearchFilters {
             session_id: Some("sess-a".to_string()),
+            exclude_session_id: None,
             tool_name: Some("event_msg".to_string()),
             since_ts_ms: Some(150),
             until_ts_ms: Some(220),
         };
-        let filtered = db.search_similar_chunks(&exact, &filters, 10)?;
+        let filtered = db.search_similar_chunks(&small_model(&db)?, &exact, &filters, 10)?;
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].chunk_id, 22);
         Ok(())
@@ -0,0 +1,327 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use rusqlite::params;
+
+use super::{Database, IndexedSession};
+use crate::config::model::RetentionConfig;
+use crate::util::unix_timestamp;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Which `[retention]` limit selected a session for pruning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneReason {
+    MaxAge,
+    MaxSessions,
+}
+
+impl PruneReason {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PruneReason::MaxAge => "max_age_days",
+            PruneReason::MaxSessions => "max_sessions_per_provider",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PruneCandidate {
+    pub id: String,
+    pub provider: String,
+    pub path: PathBuf,
+    /// Last activity in unix seconds, falling back to the transcript mtime.
+    pub last_active: i64,
+    pub messages: usize,
+    pub reason: PruneReason,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub sessions: usize,
+    pub messages: usize,
+    pub token_usage: usize,
+    pub vectors: usize,
+}
+
+impl Database {
+    /// Select the sessions that fall outside `policy` as of `now` (unix seconds),
+    /// least recently active first.
+    ///
+    /// Sessions listed in `keep`, and named sessions when `keep_named` is set, are
+    /// never selected and do not count toward `max_sessions_per_provider`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sessions cannot be read.
+    pub fn prune_candidates(
+        &self,
+        policy: &RetentionConfig,
+        now: i64,
+    ) -> Result<Vec<PruneCandidate>> {
+        if !policy.has_limits() {
+            return Ok(Vec::new());
+        }
+        let keep: HashSet<&str> = policy.keep.iter().map(String::as_str).collect();
+        let cutoff = policy.max_age_days.map(|days| {
+            let age = i64::try_from(days)
+                .unwrap_or(i64::MAX)
+                .saturating_mul(SECONDS_PER_DAY);
+            now.saturating_sub(age)
+        });
+
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT
+                s.id,
+                s.provider,
+                s.path,
+                s.uuid,
+                s.thread_name,
+                COALESCE(s.last_active, s.mtime) AS activity,
+                (SELECT COUNT(*) FROM messages m WHERE m.session_id = s.id)
+            FROM sessions s
+            ORDER BY s.provider, activity DESC, s.id
+            ",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut ranks: HashMap<String, usize> = HashMap::new();
+        let mut out = Vec::new();
+        while let Some(row) = rows.next()? {
+            let id: String = row.get(0)?;
+            let uuid: Option<String> = row.get(3)?;
+            let thread_name: Option<String> = row.get(4)?;
+            let named = thread_name.is_some_and(|name| !name.trim().is_empty());
+            if keep.contains(id.as_str())
+                || uuid.as_deref().is_some_and(|uuid| keep.contains(uuid))
+                || (policy.keep_named && named)
+            {
+                continue;
+            }
+
+            let provider: String = row.get(1)?;
+            let last_active: i64 = row.get(5)?;
+            let rank = ranks.entry(provider.clone()).or_default();
+            *rank += 1;
+            let reason = if cutoff.is_some_and(|cutoff| last_active < cutoff) {
+                PruneReason::MaxAge
+            } else if policy
+                .max_sessions_per_provider
+                .is_some_and(|max| *rank > max)
+            {
+                PruneReason::MaxSessions
+            } else {
+                continue;
+            };
+
+            out.push(PruneCandidate {
+                id,
+                provider,
+                path: PathBuf::from(row.get::<_, String>(2)?),
+                last_active,
+                messages: usize::try_from(row.get::<_, i64>(6)?).unwrap_or(0),
+                reason,
+            });
+        }
+        out.sort_by(|a, b| (a.last_active, &a.id).cmp(&(b.last_active, &b.id)));
+        Ok(out)
+    }
+
+    /// Delete the index rows of `candidates` (sessions, messages, full-text
+    /// entries, vectors, and token usage) and remember them so later scans do not
+    /// add them back. Transcript files are never touched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any delete fails; nothing is pruned in that case.
+    pub fn prune_sessions(&mut self, candidates: &[PruneCandidate]) -> Result<PruneReport> {
+        let vector_tables: BTreeSet<String> = self
+            .rag_models()?
+            .into_iter()
+            .map(|model| model.table_name)
+            .collect();
+        let pruned_ms = unix_timestamp().saturating_mul(1000);
+        let mut report = PruneReport::default();
+
+        let tx = self.conn.transaction()?;
+        for candidate in candidates {
+            let id = candidate.id.as_str();
+            report.messages +=
+                tx.execute("DELETE FROM message_store WHERE session_id = ?1", [id])?;
+            report.token_usage +=
+                tx.execute("DELETE FROM token_usage WHERE session_id = ?1", [id])?;
+            for table in &vector_tables {
+                report.vectors +=
+                    tx.execute(&format!("DELETE FROM {table} WHERE session_id = ?1"), [id])?;
+            }
+            tx.execute(
+                r"
+                INSERT INTO pruned_sessions (id, provider, path, size, mtime, pruned_ms)
+                SELECT id, provider, path, size, mtime, ?2 FROM sessions WHERE id = ?1
+                ON CONFLICT(id) DO UPDATE SET
+                    provider = excluded.provider,
+                    path = excluded.path,
+                    size = excluded.size,
+                    mtime = excluded.mtime,
+                    pruned_ms = excluded.pruned_ms
+                ",
+                params![id, pruned_ms],
+            )?;
+            report.sessions += tx.execute("DELETE FROM sessions WHERE id = ?1", [id])?;
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// List pruned sessions for a provider keyed by session id, with the transcript
+    /// size and mtime recorded when they were pruned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn pruned_sessions_for_provider(
+        &self,
+        provider: &str,
+    ) -> Result<HashMap<String, IndexedSession>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, path, size, mtime FROM pruned_sessions WHERE provider = ?1")?;
+        let rows = stmt.query_map([provider], |row| {
+            Ok(IndexedSession {
+                id: row.get(0)?,
+                path: PathBuf::from(row.get::<_, String>(1)?),
+                size: row.get(2)?,
+                mtime: row.get(3)?,
+            })
+        })?;
+        let mut out = HashMap::new();
+        for row in rows {
+            let session = row?;
+            out.insert(session.id.clone(), session);
+        }
+        Ok(out)
+    }
+
+    /// Drop the pruned marker for a session, letting it be indexed again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete fails.
+    pub fn forget_pruned_session(&self, id: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM pruned_sessions WHERE id = ?1", [id])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RagChunkRecord;
+    use crate::session::{MessageRecord, SessionIngest, SessionSummary};
+    use crate::test_support::session_summary;
+    use tempfile::TempDir;
+
+    const DAY: i64 = SECONDS_PER_DAY;
+
+    fn session(id: &str, last_active: i64, thread_name: Option<&str>) -> SessionIngest {
+        let summary = SessionSummary {
+            thread_name: thread_name.map(ToString::to_string),
+            uuid: Some(format!("uuid-{id}")),
+            size: 10,
+            ..session_summary(id, last_active)
+        };
+        let message = MessageRecord::new(id, 0, "user", "hello", None, Some(last_active));
+        SessionIngest::new(summary, vec![message])
+    }
+
+    fn policy(max_age_days: Option<u64>, max_sessions: Option<usize>) -> RetentionConfig {
+        RetentionConfig {
+            max_age_days,
+            max_sessions_per_provider: max_sessions,
+            ..RetentionConfig::default()
+        }
+    }
+
+    #[test]
+    fn prune_candidates_apply_limits_and_keep_rules() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let now = 1_000 * DAY;
+        db.upsert_session(&session("new", now - DAY, None))?;
+        db.upsert_session(&session("mid", now - 5 * DAY, None))?;
+        db.upsert_session(&session("old", now - 40 * DAY, None))?;
+        db.upsert_session(&session("named", now - 50 * DAY, Some("Release")))?;
+        db.upsert_session(&session("pinned", now - 60 * DAY, None))?;
+
+        assert!(db.prune_candidates(&policy(None, None), now)?.is_empty());
+
+        let mut by_age = policy(Some(30), None);
+        by_age.keep = vec!["uuid-pinned".into()];
+        let ids: Vec<_> = db
+            .prune_candidates(&by_age, now)?
+            .into_iter()
+            .map(|c| (c.id, c.reason))
+            .collect();
+        assert_eq!(ids, vec![("old".to_string(), PruneReason::MaxAge)]);
+
+        let mut by_count = policy(None, Some(1));
+        by_count.keep_named = false;
+        let ids: Vec<_> = db
+            .prune_candidates(&by_count, now)?
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec!["pinned", "named", "old", "mid"]);
+        Ok(())
+    }
+
+    #[test]
+    fn prune_sessions_removes_index_rows_and_records_tombstones() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let now = 1_000 * DAY;
+        db.upsert_session(&session("old", now - 40 * DAY, None))?;
+        db.upsert_session(&session("new", now, None))?;
+        db.ensure_rag_model("small", 3)?;
+        db.upsert_rag_chunks(&[RagChunkRecord {
+            chunk_id: 1,
+            embedding: vec![0.1, 0.2, 0.3],
+            session_id: "old".into(),
+            ts_ms: 0,
+            tool_name: None,
+            kind: "user".into(),
+            model: "small".into(),
+            content_hash: "h".into(),
+            text: "hello".into(),
+            source_event_id: 0,
+            chunk_ordinal: 0,
+            span_start: 0,
+            span_end: 5,
+        }])?;
+
+        let candidates = db.prune_candidates(&policy(Some(30), None), now)?;
+        let report = db.prune_sessions(&candidates)?;
+        assert_eq!(
+            report,
+            PruneReport {
+                sessions: 1,
+                messages: 1,
+                token_usage: 0,
+                vectors: 1,
+            }
+        );
+        assert!(db.session_summary("old")?.is_none());
+        assert!(db.session_summary("new")?.is_some());
+        assert_eq!(db.search_full_text("hello", None, false)?.len(), 1);
+        assert_eq!(db.check()?.problem_count(), 0);
+
+        let pruned = db.pruned_sessions_for_provider("codex")?;
+        assert_eq!(pruned["old"].mtime, now - 40 * DAY);
+        db.forget_pruned_session("old")?;
+        assert!(db.pruned_sessions_for_provider("codex")?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use color_eyre::eyre::WrapErr;
+use rusqlite::{OptionalExtension, Row, params};
+
+use super::Database;
+use crate::pipeline::PipelinePlan;
+
+/// A provider launch recorded in the run history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunRecord {
+    pub id: i64,
+    pub started_ms: i64,
+    pub profile: Option<String>,
+    pub provider: String,
+    /// The serialized [`PipelinePlan`]; secrets stay masked.
+    pub plan: String,
+    pub cwd: PathBuf,
+    /// `None` when the provider was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub duration_ms: i64,
+    /// Session transcript that appeared while the provider ran.
+    pub session_path: Option<PathBuf>,
+}
+
+impl RunRecord {
+    /// Decode the recorded plan.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the stored JSON no longer matches [`PipelinePlan`].
+    pub fn decode_plan(&self) -> Result<PipelinePlan> {
+        serde_json::from_str(&self.plan)
+            .wrap_err_with(|| format!("run {} has an unreadable plan", self.id))
+    }
+
+    #[must_use]
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// The fields of a run written before its id is known.
+#[derive(Debug, Clone)]
+pub struct NewRun<'a> {
+    pub started_ms: i64,
+    pub plan: &'a PipelinePlan,
+    pub exit_code: Option<i32>,
+    pub duration_ms: i64,
+    pub session_path: Option<&'a Path>,
+}
+
+const RUN_COLUMNS: &str =
+    "id, started_ms, profile, provider, plan, cwd, exit_code, duration_ms, session_path";
+
+impl Database {
+    /// Record a finished launch and return its run id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plan cannot be serialized or the row cannot be written.
+    pub fn record_run(&self, run: &NewRun<'_>) -> Result<i64> {
+        let plan = serde_json::to_string(run.plan)?;
+        self.conn.execute(
+            r"
+            INSERT INTO runs(started_ms, profile, provider, plan, cwd, exit_code, duration_ms, session_path)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ",
+            params![
+                run.started_ms,
+                run.plan.profile,
+                run.plan.provider,
+                plan,
+                run.plan.cwd.to_string_lossy(),
+                run.exit_code,
+                run.duration_ms,
+                run.session_path.map(|path| path.to_string_lossy().into_owned()),
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// List up to `limit` runs, most recent first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn recent_runs(&self, limit: usize) -> Result<Vec<RunRecord>> {
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {RUN_COLUMNS} FROM runs ORDER BY started_ms DESC, id DESC LIMIT ?1"
+        ))?;
+        let rows = stmt.query_map([limit], map_run)?;
+        let mut runs = Vec::new();
+        for row in rows {
+            runs.push(row?);
+        }
+        Ok(runs)
+    }
+
+    /// Look up a run by id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn run_by_id(&self, id: i64) -> Result<Option<RunRecord>> {
+        let run = self
+            .conn
+            .query_row(
+                &format!("SELECT {RUN_COLUMNS} FROM runs WHERE id = ?1"),
+                [id],
+                map_run,
+            )
+            .optional()?;
+        Ok(run)
+    }
+
+    /// The most recent run tied to the session transcript at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn latest_run_for_session(&self, path: &Path) -> Result<Option<RunRecord>> {
+        let run = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT {RUN_COLUMNS} FROM runs WHERE session_path = ?1 \
+                     ORDER BY started_ms DESC, id DESC LIMIT 1"
+                ),
+                [path.to_string_lossy()],
+                map_run,
+            )
+            .optional()?;
+        Ok(run)
+    }
+}
+
+fn map_run(row: &Row<'_>) -> rusqlite::Result<RunRecord> {
+    Ok(RunRecord {
+        id: row.get(0)?,
+        started_ms: row.get(1)?,
+        profile: row.get(2)?,
+        provider: row.get(3)?,
+        plan: row.get(4)?,
+        cwd: PathBuf::from(row.get::<_, String>(5)?),
+        exit_code: row.get(6)?,
+        duration_ms: row.get(7)?,
+        session_path: row.get::<_, Option<String>>(8)?.map(PathBuf::from),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::model::HooksConfig;
+    use crate::pipeline::{Invocation, LaunchOptions};
+    use tempfile::TempDir;
+
+    fn plan(command: &str, profile: Option<&str>) -> PipelinePlan {
+        PipelinePlan {
+            pipeline: command.into(),
+            display: command.into(),
+            friendly_display: command.into(),
+            env: vec![("TOKEN".into(), "***".into())],
+            env_origins: vec!["provider codex".into()],
+            invocation: Invocation::Shell {
+                command: command.into(),
+            },
+            provider: "codex".into(),
+            profile: profile.map(str::to_string),
+            launch: LaunchOptions::default(),
+            terminal_title: "codex".into(),
+            pre_snippets: Vec::new(),
+            post_snippets: Vec::new(),
+            wrappers: Vec::new(),
+            needs_stdin_prompt: false,
+            uses_capture_arg: false,
+            capture_has_pre_commands: false,
+            stdin_prompt_label: None,
+            cwd: PathBuf::from("/work"),
+            prompt_assembler: None,
+            context: None,
+            secret_env: Vec::new(),
+            hooks: HooksConfig::default(),
+            session_id: None,
+            programs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn record_run_round_trips_plans_newest_first() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let first = plan("codex", None);
+        let second = plan("codex --search", Some("review"));
+        let session = PathBuf::from("/sessions/new.jsonl");
+
+        let first_id = db.record_run(&NewRun {
+            started_ms: 1_000,
+            plan: &first,
+            exit_code: Some(0),
+            duration_ms: 1_500,
+            session_path: Some(&session),
+        })?;
+        let second_id = db.record_run(&NewRun {
+            started_ms: 2_000,
+            plan: &second,
+            exit_code: None,
+            duration_ms: 20,
+            session_path: None,
+        })?;
+
+        let runs = db.recent_runs(10)?;
+        assert_eq!(
+            runs.iter().map(|run| run.id).collect::<Vec<_>>(),
+            [second_id, first_id]
+        );
+        assert_eq!(runs[0].profile.as_deref(), Some("review"));
+        assert!(!runs[0].succeeded());
+        assert!(runs[1].succeeded());
+        assert_eq!(runs[1].session_path.as_deref(), Some(session.as_path()));
+        assert_eq!(runs[1].cwd, PathBuf::from("/work"));
+
+        let decoded = runs[0].decode_plan()?;
+        assert_eq!(decoded.display, "codex --search");
+        assert_eq!(decoded.env, second.env);
+        assert!(matches!(
+            decoded.invocation,
+            Invocation::Shell { ref command } if command == "codex --search"
+        ));
+
+        assert_eq!(db.recent_runs(1)?.len(), 1);
+        assert_eq!(db.run_by_id(first_id)?, Some(runs[1].clone()));
+        assert_eq!(db.run_by_id(first_id + 100)?, None);
+        assert_eq!(
+            db.latest_run_for_session(&session)?.map(|run| run.id),
+            Some(first_id)
+        );
+        assert_eq!(db.latest_run_for_session(Path::new("/other.jsonl"))?, None);
+        Ok(())
+    }
+}
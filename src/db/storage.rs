@@ -0,0 +1,356 @@
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{Connection, Statement, params};
+
+use crate::session::MessageRecord;
+
+/// Message bodies of at least this many bytes are stored zstd-compressed.
+pub const COMPRESS_MIN_BYTES: usize = 2048;
+const ZSTD_LEVEL: i32 = 3;
+
+/// `SQLite` function that decompresses a `message_store.content_zstd` blob.
+const UNZSTD_FUNCTION: &str = "tx_unzstd";
+
+/// The pre-v23 `messages` table: plain text, with an integer `id` that gives
+/// the full-text index a stable rowid to point at.
+const MESSAGES_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT NOT NULL,
+        idx INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        source TEXT,
+        timestamp INTEGER,
+        is_first INTEGER NOT NULL DEFAULT 0,
+        source_event_id INTEGER,
+        UNIQUE (session_id, idx),
+        FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_messages_session_timestamp
+        ON messages(session_id, timestamp);
+";
+
+/// `messages_fts` reads its content from `messages` instead of keeping a
+/// second copy of every body.
+const MESSAGES_FTS_SQL: &str = r"
+    CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+        session_id UNINDEXED,
+        role UNINDEXED,
+        content,
+        content = 'messages',
+        content_rowid = 'id'
+    );
+";
+
+/// Sync triggers for the pre-v23 `messages` table.
+const MESSAGES_FTS_TRIGGERS_SQL: &str = r"
+    CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, session_id, role, content)
+        VALUES (new.id, new.session_id, new.role, new.content);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, session_id, role, content)
+        VALUES ('delete', old.id, old.session_id, old.role, old.content);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, session_id, role, content)
+        VALUES ('delete', old.id, old.session_id, old.role, old.content);
+        INSERT INTO messages_fts (rowid, session_id, role, content)
+        VALUES (new.id, new.session_id, new.role, new.content);
+    END;
+";
+
+/// Messages live in `message_store`, with large bodies compressed into
+/// `content_zstd`. The `messages` view hands every reader plain text, and the
+/// full-text index reads its content from that view.
+const MESSAGE_STORE_SQL: &str = r"
+    CREATE TABLE IF NOT EXISTS message_store (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT NOT NULL,
+        idx INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT,
+        content_zstd BLOB,
+        source TEXT,
+        timestamp INTEGER,
+        is_first INTEGER NOT NULL DEFAULT 0,
+        source_event_id INTEGER,
+        UNIQUE (session_id, idx),
+        FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_messages_session_timestamp
+        ON message_store(session_id, timestamp);
+";
+
+const MESSAGES_VIEW_SQL: &str = r"
+    CREATE VIEW IF NOT EXISTS messages AS
+    SELECT
+        id,
+        session_id,
+        idx,
+        role,
+        COALESCE(content, tx_unzstd(content_zstd)) AS content,
+        source,
+        timestamp,
+        is_first,
+        source_event_id
+    FROM message_store;
+
+    CREATE TRIGGER IF NOT EXISTS message_store_fts_insert AFTER INSERT ON message_store BEGIN
+        INSERT INTO messages_fts (rowid, session_id, role, content)
+        VALUES (new.id, new.session_id, new.role, COALESCE(new.content, tx_unzstd(new.content_zstd)));
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS message_store_fts_delete AFTER DELETE ON message_store BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, session_id, role, content)
+        VALUES ('delete', old.id, old.session_id, old.role, COALESCE(old.content, tx_unzstd(old.content_zstd)));
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS message_store_fts_update AFTER UPDATE ON message_store BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, session_id, role, content)
+        VALUES ('delete', old.id, old.session_id, old.role, COALESCE(old.content, tx_unzstd(old.content_zstd)));
+        INSERT INTO messages_fts (rowid, session_id, role, content)
+        VALUES (new.id, new.session_id, new.role, COALESCE(new.content, tx_unzstd(new.content_zstd)));
+    END;
+";
+
+pub(super) const INSERT_MESSAGE_SQL: &str = r"
+    INSERT INTO message_store (
+        session_id, idx, role, content, content_zstd, source, timestamp, is_first, source_event_id
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+";
+
+/// Create the message store, the `messages` view, the full-text index, and the
+/// sync triggers.
+pub(super) fn create_message_store(conn: &Connection) -> Result<()> {
+    conn.execute_batch(MESSAGE_STORE_SQL)?;
+    conn.execute_batch(MESSAGES_FTS_SQL)?;
+    conn.execute_batch(MESSAGES_VIEW_SQL)?;
+    Ok(())
+}
+
+/// Rebuild a pre-v20 `messages` table with an integer id and replace its
+/// standalone full-text copy with the external-content index.
+///
+/// Views that read `messages` are dropped first so the table can be swapped;
+/// callers recreate them afterwards.
+pub(super) fn migrate_messages(conn: &Connection) -> Result<()> {
+    let has_id: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info('messages') WHERE name = 'id')",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_id {
+        return Ok(());
+    }
+
+    conn.execute_batch(
+        r"
+        DROP VIEW IF EXISTS v_sessions;
+        DROP VIEW IF EXISTS v_messages;
+        DROP TABLE messages_fts;
+        ALTER TABLE messages RENAME TO messages_v19;
+        DROP INDEX IF EXISTS idx_messages_session_timestamp;
+        ",
+    )?;
+    conn.execute_batch(MESSAGES_SQL)?;
+    conn.execute_batch(
+        r"
+        INSERT INTO messages (
+            session_id, idx, role, content, source, timestamp, is_first, source_event_id
+        )
+        SELECT session_id, idx, role, content, source, timestamp, is_first, source_event_id
+        FROM messages_v19
+        ORDER BY session_id, idx;
+
+        DROP TABLE messages_v19;
+        ",
+    )?;
+    conn.execute_batch(MESSAGES_FTS_SQL)?;
+    conn.execute_batch(MESSAGES_FTS_TRIGGERS_SQL)?;
+    conn.execute(
+        "INSERT INTO messages_fts (messages_fts) VALUES ('rebuild')",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Move the plain `messages` table into `message_store`, compressing large
+/// bodies, and put the `messages` view in its place.
+///
+/// Rows keep their ids and the view yields the same text, so the full-text
+/// index stays valid without a rebuild. Views that read `messages` are dropped
+/// first; callers recreate them afterwards.
+pub(super) fn migrate_message_store(conn: &Connection) -> Result<()> {
+    let migrated: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_schema WHERE type = 'table' AND name = 'message_store')",
+        [],
+        |row| row.get(0),
+    )?;
+    if migrated {
+        return Ok(());
+    }
+
+    // The old table owns an index with the name `message_store` reuses.
+    conn.execute_batch(
+        r"
+        DROP VIEW IF EXISTS v_sessions;
+        DROP VIEW IF EXISTS v_messages;
+        DROP INDEX IF EXISTS idx_messages_session_timestamp;
+        ",
+    )?;
+    conn.execute_batch(MESSAGE_STORE_SQL)?;
+    conn.execute_batch(
+        r"
+        INSERT INTO message_store (
+            id, session_id, idx, role, content, source, timestamp, is_first, source_event_id
+        )
+        SELECT id, session_id, idx, role, content, source, timestamp, is_first, source_event_id
+        FROM messages;
+        ",
+    )?;
+    {
+        let mut read = conn.prepare(
+            "SELECT id, content FROM message_store WHERE length(CAST(content AS BLOB)) >= ?1",
+        )?;
+        let mut compress = conn
+            .prepare("UPDATE message_store SET content = NULL, content_zstd = ?2 WHERE id = ?1")?;
+        let mut rows = read.query([i64::try_from(COMPRESS_MIN_BYTES)?])?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let content: String = row.get(1)?;
+            if let Some(compressed) = compress_body(&content)? {
+                compress.execute(params![id, compressed])?;
+            }
+        }
+    }
+    // Dropping the table also drops its sync triggers.
+    conn.execute_batch("DROP TABLE messages")?;
+    conn.execute_batch(MESSAGES_VIEW_SQL)?;
+    Ok(())
+}
+
+/// Insert one message with [`INSERT_MESSAGE_SQL`], compressing large bodies.
+pub(super) fn insert_message(stmt: &mut Statement<'_>, message: &MessageRecord) -> Result<()> {
+    let compressed = compress_body(&message.content)?;
+    let content = compressed.is_none().then_some(message.content.as_str());
+    stmt.execute(params![
+        message.session_id,
+        message.index,
+        message.role,
+        content,
+        compressed,
+        message.source.as_deref(),
+        message.timestamp,
+        i64::from(message.is_first),
+        message.source_event_id.unwrap_or(message.index),
+    ])?;
+    Ok(())
+}
+
+/// Compress a body that is large enough and actually shrinks.
+fn compress_body(content: &str) -> Result<Option<Vec<u8>>> {
+    if content.len() < COMPRESS_MIN_BYTES {
+        return Ok(None);
+    }
+    let compressed = zstd::bulk::compress(content.as_bytes(), ZSTD_LEVEL)
+        .context("failed to compress message body")?;
+    Ok((compressed.len() < content.len()).then_some(compressed))
+}
+
+fn decompress_body(bytes: &[u8]) -> std::io::Result<String> {
+    let raw = zstd::stream::decode_all(bytes)?;
+    String::from_utf8(raw).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// Register the SQL functions the schema relies on on one connection.
+pub(crate) fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        UNZSTD_FUNCTION,
+        1,
+        FunctionFlags::SQLITE_UTF8
+            | FunctionFlags::SQLITE_DETERMINISTIC
+            | FunctionFlags::SQLITE_INNOCUOUS,
+        |ctx| {
+            let Some(bytes) = ctx.get_raw(0).as_blob_or_null()? else {
+                return Ok(None);
+            };
+            decompress_body(bytes)
+                .map(Some)
+                .map_err(|err| rusqlite::Error::UserFunctionError(Box::new(err)))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::session::{MessageRecord, SessionIngest, SessionSummary};
+    use crate::test_support::session_summary;
+    use tempfile::TempDir;
+
+    fn ingest(messages: Vec<MessageRecord>) -> SessionIngest {
+        let summary = SessionSummary {
+            created_at: Some(1),
+            started_at: Some(1),
+            ..session_summary("sess", 2)
+        };
+        SessionIngest::new(summary, messages)
+    }
+
+    #[test]
+    fn large_bodies_are_compressed_and_read_back_transparently() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let tool_output = "error: connection reset, retrying request\n".repeat(200);
+        db.upsert_session(&ingest(vec![
+            MessageRecord::new("sess", 0, "user", "why do retries fail", None, Some(1)),
+            MessageRecord::new("sess", 1, "tool", tool_output.clone(), None, Some(2)),
+        ]))?;
+
+        // The index keeps no copy of its own; the text lives only in `message_store`.
+        let fts_copies: i64 = db.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_schema WHERE name = 'messages_fts_content'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(fts_copies, 0);
+        let stored: Vec<(Option<String>, Option<i64>)> = db
+            .conn
+            .prepare("SELECT content, length(content_zstd) FROM message_store ORDER BY idx")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        assert_eq!(stored[0], (Some("why do retries fail".into()), None));
+        assert_eq!(stored[1].0, None);
+        assert!(stored[1].1.is_some_and(|len| len < 1000));
+
+        let transcript = db.fetch_transcript("sess")?.expect("transcript");
+        assert_eq!(transcript.messages[1].content, tool_output);
+        let hits = db.search_full_text("retrying", None, false)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet.as_deref(), Some(tool_output.as_str()));
+
+        db.upsert_session(&ingest(vec![MessageRecord::new(
+            "sess",
+            0,
+            "user",
+            "short again",
+            None,
+            Some(3),
+        )]))?;
+        assert!(db.search_full_text("retrying", None, false)?.is_empty());
+        assert_eq!(db.search_full_text("again", None, false)?.len(), 1);
+        db.conn.execute(
+            "INSERT INTO messages_fts (messages_fts) VALUES ('integrity-check')",
+            [],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use color_eyre::Result;
+use rusqlite::Connection;
+
+use super::Database;
+
+/// Version of the documented `v_*` views.
+///
+/// Columns are only ever added to the views within a version; renaming or
+/// removing one bumps it so queries can check `SELECT views_version FROM v_version`.
+pub const VIEWS_VERSION: i32 = 1;
+
+/// Columns exposed by `v_rag_chunks`, shared by every per-model vector table.
+const RAG_CHUNK_COLUMNS: &str = "chunk_id, session_id, model, kind, tool_name, ts_ms, \
+    content_hash, text, source_event_id, chunk_ordinal, span_start, span_end";
+
+const VIEWS_SQL: &str = r"
+    DROP VIEW IF EXISTS v_version;
+    DROP VIEW IF EXISTS v_sessions;
+    DROP VIEW IF EXISTS v_messages;
+    DROP VIEW IF EXISTS v_token_usage;
+
+    CREATE VIEW v_version AS SELECT 1 AS views_version;
+
+    CREATE VIEW v_sessions AS
+    SELECT
+        s.id,
+        s.provider,
+        s.wrapper,
+        s.model,
+        s.label,
+        s.thread_name,
+        s.path,
+        s.uuid,
+        s.first_prompt,
+        s.actionable,
+        s.subagent,
+        s.created_at,
+        s.started_at,
+        s.last_active,
+        s.size,
+        s.mtime,
+        (SELECT COUNT(*) FROM messages m WHERE m.session_id = s.id) AS message_count,
+        o.machine AS origin_machine
+    FROM sessions s
+    LEFT JOIN session_origins o ON o.session_id = s.id;
+
+    CREATE VIEW v_messages AS
+    SELECT
+        m.session_id,
+        s.provider,
+        m.idx AS message_index,
+        m.role,
+        m.content,
+        m.source,
+        m.timestamp,
+        m.is_first,
+        m.source_event_id
+    FROM messages m
+    JOIN sessions s ON s.id = m.session_id;
+
+    CREATE VIEW v_token_usage AS
+    SELECT
+        t.session_id,
+        s.provider,
+        t.timestamp,
+        t.model,
+        t.input_tokens,
+        t.cached_input_tokens,
+        t.output_tokens,
+        t.reasoning_output_tokens,
+        t.total_tokens
+    FROM token_usage t
+    JOIN sessions s ON s.id = t.session_id;
+";
+
+/// Recreate every documented view for the current schema.
+pub(super) fn create_views(conn: &Connection) -> Result<()> {
+    conn.execute_batch(VIEWS_SQL)?;
+    refresh_rag_chunks_view(conn)
+}
+
+/// Rebuild `v_rag_chunks` as a union over every registered vector table.
+///
+/// Called whenever a model gets its own table, so the view always covers all of them.
+pub(super) fn refresh_rag_chunks_view(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT table_name FROM rag_models ORDER BY created_ms, model")?;
+    let mut tables = Vec::new();
+    for table in stmt.query_map([], |row| row.get::<_, String>(0))? {
+        let table = table?;
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+
+    let body = if tables.is_empty() {
+        format!(
+            "SELECT {} WHERE 0",
+            RAG_CHUNK_COLUMNS
+                .split(", ")
+                .map(|column| format!("NULL AS {column}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    } else {
+        tables
+            .iter()
+            .map(|table| format!("SELECT {RAG_CHUNK_COLUMNS} FROM {table}"))
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ")
+    };
+    conn.execute_batch(&format!(
+        "DROP VIEW IF EXISTS v_rag_chunks; CREATE VIEW v_rag_chunks AS {body};"
+    ))?;
+    Ok(())
+}
+
+impl Database {
+    /// Version of the `v_*` views in this database.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the views are missing.
+    pub fn views_version(&self) -> Result<i32> {
+        Ok(self
+            .conn
+            .query_row("SELECT views_version FROM v_version", [], |row| row.get(0))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RagChunkRecord;
+    use crate::session::{MessageRecord, SessionIngest, SessionSummary, TokenUsageRecord};
+    use crate::test_support::session_summary;
+    use tempfile::TempDir;
+
+    #[test]
+    fn views_expose_sessions_messages_usage_and_chunks() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        assert_eq!(db.views_version()?, VIEWS_VERSION);
+        let chunks: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM v_rag_chunks", [], |row| row.get(0))?;
+        assert_eq!(chunks, 0);
+
+        let summary = SessionSummary {
+            created_at: Some(10),
+            started_at: Some(10),
+            size: 10,
+            ..session_summary("sess", 20)
+        };
+        let mut ingest = SessionIngest::new(
+            summary,
+            vec![
+                MessageRecord::new("sess", 0, "user", "hello", None, Some(10)),
+                MessageRecord::new("sess", 1, "assistant", "hi there", None, Some(20)),
+            ],
+        );
+        ingest.token_usage.push(TokenUsageRecord {
+            session_id: "sess".into(),
+            timestamp: 20,
+            input_tokens: 5,
+            cached_input_tokens: 0,
+            output_tokens: 7,
+            reasoning_output_tokens: 0,
+            total_tokens: 12,
+            model: Some("gpt-5".into()),
+            rate_limits: None,
+        });
+        db.upsert_session(&ingest)?;
+        db.ensure_rag_model("small", 3)?;
+        db.upsert_rag_chunks(&[RagChunkRecord {
+            chunk_id: 1,
+            embedding: vec![0.1, 0.2, 0.3],
+            session_id: "sess".into(),
+            ts_ms: 10_000,
+            tool_name: None,
+            kind: "user".into(),
+            model: "small".into(),
+            content_hash: "h".into(),
+            text: "hello".into(),
+            source_event_id: 0,
+            chunk_ordinal: 0,
+            span_start: 0,
+            span_end: 5,
+        }])?;
+
+        let (messages, origin): (i64, Option<String>) = db.conn.query_row(
+            "SELECT message_count, origin_machine FROM v_sessions WHERE id = 'sess'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!((messages, origin), (2, None));
+        let content: String = db.conn.query_row(
+            "SELECT content FROM v_messages WHERE provider = 'codex' AND message_index = 1",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(content, "hi there");
+        let total: i64 = db.conn.query_row(
+            "SELECT SUM(total_tokens) FROM v_token_usage WHERE provider = 'codex'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(total, 12);
+        let text: String = db.conn.query_row(
+            "SELECT text FROM v_rag_chunks WHERE model = 'small'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(text, "hello");
+        Ok(())
+    }
+}
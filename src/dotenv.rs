@@ -0,0 +1,150 @@
+//! Dotenv files named by `env_file` on providers and profiles.
+//!
+//! Each line is `KEY=VALUE`, optionally prefixed with `export`. Blank lines and
+//! lines starting with `#` are skipped. Unquoted values end at ` #`, single-quoted
+//! values are taken literally, and double-quoted values understand `\n`, `\t`,
+//! `\"`, and `\\`. Values are used as written; nothing is interpolated.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use color_eyre::Result;
+use color_eyre::eyre::{WrapErr, eyre};
+
+/// Read a dotenv file, or `None` when it does not exist.
+///
+/// # Errors
+///
+/// Returns an error when the file cannot be read or contains a malformed line.
+pub fn load(path: &Path) -> Result<Option<Vec<(String, String)>>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| format!("failed to read {}", path.display()));
+        }
+    };
+    parse(&contents)
+        .map(Some)
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))
+}
+
+/// Parse dotenv `contents` into entries in file order.
+///
+/// # Errors
+///
+/// Returns an error naming the line of the first malformed entry.
+pub fn parse(contents: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entry = parse_line(line).map_err(|message| eyre!("line {}: {message}", index + 1))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn parse_line(line: &str) -> std::result::Result<(String, String), String> {
+    let line = line
+        .strip_prefix("export")
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map_or(line, str::trim_start);
+    let (key, raw) = line
+        .split_once('=')
+        .ok_or_else(|| "expected KEY=VALUE".to_string())?;
+    let key = key.trim();
+    let valid_key = key.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+        && key
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+    if !valid_key {
+        return Err(format!("invalid variable name '{key}'"));
+    }
+    Ok((key.to_string(), parse_value(raw.trim())?))
+}
+
+fn parse_value(raw: &str) -> std::result::Result<String, String> {
+    if let Some(rest) = raw.strip_prefix('\'') {
+        let end = rest
+            .find('\'')
+            .ok_or_else(|| "unterminated single-quoted value".to_string())?;
+        return Ok(rest[..end].to_string());
+    }
+    if let Some(rest) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '"' => return Ok(value),
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(other @ ('"' | '\\')) => value.push(other),
+                    Some(other) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => break,
+                },
+                _ => value.push(ch),
+            }
+        }
+        return Err("unterminated double-quoted value".to_string());
+    }
+    let value = raw.find(" #").map_or(raw, |index| &raw[..index]);
+    Ok(value.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_handles_quotes_comments_and_export() -> Result<()> {
+        let entries = parse(
+            "# tokens\n\
+             export API_URL=https://example.test # staging\n\
+             \n\
+             SINGLE='a # ${literal}'\n\
+             DOUBLE=\"line\\nnext \\\"quoted\\\"\"\n\
+             EMPTY=\n",
+        )?;
+        assert_eq!(
+            entries,
+            [
+                ("API_URL".to_string(), "https://example.test".to_string()),
+                ("SINGLE".to_string(), "a # ${literal}".to_string()),
+                ("DOUBLE".to_string(), "line\nnext \"quoted\"".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_reports_line_numbers() {
+        let err = parse("OK=1\nnot an entry\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: expected KEY=VALUE");
+        let err = parse("1BAD=x").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: invalid variable name '1BAD'");
+        let err = parse("OPEN=\"never closed").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: unterminated double-quoted value");
+    }
+
+    #[test]
+    fn load_skips_missing_files() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        assert!(load(&temp.path().join("absent.env"))?.is_none());
+        let path = temp.path().join("present.env");
+        fs::write(&path, "KEY=value\n")?;
+        assert_eq!(
+            load(&path)?,
+            Some(vec![("KEY".to_string(), "value".to_string())])
+        );
+        Ok(())
+    }
+}
@@ -26,6 +26,8 @@ use crate::session::{
 pub struct IndexReport {
     pub scanned: usize,
     pub updated: usize,
+    /// Ids of the sessions counted in `updated`, in the order they were ingested.
+    pub updated_sessions: Vec<String>,
     pub skipped: usize,
     pub removed: usize,
     pub errors: Vec<IndexError>,
@@ -145,6 +147,7 @@ impl<'a> Indexer<'a> {
                 .iter()
                 .map(|session| (session.path.to_string_lossy().to_string(), session.clone()))
                 .collect();
+            let pruned = self.db.pruned_sessions_for_provider(&provider.name)?;
             let mut seen = HashSet::new();
             for root in &provider.session_roots {
                 if !root.exists() {
@@ -155,9 +158,9 @@ impl<'a> Indexer<'a> {
                     if is_jsonl(root) {
                         let canonical_path = root.canonicalize().unwrap_or_else(|_| root.clone());
                         let file = read_session_file(root.clone(), canonical_path);
-                        match file
-                            .and_then(|file| self.process_file(provider, &file, &existing_by_path))
-                        {
+                        match file.and_then(|file| {
+                            self.process_file(provider, &file, &existing_by_path, &pruned)
+                        }) {
                             Ok(FileProcess::Updated(summary)) => {
                                 seen.insert(summary.id.clone());
                                 existing_by_path.insert(
@@ -165,6 +168,7 @@ impl<'a> Indexer<'a> {
                                     indexed_session(&summary),
                                 );
                                 report.updated += 1;
+                                report.updated_sessions.push(summary.id);
                                 report.scanned += 1;
                             }
                             Ok(FileProcess::Skipped(id)) => {
@@ -201,9 +205,9 @@ impl<'a> Indexer<'a> {
 
                 report.scanned += paths.len();
                 for (path, file) in read_session_files(paths) {
-                    match file
-                        .and_then(|file| self.process_file(provider, &file, &existing_by_path))
-                    {
+                    match file.and_then(|file| {
+                        self.process_file(provider, &file, &existing_by_path, &pruned)
+                    }) {
                         Ok(FileProcess::Updated(summary)) => {
                             seen.insert(summary.id.clone());
                             existing_by_path.insert(
@@ -211,6 +215,7 @@ impl<'a> Indexer<'a> {
                                 indexed_session(&summary),
                             );
                             report.updated += 1;
+                            report.updated_sessions.push(summary.id);
                         }
                         Ok(FileProcess::Skipped(id)) => {
                             seen.insert(id);
@@ -240,6 +245,7 @@ impl<'a> Indexer<'a> {
         provider: &ProviderConfig,
         file: &SessionFile,
         existing_by_path: &HashMap<String, IndexedSession>,
+        pruned: &HashMap<String, IndexedSession>,
     ) -> Result<FileProcess> {
         let path_str = file.canonical_path.to_string_lossy().to_string();
 
@@ -262,6 +268,15 @@ impl<'a> Indexer<'a> {
             }
         }
 
+        // Pruned sessions stay out of the index until their transcript changes.
+        let (session_id, _) = compute_session_id(provider, &canonical_path);
+        let tombstone = pruned.get(&session_id);
+        if let Some(tombstone) = tombstone
+            && !tombstone.is_stale(file.size, file.mtime)
+        {
+            return Ok(FileProcess::Skipped(session_id));
+        }
+
         let ingest = Self::build_ingest(
             provider,
             &canonical_path,
@@ -272,6 +287,9 @@ impl<'a> Indexer<'a> {
         .with_context(|| format!("failed to ingest session from {}", file.path.display()))?;
         let summary = ingest.summary.clone();
         self.db.upsert_session(&ingest)?;
+        if tombstone.is_some() {
+            self.db.forget_pruned_session(&summary.id)?;
+        }
         Ok(FileProcess::Updated(Box::new(summary)))
     }
 
@@ -638,6 +656,19 @@ fn parse_timestamp(value: &Value) -> Option<i64> {
         .map(OffsetDateTime::unix_timestamp)
 }
 
+/// Session transcripts currently under `provider`'s session roots.
+#[must_use]
+pub fn session_files(provider: &ProviderConfig) -> HashSet<PathBuf> {
+    provider
+        .session_roots
+        .iter()
+        .flat_map(|root| WalkDir::new(root).follow_links(true))
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| entry.file_type().is_file() && is_jsonl(entry.path()))
+        .map(walkdir::DirEntry::into_path)
+        .collect()
+}
+
 fn is_jsonl(path: &Path) -> bool {
     path.extension()
         .and_then(OsStr::to_str)
@@ -930,7 +961,10 @@ fn summarize_instructions(raw: &str) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::model::{Defaults, FeatureConfig, SearchMode, SnippetConfig};
+    use crate::config::model::{
+        Defaults, FeatureConfig, HooksConfig, RagConfig, RetentionConfig, SearchFormat, SearchMode,
+        SnippetConfig,
+    };
     use assert_fs::TempDir;
     use assert_fs::prelude::*;
     use color_eyre::Result;
@@ -946,6 +980,8 @@ mod tests {
             bin: "codex".to_string(),
             flags: Vec::new(),
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: vec![root.to_path_buf()],
             stdin: None,
         }
@@ -959,6 +995,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -970,7 +1007,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         }
     }
 
@@ -1225,6 +1266,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -1236,7 +1278,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let db_path = temp.child("tx.sqlite3");
@@ -1269,6 +1315,11 @@ mod tests {
         let report = indexer.run()?;
         assert_eq!(report.scanned, 1);
         assert_eq!(report.updated, 1);
+        assert_eq!(report.updated_sessions.len(), 1);
+
+        let rerun = Indexer::new(&mut db, &config).run()?;
+        assert_eq!(rerun.updated, 0);
+        assert!(rerun.updated_sessions.is_empty());
         Ok(())
     }
 
@@ -1322,6 +1373,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -1333,7 +1385,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let db_path = temp.child("tx.sqlite3");
@@ -1399,6 +1455,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -1410,7 +1467,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let db_path = temp.child("tx.sqlite3");
@@ -1430,6 +1491,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn indexer_keeps_pruned_sessions_out_until_their_transcript_changes() -> Result<()> {
+        let temp = TempDir::new()?;
+        let sessions_dir = temp.child("sessions");
+        sessions_dir.create_dir_all()?;
+        let session_file = sessions_dir.child("session.jsonl");
+        session_file.write_str(
+            "{\"type\":\"event_msg\",\"payload\":{\"type\":\"user_message\",\"message\":\"Ping\"}}\n",
+        )?;
+        let config = config_from_provider(provider_with_root(sessions_dir.path()));
+        let mut db = Database::open(temp.child("tx.sqlite3").path())?;
+        Indexer::new(&mut db, &config).run()?;
+
+        let policy = RetentionConfig {
+            max_age_days: Some(1),
+            ..RetentionConfig::default()
+        };
+        let candidates = db.prune_candidates(&policy, current_unix_time() + 7 * 86_400)?;
+        assert_eq!(candidates.len(), 1);
+        db.prune_sessions(&candidates)?;
+
+        let report = Indexer::new(&mut db, &config).run()?;
+        assert_eq!((report.updated, report.skipped), (0, 1));
+        assert!(db.session_summary("codex/session.jsonl")?.is_none());
+
+        session_file.write_str(concat!(
+            "{\"type\":\"event_msg\",\"payload\":{\"type\":\"user_message\",\"message\":\"Ping\"}}\n",
+            "{\"type\":\"event_msg\",\"payload\":{\"type\":\"user_message\",\"message\":\"Again\"}}\n",
+        ))?;
+        let report = Indexer::new(&mut db, &config).run()?;
+        assert_eq!(report.updated, 1);
+        assert!(db.session_summary("codex/session.jsonl")?.is_some());
+        assert!(db.pruned_sessions_for_provider("codex")?.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn indexer_handles_missing_roots_and_single_file_providers() -> Result<()> {
         let temp = TempDir::new()?;
@@ -1448,6 +1545,8 @@ mod tests {
                 bin: "echo".into(),
                 flags: Vec::new(),
                 env: Vec::new(),
+                env_file: Vec::new(),
+                hooks: HooksConfig::default(),
                 session_roots: vec![missing.path().to_path_buf(), single.path().to_path_buf()],
                 stdin: None,
             },
@@ -1458,6 +1557,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -1469,7 +1569,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let db_path = temp.child("tx.sqlite3");
@@ -1500,6 +1604,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -1511,7 +1616,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let db_path = temp.child("tx.sqlite3");
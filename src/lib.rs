@@ -3,13 +3,16 @@
 pub mod commands;
 pub mod config;
 pub mod db;
+pub mod dotenv;
 pub mod indexer;
 pub mod pipeline;
 pub mod prompts;
 pub mod providers;
 pub mod rag;
+pub mod secrets;
 pub mod session;
 pub mod sqlite_ext;
+pub mod template;
 
 mod app;
 pub mod cli;
@@ -42,6 +45,12 @@ pub fn run(cli: &Cli) -> color_eyre::Result<()> {
     }
 
     let mut app = app::App::bootstrap(cli)?;
+    dispatch(&mut app, cli)?;
+    app.auto_index();
+    Ok(())
+}
+
+fn dispatch(app: &mut app::App<'_>, cli: &Cli) -> color_eyre::Result<()> {
     if let Some(Command::Search(cmd)) = &cli.command {
         return app.search(cmd);
     }
@@ -51,12 +60,27 @@ pub fn run(cli: &Cli) -> color_eyre::Result<()> {
     if let Some(Command::Export(cmd)) = &cli.command {
         return app.export(cmd);
     }
+    if let Some(Command::Related(cmd)) = &cli.command {
+        return app.related(cmd);
+    }
+    if let Some(Command::History(cmd)) = &cli.command {
+        return app.history(cmd);
+    }
+    if let Some(Command::Rerun(cmd)) = &cli.command {
+        return app.rerun(cmd);
+    }
+    if let Some(Command::Exec(cmd)) = &cli.command {
+        return app.exec(cmd);
+    }
     if let Some(Command::Rag(cmd)) = &cli.command {
         return app.rag(cmd);
     }
     if let Some(Command::Stats(cmd)) = &cli.command {
         return app.stats(cmd);
     }
+    if let Some(Command::Prune(cmd)) = &cli.command {
+        return app.prune(cmd);
+    }
     if let Some(Command::Config(cmd)) = &cli.command {
         return app.config(cmd);
     }
@@ -468,11 +492,16 @@ provider = "alt"
                 profile: Some("alt".into()),
                 pre_snippets: Vec::new(),
                 post_snippets: Vec::new(),
-                wrap: None,
+                wrap: Vec::new(),
+                fresh: false,
                 emit_command: false,
                 emit_json: false,
                 vars: Vec::new(),
                 dry_run: false,
+                with_context: None,
+                context_k: 5,
+                context_model: None,
+                preview_context: false,
                 provider_args: Vec::new(),
             })),
         };
@@ -1,18 +1,28 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use color_eyre::Result;
 use color_eyre::eyre::{WrapErr, eyre};
+use indexmap::IndexMap;
 use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
 use shell_escape::unix::escape as shell_escape;
 use std::sync::LazyLock;
+use tempfile::TempPath;
+
+use crate::dotenv;
+use crate::secrets::{self, EnvPart, SecretEnv};
+use crate::template::{CmdMode, Template, TemplateContext, TemplateError};
 
 use crate::config::model::{
-    Config, ProfileConfig, ProviderConfig, Snippet, StdinMode, WrapperConfig, WrapperMode,
+    Config, ENV_FILE_ARG, EnvVar, HooksConfig, ProfileConfig, ProfileVar, ProviderConfig,
+    SecretConfig, Snippet, StdinMode, WrapperConfig, WrapperMode,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptInvocation {
     pub name: String,
     pub args: Vec<String>,
@@ -26,7 +36,8 @@ pub struct PipelineRequest<'a> {
     pub additional_pre: Vec<String>,
     pub additional_post: Vec<String>,
     pub inline_pre: Vec<String>,
-    pub wrap: Option<&'a str>,
+    /// Wrapper names, outermost first; replaces the profile's `wrap` when set.
+    pub wrap: Vec<String>,
     pub provider_args: Vec<String>,
     pub capture_prompt: bool,
     pub prompt_assembler: Option<PromptInvocation>,
@@ -41,59 +52,247 @@ pub struct SessionContext {
     pub label: Option<String>,
     pub path: Option<String>,
     pub resume_token: Option<String>,
+    pub thread_name: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+/// Version of the `--emit-json` document that `tx exec --plan` accepts.
+pub const PLAN_SCHEMA_VERSION: u32 = 1;
+
+/// A fully resolved launch. Plans serialize to JSON for the run history, so
+/// `tx rerun` can execute one again exactly as it was built.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelinePlan {
     pub pipeline: String,
     pub display: String,
     pub friendly_display: String,
     pub env: Vec<(String, String)>,
+    /// Where each `env` entry came from, e.g. `provider codex` or `env_file /work/.env`.
+    pub env_origins: Vec<String>,
     pub invocation: Invocation,
     pub provider: String,
+    /// Profile the plan was built from, if any.
+    pub profile: Option<String>,
+    /// Choices made for this launch on top of the profile.
+    #[serde(default)]
+    pub launch: LaunchOptions,
     pub terminal_title: String,
     pub pre_snippets: Vec<String>,
     pub post_snippets: Vec<String>,
-    pub wrapper: Option<String>,
+    /// Wrapper names applied around the pipeline, outermost first.
+    #[serde(default, alias = "wrapper", deserialize_with = "wrapper_names")]
+    pub wrappers: Vec<String>,
     pub needs_stdin_prompt: bool,
     pub uses_capture_arg: bool,
     pub capture_has_pre_commands: bool,
     pub stdin_prompt_label: Option<String>,
     pub cwd: PathBuf,
     pub prompt_assembler: Option<PromptInvocation>,
+    /// Retrieved context prepended to the captured prompt (see `--with-context`).
+    pub context: Option<String>,
+    /// Entries of `env` that are masked until [`PipelinePlan::launch_env`] runs.
+    pub secret_env: Vec<SecretEnv>,
+    /// Hook commands to run around the launch: global, then provider, then profile.
+    pub hooks: HooksConfig,
+    /// Session the plan resumes, exposed to hooks as `TX_SESSION_ID`.
+    pub session_id: Option<String>,
+    /// Executables the launch starts: the provider binary, an exec-mode
+    /// wrapper, and the first program of each snippet. `tx exec --plan`
+    /// checks they still exist.
+    #[serde(default)]
+    pub programs: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+/// Snippets, wrapper, and variables chosen for a launch in addition to its
+/// profile. `tx resume` restores them from the run that created a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchOptions {
+    pub pre: Vec<String>,
+    pub post: Vec<String>,
+    /// Wrappers requested explicitly rather than through the profile.
+    #[serde(default, deserialize_with = "wrapper_names")]
+    pub wrap: Vec<String>,
+    pub vars: BTreeMap<String, String>,
+}
+
+/// Read a list of wrapper names, or the single name (possibly `null`) that
+/// plans recorded before wrappers could be chained.
+fn wrapper_names<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Names {
+        One(Option<String>),
+        Chain(Vec<String>),
+    }
+    Ok(match Names::deserialize(deserializer)? {
+        Names::One(name) => name.into_iter().collect(),
+        Names::Chain(names) => names,
+    })
+}
+
+impl LaunchOptions {
+    fn from_request(request: &PipelineRequest<'_>) -> Self {
+        Self {
+            pre: request.additional_pre.clone(),
+            post: request.additional_post.clone(),
+            wrap: request.wrap.clone(),
+            vars: request
+                .vars
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pre.is_empty() && self.post.is_empty() && self.wrap.is_empty() && self.vars.is_empty()
+    }
+}
+
+impl PipelinePlan {
+    /// The environment to launch with, with secret references resolved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a referenced secret cannot be resolved.
+    pub fn launch_env(&self) -> Result<Vec<(String, String)>> {
+        let mut env = self.env.clone();
+        for pending in &self.secret_env {
+            let (key, value) = env.get_mut(pending.index).ok_or_else(|| {
+                eyre!(
+                    "plan masks env entry {} but has only {} entries",
+                    pending.index,
+                    self.env.len()
+                )
+            })?;
+            *value = pending
+                .resolve()
+                .wrap_err_with(|| format!("while resolving ${key}"))?;
+        }
+        Ok(env)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Invocation {
-    Shell { command: String },
-    Exec { argv: Vec<String> },
+    Shell {
+        command: String,
+    },
+    Exec {
+        argv: Vec<String>,
+        /// Position in `argv` where the path of the launch's env file is
+        /// inserted; see [`ENV_FILE_ARG`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        env_at: Option<usize>,
+    },
+}
+
+impl Invocation {
+    /// The command line as a shell would read it.
+    #[must_use]
+    pub fn display(&self) -> String {
+        match self {
+            Invocation::Shell { command } => command.clone(),
+            Invocation::Exec { argv, env_at } => {
+                launch_argv(argv, *env_at, Some(Path::new(ENV_FILE_ARG)))
+                    .iter()
+                    .map(|arg| shell_escape(Cow::Borrowed(arg)).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+        }
+    }
+}
+
+/// The argv an exec invocation starts, with `env_file` inserted at `env_at`.
+#[must_use]
+pub fn launch_argv(argv: &[String], env_at: Option<usize>, env_file: Option<&Path>) -> Vec<String> {
+    let mut argv = argv.to_vec();
+    if let (Some(at), Some(env_file)) = (env_at, env_file) {
+        let at = at.min(argv.len());
+        argv.insert(at, env_file.to_string_lossy().into_owned());
+    }
+    argv
+}
+
+/// Write `env` as `export KEY='VALUE'` lines to a file only the current user
+/// can read, for a wrapper's [`ENV_FILE_ARG`]. The file is deleted when the
+/// returned path is dropped.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be created or written.
+pub fn write_env_file(env: &[(String, String)]) -> Result<TempPath> {
+    let mut file = tempfile::Builder::new()
+        .prefix("tx-env-")
+        .tempfile()
+        .wrap_err("failed to create env file")?;
+    for (key, value) in env {
+        writeln!(file, "export {key}={}", shell_escape(Cow::Borrowed(value)))
+            .wrap_err("failed to write env file")?;
+    }
+    file.flush().wrap_err("failed to write env file")?;
+    Ok(file.into_temp_path())
 }
 
 static ENV_TOKEN: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"\$\{env:([A-Za-z0-9_]+)\}").unwrap());
-static TEMPLATE_TOKEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\{([^}]+)\}\}").unwrap());
 
 /// Construct a pipeline plan from the provided request and configuration.
 ///
 /// # Errors
 ///
-/// Returns an error when referenced profiles, snippets, or wrappers are missing or
-/// when template rendering fails.
+/// Returns an error when referenced profiles, snippets, or wrappers are missing,
+/// when a declared profile variable is missing or invalid, or when template
+/// rendering fails.
 pub fn build_pipeline(request: &PipelineRequest<'_>) -> Result<PipelinePlan> {
     let config = request.config;
     let profile = resolve_profile(config, request.profile)?;
+    let vars = resolve_vars(profile, &request.vars)?;
     let provider = resolve_provider(config, profile, request.provider_hint)?;
-    let (wrapper, wrap_name) = determine_wrapper(config, request.wrap, profile)?;
+    let (wrappers, wrap_names) = determine_wrappers(config, &request.wrap, profile)?;
+
+    let base_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let cwd = profile
+        .and_then(|profile| profile.cwd.as_deref())
+        .map_or_else(|| request.cwd.clone(), |dir| base_dir.join(dir));
+    let cwd_str = cwd.to_string_lossy().to_string();
+    let mut template_ctx = TemplateContext {
+        pipeline: None,
+        title: None,
+        provider: &provider.name,
+        session_id: request.session.id.as_deref(),
+        session_label: request.session.label.as_deref(),
+        session_path: request.session.path.as_deref(),
+        session_resume_token: request.session.resume_token.as_deref(),
+        session_thread_name: request.session.thread_name.as_deref(),
+        cwd: &cwd_str,
+        vars: &vars,
+    };
 
     let (pre_snippet_names, post_snippet_names) = collect_snippet_names(profile, request);
-    let pre_commands = build_pre_commands(config, &pre_snippet_names, &request.inline_pre)?;
-    let post_commands = resolve_snippets(&config.snippets.post, &post_snippet_names, "post")?;
+    let pre_commands = build_pre_commands(
+        config,
+        &pre_snippet_names,
+        &request.inline_pre,
+        &template_ctx,
+    )?;
+    let post_commands = resolve_snippets(
+        &config.snippets.post,
+        &post_snippet_names,
+        "post",
+        &template_ctx,
+    )?;
 
     let provider_capture = provider
         .stdin
         .as_ref()
         .is_some_and(|stdin| matches!(stdin.mode, StdinMode::CaptureArg));
-    let provider_args = build_provider_args(provider, request, provider_capture);
+    let provider_args = build_provider_args(provider, profile, request, provider_capture);
     let capture_prompt =
         (request.capture_prompt || request.prompt_assembler.is_some()) && provider_capture;
 
@@ -105,69 +304,171 @@ pub fn build_pipeline(request: &PipelineRequest<'_>) -> Result<PipelinePlan> {
         &provider_args,
     );
     let pipeline = stages.join(" | ");
-    let env = render_env(provider)?;
+    let env = render_env(provider, profile, &config.secrets, &base_dir)?;
 
-    let cwd_str = request.cwd.to_string_lossy().to_string();
-    let template_ctx = TemplateContext {
-        pipeline: &pipeline,
-        provider: &provider.name,
-        session_id: request.session.id.as_deref(),
-        session_label: request.session.label.as_deref(),
-        session_path: request.session.path.as_deref(),
-        session_resume_token: request.session.resume_token.as_deref(),
-        cwd: &cwd_str,
-        vars: &request.vars,
-    };
+    template_ctx.pipeline = Some(&pipeline);
 
-    let invocation = match wrapper {
-        Some(wrapper) => render_wrapper(wrapper, &template_ctx)?,
-        None => Invocation::Shell {
-            command: pipeline.clone(),
-        },
-    };
+    let title_template = config
+        .defaults
+        .terminal_title
+        .as_deref()
+        .unwrap_or("{{provider}}");
+    let terminal_title = render_template(title_template, &template_ctx, CmdMode::Raw)
+        .map_err(|err| eyre!("terminal_title: {err}"))?;
 
-    let display = match &invocation {
-        Invocation::Shell { command } => command.clone(),
-        Invocation::Exec { argv } => argv
-            .iter()
-            .map(|arg| shell_escape(Cow::Borrowed(arg)).to_string())
-            .collect::<Vec<_>>()
-            .join(" "),
-    };
+    let (invocation, wrapper_programs) =
+        wrap_pipeline(&wrappers, &pipeline, &terminal_title, &template_ctx)?;
+    let display = invocation.display();
+    let programs = launched_programs(
+        provider,
+        wrapper_programs,
+        pre_commands.iter().chain(&post_commands),
+    );
 
-    let friendly_display = if wrapper.is_none() && capture_prompt {
+    let friendly_display = if wrappers.is_empty() && capture_prompt {
         friendly_capture_display(provider, &pre_commands, &post_commands, &provider_args)
     } else {
         display.clone()
     };
 
-    let title_template = config
-        .defaults
-        .terminal_title
-        .as_deref()
-        .unwrap_or("{{provider}}");
-    let terminal_title = render_template(title_template, &template_ctx, CmdMode::Raw)?;
-
     Ok(PipelinePlan {
         pipeline,
         display,
         friendly_display,
-        env,
+        env: env.env,
+        env_origins: env.origins,
         invocation,
         provider: provider.name.clone(),
+        profile: profile.map(|profile| profile.name.clone()),
+        launch: LaunchOptions::from_request(request),
         terminal_title,
         pre_snippets: pre_snippet_names,
         post_snippets: post_snippet_names,
-        wrapper: wrap_name,
+        wrappers: wrap_names,
         needs_stdin_prompt: false,
         uses_capture_arg: capture_prompt,
         capture_has_pre_commands: capture_prompt && !pre_commands.is_empty(),
         stdin_prompt_label: None,
-        cwd: request.cwd.clone(),
+        cwd,
         prompt_assembler: request.prompt_assembler.clone(),
+        context: None,
+        secret_env: env.secret_env,
+        hooks: collect_hooks(config, provider, profile),
+        session_id: request.session.id.clone(),
+        programs,
+    })
+}
+
+/// Apply `wrappers` around `pipeline`, starting with the last (innermost) one.
+/// Each wrapper's `{{CMD}}` is the command line of the stage it wraps.
+///
+/// Returns the outermost invocation and the programs that exec-mode stages start.
+fn wrap_pipeline(
+    wrappers: &[&WrapperConfig],
+    pipeline: &str,
+    title: &str,
+    ctx: &TemplateContext<'_>,
+) -> Result<(Invocation, Vec<String>)> {
+    let mut invocation = Invocation::Shell {
+        command: pipeline.to_string(),
+    };
+    let mut programs = Vec::new();
+    for (position, wrapper) in wrappers.iter().enumerate().rev() {
+        let inner = invocation.display();
+        let stage_ctx = TemplateContext {
+            pipeline: Some(&inner),
+            title: Some(title),
+            ..*ctx
+        };
+        invocation = render_wrapper(wrapper, &stage_ctx)?;
+        // The env file only exists once tx launches the outermost stage.
+        if position > 0
+            && matches!(
+                invocation,
+                Invocation::Exec {
+                    env_at: Some(_),
+                    ..
+                }
+            )
+        {
+            return Err(eyre!(
+                "wrapper '{}' passes the environment through {ENV_FILE_ARG}, so it must come first in wrap",
+                wrapper.name
+            ));
+        }
+        if let Invocation::Exec { argv, .. } = &invocation
+            && let Some(program) = argv.first()
+        {
+            programs.push(program.clone());
+        }
+    }
+    Ok((invocation, programs))
+}
+
+fn launched_programs<'a>(
+    provider: &ProviderConfig,
+    wrapper_programs: Vec<String>,
+    snippet_commands: impl IntoIterator<Item = &'a String>,
+) -> Vec<String> {
+    let snippet_programs = snippet_commands
+        .into_iter()
+        .filter_map(|command| command_program(command));
+    let mut programs = vec![provider.bin.clone()];
+    for program in wrapper_programs.into_iter().chain(snippet_programs) {
+        if !programs.contains(&program) {
+            programs.push(program);
+        }
+    }
+    programs
+}
+
+const SHELL_BUILTINS: &[&str] = &[
+    "!", ".", ":", "[", "alias", "break", "case", "cd", "command", "continue", "echo", "eval",
+    "exec", "exit", "export", "false", "for", "if", "printf", "pwd", "read", "return", "set",
+    "shift", "source", "test", "trap", "true", "type", "ulimit", "umask", "unset", "until", "wait",
+    "while",
+];
+
+/// The program a shell command line starts, skipping leading `NAME=value`
+/// assignments. Returns `None` for shell builtins and words the shell would
+/// expand, since neither can be looked up ahead of time.
+#[must_use]
+pub fn command_program(command: &str) -> Option<String> {
+    let words = shlex::split(command)?;
+    let word = words.into_iter().find(|word| !is_assignment(word))?;
+    let expands = word.contains(['$', '`', '(', '{', '*', '?', '~']);
+    (!expands && !SHELL_BUILTINS.contains(&word.as_str())).then_some(word)
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
     })
 }
 
+fn collect_hooks(
+    config: &Config,
+    provider: &ProviderConfig,
+    profile: Option<&ProfileConfig>,
+) -> HooksConfig {
+    let mut hooks = HooksConfig::default();
+    for layer in [
+        Some(&config.hooks),
+        Some(&provider.hooks),
+        profile.map(|p| &p.hooks),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        hooks.before.extend(layer.before.iter().cloned());
+        hooks.after.extend(layer.after.iter().cloned());
+        hooks.on_failure.extend(layer.on_failure.iter().cloned());
+    }
+    hooks
+}
+
 fn resolve_profile<'a>(
     config: &'a Config,
     profile_name: Option<&str>,
@@ -182,6 +483,60 @@ fn resolve_profile<'a>(
     }
 }
 
+/// Declared variables of `profile` that still need a value from the user.
+///
+/// Callers prompt for these before [`build_pipeline`], which rejects them.
+#[must_use]
+pub fn missing_vars<'a, S: BuildHasher>(
+    config: &'a Config,
+    profile: Option<&str>,
+    vars: &HashMap<String, String, S>,
+) -> Vec<&'a ProfileVar> {
+    profile
+        .and_then(|name| config.profiles.get(name))
+        .map(|profile| {
+            profile
+                .vars
+                .values()
+                .filter(|var| var.needs_value() && !vars.contains_key(&var.name))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Check supplied variables against the profile's declarations and fill in defaults.
+///
+/// Undeclared variables pass through unchanged. Declared optional variables without
+/// a value render as empty strings.
+fn resolve_vars(
+    profile: Option<&ProfileConfig>,
+    supplied: &HashMap<String, String>,
+) -> Result<HashMap<String, String>> {
+    let mut vars = supplied.clone();
+    let Some(profile) = profile else {
+        return Ok(vars);
+    };
+    for var in profile.vars.values() {
+        let value = match (supplied.get(&var.name), &var.default) {
+            (Some(value), _) => var
+                .validate(value)
+                .wrap_err_with(|| format!("invalid value for profile '{}'", profile.name))?,
+            (None, Some(default)) => default.clone(),
+            (None, None) if var.required => {
+                return Err(eyre!(
+                    "profile '{}' requires variable '{}'; pass --var {}=VALUE",
+                    profile.name,
+                    var.name,
+                    var.name
+                ));
+            }
+            (None, None) => String::new(),
+        };
+        vars.insert(var.name.clone(), value);
+    }
+    Ok(vars)
+}
+
 fn resolve_provider<'a>(
     config: &'a Config,
     profile: Option<&ProfileConfig>,
@@ -199,26 +554,28 @@ fn resolve_provider<'a>(
         .ok_or_else(|| eyre!("provider '{provider_name}' not defined"))
 }
 
-fn determine_wrapper<'a>(
+/// Requested wrappers replace the profile's; both are listed outermost first.
+fn determine_wrappers<'a>(
     config: &'a Config,
-    requested_wrap: Option<&str>,
+    requested_wrap: &[String],
     profile: Option<&ProfileConfig>,
-) -> Result<(Option<&'a WrapperConfig>, Option<String>)> {
-    let wrap_name = requested_wrap
-        .map(str::to_string)
-        .or_else(|| profile.and_then(|p| p.wrap.clone()));
+) -> Result<(Vec<&'a WrapperConfig>, Vec<String>)> {
+    let wrap_names = if requested_wrap.is_empty() {
+        profile.map(|p| p.wrap.clone()).unwrap_or_default()
+    } else {
+        requested_wrap.to_vec()
+    };
 
-    let wrapper = match wrap_name.as_deref() {
-        Some(name) => Some(
+    let wrappers = wrap_names
+        .iter()
+        .map(|name| {
             config
-                .wrappers
-                .get(name)
-                .ok_or_else(|| eyre!("wrapper '{name}' not found"))?,
-        ),
-        None => None,
-    };
+                .wrapper(name)
+                .ok_or_else(|| eyre!("wrapper '{name}' not found"))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    Ok((wrapper, wrap_name))
+    Ok((wrappers, wrap_names))
 }
 
 fn collect_snippet_names(
@@ -238,18 +595,22 @@ fn build_pre_commands(
     config: &Config,
     snippet_names: &[String],
     inline_commands: &[String],
+    ctx: &TemplateContext<'_>,
 ) -> Result<Vec<String>> {
-    let mut commands = resolve_snippets(&config.snippets.pre, snippet_names, "pre")?;
+    let mut commands = resolve_snippets(&config.snippets.pre, snippet_names, "pre", ctx)?;
     commands.extend(inline_commands.iter().cloned());
     Ok(commands)
 }
 
 fn build_provider_args(
     provider: &ProviderConfig,
+    profile: Option<&ProfileConfig>,
     request: &PipelineRequest<'_>,
     provider_capture: bool,
 ) -> Vec<String> {
-    let mut provider_args = provider.flags.clone();
+    let mut provider_args = profile
+        .and_then(|profile| profile.flags.clone())
+        .unwrap_or_else(|| provider.flags.clone());
     if let Some(stdin) = &provider.stdin {
         let wants_capture = request.capture_prompt || request.prompt_assembler.is_some();
         if provider_capture {
@@ -419,34 +780,122 @@ fn prompt_inside_double_quotes(input: &str) -> bool {
 }
 
 fn resolve_snippets(
-    snippets: &indexmap::IndexMap<String, Snippet>,
+    snippets: &IndexMap<String, Snippet>,
     names: &[String],
     kind: &str,
+    ctx: &TemplateContext<'_>,
 ) -> Result<Vec<String>> {
     let mut commands = Vec::new();
     for name in names {
         let snippet = snippets.get(name).ok_or_else(|| {
             eyre!("unknown {kind} snippet '{name}' — define it under [snippets.{kind}] in configuration")
         })?;
-        commands.push(snippet.command.clone());
+        let command = render_template(&snippet.command, ctx, CmdMode::Raw)
+            .map_err(|err| eyre!("{kind} snippet '{name}': {err}"))?;
+        commands.push(command);
     }
     Ok(commands)
 }
 
-fn render_env(provider: &ProviderConfig) -> Result<Vec<(String, String)>> {
-    provider
-        .env
-        .iter()
-        .map(|entry| {
-            let value = expand_env_template(&entry.value_template).with_context(|| {
-                format!(
-                    "while expanding ${} for provider {}",
-                    entry.key, provider.name
-                )
-            })?;
-            Ok((entry.key.clone(), value))
-        })
-        .collect()
+/// The layered provider environment for a [`PipelinePlan`].
+#[derive(Debug)]
+struct PlanEnv {
+    env: Vec<(String, String)>,
+    origins: Vec<String>,
+    secret_env: Vec<SecretEnv>,
+}
+
+/// Layer the provider's `env_file` and `env`, then the profile's. Later layers
+/// override earlier keys. `${env:VAR}` is expanded; `${secret:NAME}` and dotenv
+/// values stay masked.
+fn render_env(
+    provider: &ProviderConfig,
+    profile: Option<&ProfileConfig>,
+    secrets: &IndexMap<String, SecretConfig>,
+    base_dir: &Path,
+) -> Result<PlanEnv> {
+    let mut layers: IndexMap<String, (Vec<EnvPart>, String)> = IndexMap::new();
+    load_env_files(&mut layers, &provider.env_file, base_dir)?;
+    expand_env_entries(
+        &mut layers,
+        &provider.env,
+        &format!("provider {}", provider.name),
+        secrets,
+    )?;
+    if let Some(profile) = profile {
+        load_env_files(&mut layers, &profile.env_file, base_dir)?;
+        expand_env_entries(
+            &mut layers,
+            &profile.env,
+            &format!("profile {}", profile.name),
+            secrets,
+        )?;
+    }
+
+    let mut plan_env = PlanEnv {
+        env: Vec::new(),
+        origins: Vec::new(),
+        secret_env: Vec::new(),
+    };
+    for (key, (parts, origin)) in layers {
+        let pending = SecretEnv {
+            index: plan_env.env.len(),
+            parts,
+        };
+        plan_env.env.push((key, pending.masked()));
+        plan_env.origins.push(origin);
+        if pending
+            .parts
+            .iter()
+            .any(|part| !matches!(part, EnvPart::Text(_)))
+        {
+            plan_env.secret_env.push(pending);
+        }
+    }
+    Ok(plan_env)
+}
+
+fn load_env_files(
+    layers: &mut IndexMap<String, (Vec<EnvPart>, String)>,
+    files: &[PathBuf],
+    base_dir: &Path,
+) -> Result<()> {
+    for file in files {
+        let path = base_dir.join(file);
+        let Some(entries) = dotenv::load(&path)? else {
+            continue;
+        };
+        let origin = format!("env_file {}", path.display());
+        // Only the location is kept; the value is read again at launch so
+        // plans never carry it.
+        for (key, _) in entries {
+            let part = EnvPart::Dotenv {
+                path: path.clone(),
+                key: key.clone(),
+            };
+            layers.insert(key, (vec![part], origin.clone()));
+        }
+    }
+    Ok(())
+}
+
+fn expand_env_entries(
+    layers: &mut IndexMap<String, (Vec<EnvPart>, String)>,
+    entries: &[EnvVar],
+    origin: &str,
+    secrets: &IndexMap<String, SecretConfig>,
+) -> Result<()> {
+    for entry in entries {
+        let context = || format!("while expanding ${} for {origin}", entry.key);
+        let mut parts = secrets::split(&entry.value_template, secrets).with_context(context)?;
+        for part in &mut parts {
+            if let EnvPart::Text(text) = part {
+                *text = expand_env_template(text).with_context(context)?;
+            }
+        }
+        layers.insert(entry.key.clone(), (parts, origin.to_string()));
+    }
+    Ok(())
 }
 
 fn build_capture_command(
@@ -497,91 +946,40 @@ fn expand_env_template(template: &str) -> Result<String> {
     Ok(result)
 }
 
-#[derive(Debug)]
-struct TemplateContext<'a> {
-    pipeline: &'a str,
-    provider: &'a str,
-    session_id: Option<&'a str>,
-    session_label: Option<&'a str>,
-    session_path: Option<&'a str>,
-    session_resume_token: Option<&'a str>,
-    cwd: &'a str,
-    vars: &'a HashMap<String, String>,
-}
-
-#[derive(Debug, Clone, Copy)]
-enum CmdMode {
-    Raw,
-    Shell,
-}
-
 fn render_wrapper(wrapper: &WrapperConfig, ctx: &TemplateContext<'_>) -> Result<Invocation> {
+    let render = |template: &str, mode| {
+        render_template(template, ctx, mode)
+            .map_err(|err| eyre!("wrapper '{}': {err}", wrapper.name))
+    };
     match &wrapper.mode {
         WrapperMode::Shell { command } => {
-            let rendered = render_template(command, ctx, CmdMode::Shell)?;
+            let rendered = render(command, CmdMode::Shell)?;
             Ok(Invocation::Shell { command: rendered })
         }
         WrapperMode::Exec { argv } => {
-            let rendered = argv
-                .iter()
-                .map(|arg| render_template(arg, ctx, CmdMode::Raw))
-                .collect::<Result<Vec<_>>>()?;
-            Ok(Invocation::Exec { argv: rendered })
-        }
-    }
-}
-
-fn render_template(input: &str, ctx: &TemplateContext<'_>, mode: CmdMode) -> Result<String> {
-    let mut out = String::new();
-    let mut last = 0;
-    for caps in TEMPLATE_TOKEN.captures_iter(input) {
-        let mat = caps.get(0).unwrap();
-        out.push_str(&input[last..mat.start()]);
-        let key = caps.get(1).unwrap().as_str();
-        let replacement = resolve_placeholder(key, ctx, mode)?;
-        out.push_str(&replacement);
-        last = mat.end();
-    }
-    out.push_str(&input[last..]);
-    Ok(out)
-}
-
-fn resolve_placeholder(key: &str, ctx: &TemplateContext<'_>, mode: CmdMode) -> Result<String> {
-    match key {
-        "CMD" => Ok(match mode {
-            CmdMode::Raw => ctx.pipeline.to_string(),
-            CmdMode::Shell => single_quote(ctx.pipeline),
-        }),
-        "provider" => Ok(ctx.provider.to_string()),
-        "session.id" => Ok(ctx.session_id.unwrap_or("").to_string()),
-        "session.label" => Ok(ctx.session_label.unwrap_or("").to_string()),
-        "session.path" => Ok(ctx.session_path.unwrap_or("").to_string()),
-        "session.resume_token" => Ok(ctx.session_resume_token.unwrap_or("").to_string()),
-        "cwd" => Ok(ctx.cwd.to_string()),
-        other if other.starts_with("var:") => {
-            let name = &other[4..];
-            let value = ctx
-                .vars
-                .get(name)
-                .ok_or_else(|| eyre!("missing value for variable '{name}'"))?;
-            Ok(value.clone())
+            let mut rendered = Vec::with_capacity(argv.len());
+            let mut env_at = None;
+            for arg in argv {
+                if arg == ENV_FILE_ARG {
+                    env_at = Some(rendered.len());
+                } else {
+                    rendered.push(render(arg, CmdMode::Raw)?);
+                }
+            }
+            Ok(Invocation::Exec {
+                argv: rendered,
+                env_at,
+            })
         }
-        other => Err(eyre!("unknown template placeholder '{{{{{other}}}}}'")),
     }
 }
 
-fn single_quote(input: &str) -> String {
-    let mut quoted = String::with_capacity(input.len() + 2);
-    quoted.push('\'');
-    for ch in input.chars() {
-        if ch == '\'' {
-            quoted.push_str("'\\''");
-        } else {
-            quoted.push(ch);
-        }
-    }
-    quoted.push('\'');
-    quoted
+fn render_template(
+    input: &str,
+    ctx: &TemplateContext<'_>,
+    mode: CmdMode,
+) -> std::result::Result<String, TemplateError> {
+    Template::parse(input)?.render(ctx, mode)
 }
 
 fn command_string(bin: &str, args: &[String]) -> String {
@@ -597,9 +995,12 @@ fn command_string(bin: &str, args: &[String]) -> String {
 mod tests {
     use super::*;
     use crate::config::model::{
-        Defaults, EnvVar, FeatureConfig, ProviderConfig, SearchMode, SnippetConfig, StdinMapping,
+        Defaults, EnvVar, FeatureConfig, ProviderConfig, RagConfig, RetentionConfig,
+        SOURCE_ENV_FILE, SearchFormat, SearchMode, SnippetConfig, StdinMapping, VarKind,
         WrapperConfig, WrapperMode,
     };
+    use crate::secrets::MASK;
+    use crate::template::single_quote;
     use indexmap::IndexMap;
     use std::collections::HashMap;
 
@@ -613,6 +1014,8 @@ mod tests {
                 bin: "codex".into(),
                 flags: Vec::new(),
                 env: Vec::new(),
+                env_file: Vec::new(),
+                hooks: HooksConfig::default(),
                 session_roots: Vec::new(),
                 stdin: None,
             },
@@ -623,6 +1026,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -634,7 +1038,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let request = PipelineRequest {
@@ -644,7 +1052,7 @@ mod tests {
             additional_pre: Vec::new(),
             additional_post: Vec::new(),
             inline_pre: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             provider_args: Vec::new(),
             capture_prompt: false,
             prompt_assembler: None,
@@ -668,6 +1076,8 @@ mod tests {
                 bin: "codex".into(),
                 flags: Vec::new(),
                 env: Vec::new(),
+                env_file: Vec::new(),
+                hooks: HooksConfig::default(),
                 session_roots: Vec::new(),
                 stdin: None,
             },
@@ -678,6 +1088,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: Some(
                     "provider={{provider}} session=[{{session.id}}] cwd={{cwd}}".into(),
                 ),
@@ -691,7 +1102,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let request = PipelineRequest {
@@ -701,7 +1116,7 @@ mod tests {
             additional_pre: Vec::new(),
             additional_post: Vec::new(),
             inline_pre: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             provider_args: Vec::new(),
             capture_prompt: false,
             prompt_assembler: None,
@@ -725,6 +1140,8 @@ mod tests {
                 bin: "codex".into(),
                 flags: vec!["--search".into()],
                 env: Vec::new(),
+                env_file: Vec::new(),
+                hooks: HooksConfig::default(),
                 session_roots: Vec::new(),
                 stdin: Some(StdinMapping {
                     args: vec!["{prompt}".into()],
@@ -738,6 +1155,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -749,7 +1167,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let request = PipelineRequest {
@@ -759,7 +1181,7 @@ mod tests {
             additional_pre: Vec::new(),
             additional_post: Vec::new(),
             inline_pre: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             provider_args: Vec::new(),
             capture_prompt: false,
             prompt_assembler: Some(PromptInvocation {
@@ -788,6 +1210,8 @@ mod tests {
                 bin: "codex".into(),
                 flags: vec!["--search".into()],
                 env: Vec::new(),
+                env_file: Vec::new(),
+                hooks: HooksConfig::default(),
                 session_roots: Vec::new(),
                 stdin: Some(StdinMapping {
                     args: vec!["{prompt}".into()],
@@ -801,6 +1225,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -812,7 +1237,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let request = PipelineRequest {
@@ -822,7 +1251,7 @@ mod tests {
             additional_pre: Vec::new(),
             additional_post: Vec::new(),
             inline_pre: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             provider_args: Vec::new(),
             capture_prompt: false,
             prompt_assembler: None,
@@ -849,6 +1278,8 @@ mod tests {
                 bin: "codex".into(),
                 flags: vec!["--search".into()],
                 env: Vec::new(),
+                env_file: Vec::new(),
+                hooks: HooksConfig::default(),
                 session_roots: Vec::new(),
                 stdin: Some(StdinMapping {
                     args: vec!["{prompt}".into()],
@@ -862,6 +1293,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -873,7 +1305,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let request = PipelineRequest {
@@ -883,7 +1319,7 @@ mod tests {
             additional_pre: Vec::new(),
             additional_post: Vec::new(),
             inline_pre: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             provider_args: Vec::new(),
             capture_prompt: true,
             prompt_assembler: None,
@@ -905,6 +1341,8 @@ mod tests {
             bin: "codex".into(),
             flags: Vec::new(),
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: Vec::new(),
             stdin: Some(StdinMapping {
                 args: vec!["{prompt}".into()],
@@ -946,12 +1384,14 @@ mod tests {
             },
         };
         let ctx = TemplateContext {
-            pipeline: "echo hi",
+            pipeline: Some("echo hi"),
+            title: None,
             provider: "codex",
             session_id: None,
             session_label: None,
             session_path: None,
             session_resume_token: None,
+            session_thread_name: None,
             cwd: ".",
             vars: &HashMap::new(),
         };
@@ -963,18 +1403,20 @@ mod tests {
     }
 
     #[test]
-    fn resolve_placeholder_errors_on_missing_variable() {
+    fn render_template_errors_on_missing_variable() {
         let ctx = TemplateContext {
-            pipeline: "pipeline",
+            pipeline: Some("pipeline"),
+            title: None,
             provider: "codex",
             session_id: None,
             session_label: None,
             session_path: None,
             session_resume_token: None,
+            session_thread_name: None,
             cwd: ".",
             vars: &HashMap::new(),
         };
-        let err = resolve_placeholder("var:MISSING", &ctx, CmdMode::Raw).unwrap_err();
+        let err = render_template("{{var:MISSING}}", &ctx, CmdMode::Raw).unwrap_err();
         assert!(err.to_string().contains("missing value"));
     }
 
@@ -985,6 +1427,8 @@ mod tests {
             bin: "codex".into(),
             flags: vec!["--flag".into()],
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: Vec::new(),
             stdin: Some(StdinMapping {
                 args: vec!["--stdin".into()],
@@ -997,6 +1441,7 @@ mod tests {
                     provider: Some("codex".into()),
                     profile: None,
                     search_mode: SearchMode::FirstPrompt,
+                    search_format: SearchFormat::Json,
                     terminal_title: None,
                 },
                 providers: IndexMap::new(),
@@ -1008,14 +1453,18 @@ mod tests {
                 profiles: IndexMap::new(),
                 features: FeatureConfig {
                     prompt_assembler: None,
+                    rag: RagConfig::default(),
                 },
+                retention: RetentionConfig::default(),
+                secrets: IndexMap::new(),
+                hooks: HooksConfig::default(),
             },
             provider_hint: Some("codex"),
             profile: None,
             additional_pre: Vec::new(),
             additional_post: Vec::new(),
             inline_pre: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             provider_args: vec!["--extra".into()],
             capture_prompt: false,
             prompt_assembler: None,
@@ -1023,7 +1472,7 @@ mod tests {
             session: SessionContext::default(),
             cwd: std::env::current_dir().unwrap(),
         };
-        let args = build_provider_args(&provider, &request, false);
+        let args = build_provider_args(&provider, None, &request, false);
         assert!(args.contains(&"--stdin".to_string()));
     }
 
@@ -1040,26 +1489,83 @@ mod tests {
         assert_eq!(single_quote("plain"), "'plain'");
     }
 
+    fn snippet_ctx(vars: &HashMap<String, String>) -> TemplateContext<'_> {
+        TemplateContext {
+            pipeline: None,
+            title: None,
+            provider: "codex",
+            session_id: Some("sess-1"),
+            session_label: None,
+            session_path: None,
+            session_resume_token: None,
+            session_thread_name: Some("Fix Login Flow"),
+            cwd: "/tmp/project",
+            vars,
+        }
+    }
+
     #[test]
     fn resolve_snippets_errors_for_missing_name() {
         let snippets: IndexMap<String, Snippet> = IndexMap::new();
-        let err = resolve_snippets(&snippets, &["missing".into()], "pre").unwrap_err();
+        let vars = HashMap::new();
+        let err = resolve_snippets(&snippets, &["missing".into()], "pre", &snippet_ctx(&vars))
+            .unwrap_err();
         let message = err.to_string();
         assert!(message.contains("unknown pre snippet 'missing'"));
     }
 
+    #[test]
+    fn resolve_snippets_renders_templates() {
+        let mut snippets: IndexMap<String, Snippet> = IndexMap::new();
+        for (name, command) in [
+            ("notes", "cat notes/{{thread_name | kebab}}.md"),
+            (
+                "tag",
+                "{{#if var:tag}}echo {{var:tag | shell_quote}}{{else}}true{{/if}}",
+            ),
+            ("wrapped", "echo {{CMD}}"),
+        ] {
+            snippets.insert(
+                name.into(),
+                Snippet {
+                    name: name.into(),
+                    command: command.into(),
+                },
+            );
+        }
+        let mut vars = HashMap::new();
+        let ctx = snippet_ctx(&vars);
+        let commands = resolve_snippets(&snippets, &["notes".into(), "tag".into()], "pre", &ctx)
+            .expect("render snippets");
+        assert_eq!(commands, ["cat notes/fix-login-flow.md", "true"]);
+
+        vars.insert("tag".into(), "it's".into());
+        let ctx = snippet_ctx(&vars);
+        let commands =
+            resolve_snippets(&snippets, &["tag".into()], "post", &ctx).expect("render snippet");
+        assert_eq!(commands, ["echo 'it'\\''s'"]);
+
+        let err = resolve_snippets(&snippets, &["wrapped".into()], "post", &ctx).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "post snippet 'wrapped': line 1, column 8: {{CMD}} is only available in wrappers and terminal titles"
+        );
+    }
+
     #[test]
     fn render_wrapper_handles_shell_and_exec_modes() {
         let mut vars = HashMap::new();
         vars.insert("token".to_string(), "abc123".to_string());
 
         let ctx = TemplateContext {
-            pipeline: "ls | cat",
+            pipeline: Some("ls | cat"),
+            title: None,
             provider: "codex",
             session_id: Some("sess-1"),
             session_label: Some("Demo"),
             session_path: Some("/tmp/sess-1"),
             session_resume_token: Some("resume-1"),
+            session_thread_name: None,
             cwd: "/tmp/project",
             vars: &vars,
         };
@@ -1092,7 +1598,7 @@ mod tests {
         let invocation = render_wrapper(&exec_wrapper, &ctx).expect("exec wrapper");
         assert!(matches!(
             invocation,
-            Invocation::Exec { ref argv }
+            Invocation::Exec { ref argv, .. }
                 if argv.len() == 5
                     && argv[0] == "exec"
                     && argv[1] == "ls | cat"
@@ -1133,6 +1639,7 @@ mod tests {
                 provider: Some("codex".into()),
                 profile: None,
                 search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
                 terminal_title: None,
             },
             providers,
@@ -1144,7 +1651,11 @@ mod tests {
             profiles: IndexMap::new(),
             features: FeatureConfig {
                 prompt_assembler: None,
+                rag: RagConfig::default(),
             },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
         };
 
         let request = PipelineRequest {
@@ -1154,7 +1665,7 @@ mod tests {
             additional_pre: Vec::new(),
             additional_post: Vec::new(),
             inline_pre: Vec::new(),
-            wrap: Some("exec-wrap"),
+            wrap: vec!["exec-wrap".into()],
             provider_args: Vec::new(),
             capture_prompt: false,
             prompt_assembler: None,
@@ -1170,6 +1681,323 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn build_pipeline_chains_wrappers_outermost_first() -> Result<()> {
+        let mut config = ticketed_config();
+        config.wrappers.insert(
+            "logged".into(),
+            WrapperConfig {
+                name: "logged".into(),
+                mode: WrapperMode::Shell {
+                    command: "script -q {{CMD}}".into(),
+                },
+            },
+        );
+
+        let mut request = PipelineRequest {
+            config: &config,
+            provider_hint: Some("codex"),
+            profile: None,
+            additional_pre: Vec::new(),
+            additional_post: Vec::new(),
+            inline_pre: Vec::new(),
+            wrap: vec!["tmux".into(), "logged".into()],
+            provider_args: Vec::new(),
+            capture_prompt: false,
+            prompt_assembler: None,
+            vars: HashMap::new(),
+            session: SessionContext::default(),
+            cwd: PathBuf::from("/tmp/project"),
+        };
+        let plan = build_pipeline(&request)?;
+
+        assert_eq!(plan.wrappers, ["tmux", "logged"]);
+        assert!(matches!(
+            plan.invocation,
+            Invocation::Exec { ref argv, env_at: Some(10) }
+                if argv == &[
+                    "tmux",
+                    "new-window",
+                    "-n",
+                    "codex",
+                    "-c",
+                    "/tmp/project",
+                    "sh",
+                    "-c",
+                    SOURCE_ENV_FILE,
+                    "tx-env",
+                    "script -q 'codex --search'",
+                ]
+        ));
+        assert!(plan.display.contains("tx-env '{{ENV_FILE}}' 'script -q"));
+        assert_eq!(plan.programs, ["codex", "tmux"]);
+
+        // Only the stage tx starts can receive the env file.
+        request.wrap = vec!["logged".into(), "tmux".into()];
+        let err = build_pipeline(&request).unwrap_err();
+        assert!(err.to_string().contains("'tmux'"), "{err}");
+        assert!(err.to_string().contains("must come first in wrap"), "{err}");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn env_file_hands_the_environment_over_without_argv() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let env = vec![
+            ("API_KEY".to_string(), "s3cret 'quoted' $HOME".to_string()),
+            (
+                "TX_CAPTURE_STDIN_DATA".to_string(),
+                "fix the bug".to_string(),
+            ),
+        ];
+        let env_file = write_env_file(&env)?;
+        assert_eq!(
+            std::fs::metadata(&env_file)?.permissions().mode() & 0o777,
+            0o600
+        );
+
+        let wrapper: Vec<String> = ["sh", "-c", SOURCE_ENV_FILE, "tx-env"]
+            .map(String::from)
+            .into();
+        let temp = tempfile::TempDir::new()?;
+        let seen = temp.path().join("seen");
+        let mut argv = launch_argv(&wrapper, Some(4), Some(&env_file));
+        argv.push(format!(
+            r#"printf '%s|%s' "$API_KEY" "$TX_CAPTURE_STDIN_DATA" > {}"#,
+            seen.display()
+        ));
+        assert!(
+            argv.iter()
+                .all(|arg| !arg.contains("s3cret") && !arg.contains("fix the bug")),
+            "{argv:?}"
+        );
+        assert_eq!(launch_argv(&wrapper, None, Some(&env_file)), wrapper);
+
+        let path = env_file.keep()?;
+        let status = std::process::Command::new(&argv[0])
+            .args(&argv[1..])
+            .env_remove("API_KEY")
+            .status()?;
+        assert!(status.success());
+        assert_eq!(
+            std::fs::read_to_string(&seen)?,
+            "s3cret 'quoted' $HOME|fix the bug"
+        );
+        assert!(!path.exists(), "the wrapped shell deletes the env file");
+        Ok(())
+    }
+
+    #[test]
+    fn plans_with_a_single_wrapper_name_still_decode() -> Result<()> {
+        let config = ticketed_config();
+        let plan = build_pipeline(&PipelineRequest {
+            config: &config,
+            provider_hint: Some("codex"),
+            profile: None,
+            additional_pre: Vec::new(),
+            additional_post: Vec::new(),
+            inline_pre: Vec::new(),
+            wrap: Vec::new(),
+            provider_args: Vec::new(),
+            capture_prompt: false,
+            prompt_assembler: None,
+            vars: HashMap::new(),
+            session: SessionContext::default(),
+            cwd: PathBuf::from("/tmp/project"),
+        })?;
+        let mut plan = serde_json::to_value(plan)?;
+        let fields = plan.as_object_mut().expect("plan is an object");
+        fields.remove("wrappers");
+        fields.insert("wrapper".into(), serde_json::json!("tmux"));
+        fields.insert(
+            "launch".into(),
+            serde_json::json!({ "pre": [], "post": [], "wrap": null, "vars": {} }),
+        );
+        let plan: PipelinePlan = serde_json::from_value(plan)?;
+        assert_eq!(plan.wrappers, ["tmux"]);
+        assert!(plan.launch.wrap.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn build_pipeline_lists_snippet_programs() -> Result<()> {
+        let config = ticketed_config();
+        let plan = build_pipeline(&PipelineRequest {
+            config: &config,
+            provider_hint: Some("codex"),
+            profile: None,
+            additional_pre: Vec::new(),
+            additional_post: Vec::new(),
+            inline_pre: vec!["LC_ALL=C sort -u notes.txt".into(), "echo hi".into()],
+            wrap: Vec::new(),
+            provider_args: Vec::new(),
+            capture_prompt: false,
+            prompt_assembler: None,
+            vars: HashMap::new(),
+            session: SessionContext::default(),
+            cwd: PathBuf::from("/tmp/project"),
+        })?;
+        assert_eq!(plan.programs, ["codex", "sort"]);
+        Ok(())
+    }
+
+    #[test]
+    fn command_program_skips_assignments_builtins_and_expansions() {
+        assert_eq!(
+            command_program("FOO=1 ./run.sh --fast").as_deref(),
+            Some("./run.sh")
+        );
+        assert_eq!(
+            command_program("'my tool' | tee out").as_deref(),
+            Some("my tool")
+        );
+        assert_eq!(command_program("cd /tmp && make"), None);
+        assert_eq!(command_program("$EDITOR notes.md"), None);
+        assert_eq!(command_program("echo 'unbalanced"), None);
+    }
+
+    fn ticketed_config() -> Config {
+        let mut providers = IndexMap::new();
+        providers.insert("codex".into(), test_provider_config());
+
+        let mut wrappers = IndexMap::new();
+        wrappers.insert(
+            "tagged".into(),
+            WrapperConfig {
+                name: "tagged".into(),
+                mode: WrapperMode::Exec {
+                    argv: vec![
+                        "{{var:ticket}}".into(),
+                        "{{var:effort}}".into(),
+                        "{{var:dry}}".into(),
+                        "[{{var:note}}]".into(),
+                    ],
+                },
+            },
+        );
+
+        let var = |name: &str, kind: VarKind| ProfileVar {
+            name: name.into(),
+            kind,
+            default: None,
+            description: None,
+            choices: Vec::new(),
+            required: false,
+        };
+        let mut vars = IndexMap::new();
+        vars.insert(
+            "ticket".into(),
+            ProfileVar {
+                required: true,
+                ..var("ticket", VarKind::Integer)
+            },
+        );
+        vars.insert(
+            "effort".into(),
+            ProfileVar {
+                default: Some("low".into()),
+                choices: vec!["low".into(), "high".into()],
+                ..var("effort", VarKind::String)
+            },
+        );
+        vars.insert("dry".into(), var("dry", VarKind::Boolean));
+        vars.insert("note".into(), var("note", VarKind::String));
+
+        let mut profiles = IndexMap::new();
+        profiles.insert(
+            "ticketed".into(),
+            ProfileConfig {
+                name: "ticketed".into(),
+                extends: None,
+                provider: "codex".into(),
+                description: None,
+                pre: Vec::new(),
+                post: Vec::new(),
+                wrap: vec!["tagged".into()],
+                prompt_assembler: None,
+                prompt_assembler_args: Vec::new(),
+                vars,
+                flags: None,
+                env: Vec::new(),
+                env_file: Vec::new(),
+                cwd: None,
+                hooks: HooksConfig::default(),
+            },
+        );
+
+        Config {
+            defaults: Defaults {
+                provider: Some("codex".into()),
+                profile: None,
+                search_mode: SearchMode::FirstPrompt,
+                search_format: SearchFormat::Json,
+                terminal_title: None,
+            },
+            providers,
+            snippets: SnippetConfig {
+                pre: IndexMap::new(),
+                post: IndexMap::new(),
+            },
+            wrappers,
+            profiles,
+            features: FeatureConfig {
+                prompt_assembler: None,
+                rag: RagConfig::default(),
+            },
+            retention: RetentionConfig::default(),
+            secrets: IndexMap::new(),
+            hooks: HooksConfig::default(),
+        }
+    }
+
+    #[test]
+    fn build_pipeline_validates_and_defaults_profile_vars() -> Result<()> {
+        let config = ticketed_config();
+
+        let request_with = |pairs: &[(&str, &str)]| PipelineRequest {
+            config: &config,
+            provider_hint: None,
+            profile: Some("ticketed"),
+            additional_pre: Vec::new(),
+            additional_post: Vec::new(),
+            inline_pre: Vec::new(),
+            wrap: Vec::new(),
+            provider_args: Vec::new(),
+            capture_prompt: false,
+            prompt_assembler: None,
+            vars: pairs
+                .iter()
+                .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+                .collect(),
+            session: SessionContext::default(),
+            cwd: PathBuf::from("/tmp"),
+        };
+
+        let missing = missing_vars(&config, Some("ticketed"), &HashMap::new());
+        assert_eq!(
+            missing
+                .iter()
+                .map(|var| var.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["ticket"]
+        );
+        let err = build_pipeline(&request_with(&[])).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("profile 'ticketed' requires variable 'ticket'")
+        );
+        let err = build_pipeline(&request_with(&[("ticket", "abc")])).unwrap_err();
+        assert!(format!("{err:#}").contains("expects an integer"));
+        let err = build_pipeline(&request_with(&[("ticket", "7"), ("effort", "max")])).unwrap_err();
+        assert!(format!("{err:#}").contains("must be one of low, high"));
+
+        let plan = build_pipeline(&request_with(&[("ticket", " 42"), ("dry", "yes")]))?;
+        assert_eq!(plan.display, "42 low true '[]'");
+        Ok(())
+    }
+
     #[test]
     fn expand_env_template_and_render_env_paths() {
         let home_key = "PIPELINE_TEST_HOME";
@@ -1198,7 +2026,10 @@ mod tests {
             },
         ];
 
-        let pairs = render_env(&provider).expect("render env entries");
+        let rendered = render_env(&provider, None, &IndexMap::new(), Path::new("/"))
+            .expect("render env entries");
+        assert!(rendered.secret_env.is_empty());
+        let pairs = rendered.env;
         assert_eq!(
             pairs,
             vec![
@@ -1210,7 +2041,8 @@ mod tests {
         unsafe {
             std::env::remove_var(token_key);
         }
-        let error = render_env(&provider).expect_err("missing env should error");
+        let error = render_env(&provider, None, &IndexMap::new(), Path::new("/"))
+            .expect_err("missing env should error");
         let message = format!("{error:?}");
         assert!(message.contains("while expanding $TOKEN"));
 
@@ -1259,12 +2091,144 @@ mod tests {
         assert_eq!(resolve_tx_path(None), "tx");
     }
 
+    #[test]
+    fn build_pipeline_layers_env_files_and_profile_overrides() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let provider_file = temp.path().join("provider.env");
+        let profile_file = temp.path().join("profile.env");
+        std::fs::write(&provider_file, "SHARED=provider-file\nBASE=base\n")?;
+        std::fs::write(&profile_file, "SHARED=profile-file\nFILE_ONLY=1\n")?;
+
+        let mut config = ticketed_config();
+        let provider = config.providers.get_mut("codex").expect("codex provider");
+        provider.env_file = vec![provider_file.clone(), temp.path().join("missing.env")];
+        provider.env = vec![EnvVar {
+            key: "BASE".into(),
+            value_template: "provider-env".into(),
+        }];
+        let profile = config
+            .profiles
+            .get_mut("ticketed")
+            .expect("ticketed profile");
+        profile.wrap = Vec::new();
+        profile.flags = Some(vec!["--model".into(), "fast".into()]);
+        profile.env_file = vec![profile_file.clone()];
+        profile.env = vec![EnvVar {
+            key: "FILE_ONLY".into(),
+            value_template: "profile-env".into(),
+        }];
+        profile.cwd = Some(temp.path().join("work"));
+
+        let mut vars = HashMap::new();
+        vars.insert("ticket".to_string(), "7".to_string());
+        let plan = build_pipeline(&PipelineRequest {
+            config: &config,
+            provider_hint: None,
+            profile: Some("ticketed"),
+            additional_pre: Vec::new(),
+            additional_post: Vec::new(),
+            inline_pre: Vec::new(),
+            wrap: Vec::new(),
+            provider_args: vec!["resume".into()],
+            capture_prompt: false,
+            prompt_assembler: None,
+            vars,
+            session: SessionContext::default(),
+            cwd: PathBuf::from("/tmp"),
+        })?;
+
+        let profile_origin = format!("env_file {}", profile_file.display());
+        let layered: Vec<_> = plan
+            .env
+            .iter()
+            .zip(&plan.env_origins)
+            .map(|((key, value), origin)| (key.as_str(), value.as_str(), origin.as_str()))
+            .collect();
+        assert_eq!(
+            layered,
+            vec![
+                ("SHARED", MASK, profile_origin.as_str()),
+                ("BASE", "provider-env", "provider codex"),
+                ("FILE_ONLY", "profile-env", "profile ticketed"),
+            ]
+        );
+        assert!(
+            !serde_json::to_string(&plan)?.contains("profile-file"),
+            "dotenv values stay out of recorded plans"
+        );
+        let launch = plan.launch_env()?;
+        assert_eq!(launch[0], ("SHARED".into(), "profile-file".into()));
+        assert_eq!(plan.pipeline, "codex --model fast resume");
+        assert_eq!(plan.cwd, temp.path().join("work"));
+        Ok(())
+    }
+
+    #[test]
+    fn build_pipeline_orders_hooks_global_provider_profile() -> Result<()> {
+        let hooks = |stage: &str| HooksConfig {
+            before: vec![format!("{stage}-before")],
+            after: vec![format!("{stage}-after")],
+            on_failure: vec![format!("{stage}-failure")],
+        };
+        let mut config = ticketed_config();
+        config.hooks = hooks("global");
+        config
+            .providers
+            .get_mut("codex")
+            .expect("codex provider")
+            .hooks = hooks("provider");
+        let profile = config
+            .profiles
+            .get_mut("ticketed")
+            .expect("ticketed profile");
+        profile.wrap = Vec::new();
+        profile.hooks = hooks("profile");
+
+        let mut vars = HashMap::new();
+        vars.insert("ticket".to_string(), "7".to_string());
+        let plan = build_pipeline(&PipelineRequest {
+            config: &config,
+            provider_hint: None,
+            profile: Some("ticketed"),
+            additional_pre: Vec::new(),
+            additional_post: Vec::new(),
+            inline_pre: Vec::new(),
+            wrap: Vec::new(),
+            provider_args: Vec::new(),
+            capture_prompt: false,
+            prompt_assembler: None,
+            vars,
+            session: SessionContext {
+                id: Some("sess-1".into()),
+                ..SessionContext::default()
+            },
+            cwd: PathBuf::from("/tmp"),
+        })?;
+
+        assert_eq!(
+            plan.hooks.before,
+            ["global-before", "provider-before", "profile-before"]
+        );
+        assert_eq!(
+            plan.hooks.after,
+            ["global-after", "provider-after", "profile-after"]
+        );
+        assert_eq!(
+            plan.hooks.on_failure,
+            ["global-failure", "provider-failure", "profile-failure"]
+        );
+        assert_eq!(plan.session_id.as_deref(), Some("sess-1"));
+        Ok(())
+    }
+
     fn test_provider_config() -> ProviderConfig {
         ProviderConfig {
             name: "codex".into(),
             bin: "codex".into(),
             flags: vec!["--search".into()],
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: Vec::new(),
             stdin: None,
         }
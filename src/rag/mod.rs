@@ -13,14 +13,35 @@ use serde::Deserialize;
 #[cfg(not(coverage))]
 use serde_json::json;
 
-use crate::db::{Database, RagChunkRecord, RagSearchFilters, RagSearchHit, RagSourceMessage};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::config::model::RagConfig;
+use crate::db::{
+    Database, RagAutoIndexRun, RagChunkRecord, RagIndexedChunk, RagSearchFilters, RagSearchHit,
+    RagSourceMessage,
+};
+use crate::util::unix_timestamp;
+
+mod topics;
+
+pub use topics::{TopicCluster, TopicMember, TopicReport, topic_clusters};
+
+/// Vector length produced by the default `text-embedding-3-small` model.
 pub const EMBEDDING_DIM: usize = 1536;
 
 const DEFAULT_OPENAI_MODEL: &str = "text-embedding-3-small";
 const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
 const OPENAI_EMBED_BATCH: usize = 64;
 const MAX_RETRIES: usize = 3;
+const CHARS_PER_TOKEN: usize = 4;
+/// Upper bound on `k` accepted by sqlite-vec KNN queries.
+const MAX_KNN: usize = 4096;
+/// Chunks fetched per requested related session, so near-duplicates of one
+/// session do not crowd out the rest.
+const RELATED_OVERFETCH: usize = 16;
 
 #[derive(Debug, Clone)]
 pub struct RagIndexOptions {
@@ -28,6 +49,38 @@ pub struct RagIndexOptions {
     pub since_ts_ms: Option<i64>,
     pub reindex: bool,
     pub batch_size: usize,
+    pub chunking: ChunkingOptions,
+    /// Embed at most this many pending chunks and leave the rest for later runs.
+    pub max_chunks: Option<usize>,
+}
+
+/// Sliding-window sizes used when splitting long messages into chunks.
+///
+/// Token counts are estimated at roughly four characters per token so chunking
+/// stays independent of any particular embedding model's tokenizer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkingOptions {
+    pub max_tokens: usize,
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkingOptions {
+    fn default() -> Self {
+        let defaults = RagConfig::default();
+        Self {
+            max_tokens: defaults.chunk_tokens,
+            overlap_tokens: defaults.chunk_overlap,
+        }
+    }
+}
+
+impl From<RagConfig> for ChunkingOptions {
+    fn from(config: RagConfig) -> Self {
+        Self {
+            max_tokens: config.chunk_tokens,
+            overlap_tokens: config.chunk_overlap,
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -36,6 +89,76 @@ pub struct RagIndexReport {
     pub embedded: usize,
     pub skipped: usize,
     pub deleted: usize,
+    /// Chunks whose vectors came from the embedding cache instead of the provider.
+    pub reused: usize,
+    /// Pending chunks left unembedded because of [`RagIndexOptions::max_chunks`].
+    pub deferred: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RagStatusCounts {
+    /// Chunks the current transcripts produce with the configured chunking.
+    pub total: usize,
+    /// Chunks stored with a matching content hash.
+    pub indexed: usize,
+    /// Chunks stored with an outdated content hash.
+    pub stale: usize,
+    /// Chunks that have never been embedded.
+    pub missing: usize,
+    /// Stored chunks that no current transcript message produces.
+    pub orphaned: usize,
+    /// Estimated input tokens needed to embed stale and missing chunks.
+    pub pending_tokens: usize,
+}
+
+impl RagStatusCounts {
+    fn add(&mut self, other: &Self) {
+        self.total += other.total;
+        self.indexed += other.indexed;
+        self.stale += other.stale;
+        self.missing += other.missing;
+        self.orphaned += other.orphaned;
+        self.pending_tokens += other.pending_tokens;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RagSessionStatus {
+    pub session_id: String,
+    pub provider: Option<String>,
+    pub counts: RagStatusCounts,
+}
+
+#[derive(Debug, Clone)]
+pub struct RagStatusReport {
+    pub model: String,
+    pub dim: Option<usize>,
+    pub last_indexed_ms: Option<i64>,
+    pub totals: RagStatusCounts,
+    /// Counts grouped by provider; sessions no longer in the index are grouped under `None`.
+    pub providers: Vec<(Option<String>, RagStatusCounts)>,
+    pub sessions: Vec<RagSessionStatus>,
+}
+
+impl RagStatusReport {
+    /// Estimated USD cost of embedding the pending chunks, when the model's price is known.
+    #[must_use]
+    pub fn estimated_cost_usd(&self) -> Option<f64> {
+        embedding_price_per_million(&self.model).map(|price| {
+            #[allow(clippy::cast_precision_loss)]
+            let tokens = self.totals.pending_tokens as f64;
+            tokens / 1_000_000.0 * price
+        })
+    }
+}
+
+/// A session whose indexed chunks sit close to another session's centroid.
+#[derive(Debug, Clone)]
+pub struct RelatedSession {
+    pub session_id: String,
+    pub distance: f64,
+    /// Closest chunk from this session.
+    pub best_chunk: RagSearchHit,
 }
 
 #[derive(Debug, Clone)]
@@ -49,6 +172,38 @@ pub struct IndexableChunk {
     pub embedding_text: String,
     pub content_hash: String,
     pub source_event_id: i64,
+    pub chunk_ordinal: i64,
+    pub span_start: i64,
+    pub span_end: i64,
+}
+
+/// Embedding model selected by `TX_RAG_EMBED_MODEL`, falling back to the default.
+#[must_use]
+pub fn configured_model() -> String {
+    env::var("TX_RAG_EMBED_MODEL")
+        .unwrap_or_else(|_| DEFAULT_OPENAI_MODEL.to_string())
+        .trim()
+        .to_string()
+}
+
+/// Model to search when none was requested explicitly.
+///
+/// Until the configured model has been indexed, searches use the most recently
+/// indexed model instead, so switching `TX_RAG_EMBED_MODEL` keeps existing
+/// searches working while the new model is embedded.
+///
+/// # Errors
+///
+/// Returns an error if the registered models cannot be listed.
+pub fn search_model(db: &Database, configured: &str) -> Result<String> {
+    let registered = db.rag_models()?;
+    if registered.iter().any(|model| model.model == configured) {
+        return Ok(configured.to_string());
+    }
+    Ok(registered
+        .into_iter()
+        .next()
+        .map_or_else(|| configured.to_string(), |latest| latest.model))
 }
 
 pub trait EmbeddingProvider {
@@ -88,10 +243,7 @@ impl OpenAIEmbeddingProvider {
                 "OPENAI_API_KEY is not set; semantic indexing/search requires embeddings configuration"
             )
         })?;
-        let model = env::var("TX_RAG_EMBED_MODEL")
-            .unwrap_or_else(|_| DEFAULT_OPENAI_MODEL.to_string())
-            .trim()
-            .to_string();
+        let model = configured_model();
         let base_url = env::var("TX_RAG_OPENAI_BASE_URL")
             .unwrap_or_else(|_| DEFAULT_OPENAI_BASE_URL.to_string())
             .trim_end_matches('/')
@@ -118,6 +270,20 @@ impl OpenAIEmbeddingProvider {
         })
     }
 
+    /// Replace the embedding model selected from the environment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `model` is blank.
+    pub fn with_model(mut self, model: &str) -> Result<Self> {
+        let model = model.trim();
+        if model.is_empty() {
+            return Err(eyre!("--model must not be empty"));
+        }
+        model.clone_into(&mut self.model);
+        Ok(self)
+    }
+
     #[cfg(not(coverage))]
     fn embed_batch_with_retry(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
         let mut attempt = 0usize;
@@ -265,36 +431,155 @@ fn deterministic_embedding(text: &str) -> Vec<f32> {
 }
 
 /// Convert a source event into one or more indexable semantic chunks.
+///
+/// Messages longer than `options.max_tokens` are split into overlapping windows.
+/// Each chunk records its ordinal and the character span it covers within the
+/// whitespace-normalized message text.
 #[must_use]
-pub fn to_indexable_chunks(message: &RagSourceMessage) -> Vec<IndexableChunk> {
+pub fn to_indexable_chunks(
+    message: &RagSourceMessage,
+    options: ChunkingOptions,
+) -> Vec<IndexableChunk> {
     let normalized = normalize_text(&message.text);
     if normalized.is_empty() {
         return Vec::new();
     }
 
-    let embedding_text = if let Some(tool_name) = message.tool_name.as_deref() {
-        format!(
-            "kind: {}\ntool: {}\ntext: {}",
-            message.kind, tool_name, normalized
-        )
-    } else {
-        format!("kind: {}\ntext: {}", message.kind, normalized)
-    };
+    chunk_windows(&normalized, options)
+        .into_iter()
+        .enumerate()
+        .map(|(ordinal, window)| {
+            let text = normalized[window.byte_start..window.byte_end].to_string();
+            let embedding_text = embedding_text(&message.kind, message.tool_name.as_deref(), &text);
+            let chunk_ordinal = i64::try_from(ordinal).unwrap_or(i64::MAX);
+
+            IndexableChunk {
+                chunk_id: stable_chunk_id(
+                    &message.session_id,
+                    message.source_event_id,
+                    chunk_ordinal,
+                ),
+                session_id: message.session_id.clone(),
+                ts_ms: message.ts_ms,
+                tool_name: message.tool_name.clone(),
+                kind: message.kind.clone(),
+                content_hash: content_hash(&text),
+                text,
+                embedding_text,
+                source_event_id: message.source_event_id,
+                chunk_ordinal,
+                span_start: i64::try_from(window.char_start).unwrap_or(i64::MAX),
+                span_end: i64::try_from(window.char_end).unwrap_or(i64::MAX),
+            }
+        })
+        .collect()
+}
+
+/// The input sent to the embedding provider for a chunk; its hash keys the
+/// embedding cache.
+fn embedding_text(kind: &str, tool_name: Option<&str>, text: &str) -> String {
+    match tool_name {
+        Some(tool_name) => format!("kind: {kind}\ntool: {tool_name}\ntext: {text}"),
+        None => format!("kind: {kind}\ntext: {text}"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    byte_start: usize,
+    byte_end: usize,
+    char_start: usize,
+    char_end: usize,
+    tokens: usize,
+}
+
+/// Split normalized text into word segments, breaking words that alone exceed
+/// the chunk budget (pasted blobs, long URLs) into budget-sized pieces.
+fn segments(text: &str, max_tokens: usize) -> Vec<Segment> {
+    let max_chars = max_tokens.saturating_mul(CHARS_PER_TOKEN).max(1);
+    let mut out = Vec::new();
+    let mut char_offset = 0usize;
+    let mut byte_offset = 0usize;
+
+    for word in text.split(' ') {
+        let mut piece_byte_start = byte_offset;
+        let mut piece_char_start = char_offset;
+        let mut piece_chars = 0usize;
+        for (index, _) in word.char_indices() {
+            if piece_chars == max_chars {
+                out.push(Segment {
+                    byte_start: piece_byte_start,
+                    byte_end: byte_offset + index,
+                    char_start: piece_char_start,
+                    char_end: piece_char_start + piece_chars,
+                    tokens: estimate_tokens(piece_chars),
+                });
+                piece_byte_start = byte_offset + index;
+                piece_char_start += piece_chars;
+                piece_chars = 0;
+            }
+            piece_chars += 1;
+        }
+        if piece_chars > 0 {
+            out.push(Segment {
+                byte_start: piece_byte_start,
+                byte_end: byte_offset + word.len(),
+                char_start: piece_char_start,
+                char_end: piece_char_start + piece_chars,
+                tokens: estimate_tokens(piece_chars),
+            });
+        }
+        char_offset = piece_char_start + piece_chars + 1;
+        byte_offset += word.len() + 1;
+    }
+
+    out
+}
+
+fn estimate_tokens(chars: usize) -> usize {
+    chars.div_ceil(CHARS_PER_TOKEN).max(1)
+}
+
+fn chunk_windows(text: &str, options: ChunkingOptions) -> Vec<Segment> {
+    let max_tokens = options.max_tokens.max(1);
+    let overlap_tokens = options.overlap_tokens.min(max_tokens - 1);
+    let segments = segments(text, max_tokens);
+    let mut windows = Vec::new();
+    let mut start = 0usize;
+
+    while start < segments.len() {
+        let mut end = start;
+        let mut tokens = 0usize;
+        while end < segments.len() && (end == start || tokens + segments[end].tokens <= max_tokens)
+        {
+            tokens += segments[end].tokens;
+            end += 1;
+        }
 
-    let chunk_id = stable_chunk_id(&message.session_id, message.source_event_id, 0);
-    let content_hash = content_hash(&normalized);
+        let first = segments[start];
+        let last = segments[end - 1];
+        windows.push(Segment {
+            byte_start: first.byte_start,
+            byte_end: last.byte_end,
+            char_start: first.char_start,
+            char_end: last.char_end,
+            tokens,
+        });
+
+        if end == segments.len() {
+            break;
+        }
 
-    vec![IndexableChunk {
-        chunk_id,
-        session_id: message.session_id.clone(),
-        ts_ms: message.ts_ms,
-        tool_name: message.tool_name.clone(),
-        kind: message.kind.clone(),
-        text: normalized,
-        embedding_text,
-        content_hash,
-        source_event_id: message.source_event_id,
-    }]
+        let mut next = end;
+        let mut overlap = 0usize;
+        while next > start + 1 && overlap + segments[next - 1].tokens <= overlap_tokens {
+            overlap += segments[next - 1].tokens;
+            next -= 1;
+        }
+        start = next;
+    }
+
+    windows
 }
 
 fn stable_chunk_id(session_id: &str, source_event_id: i64, chunk_ordinal: i64) -> i64 {
@@ -330,67 +615,118 @@ pub fn index_history<P: EmbeddingProvider>(
     let mut report = RagIndexReport::default();
     let batch_size = options.batch_size.max(1);
 
+    let model_name = provider.model_name();
+
     if options.reindex {
-        report.deleted =
-            db.delete_rag_chunks(options.session_id.as_deref(), options.since_ts_ms)?;
+        report.deleted = db.delete_rag_chunks(
+            model_name,
+            options.session_id.as_deref(),
+            options.since_ts_ms,
+        )?;
     }
 
+    // The most recently indexed table for this model decides which chunks are
+    // already current and which dimension new vectors must have.
+    let current = db
+        .rag_models()?
+        .into_iter()
+        .find(|registered| registered.model == model_name);
     let source_rows = db.rag_source_messages(options.session_id.as_deref(), options.since_ts_ms)?;
+    let existing: HashMap<i64, RagIndexedChunk> = match current.as_ref() {
+        Some(current) if !options.reindex => db
+            .rag_indexed_chunks(current, options.session_id.as_deref(), options.since_ts_ms)?
+            .into_iter()
+            .map(|chunk| (chunk.chunk_id, chunk))
+            .collect(),
+        _ => HashMap::new(),
+    };
     let mut pending = Vec::new();
+    let mut produced = HashSet::new();
+    let mut sources = HashSet::new();
 
     for source in &source_rows {
-        for chunk in to_indexable_chunks(source) {
+        sources.insert((source.session_id.as_str(), source.source_event_id));
+        for chunk in to_indexable_chunks(source, options.chunking) {
             report.scanned += 1;
-
-            if !options.reindex {
-                let existing = db.rag_chunk_content_hash(chunk.chunk_id)?;
-                if existing.as_deref() == Some(chunk.content_hash.as_str()) {
-                    report.skipped += 1;
-                    continue;
-                }
+            produced.insert(chunk.chunk_id);
+
+            if existing
+                .get(&chunk.chunk_id)
+                .is_some_and(|indexed| indexed.content_hash == chunk.content_hash)
+            {
+                report.skipped += 1;
+                continue;
             }
 
             pending.push(chunk);
         }
     }
+    if let Some(max_chunks) = options.max_chunks
+        && pending.len() > max_chunks
+    {
+        report.deferred = pending.len() - max_chunks;
+        pending.truncate(max_chunks);
+    }
+
+    // Drop chunks left over from a previous, longer chunking of a message that
+    // was re-chunked in this run (for example after changing `chunk_tokens`).
+    let stale: Vec<i64> = existing
+        .values()
+        .filter(|indexed| {
+            !produced.contains(&indexed.chunk_id)
+                && sources.contains(&(indexed.session_id.as_str(), indexed.source_event_id))
+        })
+        .map(|indexed| indexed.chunk_id)
+        .collect();
+    if let Some(current) = current.as_ref() {
+        report.deleted += db.delete_rag_chunk_ids(current, &stale)?;
+    }
+
+    let mut expected_dim = current
+        .as_ref()
+        .filter(|_| !options.reindex)
+        .map(|current| current.dim);
+
+    // Identical inputs (boilerplate, repeated prompts, pasted logs) are embedded
+    // once per model and served from the cache afterwards. Without an existing
+    // table to fix the dimension, one uncached input is embedded up front so
+    // cached vectors from an earlier dimension of the same model are not reused.
+    let mut fresh = HashSet::new();
+    if expected_dim.is_none() && !pending.is_empty() {
+        expected_dim = probe_dimension(db, provider, &pending, &mut fresh)?;
+    }
 
     for batch in pending.chunks(batch_size) {
-        let texts: Vec<String> = batch
+        let input_hashes: Vec<String> = batch
             .iter()
-            .map(|chunk| chunk.embedding_text.clone())
+            .map(|chunk| content_hash(&chunk.embedding_text))
             .collect();
-        let embeddings = provider.embed(&texts)?;
-        if embeddings.len() != batch.len() {
-            return Err(eyre!(
-                "embedding provider returned {} vectors for {} inputs",
-                embeddings.len(),
-                batch.len()
-            ));
-        }
+        let BatchVectors {
+            vectors,
+            fresh: embedded,
+        } = cached_batch_vectors(db, provider, batch, &input_hashes, expected_dim)?;
+        fresh.extend(embedded);
+        // Each freshly embedded input is charged to its first chunk; every other
+        // chunk in the batch was served from the cache.
+        report.reused += input_hashes
+            .iter()
+            .filter(|hash| !fresh.remove(*hash))
+            .count();
 
         let mut records = Vec::with_capacity(batch.len());
-        for (chunk, embedding) in batch.iter().zip(embeddings) {
-            if embedding.len() != EMBEDDING_DIM {
+        for (chunk, hash) in batch.iter().zip(&input_hashes) {
+            let embedding = vectors[hash].clone();
+            let expected = *expected_dim.get_or_insert(embedding.len());
+            if embedding.len() != expected {
                 return Err(eyre!(
-                    "embedding dimension mismatch for model {}: expected {}, got {}",
-                    provider.model_name(),
-                    EMBEDDING_DIM,
+                    "embedding dimension mismatch for model {}: expected {}, got {}; rerun with --reindex to rebuild vectors at the new dimension",
+                    model_name,
+                    expected,
                     embedding.len()
                 ));
             }
 
-            records.push(RagChunkRecord {
-                chunk_id: chunk.chunk_id,
-                embedding,
-                session_id: chunk.session_id.clone(),
-                ts_ms: chunk.ts_ms,
-                tool_name: chunk.tool_name.clone(),
-                kind: chunk.kind.clone(),
-                model: provider.model_name().to_string(),
-                content_hash: chunk.content_hash.clone(),
-                text: chunk.text.clone(),
-                source_event_id: chunk.source_event_id,
-            });
+            records.push(chunk_record(chunk, embedding, model_name));
         }
 
         report.embedded += db.upsert_rag_chunks(&records)?;
@@ -399,6 +735,302 @@ pub fn index_history<P: EmbeddingProvider>(
     Ok(report)
 }
 
+/// Embed the messages of queued sessions when `[features.rag] auto_index` is
+/// enabled.
+///
+/// Only sessions queued with [`Database::queue_rag_auto_index`] are scanned,
+/// and each leaves the queue once none of its chunks is deferred, so work cut
+/// short by the chunk cap, the rate limit, or a failure resumes on a later run.
+/// Runs at most once per `auto_index_interval_secs`, embeds at most
+/// `auto_index_max_chunks` chunks across all sessions, and records the outcome
+/// (including provider failures) instead of returning it, so callers never fail
+/// because of it. Returns `None` when disabled, rate limited, or nothing is
+/// queued.
+///
+/// # Errors
+///
+/// Returns an error only if the run history cannot be read or written.
+pub fn auto_index<P, F>(
+    db: &mut Database,
+    config: RagConfig,
+    now_ms: i64,
+    provider: F,
+) -> Result<Option<RagAutoIndexRun>>
+where
+    P: EmbeddingProvider,
+    F: FnOnce() -> Result<P>,
+{
+    const AUTO_INDEX_BATCH_SIZE: usize = 64;
+    if !config.auto_index {
+        return Ok(None);
+    }
+    let sessions = db.rag_auto_index_queue()?;
+    if sessions.is_empty() {
+        return Ok(None);
+    }
+    let interval_ms = i64::try_from(config.auto_index_interval_secs)
+        .unwrap_or(i64::MAX)
+        .saturating_mul(1000);
+    if let Some(last) = db.last_rag_auto_index_run()?
+        && now_ms.saturating_sub(last.started_ms) < interval_ms
+    {
+        return Ok(None);
+    }
+
+    let mut run = RagAutoIndexRun {
+        started_ms: now_ms,
+        ..RagAutoIndexRun::default()
+    };
+    let mut done = Vec::new();
+    let outcome = provider().and_then(|provider| {
+        run.model = Some(provider.model_name().to_string());
+        let mut budget = config.auto_index_max_chunks;
+        for session_id in &sessions {
+            let options = RagIndexOptions {
+                session_id: Some(session_id.clone()),
+                since_ts_ms: None,
+                reindex: false,
+                batch_size: AUTO_INDEX_BATCH_SIZE,
+                chunking: config.into(),
+                max_chunks: Some(budget),
+            };
+            let report = index_history(db, &provider, &options)?;
+            run.embedded += report.embedded;
+            run.deferred += report.deferred;
+            budget = budget.saturating_sub(report.embedded);
+            if report.deferred == 0 {
+                done.push(session_id.clone());
+            }
+        }
+        Ok(())
+    });
+    if let Err(err) = outcome {
+        run.error = Some(format!("{err:#}"));
+    }
+    db.dequeue_rag_auto_index(&done)?;
+    run.finished_ms = unix_timestamp().saturating_mul(1000).max(now_ms);
+    db.record_rag_auto_index_run(&run)?;
+    Ok(Some(run))
+}
+
+fn chunk_record(chunk: &IndexableChunk, embedding: Vec<f32>, model: &str) -> RagChunkRecord {
+    RagChunkRecord {
+        chunk_id: chunk.chunk_id,
+        embedding,
+        session_id: chunk.session_id.clone(),
+        ts_ms: chunk.ts_ms,
+        tool_name: chunk.tool_name.clone(),
+        kind: chunk.kind.clone(),
+        model: model.to_string(),
+        content_hash: chunk.content_hash.clone(),
+        text: chunk.text.clone(),
+        source_event_id: chunk.source_event_id,
+        chunk_ordinal: chunk.chunk_ordinal,
+        span_start: chunk.span_start,
+        span_end: chunk.span_end,
+    }
+}
+
+/// Learn the provider's current dimension by embedding the first of `pending`
+/// that has no cached vector, marking its input hash as freshly embedded. When
+/// every input is cached, the provider is not called and the first cached
+/// vector decides.
+fn probe_dimension<P: EmbeddingProvider>(
+    db: &mut Database,
+    provider: &P,
+    pending: &[IndexableChunk],
+    fresh: &mut HashSet<String>,
+) -> Result<Option<usize>> {
+    let hashes: Vec<String> = pending
+        .iter()
+        .map(|chunk| content_hash(&chunk.embedding_text))
+        .collect();
+    let cached = db.rag_cached_embeddings(provider.model_name(), &hashes)?;
+    let Some((chunk, hash)) = pending
+        .iter()
+        .zip(hashes.iter())
+        .find(|(_, hash)| !cached.contains_key(*hash))
+    else {
+        return Ok(hashes
+            .first()
+            .and_then(|hash| cached.get(hash))
+            .map(Vec::len));
+    };
+    let hash = hash.clone();
+    let embedded = embed_into_cache(
+        db,
+        provider,
+        std::slice::from_ref(&hash),
+        std::slice::from_ref(&chunk.embedding_text),
+    )?;
+    fresh.insert(hash);
+    Ok(embedded.first().map(|(_, vector)| vector.len()))
+}
+
+struct BatchVectors {
+    vectors: HashMap<String, Vec<f32>>,
+    fresh: Vec<String>,
+}
+
+/// Resolve vectors for `batch` keyed by input hash, embedding only the
+/// distinct inputs the cache has no vector of `expected_dim` for.
+fn cached_batch_vectors<P: EmbeddingProvider>(
+    db: &mut Database,
+    provider: &P,
+    batch: &[IndexableChunk],
+    input_hashes: &[String],
+    expected_dim: Option<usize>,
+) -> Result<BatchVectors> {
+    let mut vectors = db.rag_cached_embeddings(provider.model_name(), input_hashes)?;
+    if let Some(expected) = expected_dim {
+        vectors.retain(|_, vector| vector.len() == expected);
+    }
+
+    let mut missing = Vec::new();
+    let mut texts = Vec::new();
+    for (chunk, hash) in batch.iter().zip(input_hashes) {
+        if !vectors.contains_key(hash) && !missing.contains(hash) {
+            missing.push(hash.clone());
+            texts.push(chunk.embedding_text.clone());
+        }
+    }
+    if texts.is_empty() {
+        return Ok(BatchVectors {
+            vectors,
+            fresh: Vec::new(),
+        });
+    }
+    vectors.extend(embed_into_cache(db, provider, &missing, &texts)?);
+    Ok(BatchVectors {
+        vectors,
+        fresh: missing,
+    })
+}
+
+/// Embed `texts` and store each vector in the cache under the matching hash.
+fn embed_into_cache<P: EmbeddingProvider>(
+    db: &mut Database,
+    provider: &P,
+    hashes: &[String],
+    texts: &[String],
+) -> Result<Vec<(String, Vec<f32>)>> {
+    let embeddings = provider.embed(texts)?;
+    if embeddings.len() != texts.len() {
+        return Err(eyre!(
+            "embedding provider returned {} vectors for {} inputs",
+            embeddings.len(),
+            texts.len()
+        ));
+    }
+    let embedded: Vec<(String, Vec<f32>)> = hashes.iter().cloned().zip(embeddings).collect();
+    db.store_rag_cached_embeddings(provider.model_name(), &embedded)?;
+    Ok(embedded)
+}
+
+/// Delete cached embeddings that no stored chunk was embedded from, such as the
+/// vectors of pruned sessions, edited messages, or models without an index.
+///
+/// # Errors
+///
+/// Returns an error if the vector tables or the cache cannot be read or updated.
+pub fn prune_embedding_cache(db: &mut Database) -> Result<usize> {
+    let mut live: HashMap<String, HashSet<String>> = HashMap::new();
+    for model in db.rag_models()? {
+        let hashes = live.entry(model.model.clone()).or_default();
+        db.visit_rag_chunk_inputs(&model, |kind, tool_name, text| {
+            hashes.insert(content_hash(&embedding_text(kind, tool_name, text)));
+        })?;
+    }
+    db.retain_rag_cached_embeddings(&live)
+}
+
+/// Published `OpenAI` list prices in USD per million input tokens.
+fn embedding_price_per_million(model: &str) -> Option<f64> {
+    match model {
+        "text-embedding-3-small" => Some(0.02),
+        "text-embedding-3-large" => Some(0.13),
+        "text-embedding-ada-002" => Some(0.10),
+        _ => None,
+    }
+}
+
+/// Compare current transcript chunks against the vectors stored for `model`.
+///
+/// Chunks are recomputed with `chunking`, so changing the chunk size shows up
+/// as stale and orphaned chunks until the next index run.
+///
+/// # Errors
+///
+/// Returns an error if source rows or stored chunks cannot be read.
+pub fn index_status(
+    db: &Database,
+    model: &str,
+    chunking: ChunkingOptions,
+    session_id: Option<&str>,
+) -> Result<RagStatusReport> {
+    let current = db
+        .rag_models()?
+        .into_iter()
+        .find(|registered| registered.model == model);
+    let mut stored: HashMap<i64, RagIndexedChunk> = match current.as_ref() {
+        Some(current) => db
+            .rag_indexed_chunks(current, session_id, None)?
+            .into_iter()
+            .map(|chunk| (chunk.chunk_id, chunk))
+            .collect(),
+        None => HashMap::new(),
+    };
+    let providers_by_session = db.rag_session_providers()?;
+    let mut sessions: BTreeMap<String, RagStatusCounts> = BTreeMap::new();
+
+    for source in db.rag_source_messages(session_id, None)? {
+        for chunk in to_indexable_chunks(&source, chunking) {
+            let counts = sessions.entry(chunk.session_id.clone()).or_default();
+            counts.total += 1;
+            match stored.remove(&chunk.chunk_id) {
+                Some(indexed) if indexed.content_hash == chunk.content_hash => counts.indexed += 1,
+                found => {
+                    if found.is_some() {
+                        counts.stale += 1;
+                    } else {
+                        counts.missing += 1;
+                    }
+                    counts.pending_tokens += estimate_tokens(chunk.embedding_text.chars().count());
+                }
+            }
+        }
+    }
+
+    for orphan in stored.into_values() {
+        sessions.entry(orphan.session_id).or_default().orphaned += 1;
+    }
+
+    let mut totals = RagStatusCounts::default();
+    let mut providers: BTreeMap<Option<String>, RagStatusCounts> = BTreeMap::new();
+    let sessions = sessions
+        .into_iter()
+        .map(|(session_id, counts)| {
+            let provider = providers_by_session.get(&session_id).cloned();
+            totals.add(&counts);
+            providers.entry(provider.clone()).or_default().add(&counts);
+            RagSessionStatus {
+                session_id,
+                provider,
+                counts,
+            }
+        })
+        .collect();
+
+    Ok(RagStatusReport {
+        model: model.to_string(),
+        dim: current.as_ref().map(|current| current.dim),
+        last_indexed_ms: current.and_then(|current| current.last_indexed_ms),
+        totals,
+        providers: providers.into_iter().collect(),
+        sessions,
+    })
+}
+
 /// Search indexed semantic chunks using a natural-language query.
 ///
 /// # Errors
@@ -420,16 +1052,159 @@ pub fn search_history<P: EmbeddingProvider>(
     let Some(vector) = vectors.into_iter().next() else {
         return Err(eyre!("embedding provider returned no vectors for query"));
     };
-    if vector.len() != EMBEDDING_DIM {
+
+    let model_name = provider.model_name();
+    let registered: Vec<_> = db
+        .rag_models()?
+        .into_iter()
+        .filter(|registered| registered.model == model_name)
+        .collect();
+    if registered.is_empty() {
+        return Err(eyre!(
+            "no semantic index for model {model_name}; run `tx rag index --model {model_name}` first"
+        ));
+    }
+    let Some(table) = registered
+        .iter()
+        .find(|registered| registered.dim == vector.len())
+    else {
+        let indexed = registered
+            .iter()
+            .map(|registered| registered.dim.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
         return Err(eyre!(
-            "query embedding dimension mismatch for model {}: expected {}, got {}",
-            provider.model_name(),
-            EMBEDDING_DIM,
+            "query embedding dimension mismatch for model {}: indexed with {}, got {}",
+            model_name,
+            indexed,
             vector.len()
         ));
+    };
+
+    db.search_similar_chunks(table, &vector, filters, k)
+}
+
+/// Find sessions semantically similar to `session_id`.
+///
+/// The session's stored chunk vectors are averaged into a unit-length centroid,
+/// which is searched against the model's most recently indexed table. The session
+/// itself is excluded and each related session is reported once, with its closest chunk.
+///
+/// # Errors
+///
+/// Returns an error if the model has no semantic index, the session has no indexed
+/// chunks for it, or the vector queries fail.
+pub fn related_sessions(
+    db: &Database,
+    model: &str,
+    session_id: &str,
+    limit: usize,
+) -> Result<Vec<RelatedSession>> {
+    let Some(table) = db
+        .rag_models()?
+        .into_iter()
+        .find(|registered| registered.model == model)
+    else {
+        return Err(eyre!(
+            "no semantic index for model {model}; run `tx rag index --model {model}` first"
+        ));
+    };
+
+    let embeddings = db.rag_session_embeddings(&table, session_id)?;
+    let Some(centroid) = centroid(&embeddings) else {
+        return Err(eyre!(
+            "session {session_id} has no indexed chunks for model {model}; run `tx rag index --session {session_id}` first"
+        ));
+    };
+
+    // The source session is excluded in the query itself, so its own chunks
+    // cannot crowd the other sessions out of the nearest neighbours.
+    let filters = RagSearchFilters {
+        exclude_session_id: Some(session_id.to_string()),
+        ..RagSearchFilters::default()
+    };
+    let k = limit.saturating_mul(RELATED_OVERFETCH).clamp(1, MAX_KNN);
+    let hits = db.search_similar_chunks(&table, &centroid, &filters, k)?;
+
+    let mut seen = HashSet::new();
+    let mut related = Vec::new();
+    for hit in hits {
+        if !seen.insert(hit.session_id.clone()) {
+            continue;
+        }
+        related.push(RelatedSession {
+            session_id: hit.session_id.clone(),
+            distance: hit.distance,
+            best_chunk: hit,
+        });
+        if related.len() == limit {
+            break;
+        }
+    }
+    Ok(related)
+}
+
+fn centroid(vectors: &[Vec<f32>]) -> Option<Vec<f32>> {
+    let dim = vectors.first()?.len();
+    let mut sum = vec![0.0_f32; dim];
+    for vector in vectors.iter().filter(|vector| vector.len() == dim) {
+        for (slot, value) in sum.iter_mut().zip(vector) {
+            *slot += value;
+        }
     }
+    normalize(&mut sum);
+    Some(sum)
+}
 
-    db.search_similar_chunks(&vector, filters, k)
+/// Scale `vector` to unit length; zero vectors are left untouched.
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for slot in vector {
+            *slot /= norm;
+        }
+    }
+}
+
+/// Render search hits as a context block to prepend to a new prompt.
+///
+/// Each hit carries its session reference so the provider (and the reader) can trace
+/// where the excerpt came from. Hits are kept in rank order and the block stops before
+/// the first hit that would push it past `limit` bytes; `None` means nothing fit.
+#[must_use]
+pub fn format_context_block(query: &str, hits: &[RagSearchHit], limit: usize) -> Option<String> {
+    let mut block = format!(
+        "Context retrieved from past sessions for \"{}\":\n",
+        query.trim()
+    );
+    let mut included = 0;
+    for (index, hit) in hits.iter().enumerate() {
+        let timestamp = OffsetDateTime::from_unix_timestamp(hit.ts_ms / 1000)
+            .ok()
+            .and_then(|dt| dt.format(&Rfc3339).ok())
+            .unwrap_or_else(|| hit.ts_ms.to_string());
+        let tool = hit
+            .tool_name
+            .as_deref()
+            .map(|tool| format!(" tool={tool}"))
+            .unwrap_or_default();
+        let entry = format!(
+            "\n[{rank}] session={session} ts={timestamp} kind={kind}{tool} chunk={chunk} span={start}..{end}\n{text}\n",
+            rank = index + 1,
+            session = hit.session_id,
+            kind = hit.kind,
+            chunk = hit.chunk_ordinal,
+            start = hit.span_start,
+            end = hit.span_end,
+            text = hit.text,
+        );
+        if block.len() + entry.len() > limit {
+            break;
+        }
+        block.push_str(&entry);
+        included += 1;
+    }
+    (included > 0).then_some(block)
 }
 
 #[cfg(test)]
@@ -438,7 +1213,6 @@ mod tests {
     use crate::session::{MessageRecord, SessionIngest, SessionSummary};
     use crate::test_support::{ENV_LOCK, EnvOverride};
     use tempfile::TempDir;
-    use time::OffsetDateTime;
 
     #[test]
     fn to_indexable_chunks_normalizes_whitespace_and_hashes() {
@@ -451,7 +1225,7 @@ mod tests {
             text: "hello   world\n\nfrom   tx".to_string(),
         };
 
-        let chunks = to_indexable_chunks(&source);
+        let chunks = to_indexable_chunks(&source, ChunkingOptions::default());
         assert_eq!(chunks.len(), 1);
         assert_eq!(chunks[0].text, "hello world from tx");
         assert!(chunks[0].embedding_text.contains("kind: user"));
@@ -470,7 +1244,7 @@ mod tests {
             text: " \n\t ".to_string(),
         };
 
-        let chunks = to_indexable_chunks(&source);
+        let chunks = to_indexable_chunks(&source, ChunkingOptions::default());
         assert!(chunks.is_empty());
     }
 
@@ -485,13 +1259,91 @@ mod tests {
             text: "  output   text ".to_string(),
         };
 
-        let chunks = to_indexable_chunks(&source);
+        let chunks = to_indexable_chunks(&source, ChunkingOptions::default());
         assert_eq!(chunks.len(), 1);
         assert!(!chunks[0].embedding_text.contains("tool:"));
         assert!(chunks[0].embedding_text.contains("kind: tool_result"));
         assert!(chunks[0].embedding_text.contains("text: output text"));
     }
 
+    fn long_source(words: usize) -> RagSourceMessage {
+        let text = (0..words)
+            .map(|index| format!("word{index:03}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        RagSourceMessage {
+            session_id: "session-long".to_string(),
+            source_event_id: 7,
+            ts_ms: 1,
+            tool_name: None,
+            kind: "assistant".to_string(),
+            text,
+        }
+    }
+
+    #[test]
+    fn to_indexable_chunks_splits_long_messages_with_overlap() {
+        let source = long_source(40);
+        let options = ChunkingOptions {
+            max_tokens: 20,
+            overlap_tokens: 4,
+        };
+
+        let chunks = to_indexable_chunks(&source, options);
+        assert!(chunks.len() > 1);
+        let normalized = normalize_text(&source.text);
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.chunk_ordinal, i64::try_from(index).unwrap());
+            assert_eq!(
+                chunk.chunk_id,
+                stable_chunk_id("session-long", 7, chunk.chunk_ordinal)
+            );
+            let start = usize::try_from(chunk.span_start).unwrap();
+            let end = usize::try_from(chunk.span_end).unwrap();
+            assert_eq!(&normalized[start..end], chunk.text);
+            assert!(chunk.embedding_text.ends_with(&chunk.text));
+        }
+        assert_eq!(chunks[0].span_start, 0);
+        assert_eq!(
+            chunks.last().map(|chunk| chunk.span_end),
+            Some(i64::try_from(normalized.len()).unwrap())
+        );
+        for pair in chunks.windows(2) {
+            assert!(pair[1].span_start < pair[0].span_end, "chunks overlap");
+            assert!(pair[1].span_start > pair[0].span_start, "chunks advance");
+        }
+    }
+
+    #[test]
+    fn to_indexable_chunks_keeps_short_messages_as_single_chunk() {
+        let source = long_source(3);
+        let chunks = to_indexable_chunks(&source, ChunkingOptions::default());
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].chunk_id, stable_chunk_id("session-long", 7, 0));
+        assert_eq!(chunks[0].span_start, 0);
+        assert_eq!(chunks[0].span_end, 23);
+    }
+
+    #[test]
+    fn to_indexable_chunks_splits_oversized_words_by_characters() {
+        let source = RagSourceMessage {
+            text: format!("héllo {}", "é".repeat(50)),
+            ..long_source(0)
+        };
+        let options = ChunkingOptions {
+            max_tokens: 4,
+            overlap_tokens: 0,
+        };
+
+        let chunks = to_indexable_chunks(&source, options);
+        let spans: Vec<_> = chunks
+            .iter()
+            .map(|chunk| (chunk.span_start, chunk.span_end))
+            .collect();
+        assert_eq!(spans, vec![(0, 5), (6, 22), (22, 38), (38, 54), (54, 56)]);
+        assert!(chunks.iter().all(|chunk| chunk.text.chars().count() <= 16));
+    }
+
     struct MockProvider;
 
     impl EmbeddingProvider for MockProvider {
@@ -582,6 +1434,8 @@ mod tests {
             since_ts_ms: None,
             reindex: false,
             batch_size: 16,
+            chunking: ChunkingOptions::default(),
+            max_chunks: None,
         };
         let first = index_history(&mut db, &provider, &first_options)?;
         assert_eq!(first.embedded, 1);
@@ -591,6 +1445,8 @@ mod tests {
             since_ts_ms: None,
             reindex: false,
             batch_size: 16,
+            chunking: ChunkingOptions::default(),
+            max_chunks: None,
         };
         let second = index_history(&mut db, &provider, &second_options)?;
         assert_eq!(second.embedded, 0);
@@ -600,6 +1456,8 @@ mod tests {
             since_ts_ms: None,
             reindex: true,
             batch_size: 16,
+            chunking: ChunkingOptions::default(),
+            max_chunks: None,
         };
         let third = index_history(&mut db, &provider, &third_options)?;
         assert_eq!(third.deleted, 1);
@@ -614,6 +1472,151 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn index_history_removes_chunks_left_over_from_previous_chunking() -> Result<()> {
+        let (_temp, mut db, session_id) = seeded_db()?;
+        let provider = MockProvider;
+        let narrow = RagIndexOptions {
+            session_id: None,
+            since_ts_ms: None,
+            reindex: false,
+            batch_size: 16,
+            chunking: ChunkingOptions {
+                max_tokens: 2,
+                overlap_tokens: 0,
+            },
+            max_chunks: None,
+        };
+        let first = index_history(&mut db, &provider, &narrow)?;
+        assert_eq!(first.embedded, 3);
+
+        let wide = RagIndexOptions {
+            chunking: ChunkingOptions::default(),
+            ..narrow
+        };
+        let second = index_history(&mut db, &provider, &wide)?;
+        assert_eq!(second.embedded, 1);
+        assert_eq!(second.deleted, 2);
+
+        let model = db
+            .rag_model("mock-embed-v1", EMBEDDING_DIM)?
+            .expect("mock model registered");
+        let indexed = db.rag_indexed_chunks(&model, Some(&session_id), None)?;
+        assert_eq!(indexed.len(), 1);
+        let hits = search_history(&db, &provider, "semantic", &RagSearchFilters::default(), 5)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk_ordinal, 0);
+        assert_eq!((hits[0].span_start, hits[0].span_end), (0, 21));
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct CountingProvider {
+        inputs: std::cell::Cell<usize>,
+    }
+
+    impl EmbeddingProvider for CountingProvider {
+        fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            self.inputs.set(self.inputs.get() + texts.len());
+            MockProvider.embed(texts)
+        }
+
+        fn model_name(&self) -> &'static str {
+            "mock-embed-v1"
+        }
+    }
+
+    #[test]
+    fn index_history_reuses_cached_embeddings_on_reindex() -> Result<()> {
+        let (_temp, mut db, _session_id) = seeded_db()?;
+        let options = RagIndexOptions {
+            session_id: None,
+            since_ts_ms: None,
+            reindex: false,
+            batch_size: 16,
+            chunking: ChunkingOptions {
+                max_tokens: 2,
+                overlap_tokens: 0,
+            },
+            max_chunks: None,
+        };
+        let provider = CountingProvider::default();
+        let first = index_history(&mut db, &provider, &options)?;
+        assert_eq!((first.embedded, first.reused), (3, 0));
+        assert_eq!(provider.inputs.get(), 3);
+
+        let provider = CountingProvider::default();
+        let rebuilt = index_history(
+            &mut db,
+            &provider,
+            &RagIndexOptions {
+                reindex: true,
+                ..options
+            },
+        )?;
+        assert_eq!(rebuilt.deleted, 3);
+        assert_eq!((rebuilt.embedded, rebuilt.reused), (3, 3));
+        assert_eq!(provider.inputs.get(), 0, "dimension comes from the cache");
+        let hits = search_history(
+            &db,
+            &MockProvider,
+            "semantic",
+            &RagSearchFilters::default(),
+            5,
+        )?;
+        assert_eq!(hits.len(), 3);
+
+        assert_eq!(prune_embedding_cache(&mut db)?, 0);
+        db.delete_rag_chunks("mock-embed-v1", None, None)?;
+        assert_eq!(prune_embedding_cache(&mut db)?, 3);
+        assert_eq!(prune_embedding_cache(&mut db)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn auto_index_caps_rate_limits_and_records_failures() -> Result<()> {
+        let (_temp, mut db, session_id) = seeded_db()?;
+        let sessions = [session_id];
+        let config = RagConfig {
+            chunk_tokens: 2,
+            chunk_overlap: 0,
+            auto_index: true,
+            auto_index_interval_secs: 60,
+            auto_index_max_chunks: 2,
+        };
+        let disabled = RagConfig {
+            auto_index: false,
+            ..config
+        };
+        assert!(auto_index(&mut db, config, 0, || Ok(MockProvider))?.is_none());
+        db.queue_rag_auto_index(&sessions, 0)?;
+        assert!(auto_index(&mut db, disabled, 0, || Ok(MockProvider))?.is_none());
+
+        let start = 1_000_000;
+        let first = auto_index(&mut db, config, start, || Ok(MockProvider))?
+            .expect("first run is not rate limited");
+        assert_eq!((first.embedded, first.deferred), (2, 1));
+        assert_eq!(first.model.as_deref(), Some("mock-embed-v1"));
+        assert_eq!(db.rag_auto_index_queue()?, sessions);
+        assert!(auto_index(&mut db, config, start + 59_000, || Ok(MockProvider))?.is_none());
+
+        let failed = auto_index(&mut db, config, start + 60_000, || {
+            Err::<MockProvider, _>(eyre!("no embeddings key"))
+        })?
+        .expect("interval elapsed");
+        assert_eq!(failed.error.as_deref(), Some("no embeddings key"));
+        assert_eq!(db.last_rag_auto_index_run()?, Some(failed));
+        assert_eq!(db.rag_auto_index_queue()?, sessions);
+
+        let resumed = auto_index(&mut db, config, start + 120_000, || Ok(MockProvider))?
+            .expect("interval elapsed");
+        assert_eq!((resumed.embedded, resumed.deferred), (1, 0));
+        assert!(resumed.error.is_none());
+        assert!(db.rag_auto_index_queue()?.is_empty());
+        assert!(auto_index(&mut db, config, start + 180_000, || Ok(MockProvider))?.is_none());
+        Ok(())
+    }
+
     struct MissingVectorsProvider;
 
     impl EmbeddingProvider for MissingVectorsProvider {
@@ -626,6 +1629,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn index_status_reports_indexed_stale_missing_and_orphaned_chunks() -> Result<()> {
+        let (_temp, mut db, session_id) = seeded_db()?;
+        let narrow = ChunkingOptions {
+            max_tokens: 2,
+            overlap_tokens: 0,
+        };
+
+        let before = index_status(&db, "mock-embed-v1", ChunkingOptions::default(), None)?;
+        assert_eq!(before.dim, None);
+        assert_eq!(before.totals.total, 1);
+        assert_eq!(before.totals.missing, 1);
+        assert!(before.totals.pending_tokens > 0);
+        assert_eq!(before.estimated_cost_usd(), None);
+
+        index_history(
+            &mut db,
+            &MockProvider,
+            &RagIndexOptions {
+                session_id: None,
+                since_ts_ms: None,
+                reindex: false,
+                batch_size: 8,
+                chunking: narrow,
+                max_chunks: None,
+            },
+        )?;
+
+        let same = index_status(&db, "mock-embed-v1", narrow, None)?;
+        assert_eq!(same.dim, Some(EMBEDDING_DIM));
+        assert!(same.last_indexed_ms.is_some());
+        assert_eq!(same.totals.total, 3);
+        assert_eq!(same.totals.indexed, 3);
+        assert_eq!(same.totals.pending_tokens, 0);
+
+        let rechunked = index_status(&db, "mock-embed-v1", ChunkingOptions::default(), None)?;
+        assert_eq!(
+            rechunked.totals,
+            RagStatusCounts {
+                total: 1,
+                indexed: 0,
+                stale: 1,
+                missing: 0,
+                orphaned: 2,
+                pending_tokens: rechunked.totals.pending_tokens,
+            }
+        );
+        assert_eq!(rechunked.providers.len(), 1);
+        assert_eq!(rechunked.providers[0].0.as_deref(), Some("codex"));
+        assert_eq!(rechunked.sessions.len(), 1);
+        assert_eq!(rechunked.sessions[0].session_id, session_id);
+
+        let large = index_status(&db, "text-embedding-3-large", narrow, None)?;
+        assert_eq!(large.totals.missing, 3);
+        assert!(large.estimated_cost_usd().is_some_and(|cost| cost > 0.0));
+        Ok(())
+    }
+
+    /// Claims to be the mock model but produces vectors of a different length.
     struct WrongDimProvider;
 
     impl EmbeddingProvider for WrongDimProvider {
@@ -634,7 +1696,22 @@ mod tests {
         }
 
         fn model_name(&self) -> &'static str {
-            "wrong-dim"
+            "mock-embed-v1"
+        }
+    }
+
+    struct SmallProvider;
+
+    impl EmbeddingProvider for SmallProvider {
+        fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|text| test_embedding(text)[..8].to_vec())
+                .collect())
+        }
+
+        fn model_name(&self) -> &'static str {
+            "small-embed"
         }
     }
 
@@ -649,6 +1726,8 @@ mod tests {
                 since_ts_ms: None,
                 reindex: true,
                 batch_size: 8,
+                chunking: ChunkingOptions::default(),
+                max_chunks: None,
             },
         )
         .expect_err("provider output count mismatch should fail");
@@ -662,21 +1741,114 @@ mod tests {
     #[test]
     fn index_history_rejects_embedding_dimension_mismatch() -> Result<()> {
         let (_temp, mut db, _session_id) = seeded_db()?;
-        let err = index_history(
+        let options = RagIndexOptions {
+            session_id: None,
+            since_ts_ms: None,
+            reindex: false,
+            batch_size: 8,
+            chunking: ChunkingOptions::default(),
+            max_chunks: None,
+        };
+        index_history(&mut db, &MockProvider, &options)?;
+
+        let rechunked = RagIndexOptions {
+            chunking: ChunkingOptions {
+                max_tokens: 2,
+                overlap_tokens: 0,
+            },
+            ..options.clone()
+        };
+        let err = index_history(&mut db, &WrongDimProvider, &rechunked)
+            .expect_err("wrong embedding dimension should fail");
+        assert!(err.to_string().contains("embedding dimension mismatch"));
+
+        // The failed run cached the new-dimension vectors, so the rebuild uses
+        // them without calling the provider again.
+        let rebuilt = index_history(
             &mut db,
             &WrongDimProvider,
             &RagIndexOptions {
-                session_id: None,
-                since_ts_ms: None,
                 reindex: true,
-                batch_size: 8,
+                ..rechunked
             },
+        )?;
+        assert_eq!((rebuilt.embedded, rebuilt.reused), (3, 3));
+        assert!(db.rag_model("mock-embed-v1", 8)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn index_history_keeps_models_in_separate_tables() -> Result<()> {
+        let (_temp, mut db, session_id) = seeded_db()?;
+        let options = RagIndexOptions {
+            session_id: None,
+            since_ts_ms: None,
+            reindex: false,
+            batch_size: 8,
+            chunking: ChunkingOptions::default(),
+            max_chunks: None,
+        };
+        index_history(&mut db, &MockProvider, &options)?;
+        let small = index_history(&mut db, &SmallProvider, &options)?;
+        assert_eq!(small.embedded, 1);
+        assert_eq!(small.skipped, 0);
+
+        let models = db.rag_models()?;
+        assert_eq!(models.len(), 2);
+        assert!(
+            models
+                .iter()
+                .any(|model| model.model == "small-embed" && model.dim == 8)
+        );
+        assert_ne!(models[0].table_name, models[1].table_name);
+
+        for hits in [
+            search_history(
+                &db,
+                &MockProvider,
+                "semantic",
+                &RagSearchFilters::default(),
+                5,
+            )?,
+            search_history(
+                &db,
+                &SmallProvider,
+                "semantic",
+                &RagSearchFilters::default(),
+                5,
+            )?,
+        ] {
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].session_id, session_id);
+        }
+
+        let err = search_history(
+            &db,
+            &StubModelProvider("unindexed"),
+            "semantic",
+            &RagSearchFilters::default(),
+            5,
         )
-        .expect_err("wrong embedding dimension should fail");
-        assert!(err.to_string().contains("embedding dimension mismatch"));
+        .expect_err("unindexed model should fail");
+        assert!(
+            err.to_string()
+                .contains("no semantic index for model unindexed")
+        );
         Ok(())
     }
 
+    struct StubModelProvider(&'static str);
+
+    impl EmbeddingProvider for StubModelProvider {
+        fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|text| test_embedding(text)).collect())
+        }
+
+        fn model_name(&self) -> &str {
+            self.0
+        }
+    }
+
     #[test]
     fn search_history_rejects_missing_query_embedding() -> Result<()> {
         let (_temp, db, _session_id) = seeded_db()?;
@@ -698,7 +1870,19 @@ mod tests {
 
     #[test]
     fn search_history_rejects_query_embedding_dimension_mismatch() -> Result<()> {
-        let (_temp, db, _session_id) = seeded_db()?;
+        let (_temp, mut db, _session_id) = seeded_db()?;
+        index_history(
+            &mut db,
+            &MockProvider,
+            &RagIndexOptions {
+                session_id: None,
+                since_ts_ms: None,
+                reindex: false,
+                batch_size: 8,
+                chunking: ChunkingOptions::default(),
+                max_chunks: None,
+            },
+        )?;
         let err = search_history(
             &db,
             &WrongDimProvider,
@@ -723,4 +1907,120 @@ mod tests {
         assert!(err.to_string().contains("must not be empty"));
         Ok(())
     }
+
+    fn vector_chunk(session_id: &str, chunk_id: i64, embedding: Vec<f32>) -> RagChunkRecord {
+        RagChunkRecord {
+            chunk_id,
+            embedding,
+            session_id: session_id.to_string(),
+            ts_ms: chunk_id,
+            tool_name: None,
+            kind: "assistant".to_string(),
+            model: "mock-small".to_string(),
+            content_hash: format!("hash-{chunk_id}"),
+            text: format!("{session_id} chunk {chunk_id}"),
+            source_event_id: chunk_id,
+            chunk_ordinal: 0,
+            span_start: 0,
+            span_end: 10,
+        }
+    }
+
+    #[test]
+    fn related_sessions_excludes_source_and_keeps_best_chunk_per_session() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        db.upsert_rag_chunks(&[
+            vector_chunk("sess-a", 1, vec![1.0, 0.0, 0.0, 0.0]),
+            vector_chunk("sess-a", 2, vec![0.9, 0.1, 0.0, 0.0]),
+            vector_chunk("sess-b", 3, vec![0.0, 0.0, 1.0, 0.0]),
+            vector_chunk("sess-b", 4, vec![1.0, 0.05, 0.0, 0.0]),
+            vector_chunk("sess-c", 5, vec![0.0, 0.0, 0.0, 1.0]),
+        ])?;
+
+        let related = related_sessions(&db, "mock-small", "sess-a", 5)?;
+        let ids = related
+            .iter()
+            .map(|session| session.session_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["sess-b", "sess-c"]);
+        assert_eq!(related[0].best_chunk.chunk_id, 4);
+        assert!(related[0].distance < related[1].distance);
+
+        let limited = related_sessions(&db, "mock-small", "sess-a", 1)?;
+        assert_eq!(limited.len(), 1);
+
+        let err = related_sessions(&db, "mock-small", "sess-missing", 5)
+            .expect_err("unindexed session should fail");
+        assert!(err.to_string().contains("has no indexed chunks"));
+        let err = related_sessions(&db, "other-model", "sess-a", 5)
+            .expect_err("unknown model should fail");
+        assert!(
+            err.to_string()
+                .contains("no semantic index for model other-model")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn related_sessions_skips_past_a_long_source_session() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let mut chunks: Vec<_> = (1..=40)
+            .map(|chunk_id| vector_chunk("sess-long", chunk_id, vec![1.0, 0.0, 0.0, 0.0]))
+            .collect();
+        chunks.push(vector_chunk("sess-far", 100, vec![0.0, 1.0, 0.0, 0.0]));
+        db.upsert_rag_chunks(&chunks)?;
+
+        let related = related_sessions(&db, "mock-small", "sess-long", 1)?;
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].session_id, "sess-far");
+        Ok(())
+    }
+
+    #[test]
+    fn search_model_falls_back_to_the_latest_indexed_model() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        assert_eq!(search_model(&db, "new-model")?, "new-model");
+
+        db.upsert_rag_chunks(&[vector_chunk("sess-a", 1, vec![1.0, 0.0, 0.0, 0.0])])?;
+        assert_eq!(search_model(&db, "new-model")?, "mock-small");
+        assert_eq!(search_model(&db, "mock-small")?, "mock-small");
+        Ok(())
+    }
+
+    fn context_hit(rank: i64, text: &str) -> RagSearchHit {
+        RagSearchHit {
+            chunk_id: rank,
+            distance: 0.1,
+            session_id: format!("session-{rank}"),
+            ts_ms: 1_700_000_000_000,
+            tool_name: None,
+            kind: "assistant".to_string(),
+            text: text.to_string(),
+            source_event_id: Some(rank),
+            chunk_ordinal: 0,
+            span_start: 0,
+            span_end: 4,
+        }
+    }
+
+    #[test]
+    fn format_context_block_references_sessions_and_respects_limit() {
+        let hits = vec![context_hit(1, "first"), context_hit(2, &"x".repeat(200))];
+
+        let block = format_context_block("  retry logic ", &hits, 4096).expect("block");
+        assert!(block.starts_with("Context retrieved from past sessions for \"retry logic\""));
+        assert!(block.contains("[1] session=session-1 ts=2023-11-14T22:13:20Z kind=assistant"));
+        assert!(block.contains("[2] session=session-2"));
+
+        let trimmed = format_context_block("retry logic", &hits, 160).expect("first hit fits");
+        assert!(trimmed.contains("session-1"));
+        assert!(!trimmed.contains("session-2"));
+        assert!(trimmed.len() <= 160);
+
+        assert!(format_context_block("retry logic", &hits, 10).is_none());
+        assert!(format_context_block("retry logic", &[], 4096).is_none());
+    }
 }
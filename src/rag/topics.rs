@@ -0,0 +1,417 @@
+use std::collections::{BTreeMap, HashMap};
+
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+
+use super::normalize;
+use crate::db::Database;
+
+const MAX_ITERATIONS: usize = 50;
+const MAX_DEFAULT_TOPICS: usize = 12;
+const MIN_TERM_CHARS: usize = 3;
+const STOPWORDS: &[&str] = &[
+    "about", "after", "again", "all", "also", "and", "any", "are", "because", "been", "before",
+    "being", "but", "can", "could", "did", "does", "doing", "don", "for", "from", "had", "has",
+    "have", "here", "how", "into", "its", "just", "let", "like", "more", "need", "not", "now",
+    "only", "other", "our", "out", "over", "should", "some", "such", "than", "that", "the",
+    "their", "them", "then", "there", "these", "they", "this", "those", "through", "use", "using",
+    "was", "were", "what", "when", "where", "which", "while", "who", "why", "will", "with",
+    "would", "you", "your",
+];
+
+/// A session assigned to a topic, with its cosine distance to the topic centroid.
+#[derive(Debug, Clone)]
+pub struct TopicMember {
+    pub session_id: String,
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TopicCluster {
+    /// Most distinctive full-text terms of the cluster's sessions.
+    pub terms: Vec<String>,
+    /// Members ordered from the most to the least central.
+    pub members: Vec<TopicMember>,
+}
+
+impl TopicCluster {
+    #[must_use]
+    pub fn label(&self) -> String {
+        if self.terms.is_empty() {
+            "(unlabelled)".to_string()
+        } else {
+            self.terms.join(", ")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TopicReport {
+    pub model: String,
+    pub sessions: usize,
+    /// Clusters ordered by size, largest first.
+    pub clusters: Vec<TopicCluster>,
+}
+
+/// Cluster indexed sessions into topics.
+///
+/// Each session is represented by the unit-length mean of its chunk vectors, and the
+/// sessions are grouped with spherical k-means seeded by farthest-first traversal, so
+/// repeated runs over the same index give the same topics. When `k` is `None` it
+/// defaults to `sqrt(sessions / 2)`, capped at twelve. Clusters are labelled with
+/// the full-text terms that are most frequent in the cluster relative to the rest of
+/// the index.
+///
+/// # Errors
+///
+/// Returns an error if the model has no semantic index or the database queries fail.
+pub fn topic_clusters(
+    db: &Database,
+    model: &str,
+    k: Option<usize>,
+    label_terms: usize,
+) -> Result<TopicReport> {
+    let Some(table) = db
+        .rag_models()?
+        .into_iter()
+        .find(|registered| registered.model == model)
+    else {
+        return Err(eyre!(
+            "no semantic index for model {model}; run `tx rag index --model {model}` first"
+        ));
+    };
+
+    let mut sums: BTreeMap<String, Vec<f32>> = BTreeMap::new();
+    db.visit_rag_embeddings(&table, |session_id, embedding| {
+        let sum = sums
+            .entry(session_id.to_string())
+            .or_insert_with(|| vec![0.0; embedding.len()]);
+        if sum.len() == embedding.len() {
+            for (slot, value) in sum.iter_mut().zip(embedding) {
+                *slot += value;
+            }
+        }
+    })?;
+
+    let mut session_ids = Vec::with_capacity(sums.len());
+    let mut points = Vec::with_capacity(sums.len());
+    for (session_id, mut sum) in sums {
+        normalize(&mut sum);
+        if sum.iter().any(|value| *value != 0.0) {
+            session_ids.push(session_id);
+            points.push(sum);
+        }
+    }
+
+    let mut report = TopicReport {
+        model: model.to_string(),
+        sessions: points.len(),
+        clusters: Vec::new(),
+    };
+    if points.is_empty() {
+        return Ok(report);
+    }
+
+    let k = k
+        .unwrap_or_else(|| default_topic_count(points.len()))
+        .clamp(1, points.len());
+    let (assignments, centroids) = kmeans(&points, k);
+
+    let mut members: Vec<Vec<TopicMember>> = vec![Vec::new(); centroids.len()];
+    for ((session_id, point), cluster) in session_ids.iter().zip(&points).zip(&assignments) {
+        members[*cluster].push(TopicMember {
+            session_id: session_id.clone(),
+            distance: cosine_distance(point, &centroids[*cluster]),
+        });
+    }
+    for cluster in &mut members {
+        cluster.sort_by(|a, b| {
+            a.distance
+                .total_cmp(&b.distance)
+                .then_with(|| a.session_id.cmp(&b.session_id))
+        });
+    }
+    members.retain(|cluster| !cluster.is_empty());
+    members.sort_by(|a, b| {
+        b.len()
+            .cmp(&a.len())
+            .then_with(|| a[0].session_id.cmp(&b[0].session_id))
+    });
+
+    let global = db.fts_term_session_counts(&session_ids)?;
+    for cluster in members {
+        let ids = cluster
+            .iter()
+            .map(|member| member.session_id.clone())
+            .collect::<Vec<_>>();
+        let local = db.fts_term_session_counts(&ids)?;
+        let terms = label_cluster(&local, ids.len(), &global, session_ids.len(), label_terms);
+        report.clusters.push(TopicCluster {
+            terms,
+            members: cluster,
+        });
+    }
+    Ok(report)
+}
+
+fn default_topic_count(sessions: usize) -> usize {
+    let mut k = 1;
+    while (k + 1) * (k + 1) * 2 <= sessions {
+        k += 1;
+    }
+    k.min(MAX_DEFAULT_TOPICS)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    (1.0 - dot(a, b)).max(0.0)
+}
+
+fn nearest(point: &[f32], centroids: &[Vec<f32>]) -> usize {
+    let mut best = 0;
+    let mut best_distance = f32::INFINITY;
+    for (index, centroid) in centroids.iter().enumerate() {
+        let distance = cosine_distance(point, centroid);
+        if distance < best_distance {
+            best = index;
+            best_distance = distance;
+        }
+    }
+    best
+}
+
+/// Spherical k-means over unit vectors; returns each point's cluster and the centroids.
+fn kmeans(points: &[Vec<f32>], k: usize) -> (Vec<usize>, Vec<Vec<f32>>) {
+    let mut centroids = farthest_first(points, k);
+    let mut assignments = vec![usize::MAX; points.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (point, assignment) in points.iter().zip(assignments.iter_mut()) {
+            let cluster = nearest(point, &centroids);
+            if *assignment != cluster {
+                *assignment = cluster;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![vec![0.0_f32; points[0].len()]; centroids.len()];
+        for (point, cluster) in points.iter().zip(&assignments) {
+            for (slot, value) in sums[*cluster].iter_mut().zip(point) {
+                *slot += value;
+            }
+        }
+        for (centroid, mut sum) in centroids.iter_mut().zip(sums) {
+            normalize(&mut sum);
+            if sum.iter().any(|value| *value != 0.0) {
+                *centroid = sum;
+            }
+        }
+    }
+    (assignments, centroids)
+}
+
+/// Seed centroids with the most central point, then repeatedly the point farthest
+/// from every centroid chosen so far.
+fn farthest_first(points: &[Vec<f32>], k: usize) -> Vec<Vec<f32>> {
+    let mut mean = vec![0.0_f32; points[0].len()];
+    for point in points {
+        for (slot, value) in mean.iter_mut().zip(point) {
+            *slot += value;
+        }
+    }
+    normalize(&mut mean);
+    let first = nearest(&mean, points);
+
+    let mut centroids = vec![points[first].clone()];
+    let mut closest = points
+        .iter()
+        .map(|point| cosine_distance(point, &points[first]))
+        .collect::<Vec<_>>();
+    while centroids.len() < k {
+        let Some((next, _)) = closest
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1).then_with(|| b.0.cmp(&a.0)))
+        else {
+            break;
+        };
+        let seed = points[next].clone();
+        for (distance, point) in closest.iter_mut().zip(points) {
+            *distance = distance.min(cosine_distance(point, &seed));
+        }
+        centroids.push(seed);
+    }
+    centroids
+}
+
+/// Rank terms by in-cluster session frequency weighted by inverse frequency across
+/// every clustered session.
+fn label_cluster(
+    local: &HashMap<String, usize>,
+    cluster_size: usize,
+    global: &HashMap<String, usize>,
+    total_sessions: usize,
+    limit: usize,
+) -> Vec<String> {
+    #[allow(clippy::cast_precision_loss)]
+    let score = |term: &str, count: usize| {
+        let global_count = global.get(term).copied().unwrap_or(count).max(1);
+        let coverage = count as f64 / cluster_size as f64;
+        let rarity = (total_sessions as f64 / global_count as f64).ln();
+        coverage * (1.0 + rarity)
+    };
+
+    let mut ranked = local
+        .iter()
+        .filter(|(term, _)| is_label_term(term))
+        .map(|(term, count)| (score(term, *count), *count, term.as_str()))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| b.1.cmp(&a.1))
+            .then_with(|| a.2.cmp(b.2))
+    });
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(_, _, term)| term.to_string())
+        .collect()
+}
+
+fn is_label_term(term: &str) -> bool {
+    term.chars().count() >= MIN_TERM_CHARS
+        && term.chars().any(char::is_alphabetic)
+        && !STOPWORDS.contains(&term)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RagChunkRecord;
+    use crate::session::{MessageRecord, SessionIngest, SessionSummary};
+    use crate::test_support::session_summary;
+    use tempfile::TempDir;
+
+    fn insert_session(db: &mut Database, temp: &TempDir, id: &str, text: &str) -> Result<()> {
+        let summary = SessionSummary {
+            path: temp.path().join(format!("{id}.jsonl")),
+            first_prompt: Some(text.to_string()),
+            ..session_summary(id, 1)
+        };
+        let message = MessageRecord::new(id.to_string(), 0, "user", text, None, Some(1));
+        db.upsert_session(&SessionIngest::new(summary, vec![message]))?;
+        Ok(())
+    }
+
+    fn chunk(session_id: &str, chunk_id: i64, embedding: Vec<f32>) -> RagChunkRecord {
+        RagChunkRecord {
+            chunk_id,
+            embedding,
+            session_id: session_id.to_string(),
+            ts_ms: chunk_id,
+            tool_name: None,
+            kind: "user".to_string(),
+            model: "mock-small".to_string(),
+            content_hash: format!("hash-{chunk_id}"),
+            text: session_id.to_string(),
+            source_event_id: chunk_id,
+            chunk_ordinal: 0,
+            span_start: 0,
+            span_end: 1,
+        }
+    }
+
+    #[test]
+    fn topic_clusters_groups_sessions_and_labels_with_fts_terms() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let sessions = [
+            ("auth-1", "fix the oauth token refresh", vec![1.0, 0.1, 0.0]),
+            (
+                "auth-2",
+                "oauth token expiry in the login flow",
+                vec![0.9, 0.0, 0.1],
+            ),
+            ("auth-3", "refresh oauth cookies", vec![1.0, 0.0, 0.0]),
+            (
+                "db-1",
+                "sqlite migration for the vacuum job",
+                vec![0.0, 1.0, 0.1],
+            ),
+            ("db-2", "sqlite vacuum is slow", vec![0.1, 0.9, 0.0]),
+        ];
+        let mut chunks = Vec::new();
+        for (index, (id, text, embedding)) in sessions.iter().enumerate() {
+            insert_session(&mut db, &temp, id, text)?;
+            chunks.push(chunk(
+                id,
+                i64::try_from(index).unwrap() + 1,
+                embedding.clone(),
+            ));
+        }
+        db.upsert_rag_chunks(&chunks)?;
+
+        let report = topic_clusters(&db, "mock-small", Some(2), 2)?;
+        assert_eq!(report.sessions, 5);
+        assert_eq!(report.clusters.len(), 2);
+
+        let auth = &report.clusters[0];
+        let auth_ids = auth
+            .members
+            .iter()
+            .map(|member| member.session_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(auth_ids.len(), 3);
+        assert!(auth_ids.iter().all(|id| id.starts_with("auth-")));
+        assert_eq!(auth.terms[0], "oauth");
+        assert!(!auth.terms.contains(&"the".to_string()));
+
+        let db_cluster = &report.clusters[1];
+        assert_eq!(db_cluster.terms, vec!["sqlite", "vacuum"]);
+        assert_eq!(db_cluster.label(), "sqlite, vacuum");
+
+        let again = topic_clusters(&db, "mock-small", Some(2), 2)?;
+        assert_eq!(again.clusters[0].terms, auth.terms);
+        Ok(())
+    }
+
+    #[test]
+    fn fts_term_session_counts_accepts_more_ids_than_sqlite_variables() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        insert_session(&mut db, &temp, "auth-1", "oauth token")?;
+        insert_session(&mut db, &temp, "auth-2", "oauth cookies")?;
+
+        let mut ids: Vec<String> = (0..40_000)
+            .map(|index| format!("missing-{index}"))
+            .collect();
+        ids.extend(["auth-1".to_string(), "auth-2".to_string()]);
+        let counts = db.fts_term_session_counts(&ids)?;
+        assert_eq!(counts.get("oauth"), Some(&2));
+        assert_eq!(counts.get("token"), Some(&1));
+        Ok(())
+    }
+
+    #[test]
+    fn topic_clusters_requires_an_index() -> Result<()> {
+        let temp = TempDir::new()?;
+        let db = Database::open(&temp.path().join("tx.sqlite3"))?;
+        let err = topic_clusters(&db, "mock-small", None, 3).expect_err("no index");
+        assert!(err.to_string().contains("no semantic index"));
+        Ok(())
+    }
+
+    #[test]
+    fn default_topic_count_grows_with_sessions() {
+        assert_eq!(default_topic_count(1), 1);
+        assert_eq!(default_topic_count(8), 2);
+        assert_eq!(default_topic_count(50), 5);
+        assert_eq!(default_topic_count(10_000), MAX_DEFAULT_TOPICS);
+    }
+}
@@ -0,0 +1,311 @@
+//! Secret references in provider `env` entries.
+//!
+//! `${secret:NAME}` names a `[secrets.NAME]` entry. Plans carry the reference
+//! and show it as [`MASK`]; the value is only fetched when the plan executes,
+//! so `--emit-command`, `--emit-json`, and the TUI never see it. Values loaded
+//! from `env_file` dotenv files are masked the same way and read again at launch.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+
+use color_eyre::Result;
+use color_eyre::eyre::{WrapErr, eyre};
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::model::{SecretConfig, SecretSource};
+use crate::dotenv;
+
+/// Placeholder shown wherever a secret value would appear.
+pub const MASK: &str = "***";
+
+static SECRET_TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\{secret:([A-Za-z0-9_.-]+)\}").unwrap());
+
+/// A piece of an environment value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvPart {
+    Text(String),
+    Secret(SecretConfig),
+    /// The value of `key` in the dotenv file at `path`.
+    Dotenv {
+        path: PathBuf,
+        key: String,
+    },
+}
+
+/// An environment entry whose value includes secrets or dotenv values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretEnv {
+    /// Position of the masked entry in the plan's `env`.
+    pub index: usize,
+    pub parts: Vec<EnvPart>,
+}
+
+impl SecretEnv {
+    /// The value with every secret replaced by [`MASK`].
+    #[must_use]
+    pub fn masked(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                EnvPart::Text(text) => text.as_str(),
+                EnvPart::Secret(_) | EnvPart::Dotenv { .. } => MASK,
+            })
+            .collect()
+    }
+
+    /// Fetch each secret and dotenv value and assemble the real value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when any secret cannot be resolved, or a dotenv file no
+    /// longer sets its key.
+    pub fn resolve(&self) -> Result<String> {
+        let mut value = String::new();
+        for part in &self.parts {
+            match part {
+                EnvPart::Text(text) => value.push_str(text),
+                EnvPart::Secret(secret) => value.push_str(&resolve(secret)?),
+                EnvPart::Dotenv { path, key } => value.push_str(&read_dotenv(path, key)?),
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Read `key` from a dotenv file; the last assignment wins, as when layering.
+fn read_dotenv(path: &Path, key: &str) -> Result<String> {
+    let entries =
+        dotenv::load(path)?.ok_or_else(|| eyre!("{} no longer exists", path.display()))?;
+    entries
+        .into_iter()
+        .rev()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+        .ok_or_else(|| eyre!("{} no longer sets {key}", path.display()))
+}
+
+/// Names of the secrets `template` references, in order.
+pub fn references(template: &str) -> impl Iterator<Item = &str> {
+    SECRET_TOKEN
+        .captures_iter(template)
+        .map(|caps| caps.extract::<1>().1[0])
+}
+
+/// Split `template` into literal text and the secrets it references.
+///
+/// # Errors
+///
+/// Returns an error when a reference names an undefined secret.
+pub fn split(template: &str, secrets: &IndexMap<String, SecretConfig>) -> Result<Vec<EnvPart>> {
+    let mut parts = Vec::new();
+    let mut last = 0;
+    for caps in SECRET_TOKEN.captures_iter(template) {
+        let mat = caps.get_match();
+        let (_, [name]) = caps.extract();
+        let secret = secrets.get(name).ok_or_else(|| {
+            eyre!("unknown secret '{name}' — define it under [secrets.{name}] in configuration")
+        })?;
+        if mat.start() > last {
+            parts.push(EnvPart::Text(template[last..mat.start()].to_string()));
+        }
+        parts.push(EnvPart::Secret(secret.clone()));
+        last = mat.end();
+    }
+    if last < template.len() || parts.is_empty() {
+        parts.push(EnvPart::Text(template[last..].to_string()));
+    }
+    Ok(parts)
+}
+
+/// Fetch a secret from its source.
+///
+/// One trailing newline is removed, as most tools print one after the value.
+///
+/// # Errors
+///
+/// Returns an error when the command fails, the file is missing or readable by
+/// others, `secret-tool` finds nothing, or the value is empty.
+pub fn resolve(secret: &SecretConfig) -> Result<String> {
+    let raw = match &secret.source {
+        SecretSource::Command { argv } => run(&argv[0], &argv[1..]),
+        SecretSource::File { path } => read_file(path),
+        SecretSource::SecretTool { attributes } => {
+            let mut args = vec!["lookup".to_string()];
+            for (key, value) in attributes {
+                args.push(key.clone());
+                args.push(value.clone());
+            }
+            run("secret-tool", &args)
+        }
+    }
+    .wrap_err_with(|| {
+        format!(
+            "failed to resolve secret '{}' from {}",
+            secret.name,
+            secret.source.describe()
+        )
+    })?;
+
+    let value = raw.strip_suffix('\n').map_or(raw.as_str(), |value| {
+        value.strip_suffix('\r').unwrap_or(value)
+    });
+    if value.is_empty() {
+        return Err(eyre!("secret '{}' resolved to an empty value", secret.name));
+    }
+    Ok(value.to_string())
+}
+
+/// Check that `path` is a regular file only its owner can access.
+///
+/// # Errors
+///
+/// Returns an error when the file is missing, not a regular file, or grants
+/// any permission to group or others.
+pub fn check_file_permissions(path: &Path) -> Result<()> {
+    let metadata =
+        fs::metadata(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    if !metadata.is_file() {
+        return Err(eyre!("{} is not a regular file", path.display()));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Err(eyre!(
+                "{} is accessible by group or others (mode {mode:04o}); run chmod 600 on it",
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    check_file_permissions(path)?;
+    fs::read_to_string(path).wrap_err_with(|| format!("failed to read {}", path.display()))
+}
+
+fn run(program: &str, args: &[String]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .wrap_err_with(|| format!("failed to run '{program}'"))?;
+    if !output.status.success() {
+        return Err(eyre!("'{program}' exited with status {}", output.status));
+    }
+    String::from_utf8(output.stdout).map_err(|_| eyre!("'{program}' printed non-UTF-8 output"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn secret(name: &str, source: SecretSource) -> SecretConfig {
+        SecretConfig {
+            name: name.into(),
+            source,
+        }
+    }
+
+    #[test]
+    fn split_masks_and_resolves_references() -> Result<()> {
+        let mut secrets = IndexMap::new();
+        secrets.insert(
+            "token".to_string(),
+            secret(
+                "token",
+                SecretSource::Command {
+                    argv: vec!["printf".into(), "s3cret\\n".into()],
+                },
+            ),
+        );
+
+        let pending = SecretEnv {
+            index: 0,
+            parts: split("Bearer ${secret:token}", &secrets)?,
+        };
+        assert_eq!(pending.masked(), "Bearer ***");
+        assert_eq!(pending.resolve()?, "Bearer s3cret");
+        assert_eq!(
+            references("${secret:a}-${env:B}-${secret:c.d}").collect::<Vec<_>>(),
+            ["a", "c.d"]
+        );
+
+        let err = split("${secret:missing}", &secrets).unwrap_err();
+        assert!(err.to_string().contains("unknown secret 'missing'"));
+        Ok(())
+    }
+
+    #[test]
+    fn dotenv_parts_are_masked_and_read_at_resolve_time() -> Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().join(".env");
+        fs::write(&path, "TOKEN=first\nTOKEN=second\n")?;
+        let pending = SecretEnv {
+            index: 0,
+            parts: vec![EnvPart::Dotenv {
+                path: path.clone(),
+                key: "TOKEN".into(),
+            }],
+        };
+        assert_eq!(pending.masked(), MASK);
+        assert_eq!(pending.resolve()?, "second");
+
+        fs::write(&path, "OTHER=1\n")?;
+        let err = pending.resolve().unwrap_err();
+        assert!(err.to_string().contains("no longer sets TOKEN"));
+        Ok(())
+    }
+
+    #[test]
+    fn file_secrets_require_owner_only_permissions() -> Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().join("token");
+        fs::write(&path, "from-file\n")?;
+        let file = secret("file", SecretSource::File { path: path.clone() });
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644))?;
+        let err = resolve(&file).unwrap_err();
+        let message = format!("{err:?}");
+        assert!(message.contains("failed to resolve secret 'file'"));
+        assert!(message.contains("accessible by group or others (mode 0644)"));
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        assert_eq!(resolve(&file)?, "from-file");
+        Ok(())
+    }
+
+    #[test]
+    fn command_secrets_report_failures_and_empty_output() {
+        let failing = secret(
+            "failing",
+            SecretSource::Command {
+                argv: vec!["false".into()],
+            },
+        );
+        let message = format!("{:?}", resolve(&failing).unwrap_err());
+        assert!(message.contains("'false' exited with status"));
+
+        let empty = secret(
+            "empty",
+            SecretSource::Command {
+                argv: vec!["true".into()],
+            },
+        );
+        assert_eq!(
+            resolve(&empty).unwrap_err().to_string(),
+            "secret 'empty' resolved to an empty value"
+        );
+    }
+}
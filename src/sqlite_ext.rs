@@ -2,6 +2,8 @@ use std::sync::OnceLock;
 
 use color_eyre::Result;
 use color_eyre::eyre::eyre;
+use rusqlite::Connection;
+use rusqlite::auto_extension::{init_auto_extension, register_auto_extension};
 use rusqlite::ffi::{self, sqlite3_auto_extension};
 use sqlite_vec::sqlite3_vec_init;
 
@@ -18,7 +20,8 @@ static SQLITE_EXTENSIONS_INIT: OnceLock<InitStatus> = OnceLock::new();
 ///
 /// # Errors
 ///
-/// Returns an error if sqlite-vec cannot be registered as an auto-extension.
+/// Returns an error if sqlite-vec or the tx SQL functions cannot be registered
+/// as auto-extensions.
 pub fn init_sqlite_extensions() -> Result<()> {
     let status = SQLITE_EXTENSIONS_INIT.get_or_init(|| {
         // SAFETY:
@@ -30,12 +33,31 @@ pub fn init_sqlite_extensions() -> Result<()> {
                 std::mem::transmute::<*const (), AutoExtensionEntry>(sqlite3_vec_init as *const ());
             sqlite3_auto_extension(Some(init_fn))
         };
-        registration_status_from_rc(rc)
+        registration_status_from_rc(rc)?;
+        // SAFETY: tx_functions_init only registers functions on the connection it is
+        // handed; it never opens, closes, or registers other connections or extensions.
+        unsafe { register_auto_extension(tx_functions_init) }
+            .map_err(|err| format!("failed to register tx SQL functions: {err}"))
     });
 
     init_result_from_status(status)
 }
 
+unsafe extern "C" fn tx_functions_init(
+    db: *mut ffi::sqlite3,
+    pz_err_msg: *mut *mut std::os::raw::c_char,
+    _: *const ffi::sqlite3_api_routines,
+) -> std::os::raw::c_int {
+    // SAFETY: SQLite passes a live connection handle for the duration of the call.
+    unsafe { init_auto_extension(db, pz_err_msg, register_tx_functions) }
+}
+
+// `AutoExtension` hands the connection over by value.
+#[allow(clippy::needless_pass_by_value)]
+fn register_tx_functions(conn: Connection) -> rusqlite::Result<()> {
+    crate::db::register_functions(&conn)
+}
+
 fn registration_status_from_rc(rc: std::os::raw::c_int) -> InitStatus {
     if rc == ffi::SQLITE_OK {
         Ok(())
@@ -58,7 +80,6 @@ fn init_result_from_status(status: &InitStatus) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rusqlite::Connection;
 
     #[test]
     fn init_sqlite_extensions_is_idempotent_and_registers_vec() -> Result<()> {
@@ -68,6 +89,9 @@ mod tests {
         let conn = Connection::open_in_memory()?;
         let version: String = conn.query_row("SELECT vec_version()", [], |row| row.get(0))?;
         assert!(version.starts_with('v'));
+        let missing: Option<String> =
+            conn.query_row("SELECT tx_unzstd(NULL)", [], |row| row.get(0))?;
+        assert_eq!(missing, None);
         Ok(())
     }
 
@@ -0,0 +1,921 @@
+//! Template language shared by wrappers, snippets, and terminal titles.
+//!
+//! ```text
+//! {{provider}}                          substitute a value
+//! {{var:ticket | default: "none"}}      apply filters left to right
+//! {{#if session.id}}…{{else}}…{{/if}}   render a branch when the value is set
+//! {{#unless var:quiet}}…{{/unless}}     render a branch when the value is unset
+//! \{{                                   a literal "{{"
+//! ```
+//!
+//! A value is set when it is present, non-empty, and not `false`. Templates are
+//! parsed up front so `tx config lint` can report errors with their line and
+//! column before anything runs.
+
+use std::collections::HashMap;
+
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use time::format_description::{self, OwnedFormatItem};
+
+/// Values a template can reference.
+#[derive(Debug)]
+pub struct TemplateContext<'a> {
+    /// The assembled provider pipeline. Unset while snippets are rendered.
+    pub pipeline: Option<&'a str>,
+    /// The rendered terminal title. Only set while wrappers are rendered.
+    pub title: Option<&'a str>,
+    pub provider: &'a str,
+    pub session_id: Option<&'a str>,
+    pub session_label: Option<&'a str>,
+    pub session_path: Option<&'a str>,
+    pub session_resume_token: Option<&'a str>,
+    pub session_thread_name: Option<&'a str>,
+    pub cwd: &'a str,
+    pub vars: &'a HashMap<String, String>,
+}
+
+/// How `{{CMD}}` is substituted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmdMode {
+    /// Insert the pipeline as-is, e.g. as one argv entry.
+    Raw,
+    /// Single-quote the pipeline so it survives a shell command line.
+    Shell,
+}
+
+/// A parse or render failure with its 1-based position in the template source.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}, column {column}: {message}")]
+pub struct TemplateError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl TemplateError {
+    fn at(source: &str, offset: usize, message: impl Into<String>) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        Self {
+            line,
+            column: before[line_start..].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+}
+
+/// A parsed template, ready to render against a [`TemplateContext`].
+#[derive(Debug)]
+pub struct Template {
+    source: String,
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Expr(Expr),
+    If {
+        cond: Expr,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Debug)]
+struct Expr {
+    offset: usize,
+    operand: Operand,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug)]
+enum Operand {
+    Name(Name),
+    Literal(String),
+}
+
+#[derive(Debug)]
+enum Name {
+    Cmd,
+    Title,
+    Provider,
+    Cwd,
+    Now,
+    SessionId,
+    SessionLabel,
+    SessionPath,
+    SessionResumeToken,
+    SessionThread,
+    Var(String),
+    Env(String),
+}
+
+#[derive(Debug)]
+struct Filter {
+    offset: usize,
+    kind: FilterKind,
+}
+
+#[derive(Debug)]
+enum FilterKind {
+    Default(String),
+    ShellQuote,
+    Upper,
+    Lower,
+    Title,
+    Snake,
+    Kebab,
+    Date(OwnedFormatItem),
+}
+
+impl Template {
+    /// Parse a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for unknown placeholders or filters, malformed tags, and
+    /// unbalanced `{{#if}}` blocks.
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut parser = Parser {
+            src: source,
+            pos: 0,
+        };
+        let (nodes, end) = parser.parse_nodes()?;
+        if let Some(tag) = end {
+            return Err(parser.error(
+                tag.offset(),
+                format!("{} without a matching {{{{#if}}}}", tag.describe()),
+            ));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            nodes,
+        })
+    }
+
+    /// Render the template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a referenced variable or environment variable is
+    /// missing, `{{CMD}}` is used outside a wrapper, or a filter cannot apply.
+    pub fn render(
+        &self,
+        ctx: &TemplateContext<'_>,
+        mode: CmdMode,
+    ) -> Result<String, TemplateError> {
+        let mut out = String::new();
+        self.render_nodes(&self.nodes, ctx, mode, &mut out)?;
+        Ok(out)
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        ctx: &TemplateContext<'_>,
+        mode: CmdMode,
+        out: &mut String,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Expr(expr) => match self.eval(expr, ctx, mode)? {
+                    Some(value) => out.push_str(&value),
+                    None => {
+                        if let Operand::Name(name) = &expr.operand
+                            && let Some(message) = name.missing_message()
+                        {
+                            return Err(self.error(expr.offset, message));
+                        }
+                    }
+                },
+                Node::If {
+                    cond,
+                    negate,
+                    then,
+                    otherwise,
+                } => {
+                    let set = self
+                        .eval(cond, ctx, mode)?
+                        .is_some_and(|value| !value.is_empty() && value != "false");
+                    let branch = if set == *negate { otherwise } else { then };
+                    self.render_nodes(branch, ctx, mode, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn eval(
+        &self,
+        expr: &Expr,
+        ctx: &TemplateContext<'_>,
+        mode: CmdMode,
+    ) -> Result<Option<String>, TemplateError> {
+        let mut value = match &expr.operand {
+            Operand::Literal(text) => Some(text.clone()),
+            Operand::Name(name) => self.lookup(name, expr.offset, ctx, mode)?,
+        };
+        for filter in &expr.filters {
+            value = self.apply(filter, value)?;
+        }
+        Ok(value)
+    }
+
+    fn lookup(
+        &self,
+        name: &Name,
+        offset: usize,
+        ctx: &TemplateContext<'_>,
+        mode: CmdMode,
+    ) -> Result<Option<String>, TemplateError> {
+        let value = match name {
+            Name::Cmd => {
+                let pipeline = ctx.pipeline.ok_or_else(|| {
+                    self.error(
+                        offset,
+                        "{{CMD}} is only available in wrappers and terminal titles",
+                    )
+                })?;
+                Some(match mode {
+                    CmdMode::Raw => pipeline.to_string(),
+                    CmdMode::Shell => single_quote(pipeline),
+                })
+            }
+            Name::Title => ctx.title.map(str::to_string),
+            Name::Provider => Some(ctx.provider.to_string()),
+            Name::Cwd => Some(ctx.cwd.to_string()),
+            Name::Now => Some(OffsetDateTime::now_utc().unix_timestamp().to_string()),
+            Name::SessionId => ctx.session_id.map(str::to_string),
+            Name::SessionLabel => ctx.session_label.map(str::to_string),
+            Name::SessionPath => ctx.session_path.map(str::to_string),
+            Name::SessionResumeToken => ctx.session_resume_token.map(str::to_string),
+            Name::SessionThread => ctx.session_thread_name.map(str::to_string),
+            Name::Var(key) => ctx.vars.get(key).cloned(),
+            Name::Env(key) => std::env::var(key).ok(),
+        };
+        Ok(value)
+    }
+
+    fn apply(
+        &self,
+        filter: &Filter,
+        value: Option<String>,
+    ) -> Result<Option<String>, TemplateError> {
+        if let FilterKind::Default(fallback) = &filter.kind {
+            return Ok(match value {
+                Some(value) if !value.is_empty() => Some(value),
+                _ => Some(fallback.clone()),
+            });
+        }
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let result = match &filter.kind {
+            FilterKind::Default(_) => unreachable!("handled above"),
+            FilterKind::ShellQuote => single_quote(&value),
+            FilterKind::Upper => value.to_uppercase(),
+            FilterKind::Lower => value.to_lowercase(),
+            FilterKind::Title => title_case(&value),
+            FilterKind::Snake => words(&value).join("_"),
+            FilterKind::Kebab => words(&value).join("-"),
+            FilterKind::Date(format) => {
+                let time = parse_time(&value).ok_or_else(|| {
+                    self.error(
+                        filter.offset,
+                        format!(
+                            "date filter expects a unix timestamp or RFC 3339 time, got '{value}'"
+                        ),
+                    )
+                })?;
+                time.format(format).map_err(|err| {
+                    self.error(filter.offset, format!("date filter failed: {err}"))
+                })?
+            }
+        };
+        Ok(Some(result))
+    }
+
+    fn error(&self, offset: usize, message: impl Into<String>) -> TemplateError {
+        TemplateError::at(&self.source, offset, message)
+    }
+}
+
+impl Name {
+    fn parse(raw: &str) -> Option<Self> {
+        let name = match raw {
+            "CMD" => Name::Cmd,
+            "title" | "terminal_title" => Name::Title,
+            "provider" => Name::Provider,
+            "cwd" => Name::Cwd,
+            "now" => Name::Now,
+            "session.id" | "session_id" => Name::SessionId,
+            "session.label" | "session_label" => Name::SessionLabel,
+            "session.path" | "session_path" => Name::SessionPath,
+            "session.resume_token" | "session_resume_token" => Name::SessionResumeToken,
+            "session.thread_name" | "thread_name" => Name::SessionThread,
+            other => {
+                if let Some(key) = other
+                    .strip_prefix("var:")
+                    .or_else(|| other.strip_prefix("var."))
+                {
+                    return (!key.is_empty()).then(|| Name::Var(key.to_string()));
+                }
+                if let Some(key) = other
+                    .strip_prefix("env:")
+                    .or_else(|| other.strip_prefix("env."))
+                {
+                    return (!key.is_empty()).then(|| Name::Env(key.to_string()));
+                }
+                return None;
+            }
+        };
+        Some(name)
+    }
+
+    /// Error for names that must have a value when substituted directly.
+    fn missing_message(&self) -> Option<String> {
+        match self {
+            Name::Var(key) => Some(format!("missing value for variable '{key}'")),
+            Name::Env(key) => Some(format!("environment variable '{key}' not set")),
+            _ => None,
+        }
+    }
+}
+
+/// The tag that ended a run of nodes.
+#[derive(Debug)]
+enum EndTag {
+    Else(usize),
+    Close { offset: usize, negate: bool },
+}
+
+impl EndTag {
+    fn offset(&self) -> usize {
+        match self {
+            EndTag::Else(offset) | EndTag::Close { offset, .. } => *offset,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            EndTag::Else(_) => "{{else}}",
+            EndTag::Close { negate: false, .. } => "{{/if}}",
+            EndTag::Close { negate: true, .. } => "{{/unless}}",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Pipe,
+    Colon,
+    Comma,
+    End,
+}
+
+struct Parser<'s> {
+    src: &'s str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, offset: usize, message: impl Into<String>) -> TemplateError {
+        TemplateError::at(self.src, offset, message)
+    }
+
+    /// Parse text and tags until the end of input or an `{{else}}`/`{{/…}}` tag.
+    fn parse_nodes(&mut self) -> Result<(Vec<Node>, Option<EndTag>), TemplateError> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        while self.pos < self.src.len() {
+            let rest = &self.src[self.pos..];
+            if rest.starts_with("\\{{") {
+                text.push_str("{{");
+                self.pos += 3;
+                continue;
+            }
+            if !rest.starts_with("{{") {
+                let ch = rest.chars().next().expect("non-empty rest");
+                text.push(ch);
+                self.pos += ch.len_utf8();
+                continue;
+            }
+
+            if !text.is_empty() {
+                nodes.push(Node::Text(std::mem::take(&mut text)));
+            }
+            let tag_start = self.pos;
+            self.pos += 2;
+            self.skip_space();
+            let rest = &self.src[self.pos..];
+            if let Some(block) = ["#if", "#unless"]
+                .into_iter()
+                .find(|keyword| starts_with_keyword(rest, keyword))
+            {
+                self.pos += block.len();
+                let negate = block == "#unless";
+                let cond = self.parse_expr()?;
+                nodes.push(self.parse_block(tag_start, cond, negate)?);
+            } else if starts_with_keyword(rest, "else") {
+                self.pos += "else".len();
+                self.expect_end()?;
+                return Ok((nodes, Some(EndTag::Else(tag_start))));
+            } else if let Some(close) = ["/if", "/unless"]
+                .into_iter()
+                .find(|keyword| starts_with_keyword(rest, keyword))
+            {
+                self.pos += close.len();
+                self.expect_end()?;
+                let negate = close == "/unless";
+                return Ok((
+                    nodes,
+                    Some(EndTag::Close {
+                        offset: tag_start,
+                        negate,
+                    }),
+                ));
+            } else {
+                nodes.push(Node::Expr(self.parse_expr()?));
+            }
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        Ok((nodes, None))
+    }
+
+    fn parse_block(
+        &mut self,
+        start: usize,
+        cond: Expr,
+        negate: bool,
+    ) -> Result<Node, TemplateError> {
+        let opener = if negate { "{{#unless}}" } else { "{{#if}}" };
+        let (then, end) = self.parse_nodes()?;
+        let (otherwise, end) = match end {
+            Some(EndTag::Else(_)) => self.parse_nodes()?,
+            other => (Vec::new(), other),
+        };
+        match end {
+            Some(EndTag::Close { negate: closes, .. }) if closes == negate => Ok(Node::If {
+                cond,
+                negate,
+                then,
+                otherwise,
+            }),
+            Some(tag) => Err(self.error(
+                tag.offset(),
+                format!("{} does not close {opener}", tag.describe()),
+            )),
+            None => Err(self.error(start, format!("unclosed {opener}"))),
+        }
+    }
+
+    /// Parse `operand (| filter (: arg (, arg)*)?)*` up to and including `}}`.
+    fn parse_expr(&mut self) -> Result<Expr, TemplateError> {
+        self.skip_space();
+        let offset = self.pos;
+        let operand = match self.next_token()? {
+            Token::Ident(raw) if raw.starts_with(|ch: char| ch.is_ascii_digit()) => {
+                Operand::Literal(raw)
+            }
+            Token::Ident(raw) => Operand::Name(Name::parse(&raw).ok_or_else(|| {
+                self.error(
+                    offset,
+                    format!("unknown template placeholder '{{{{{raw}}}}}'"),
+                )
+            })?),
+            Token::Str(text) => Operand::Literal(text),
+            Token::End => return Err(self.error(offset, "empty template tag")),
+            other => return Err(self.error(offset, format!("unexpected {}", describe(&other)))),
+        };
+
+        let mut filters = Vec::new();
+        loop {
+            self.skip_space();
+            let token_start = self.pos;
+            match self.next_token()? {
+                Token::End => break,
+                Token::Pipe => filters.push(self.parse_filter()?),
+                other => {
+                    return Err(self.error(
+                        token_start,
+                        format!("expected '|' or '}}}}', found {}", describe(&other)),
+                    ));
+                }
+            }
+        }
+        Ok(Expr {
+            offset,
+            operand,
+            filters,
+        })
+    }
+
+    fn parse_filter(&mut self) -> Result<Filter, TemplateError> {
+        self.skip_space();
+        let offset = self.pos;
+        let name = match self.next_token()? {
+            Token::Ident(name) => name,
+            other => {
+                return Err(self.error(
+                    offset,
+                    format!("expected a filter name, found {}", describe(&other)),
+                ));
+            }
+        };
+
+        let mut args = Vec::new();
+        self.skip_space();
+        if self.src[self.pos..].starts_with(':') {
+            self.pos += 1;
+            loop {
+                self.skip_space();
+                let arg_start = self.pos;
+                match self.next_token()? {
+                    Token::Str(text) => args.push(text),
+                    Token::Ident(raw) if raw.starts_with(|ch: char| ch.is_ascii_digit()) => {
+                        args.push(raw);
+                    }
+                    other => {
+                        return Err(self.error(
+                            arg_start,
+                            format!("expected a quoted argument, found {}", describe(&other)),
+                        ));
+                    }
+                }
+                self.skip_space();
+                if self.src[self.pos..].starts_with(',') {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        let expect_args = |count: usize| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(self.error(
+                    offset,
+                    format!(
+                        "filter '{name}' takes {count} argument{}, got {}",
+                        if count == 1 { "" } else { "s" },
+                        args.len()
+                    ),
+                ))
+            }
+        };
+        let kind = match name.as_str() {
+            "default" => {
+                expect_args(1)?;
+                FilterKind::Default(args[0].clone())
+            }
+            "date" => {
+                expect_args(1)?;
+                let format = format_description::parse_owned::<2>(&args[0]).map_err(|err| {
+                    self.error(offset, format!("invalid date format '{}': {err}", args[0]))
+                })?;
+                FilterKind::Date(format)
+            }
+            simple => {
+                let kind = match simple {
+                    "shell_quote" => FilterKind::ShellQuote,
+                    "upper" => FilterKind::Upper,
+                    "lower" => FilterKind::Lower,
+                    "title" => FilterKind::Title,
+                    "snake" => FilterKind::Snake,
+                    "kebab" => FilterKind::Kebab,
+                    other => return Err(self.error(offset, format!("unknown filter '{other}'"))),
+                };
+                expect_args(0)?;
+                kind
+            }
+        };
+        Ok(Filter { offset, kind })
+    }
+
+    fn expect_end(&mut self) -> Result<(), TemplateError> {
+        self.skip_space();
+        let token_start = self.pos;
+        match self.next_token()? {
+            Token::End => Ok(()),
+            other => Err(self.error(
+                token_start,
+                format!("expected '}}}}', found {}", describe(&other)),
+            )),
+        }
+    }
+
+    fn skip_space(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn next_token(&mut self) -> Result<Token, TemplateError> {
+        let start = self.pos;
+        let rest = &self.src[self.pos..];
+        let Some(ch) = rest.chars().next() else {
+            return Err(self.error(start, "unterminated template tag; expected '}}'"));
+        };
+        let token = match ch {
+            '}' if rest.starts_with("}}") => {
+                self.pos += 2;
+                Token::End
+            }
+            '|' => {
+                self.pos += 1;
+                Token::Pipe
+            }
+            ':' => {
+                self.pos += 1;
+                Token::Colon
+            }
+            ',' => {
+                self.pos += 1;
+                Token::Comma
+            }
+            '"' => Token::Str(self.string_literal()?),
+            ch if is_ident_char(ch) => {
+                let mut end = 0;
+                for (index, ch) in rest.char_indices() {
+                    let continues = is_ident_char(ch)
+                        || (ch == ':' && rest[index + 1..].starts_with(is_ident_char));
+                    if !continues {
+                        break;
+                    }
+                    end = index + ch.len_utf8();
+                }
+                self.pos += end;
+                Token::Ident(rest[..end].to_string())
+            }
+            other => return Err(self.error(start, format!("unexpected character '{other}'"))),
+        };
+        Ok(token)
+    }
+
+    fn string_literal(&mut self) -> Result<String, TemplateError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut value = String::new();
+        let mut chars = self.src[self.pos..].chars();
+        while let Some(ch) = chars.next() {
+            self.pos += ch.len_utf8();
+            match ch {
+                '"' => return Ok(value),
+                '\\' => {
+                    let Some(escaped) = chars.next() else { break };
+                    self.pos += escaped.len_utf8();
+                    match escaped {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        '"' | '\\' => value.push(escaped),
+                        other => {
+                            return Err(self.error(
+                                self.pos - other.len_utf8() - 1,
+                                format!("unknown escape '\\{other}'"),
+                            ));
+                        }
+                    }
+                }
+                _ => value.push(ch),
+            }
+        }
+        Err(self.error(start, "unterminated string"))
+    }
+}
+
+fn is_ident_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '.' | '-')
+}
+
+/// Whether `rest` starts with `keyword` as a whole word.
+fn starts_with_keyword(rest: &str, keyword: &str) -> bool {
+    rest.strip_prefix(keyword)
+        .is_some_and(|after| !after.starts_with(|ch: char| is_ident_char(ch) || ch == ':'))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => format!("'{name}'"),
+        Token::Str(text) => format!("\"{text}\""),
+        Token::Pipe => "'|'".to_string(),
+        Token::Colon => "':'".to_string(),
+        Token::Comma => "','".to_string(),
+        Token::End => "'}}'".to_string(),
+    }
+}
+
+/// Quote `input` for a POSIX shell.
+#[must_use]
+pub fn single_quote(input: &str) -> String {
+    let mut quoted = String::with_capacity(input.len() + 2);
+    quoted.push('\'');
+    for ch in input.chars() {
+        if ch == '\'' {
+            quoted.push_str("'\\''");
+        } else {
+            quoted.push(ch);
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+fn title_case(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut start_of_word = true;
+    for ch in input.chars() {
+        if ch.is_alphanumeric() {
+            if start_of_word {
+                out.extend(ch.to_uppercase());
+            } else {
+                out.extend(ch.to_lowercase());
+            }
+            start_of_word = false;
+        } else {
+            out.push(ch);
+            start_of_word = true;
+        }
+    }
+    out
+}
+
+/// Lowercase words split on punctuation, whitespace, and camelCase boundaries.
+fn words(input: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for ch in input.chars() {
+        if !ch.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if ch.is_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+        current.extend(ch.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn parse_time(value: &str) -> Option<OffsetDateTime> {
+    let trimmed = value.trim();
+    if let Ok(seconds) = trimmed.parse::<i64>() {
+        return OffsetDateTime::from_unix_timestamp(seconds).ok();
+    }
+    OffsetDateTime::parse(trimmed, &Rfc3339).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, vars: &HashMap<String, String>) -> Result<String, TemplateError> {
+        let ctx = TemplateContext {
+            pipeline: Some("codex resume 'abc'"),
+            title: Some("codex: Fix login"),
+            provider: "codex",
+            session_id: Some("sess-1"),
+            session_label: None,
+            session_path: Some("/tmp/sessions/sess-1.jsonl"),
+            session_resume_token: None,
+            session_thread_name: Some("Fix loginFlow bug"),
+            cwd: "/work",
+            vars,
+        };
+        Template::parse(source)?.render(&ctx, CmdMode::Shell)
+    }
+
+    #[test]
+    fn renders_names_and_filters() {
+        let vars = HashMap::from([("ticket".to_string(), "42".to_string())]);
+        let cases = [
+            ("{{provider}} in {{cwd}}", "codex in /work"),
+            (
+                "{{title}}/{{terminal_title | kebab}}",
+                "codex: Fix login/codex-fix-login",
+            ),
+            ("{{ session.id }}/{{session_id}}", "sess-1/sess-1"),
+            ("{{thread_name | kebab}}", "fix-login-flow-bug"),
+            (
+                "{{session.thread_name | snake | upper}}",
+                "FIX_LOGIN_FLOW_BUG",
+            ),
+            ("{{thread_name | title}}", "Fix Loginflow Bug"),
+            ("{{session.label | default: \"none\"}}", "none"),
+            ("{{var:ticket | default: \"none\"}}", "42"),
+            ("{{var.missing | default: \"x\" | upper}}", "X"),
+            ("{{\"it's\" | shell_quote}}", "'it'\\''s'"),
+            ("{{CMD}}", "'codex resume '\\''abc'\\'''"),
+            ("{{\"0\" | date: \"[year]-[month]-[day]\"}}", "1970-01-01"),
+            (
+                "{{\"2024-03-05T10:00:00Z\" | date: \"[day]/[month]\"}}",
+                "05/03",
+            ),
+            ("\\{{literal}}", "{{literal}}"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(render(source, &vars).expect(source), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let vars = HashMap::from([
+            ("quiet".to_string(), "false".to_string()),
+            ("empty".to_string(), String::new()),
+        ]);
+        let cases = [
+            (
+                "{{#if session_id}}resume {{session_id}}{{/if}}",
+                "resume sess-1",
+            ),
+            ("{{#if session.label}}a{{else}}b{{/if}}", "b"),
+            ("{{#if var:quiet}}a{{else}}b{{/if}}", "b"),
+            ("{{#if var:empty}}a{{else}}b{{/if}}", "b"),
+            ("{{#unless var:missing}}loud{{/unless}}", "loud"),
+            (
+                "{{#if session_id}}{{#unless session.label}}unnamed{{/unless}}{{/if}}",
+                "unnamed",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(render(source, &vars).expect(source), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn missing_vars_error_only_when_substituted() {
+        let vars = HashMap::new();
+        let err = render("echo {{var:ticket}}", &vars).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1, column 8: missing value for variable 'ticket'"
+        );
+        assert_eq!(render("{{session.label}}", &vars).unwrap(), "");
+    }
+
+    #[test]
+    fn parse_errors_report_positions() {
+        let cases = [
+            ("{{nope}}", 1, 3, "unknown template placeholder '{{nope}}'"),
+            ("a\n  {{provider | shout}}", 2, 16, "unknown filter 'shout'"),
+            (
+                "{{provider | default}}",
+                1,
+                14,
+                "filter 'default' takes 1 argument, got 0",
+            ),
+            (
+                "{{provider | upper: \"x\"}}",
+                1,
+                14,
+                "filter 'upper' takes 0 arguments, got 1",
+            ),
+            ("x {{#if provider}}y", 1, 3, "unclosed {{#if}}"),
+            ("{{/if}}", 1, 1, "{{/if}} without a matching {{#if}}"),
+            (
+                "{{#if provider}}{{/unless}}",
+                1,
+                17,
+                "{{/unless}} does not close {{#if}}",
+            ),
+            ("{{provider | default: \"x}}", 1, 23, "unterminated string"),
+            (
+                "{{provider",
+                1,
+                11,
+                "unterminated template tag; expected '}}'",
+            ),
+            ("{{}}", 1, 3, "empty template tag"),
+        ];
+        for (source, line, column, message) in cases {
+            let err = Template::parse(source).unwrap_err();
+            assert_eq!(
+                err,
+                TemplateError {
+                    line,
+                    column,
+                    message: message.to_string(),
+                },
+                "{source}"
+            );
+        }
+    }
+}
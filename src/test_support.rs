@@ -1,7 +1,9 @@
 use std::ffi::{OsStr, OsString};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{LazyLock, Mutex};
 
+use crate::session::SessionSummary;
+
 /// Global mutex for tests that mutate process-wide environment variables.
 /// Many tests tweak `PATH` or other env vars; sharing a single lock avoids
 /// cross-test races when they run in parallel.
@@ -78,3 +80,28 @@ pub fn toml_path(path: &Path) -> String {
         rendered.to_string()
     }
 }
+
+/// Build a `codex` session summary stored at `/sessions/{id}.jsonl` that was
+/// created and last active at `last_active`. Tests override the fields they
+/// care about with struct update syntax.
+#[must_use]
+pub fn session_summary(id: &str, last_active: i64) -> SessionSummary {
+    SessionSummary {
+        id: id.to_string(),
+        provider: "codex".to_string(),
+        wrapper: None,
+        model: None,
+        label: None,
+        thread_name: None,
+        path: PathBuf::from(format!("/sessions/{id}.jsonl")),
+        uuid: None,
+        first_prompt: Some("hello".to_string()),
+        actionable: true,
+        subagent: false,
+        created_at: Some(last_active),
+        started_at: Some(last_active),
+        last_active: Some(last_active),
+        size: 1,
+        mtime: last_active,
+    }
+}
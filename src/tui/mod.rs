@@ -29,15 +29,20 @@ use std::borrow::Cow;
 use tui_markdown::from_str as md_to_text;
 
 use crate::app::{self, EmitMode, UiContext};
-use crate::config::model::SearchMode;
+use crate::commands::history;
+use crate::config::model::{ProfileVar, SearchMode};
+use crate::db::RunRecord;
 #[cfg(all(test, unix))]
 use crate::indexer::Indexer;
 use crate::pipeline::{
-    PipelinePlan, PipelineRequest, PromptInvocation, SessionContext, build_pipeline,
+    PipelinePlan, PipelineRequest, PromptInvocation, SessionContext, build_pipeline, missing_vars,
 };
 use crate::prompts::{PromptStatus, VirtualProfile};
 use crate::providers;
-use crate::session::{SearchHit, SessionQuery, Transcript, is_subagent_job_session_texts};
+use crate::rag;
+use crate::session::{
+    SearchHit, SessionQuery, SessionSummary, Transcript, is_subagent_job_session_texts,
+};
 use time::format_description::FormatItem;
 use time::macros::format_description;
 use time::{OffsetDateTime, UtcOffset};
@@ -45,9 +50,12 @@ use tracing::warn;
 use unicode_width::UnicodeWidthStr;
 
 const SESSION_LIMIT: usize = 200;
+const RELATED_LIMIT: usize = 20;
+const RUN_LIMIT: usize = 50;
+const TOPIC_LABEL_TERMS: usize = 3;
 const PREVIEW_MESSAGE_LIMIT: usize = 8;
 const MESSAGE_FILTER_MODE: &str = "Filtering results";
-const DEFAULT_STATUS_HINT: &str = "↑/↓ scroll  •  Tab emit  •  Enter run  •  Ctrl-Y print ID  •  Ctrl-E export  •  Ctrl-P filter  •  Ctrl-F search  •  Ctrl-G subagents  •  Esc quit";
+const DEFAULT_STATUS_HINT: &str = "↑/↓ scroll  •  Tab emit  •  Enter run  •  Ctrl-Y print ID  •  Ctrl-E export  •  Ctrl-P filter  •  Ctrl-F search  •  Ctrl-G subagents  •  Ctrl-R related  •  Ctrl-T topics  •  Ctrl-L launches  •  Esc quit";
 const RELATIVE_TIME_WIDTH: usize = 8;
 const PROFILE_IDENTIFIER_LIMIT: usize = 40;
 
@@ -61,7 +69,7 @@ const PROFILE_IDENTIFIER_LIMIT: usize = 40;
 // tmux smoke test; instrumenting it for coverage would require nested pseudo
 // terminals, so we exclude it from coverage accounting.
 #[cfg(all(not(test), not(coverage)))]
-pub fn run<'a>(ctx: &'a mut UiContext<'a>) -> Result<()> {
+pub fn run<'a>(ctx: &'a mut UiContext<'a>) -> Result<Option<PipelinePlan>> {
     let mut stdout = io::stdout();
     enable_raw_mode()?;
     execute!(stdout, crossterm::terminal::EnterAlternateScreen)?;
@@ -83,7 +91,7 @@ pub fn run<'a>(ctx: &'a mut UiContext<'a>) -> Result<()> {
 }
 
 #[cfg(any(test, coverage))]
-pub fn run<'a>(ctx: &'a mut UiContext<'a>) -> Result<()> {
+pub fn run<'a>(ctx: &'a mut UiContext<'a>) -> Result<Option<PipelinePlan>> {
     use ratatui::backend::TestBackend;
 
     let backend = TestBackend::new(80, 24);
@@ -183,7 +191,9 @@ where
     run_app(ctx, terminal, events)
 }
 
-fn dispatch_outcome(outcome: Option<Outcome>) -> Result<()> {
+/// Carry out the chosen action, returning the plan to execute if the user
+/// picked one; the caller runs it so the launch is recorded.
+fn dispatch_outcome(outcome: Option<Outcome>) -> Result<Option<PipelinePlan>> {
     match outcome {
         Some(Outcome::Emit(plan)) => app::emit_command(
             &plan,
@@ -191,20 +201,17 @@ fn dispatch_outcome(outcome: Option<Outcome>) -> Result<()> {
                 newline: true,
                 friendly: true,
             },
-        ),
-        Some(Outcome::Execute(plan)) => app::execute_plan(&plan),
-        Some(Outcome::PrintSessionId(session_id)) => {
-            println!("{session_id}");
-            Ok(())
-        }
+        )?,
+        Some(Outcome::Execute(plan)) => return Ok(Some(plan)),
+        Some(Outcome::PrintSessionId(session_id)) => println!("{session_id}"),
         Some(Outcome::ExportMarkdown(lines)) => {
             for line in lines {
                 println!("{line}");
             }
-            Ok(())
         }
-        None => Ok(()),
+        None => {}
     }
+    Ok(None)
 }
 
 #[derive(Debug, Clone)]
@@ -225,17 +232,95 @@ struct AppState<'ctx> {
     provider_order: Vec<String>,
     full_text: bool,
     show_subagent_sessions: bool,
+    view: Option<SessionView>,
     message: Option<String>,
     overlay_message: Option<(String, Instant)>,
     preview_cache: HashMap<String, Preview>,
     outcome: Option<Outcome>,
+    var_prompt: Option<VarPrompt>,
     list_state: ratatui::widgets::ListState,
 }
 
+/// Values being collected for a profile's required variables before it launches.
+#[derive(Debug, Clone)]
+struct VarPrompt {
+    profile: ProfileEntry,
+    execute: bool,
+    pending: Vec<ProfileVar>,
+    values: HashMap<String, String>,
+    input: String,
+    error: Option<String>,
+}
+
+impl VarPrompt {
+    fn status(&self) -> String {
+        let label = self
+            .pending
+            .first()
+            .map(ProfileVar::prompt_label)
+            .unwrap_or_default();
+        let prompt = format!(
+            "{} needs {label}: {}▏  •  Enter confirm  •  Esc cancel",
+            self.profile.display, self.input
+        );
+        match &self.error {
+            Some(error) => format!("{error}  •  {prompt}"),
+            None => prompt,
+        }
+    }
+}
+
+/// A fixed set of sessions shown in place of the regular session list.
+#[derive(Debug, Clone)]
+enum SessionView {
+    /// Sessions semantically close to `source`.
+    Related {
+        source: String,
+        sessions: Vec<SessionEntry>,
+    },
+    /// Topic clusters, browsed one topic at a time.
+    Topics {
+        topics: Vec<TopicEntry>,
+        index: usize,
+    },
+    /// Recorded launches from the run history, newest first.
+    Runs { runs: Vec<RunRecord> },
+}
+
+#[derive(Debug, Clone)]
+struct TopicEntry {
+    label: String,
+    sessions: Vec<SessionEntry>,
+}
+
+impl SessionView {
+    fn sessions(&self) -> &[SessionEntry] {
+        match self {
+            Self::Related { sessions, .. } => sessions,
+            Self::Topics { topics, index } => &topics[*index].sessions,
+            Self::Runs { .. } => &[],
+        }
+    }
+
+    fn status(&self) -> String {
+        match self {
+            Self::Related { source, .. } => format!("related to {source}  •  Ctrl-R back"),
+            Self::Topics { topics, index } => format!(
+                "topic {}/{}: {}  •  Ctrl-T next",
+                index + 1,
+                topics.len(),
+                topics[*index].label
+            ),
+            Self::Runs { .. } => "recent launches  •  Enter rerun  •  Ctrl-L back".to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Entry {
     Session(SessionEntry),
     Profile(ProfileEntry),
+    Run(RunRecord),
     Empty(EmptyEntry),
 }
 
@@ -269,7 +354,7 @@ struct ProfileEntry {
     provider: String,
     pre: Vec<String>,
     post: Vec<String>,
-    wrap: Option<String>,
+    wrap: Vec<String>,
     description: Option<String>,
     tags: Vec<String>,
     inline_pre: Vec<String>,
@@ -321,6 +406,22 @@ impl SessionEntry {
         }
     }
 
+    fn from_summary(summary: SessionSummary) -> Self {
+        Self {
+            id: summary.id,
+            provider: summary.provider,
+            wrapper: summary.wrapper,
+            label: summary.label,
+            thread_name: summary.thread_name,
+            first_prompt: summary.first_prompt,
+            actionable: summary.actionable,
+            subagent: summary.subagent,
+            last_active: summary.last_active,
+            snippet: None,
+            snippet_role: None,
+        }
+    }
+
     fn from_hit(hit: SearchHit) -> Self {
         Self {
             id: hit.session_id,
@@ -653,10 +754,12 @@ impl<'ctx> AppState<'ctx> {
             provider_order,
             full_text: matches!(defaults.search_mode, SearchMode::FullText),
             show_subagent_sessions: false,
+            view: None,
             message: None,
             overlay_message: None,
             preview_cache: HashMap::new(),
             outcome: None,
+            var_prompt: None,
             list_state: ratatui::widgets::ListState::default(),
         };
 
@@ -709,6 +812,10 @@ impl<'ctx> AppState<'ctx> {
     }
 
     fn status_message(&self) -> Option<String> {
+        if let Some(prompt) = &self.var_prompt {
+            return Some(prompt.status());
+        }
+
         if let Some((text, until)) = &self.overlay_message
             && Instant::now() < *until
         {
@@ -734,6 +841,10 @@ impl<'ctx> AppState<'ctx> {
             return Some(self.filter.clone());
         }
 
+        if let Some(view) = &self.view {
+            return Some(view.status());
+        }
+
         None
     }
 
@@ -743,9 +854,110 @@ impl<'ctx> AppState<'ctx> {
     }
 
     fn handle_key(&mut self, key: KeyEvent) -> Result<bool> {
+        if self.var_prompt.is_some() {
+            return self.handle_key_var_prompt(key);
+        }
         self.handle_key_normal(key)
     }
 
+    fn handle_key_var_prompt(&mut self, key: KeyEvent) -> Result<bool> {
+        let Some(prompt) = self.var_prompt.as_mut() else {
+            return Ok(false);
+        };
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) => {
+                self.var_prompt = None;
+                self.set_temporary_status_message(
+                    "launch cancelled".to_string(),
+                    Duration::from_secs(3),
+                );
+            }
+            (KeyCode::Enter, _) => self.submit_var_prompt()?,
+            (KeyCode::Backspace, _) => {
+                prompt.input.pop();
+            }
+            (KeyCode::Char(ch), mods) if mods.is_empty() || mods == KeyModifiers::SHIFT => {
+                prompt.input.push(ch);
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Validate the current answer and launch once every variable has a value.
+    fn submit_var_prompt(&mut self) -> Result<()> {
+        let Some(prompt) = self.var_prompt.as_mut() else {
+            return Ok(());
+        };
+        let Some(var) = prompt.pending.first() else {
+            return Ok(());
+        };
+        if prompt.input.is_empty() {
+            prompt.error = Some("a value is required".to_string());
+            return Ok(());
+        }
+        match var.validate(&prompt.input) {
+            Ok(value) => {
+                prompt.values.insert(var.name.clone(), value);
+                prompt.pending.remove(0);
+                prompt.input.clear();
+                prompt.error = None;
+            }
+            Err(err) => {
+                prompt.error = Some(err.to_string());
+                return Ok(());
+            }
+        }
+        if !prompt.pending.is_empty() {
+            return Ok(());
+        }
+
+        let Some(prompt) = self.var_prompt.take() else {
+            return Ok(());
+        };
+        if let Some(plan) = self.plan_for_profile_with_vars(&prompt.profile, prompt.values)? {
+            self.outcome = Some(if prompt.execute {
+                Outcome::Execute(plan)
+            } else {
+                Outcome::Emit(plan)
+            });
+        }
+        Ok(())
+    }
+
+    /// Build the selected entry's plan, first asking for any required profile variables.
+    fn launch_selected(&mut self, execute: bool) -> Result<()> {
+        if let Some(Entry::Profile(profile)) = self.entries.get(self.index)
+            && let ProfileKind::Config { name } = &profile.kind
+        {
+            let pending: Vec<ProfileVar> =
+                missing_vars(self.ctx.config, Some(name), &HashMap::new())
+                    .into_iter()
+                    .cloned()
+                    .collect();
+            if !pending.is_empty() {
+                self.var_prompt = Some(VarPrompt {
+                    profile: profile.clone(),
+                    execute,
+                    pending,
+                    values: HashMap::new(),
+                    input: String::new(),
+                    error: None,
+                });
+                return Ok(());
+            }
+        }
+
+        if let Some(plan) = self.build_plan()? {
+            self.outcome = Some(if execute {
+                Outcome::Execute(plan)
+            } else {
+                Outcome::Emit(plan)
+            });
+        }
+        Ok(())
+    }
+
     #[allow(clippy::too_many_lines)]
     fn load_profiles(&mut self) {
         self.profiles.clear();
@@ -839,7 +1051,7 @@ impl<'ctx> AppState<'ctx> {
                 provider: provider_name,
                 pre: Vec::new(),
                 post: Vec::new(),
-                wrap: None,
+                wrap: Vec::new(),
                 description: Some(command.clone()),
                 tags: Vec::new(),
                 inline_pre: Vec::new(),
@@ -866,7 +1078,7 @@ impl<'ctx> AppState<'ctx> {
                         provider: provider.clone(),
                         pre: Vec::new(),
                         post: Vec::new(),
-                        wrap: None,
+                        wrap: Vec::new(),
                         description: vp.description.clone(),
                         tags: vp.tags.clone(),
                         inline_pre: Vec::new(),
@@ -891,9 +1103,43 @@ impl<'ctx> AppState<'ctx> {
         }
     }
 
+    fn refresh_run_entries(&mut self) {
+        let Some(SessionView::Runs { runs }) = &self.view else {
+            return;
+        };
+        let query = self.filter.to_ascii_lowercase();
+        let entries = runs
+            .iter()
+            .filter(|run| {
+                self.provider_filter
+                    .as_ref()
+                    .is_none_or(|provider| run.provider == *provider)
+            })
+            .filter(|run| query.is_empty() || run_matches(run, &query))
+            .cloned()
+            .map(Entry::Run)
+            .collect::<Vec<_>>();
+        self.entries = entries;
+        self.preview_cache.clear();
+        if self.entries.is_empty() {
+            self.index = 0;
+            self.list_state.select(None);
+        } else {
+            self.index = self.index.min(self.entries.len() - 1);
+            self.list_state.select(Some(self.index));
+        }
+    }
+
     fn refresh_entries(&mut self) -> Result<()> {
+        if matches!(self.view, Some(SessionView::Runs { .. })) {
+            self.refresh_run_entries();
+            return Ok(());
+        }
+
         let searching = !self.filter.is_empty();
-        let mut sessions = if searching && self.full_text {
+        let mut sessions = if let Some(view) = &self.view {
+            view.sessions().to_vec()
+        } else if searching && self.full_text {
             self.search_full_text_sessions(&self.filter)?
         } else {
             self.load_sessions()?
@@ -920,6 +1166,9 @@ impl<'ctx> AppState<'ctx> {
 
         let mut profile_entries = Vec::new();
         for profile in &self.profiles {
+            if self.view.is_some() {
+                break;
+            }
             if let Some(provider) = &self.provider_filter
                 && &profile.provider != provider
             {
@@ -931,7 +1180,9 @@ impl<'ctx> AppState<'ctx> {
             profile_entries.push(Entry::Profile(profile.clone()));
         }
 
-        if searching && !self.full_text {
+        if self.view.is_some() {
+            // Related and topic sessions keep their distance order.
+        } else if searching && !self.full_text {
             let query = self.filter.to_ascii_lowercase();
             sessions.sort_by(|a, b| {
                 a.match_priority(&query)
@@ -1059,6 +1310,18 @@ impl<'ctx> AppState<'ctx> {
                 self.trigger_export_markdown()?;
                 Ok(false)
             }
+            (KeyCode::Char('r' | 'R'), mods) if mods.contains(KeyModifiers::CONTROL) => {
+                self.toggle_related_sessions()?;
+                Ok(false)
+            }
+            (KeyCode::Char('t' | 'T'), mods) if mods.contains(KeyModifiers::CONTROL) => {
+                self.cycle_topics()?;
+                Ok(false)
+            }
+            (KeyCode::Char('l' | 'L'), mods) if mods.contains(KeyModifiers::CONTROL) => {
+                self.toggle_recent_runs()?;
+                Ok(false)
+            }
             (KeyCode::Down, _) => {
                 self.move_selection(1);
                 Ok(false)
@@ -1115,15 +1378,11 @@ impl<'ctx> AppState<'ctx> {
                 Ok(false)
             }
             (KeyCode::Tab, _) => {
-                if let Some(plan) = self.build_plan()? {
-                    self.outcome = Some(Outcome::Emit(plan));
-                }
+                self.launch_selected(false)?;
                 Ok(false)
             }
             (KeyCode::Enter, _) => {
-                if let Some(plan) = self.build_plan()? {
-                    self.outcome = Some(Outcome::Execute(plan));
-                }
+                self.launch_selected(true)?;
                 Ok(false)
             }
             _ => Ok(false),
@@ -1177,6 +1436,131 @@ impl<'ctx> AppState<'ctx> {
         Ok(())
     }
 
+    fn toggle_related_sessions(&mut self) -> Result<()> {
+        if matches!(self.view, Some(SessionView::Related { .. })) {
+            self.view = None;
+            self.refresh_entries()?;
+            return Ok(());
+        }
+
+        let Some(session) = self.selected_session() else {
+            self.set_temporary_status_message(
+                "Select a session to find related sessions.".into(),
+                Duration::from_secs(3),
+            );
+            return Ok(());
+        };
+        let source_id = session.id.clone();
+        let source = session.short_session_tag();
+
+        let model = rag::search_model(self.ctx.db, &rag::configured_model())?;
+        let related = match rag::related_sessions(self.ctx.db, &model, &source_id, RELATED_LIMIT) {
+            Ok(related) => related,
+            Err(err) => {
+                self.set_temporary_status_message(err.to_string(), Duration::from_secs(5));
+                return Ok(());
+            }
+        };
+
+        let mut sessions = Vec::new();
+        for related in related {
+            let Some(summary) = self.ctx.db.session_summary(&related.session_id)? else {
+                continue;
+            };
+            let mut entry = SessionEntry::from_summary(summary);
+            entry.first_prompt = None;
+            entry.snippet = Some(related.best_chunk.text);
+            entry.snippet_role = Some(format!("dist {:.3}", related.distance));
+            sessions.push(entry);
+        }
+
+        if sessions.is_empty() {
+            self.set_temporary_status_message(
+                format!("No sessions related to {source}."),
+                Duration::from_secs(3),
+            );
+            return Ok(());
+        }
+
+        self.view = Some(SessionView::Related { source, sessions });
+        self.index = 0;
+        self.refresh_entries()?;
+        Ok(())
+    }
+
+    fn cycle_topics(&mut self) -> Result<()> {
+        if let Some(SessionView::Topics { topics, index }) = &mut self.view {
+            *index += 1;
+            if *index >= topics.len() {
+                self.view = None;
+            }
+            self.index = 0;
+            self.refresh_entries()?;
+            return Ok(());
+        }
+
+        let model = rag::configured_model();
+        let report = match rag::topic_clusters(self.ctx.db, &model, None, TOPIC_LABEL_TERMS) {
+            Ok(report) => report,
+            Err(err) => {
+                self.set_temporary_status_message(err.to_string(), Duration::from_secs(5));
+                return Ok(());
+            }
+        };
+
+        let mut topics = Vec::new();
+        for cluster in report.clusters {
+            let mut sessions = Vec::new();
+            for member in &cluster.members {
+                if let Some(summary) = self.ctx.db.session_summary(&member.session_id)? {
+                    sessions.push(SessionEntry::from_summary(summary));
+                }
+            }
+            if !sessions.is_empty() {
+                topics.push(TopicEntry {
+                    label: cluster.label(),
+                    sessions,
+                });
+            }
+        }
+
+        if topics.is_empty() {
+            self.set_temporary_status_message(
+                "No indexed sessions to cluster.".into(),
+                Duration::from_secs(3),
+            );
+            return Ok(());
+        }
+
+        self.view = Some(SessionView::Topics { topics, index: 0 });
+        self.index = 0;
+        self.refresh_entries()?;
+        Ok(())
+    }
+
+    fn toggle_recent_runs(&mut self) -> Result<()> {
+        if matches!(self.view, Some(SessionView::Runs { .. })) {
+            self.view = None;
+            self.refresh_entries()?;
+            return Ok(());
+        }
+
+        let runs = self.ctx.db.recent_runs(RUN_LIMIT)?;
+        if runs.is_empty() {
+            self.set_temporary_status_message(
+                "No launches recorded yet.".into(),
+                Duration::from_secs(3),
+            );
+            return Ok(());
+        }
+
+        self.view = Some(SessionView::Runs { runs });
+        self.overlay_message = None;
+        self.index = 0;
+        self.refresh_entries()?;
+        Ok(())
+    }
+
     fn trigger_export_markdown(&mut self) -> Result<()> {
         if let Some(session) = self.selected_session() {
             match self.ctx.db.fetch_transcript(&session.id)? {
@@ -1223,6 +1607,7 @@ impl<'ctx> AppState<'ctx> {
         match entry {
             Entry::Session(session) => self.plan_for_session(&session),
             Entry::Profile(profile) => self.plan_for_profile(&profile),
+            Entry::Run(run) => run.decode_plan().map(Some),
             Entry::Empty(empty) => {
                 if let Some(message) = &empty.status {
                     self.message = Some(message.clone());
@@ -1238,6 +1623,7 @@ impl<'ctx> AppState<'ctx> {
             .db
             .session_summary(&session.id)?
             .ok_or_else(|| eyre!("session '{}' not found", session.id))?;
+        app::ensure_resumable(self.ctx.db, &summary)?;
 
         let resume_plan = providers::resume_info(&summary)?;
         let mut provider_args = Vec::new();
@@ -1247,23 +1633,45 @@ impl<'ctx> AppState<'ctx> {
             provider_args.extend(plan.args);
         }
 
+        let (profile, launch) = app::recorded_launch(self.ctx.db, self.ctx.config, &summary)?
+            .map(|recorded| (recorded.profile, recorded.launch))
+            .unwrap_or_default();
+        let profile_config = profile
+            .as_deref()
+            .and_then(|name| self.ctx.config.profiles.get(name));
+        let prompt_assembler = profile_config.and_then(|profile| {
+            profile
+                .prompt_assembler
+                .as_ref()
+                .map(|name| PromptInvocation {
+                    name: name.clone(),
+                    args: profile.prompt_assembler_args.clone(),
+                })
+        });
+        let capture_prompt = app::should_capture_prompt_for_resume(
+            prompt_assembler.as_ref(),
+            profile_config.is_some_and(|profile| !profile.pre.is_empty()),
+            &launch.pre,
+        );
+
         let request = PipelineRequest {
             config: self.ctx.config,
             provider_hint: Some(summary.provider.as_str()),
-            profile: None,
-            additional_pre: Vec::new(),
-            additional_post: Vec::new(),
+            profile: profile.as_deref(),
+            additional_pre: launch.pre,
+            additional_post: launch.post,
             inline_pre: Vec::new(),
-            wrap: None,
+            wrap: launch.wrap,
             provider_args,
-            capture_prompt: false,
-            prompt_assembler: None,
-            vars: HashMap::new(),
+            capture_prompt,
+            prompt_assembler,
+            vars: launch.vars.into_iter().collect(),
             session: SessionContext {
                 id: Some(summary.id.clone()),
                 label: summary.label.clone(),
                 path: Some(summary.path.to_string_lossy().to_string()),
                 resume_token,
+                thread_name: summary.thread_name.clone(),
             },
             cwd: summary.path.parent().map_or_else(
                 || env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
@@ -1276,6 +1684,14 @@ impl<'ctx> AppState<'ctx> {
     }
 
     fn plan_for_profile(&mut self, profile: &ProfileEntry) -> Result<Option<PipelinePlan>> {
+        self.plan_for_profile_with_vars(profile, HashMap::new())
+    }
+
+    fn plan_for_profile_with_vars(
+        &mut self,
+        profile: &ProfileEntry,
+        vars: HashMap<String, String>,
+    ) -> Result<Option<PipelinePlan>> {
         if let Some(prompt_name) = profile.prompt_assembler.as_deref()
             && !profile.prompt_available
         {
@@ -1303,11 +1719,11 @@ impl<'ctx> AppState<'ctx> {
                 additional_pre: Vec::new(),
                 additional_post: Vec::new(),
                 inline_pre: profile.inline_pre.clone(),
-                wrap: profile.wrap.as_deref(),
+                wrap: Vec::new(),
                 provider_args: Vec::new(),
                 capture_prompt,
                 prompt_assembler: prompt_invocation.clone(),
-                vars: HashMap::new(),
+                vars,
                 session: SessionContext::default(),
                 cwd: env::current_dir()?,
             },
@@ -1318,7 +1734,7 @@ impl<'ctx> AppState<'ctx> {
                 additional_pre: profile.pre.clone(),
                 additional_post: profile.post.clone(),
                 inline_pre: profile.inline_pre.clone(),
-                wrap: profile.wrap.as_deref(),
+                wrap: profile.wrap.clone(),
                 provider_args: Vec::new(),
                 capture_prompt,
                 prompt_assembler: prompt_invocation.clone(),
@@ -1333,7 +1749,7 @@ impl<'ctx> AppState<'ctx> {
                 additional_pre: Vec::new(),
                 additional_post: Vec::new(),
                 inline_pre: Vec::new(),
-                wrap: None,
+                wrap: Vec::new(),
                 provider_args: Vec::new(),
                 capture_prompt,
                 prompt_assembler: None,
@@ -1370,6 +1786,7 @@ impl<'ctx> AppState<'ctx> {
         let key = match &entry {
             Entry::Session(session) => format!("session:{}", session.id),
             Entry::Profile(profile) => format!("profile:{}", profile.display),
+            Entry::Run(run) => format!("run:{}", run.id),
             Entry::Empty(empty) => format!("empty:{}", empty.title),
         };
 
@@ -1414,6 +1831,17 @@ impl<'ctx> AppState<'ctx> {
                     updated_at: Instant::now(),
                 }
             }
+            Entry::Run(run) => {
+                let lines = run_preview_lines(&run);
+                let styled = markdown_lines_to_text(&lines);
+                Preview {
+                    lines,
+                    styled,
+                    title: Some(format!("run {}", run.id)),
+                    timestamp: format_dual_time(Some(run.started_ms / 1000)),
+                    updated_at: Instant::now(),
+                }
+            }
             Entry::Empty(empty) => {
                 let lines = empty.preview.clone();
                 let styled = markdown_lines_to_text(&lines);
@@ -1539,6 +1967,42 @@ fn draw(frame: &mut Frame<'_>, state: &mut AppState<'_>) {
     draw_status(frame, vertical[1], state);
 }
 
+fn run_matches(run: &RunRecord, query: &str) -> bool {
+    run.provider.to_ascii_lowercase().contains(query)
+        || run
+            .profile
+            .as_deref()
+            .is_some_and(|profile| profile.to_ascii_lowercase().contains(query))
+        || run.plan.to_ascii_lowercase().contains(query)
+}
+
+fn run_preview_lines(run: &RunRecord) -> Vec<String> {
+    let mut lines = Vec::new();
+    match run.decode_plan() {
+        Ok(plan) => {
+            lines.push("```".to_string());
+            lines.push(plan.display);
+            lines.push("```".to_string());
+        }
+        Err(err) => lines.push(format!("{err:#}")),
+    }
+    lines.push(String::new());
+    lines.push(format!("Provider: {}", run.provider));
+    if let Some(profile) = &run.profile {
+        lines.push(format!("Profile: {profile}"));
+    }
+    lines.push(format!("Directory: {}", run.cwd.display()));
+    lines.push(format!(
+        "Result: {} after {}",
+        history::exit_label(run),
+        history::format_duration(run.duration_ms)
+    ));
+    if let Some(session) = &run.session_path {
+        lines.push(format!("Session: {}", session.display()));
+    }
+    lines
+}
+
 fn list_identifier_width(entries: &[Entry]) -> usize {
     entries
         .iter()
@@ -1616,6 +2080,35 @@ fn draw_entries(frame: &mut Frame<'_>, area: Rect, state: &mut AppState<'_>) {
                 }
                 ListItem::new(Line::from(spans))
             }
+            Entry::Run(run) => {
+                let raw_relative = format_relative_time(Some(run.started_ms / 1000), now)
+                    .unwrap_or_else(|| "n/a".to_string());
+                let exit_color = if run.succeeded() {
+                    Color::Green
+                } else {
+                    Color::Red
+                };
+                let command = run
+                    .decode_plan()
+                    .map_or_else(|_| run.provider.clone(), |plan| plan.display);
+                let spans = vec![
+                    Span::styled(
+                        pad_relative_time(&raw_relative),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::raw("  "),
+                    Span::styled(
+                        format!("{:<9}", history::exit_label(run)),
+                        Style::default().fg(exit_color),
+                    ),
+                    Span::raw("  "),
+                    Span::styled(
+                        truncate(&normalize_whitespace(&command), 200),
+                        Style::default().fg(Color::White),
+                    ),
+                ];
+                ListItem::new(Line::from(spans))
+            }
             Entry::Empty(empty) => {
                 let spans = vec![Span::styled(
                     truncate(&empty.title, 40),
@@ -37,14 +37,15 @@ use time::{Duration, OffsetDateTime};
 use crate::config::AppDirectories;
 use crate::config::Config;
 use crate::config::model::{
-    Defaults, FeatureConfig, ProfileConfig, ProviderConfig, SearchMode, SnippetConfig,
-    StdinMapping, StdinMode, WrapperConfig, WrapperMode,
+    Defaults, FeatureConfig, HooksConfig, ProfileConfig, ProviderConfig, RagConfig,
+    RetentionConfig, SearchFormat, SearchMode, SnippetConfig, StdinMapping, StdinMode,
+    WrapperConfig, WrapperMode,
 };
 #[cfg(unix)]
-use crate::config::model::{PromptAssemblerConfig, Snippet};
+use crate::config::model::{ProfileVar, PromptAssemblerConfig, Snippet, VarKind};
 use crate::db::Database;
 #[cfg(unix)]
-use crate::pipeline::Invocation;
+use crate::pipeline::{Invocation, LaunchOptions};
 #[cfg(unix)]
 use crate::prompts::PromptAssembler;
 use crate::session::{MessageRecord, SessionIngest, SessionSummary, Transcript};
@@ -99,6 +100,8 @@ fn provider_config(root: &Path) -> ProviderConfig {
         bin: "echo".into(),
         flags: vec!["hello".into()],
         env: Vec::new(),
+        env_file: Vec::new(),
+        hooks: HooksConfig::default(),
         session_roots: vec![root.join("sessions")],
         stdin: None,
     }
@@ -118,11 +121,18 @@ fn build_config(root: &Path) -> Config {
         "default".into(),
         ProfileConfig {
             name: "default".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: Some("Default profile".into()),
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -133,6 +143,7 @@ fn build_config(root: &Path) -> Config {
             provider: Some("codex".into()),
             profile: Some("default".into()),
             search_mode: SearchMode::FirstPrompt,
+            search_format: SearchFormat::Json,
             terminal_title: None,
         },
         providers,
@@ -141,7 +152,11 @@ fn build_config(root: &Path) -> Config {
         profiles,
         features: FeatureConfig {
             prompt_assembler: None,
+            rag: RagConfig::default(),
         },
+        retention: RetentionConfig::default(),
+        secrets: IndexMap::new(),
+        hooks: HooksConfig::default(),
     }
 }
 
@@ -356,7 +371,7 @@ fn named_sessions_render_like_profile_style_entries() -> Result<()> {
             provider: "codex".into(),
             pre: vec!["echo prepare".into()],
             post: vec!["echo cleanup".into()],
-            wrap: Some("shellwrap".into()),
+            wrap: vec!["shellwrap".into()],
             description: Some("Run codex".into()),
             tags: vec!["team".into()],
             inline_pre: Vec::new(),
@@ -443,7 +458,7 @@ fn profile_entry_matching() {
         provider: "codex".into(),
         pre: vec!["pre".into()],
         post: vec!["post".into()],
-        wrap: None,
+        wrap: Vec::new(),
         description: Some("helpful".into()),
         tags: vec!["team".into()],
         inline_pre: Vec::new(),
@@ -1016,7 +1031,7 @@ fn preview_cache_invalidation_and_profile_plans() -> Result<()> {
         },
     );
     if let Some(default_profile) = config.profiles.get_mut("default") {
-        default_profile.wrap = Some("shellwrap".into());
+        default_profile.wrap = vec!["shellwrap".into()];
         default_profile.pre = vec![pre_snippet_name.into()];
     }
 
@@ -1064,7 +1079,7 @@ fn preview_cache_invalidation_and_profile_plans() -> Result<()> {
         .cloned()
         .expect("default profile");
     let wrapped_plan = state.plan_for_profile(&profile_entry)?.expect("plan");
-    assert_eq!(wrapped_plan.wrapper.as_deref(), Some("shellwrap"));
+    assert_eq!(wrapped_plan.wrappers, ["shellwrap"]);
     if let Invocation::Shell { command } = &wrapped_plan.invocation {
         assert!(command.contains("--wrapped"));
     } else {
@@ -1078,7 +1093,7 @@ fn preview_cache_invalidation_and_profile_plans() -> Result<()> {
         .cloned()
         .expect("provider entry");
     let provider_plan = state.plan_for_profile(&provider_entry)?.expect("plan");
-    assert!(provider_plan.wrapper.is_none());
+    assert!(provider_plan.wrappers.is_empty());
 
     Ok(())
 }
@@ -1173,6 +1188,57 @@ fn handle_key_normal_enter_executes_plan() -> Result<()> {
     Ok(())
 }
 
+#[cfg(unix)]
+#[test]
+fn enter_on_session_restores_recorded_launch() -> Result<()> {
+    let temp = TempDir::new()?;
+    let config = build_config(temp.path());
+    let directories = build_directories(&temp);
+    directories.ensure_all()?;
+    let mut db = Database::open(&directories.data_dir.join("tx.sqlite3"))?;
+    let session_dir = temp.path().join("sessions");
+    fs::create_dir_all(&session_dir)?;
+    let session_path = session_dir.join("restored.jsonl");
+    fs::File::create(&session_path)?.write_all(b"{\"event\":\"restored\"}\n")?;
+    let summary = insert_session(&mut db, &session_path, "sess-restored")?;
+
+    let mut ctx = UiContext {
+        config: &config,
+        directories: &directories,
+        db: &mut db,
+        prompt: None,
+    };
+    let mut state = AppState::new(&mut ctx)?;
+    let session_index = state
+        .entries
+        .iter()
+        .position(|entry| matches!(entry, Entry::Session(_)))
+        .expect("session listed");
+    state.index = session_index;
+    let fresh = state.build_plan()?.expect("session plan");
+    assert_eq!(fresh.profile, None);
+
+    let mut launched = fresh.clone();
+    launched.profile = Some("default".into());
+    launched.launch.vars = [("topic".to_string(), "retries".to_string())].into();
+    state.ctx.db.record_run(&crate::db::NewRun {
+        started_ms: 1_000,
+        plan: &launched,
+        exit_code: Some(0),
+        duration_ms: 10,
+        session_path: Some(&summary.path),
+    })?;
+
+    let restored = state.build_plan()?.expect("session plan");
+    assert_eq!(restored.profile.as_deref(), Some("default"));
+    assert_eq!(restored.session_id.as_deref(), Some("sess-restored"));
+    assert_eq!(
+        restored.launch.vars.get("topic").map(String::as_str),
+        Some("retries")
+    );
+    Ok(())
+}
+
 #[cfg(unix)]
 #[test]
 fn handle_key_normal_ctrl_tab_emits_plan() -> Result<()> {
@@ -1813,28 +1879,42 @@ fn dispatch_outcome_handles_emit_and_execute() -> Result<()> {
         terminal_title: "codex".into(),
         pre_snippets: Vec::new(),
         post_snippets: Vec::new(),
-        wrapper: None,
+        wrappers: Vec::new(),
         needs_stdin_prompt: false,
         uses_capture_arg: false,
         capture_has_pre_commands: false,
         stdin_prompt_label: None,
         cwd: std::env::current_dir()?,
         prompt_assembler: None,
+        context: None,
+        secret_env: Vec::new(),
+        hooks: HooksConfig::default(),
+        session_id: None,
+        programs: Vec::new(),
+        profile: None,
+        launch: LaunchOptions::default(),
+        env_origins: Vec::new(),
     };
 
-    dispatch_outcome(Some(Outcome::Emit(plan.clone())))?;
+    assert!(dispatch_outcome(Some(Outcome::Emit(plan.clone())))?.is_none());
 
     let mut exec_plan = plan.clone();
     exec_plan.invocation = Invocation::Exec {
         argv: vec!["/bin/sh".into(), "-c".into(), "true".into()],
+        env_at: None,
     };
-    dispatch_outcome(Some(Outcome::Execute(exec_plan)))?;
-    dispatch_outcome(Some(Outcome::PrintSessionId("session-123".into())))?;
-    dispatch_outcome(Some(Outcome::ExportMarkdown(vec![
-        "# heading".into(),
-        "body".into(),
-    ])))?;
-    dispatch_outcome(None)?;
+    let chosen = dispatch_outcome(Some(Outcome::Execute(exec_plan)))?
+        .expect("execute outcome returns its plan");
+    assert!(matches!(chosen.invocation, Invocation::Exec { ref argv, .. } if argv[2] == "true"));
+    assert!(dispatch_outcome(Some(Outcome::PrintSessionId("session-123".into())))?.is_none());
+    assert!(
+        dispatch_outcome(Some(Outcome::ExportMarkdown(vec![
+            "# heading".into(),
+            "body".into(),
+        ])))?
+        .is_none()
+    );
+    assert!(dispatch_outcome(None)?.is_none());
     Ok(())
 }
 
@@ -1977,6 +2057,8 @@ fn cycle_provider_filter_wraps_and_resets() -> Result<()> {
             bin: "echo".into(),
             flags: Vec::new(),
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: vec![temp.child("alpha-sessions").path().to_path_buf()],
             stdin: None,
         },
@@ -2116,6 +2198,7 @@ fn draw_preview_uses_cached_styled_content() -> Result<()> {
     let key = match &state.entries[state.index] {
         Entry::Session(session) => format!("session:{}", session.id),
         Entry::Profile(profile) => format!("profile:{}", profile.display),
+        Entry::Run(run) => format!("run:{}", run.id),
         Entry::Empty(empty) => format!("empty:{}", empty.title),
     };
     state.preview_cache.insert(
@@ -2169,6 +2252,7 @@ fn draw_preview_renders_plain_lines_when_styled_is_missing() -> Result<()> {
     let key = match &state.entries[state.index] {
         Entry::Session(session) => format!("session:{}", session.id),
         Entry::Profile(profile) => format!("profile:{}", profile.display),
+        Entry::Run(run) => format!("run:{}", run.id),
         Entry::Empty(empty) => format!("empty:{}", empty.title),
     };
     state.preview_cache.insert(
@@ -2504,6 +2588,252 @@ fn run_app_exports_transcript_on_ctrl_e() -> Result<()> {
     Ok(())
 }
 
+#[cfg(unix)]
+fn unit_chunk(session_id: &str, chunk_id: i64, axes: &[(usize, f32)]) -> crate::db::RagChunkRecord {
+    let mut embedding = vec![0.0_f32; crate::rag::EMBEDDING_DIM];
+    for &(axis, value) in axes {
+        embedding[axis] = value;
+    }
+    crate::db::RagChunkRecord {
+        chunk_id,
+        embedding,
+        session_id: session_id.into(),
+        ts_ms: chunk_id,
+        tool_name: None,
+        kind: "assistant".into(),
+        model: "text-embedding-3-small".into(),
+        content_hash: format!("hash-{chunk_id}"),
+        text: format!("best chunk from {session_id}"),
+        source_event_id: chunk_id,
+        chunk_ordinal: 0,
+        span_start: 0,
+        span_end: 10,
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_r_toggles_related_sessions_view() -> Result<()> {
+    let _env = crate::test_support::ENV_LOCK.lock().unwrap();
+    let _model = crate::test_support::EnvOverride::remove("TX_RAG_EMBED_MODEL");
+    let temp = TempDir::new()?;
+    let config = build_config(temp.path());
+    let directories = build_directories(&temp);
+    directories.ensure_all()?;
+    let mut db = Database::open(&directories.data_dir.join("tx.sqlite3"))?;
+    for id in ["sess-source", "sess-near", "sess-far"] {
+        insert_session(&mut db, &temp.path().join(format!("{id}.jsonl")), id)?;
+    }
+    db.upsert_rag_chunks(&[
+        unit_chunk("sess-source", 1, &[(0, 1.0)]),
+        unit_chunk("sess-near", 2, &[(0, 1.0), (1, 0.1)]),
+        unit_chunk("sess-far", 3, &[(2, 1.0)]),
+    ])?;
+
+    let mut ctx = UiContext {
+        config: &config,
+        directories: &directories,
+        db: &mut db,
+        prompt: None,
+    };
+    let mut state = AppState::new(&mut ctx)?;
+    let source_index = state
+        .entries
+        .iter()
+        .position(|entry| matches!(entry, Entry::Session(session) if session.id == "sess-source"))
+        .expect("source session listed");
+    state.index = source_index;
+
+    state.handle_key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL))?;
+    let related = state
+        .entries
+        .iter()
+        .map(|entry| match entry {
+            Entry::Session(session) => session.id.as_str(),
+            _ => "not-a-session",
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(related, vec!["sess-near", "sess-far"]);
+    let Some(Entry::Session(near)) = state.entries.first() else {
+        panic!("expected related session entry");
+    };
+    assert!(near.list_title().starts_with("[dist 0.100]"));
+    assert!(near.list_title().contains("best chunk from sess-near"));
+    assert!(
+        state
+            .status_message()
+            .is_some_and(|message| message.starts_with("related to #"))
+    );
+
+    state.handle_key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL))?;
+    assert!(state.view.is_none());
+    assert!(state.entries.len() >= 3);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_l_lists_recent_launches_and_reruns_their_plan() -> Result<()> {
+    let temp = TempDir::new()?;
+    let config = build_config(temp.path());
+    let directories = build_directories(&temp);
+    directories.ensure_all()?;
+    let mut db = Database::open(&directories.data_dir.join("tx.sqlite3"))?;
+    let mut ctx = UiContext {
+        config: &config,
+        directories: &directories,
+        db: &mut db,
+        prompt: None,
+    };
+    let mut state = AppState::new(&mut ctx)?;
+
+    state.handle_key(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL))?;
+    assert!(state.view.is_none());
+    assert_eq!(
+        state.status_message().as_deref(),
+        Some("No launches recorded yet.")
+    );
+
+    let profile_index = state
+        .entries
+        .iter()
+        .position(|entry| matches!(entry, Entry::Profile(_)))
+        .expect("profile listed");
+    state.index = profile_index;
+    let plan = state.build_plan()?.expect("profile plan");
+    let session = temp.path().join("launched.jsonl");
+    let run_id = state.ctx.db.record_run(&crate::db::NewRun {
+        started_ms: 1_700_000_000_000,
+        plan: &plan,
+        exit_code: Some(3),
+        duration_ms: 1_250,
+        session_path: Some(&session),
+    })?;
+
+    state.handle_key(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL))?;
+    assert!(matches!(state.view, Some(SessionView::Runs { .. })));
+    assert!(matches!(state.entries.as_slice(), [Entry::Run(run)] if run.id == run_id));
+    assert!(
+        state
+            .status_message()
+            .is_some_and(|message| message.starts_with("recent launches"))
+    );
+    let preview = state.preview();
+    assert!(preview.lines.iter().any(|line| line == &plan.display));
+    assert!(
+        preview
+            .lines
+            .iter()
+            .any(|line| line == "Result: exit 3 after 1s")
+    );
+
+    state.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))?;
+    match state.outcome.take() {
+        Some(Outcome::Execute(rerun)) => {
+            assert_eq!(rerun.display, plan.display);
+            assert_eq!(rerun.provider, plan.provider);
+        }
+        other => panic!("expected Execute outcome, got {other:?}"),
+    }
+
+    state.handle_key(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL))?;
+    assert!(state.view.is_none());
+    assert!(
+        state
+            .entries
+            .iter()
+            .all(|entry| !matches!(entry, Entry::Run(_)))
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_t_browses_topics_then_returns_to_sessions() -> Result<()> {
+    let _env = crate::test_support::ENV_LOCK.lock().unwrap();
+    let _model = crate::test_support::EnvOverride::remove("TX_RAG_EMBED_MODEL");
+    let temp = TempDir::new()?;
+    let config = build_config(temp.path());
+    let directories = build_directories(&temp);
+    directories.ensure_all()?;
+    let mut db = Database::open(&directories.data_dir.join("tx.sqlite3"))?;
+    for id in ["sess-one", "sess-two", "sess-unindexed"] {
+        insert_session(&mut db, &temp.path().join(format!("{id}.jsonl")), id)?;
+    }
+    db.upsert_rag_chunks(&[
+        unit_chunk("sess-one", 1, &[(0, 1.0)]),
+        unit_chunk("sess-two", 2, &[(0, 1.0), (1, 0.2)]),
+    ])?;
+
+    let mut ctx = UiContext {
+        config: &config,
+        directories: &directories,
+        db: &mut db,
+        prompt: None,
+    };
+    let mut state = AppState::new(&mut ctx)?;
+
+    state.handle_key(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL))?;
+    let mut topic_sessions = state
+        .entries
+        .iter()
+        .filter_map(|entry| match entry {
+            Entry::Session(session) => Some(session.id.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    topic_sessions.sort_unstable();
+    assert_eq!(topic_sessions, vec!["sess-one", "sess-two"]);
+    assert_eq!(state.entries.len(), 2, "topics hide profiles");
+    assert_eq!(
+        state.status_message().as_deref(),
+        Some("topic 1/1: hello  •  Ctrl-T next")
+    );
+
+    state.handle_key(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL))?;
+    assert!(state.view.is_none());
+    assert!(
+        state.entries.iter().any(
+            |entry| matches!(entry, Entry::Session(session) if session.id == "sess-unindexed")
+        )
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_r_reports_missing_semantic_index() -> Result<()> {
+    let _env = crate::test_support::ENV_LOCK.lock().unwrap();
+    let _model = crate::test_support::EnvOverride::remove("TX_RAG_EMBED_MODEL");
+    let temp = TempDir::new()?;
+    let config = build_config(temp.path());
+    let directories = build_directories(&temp);
+    directories.ensure_all()?;
+    let mut db = Database::open(&directories.data_dir.join("tx.sqlite3"))?;
+    insert_session(&mut db, &temp.path().join("sess-plain.jsonl"), "sess-plain")?;
+
+    let mut ctx = UiContext {
+        config: &config,
+        directories: &directories,
+        db: &mut db,
+        prompt: None,
+    };
+    let mut state = AppState::new(&mut ctx)?;
+    state.index = state
+        .entries
+        .iter()
+        .position(|entry| matches!(entry, Entry::Session(_)))
+        .expect("session listed");
+    state.handle_key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL))?;
+    assert!(state.view.is_none());
+    assert!(
+        state
+            .status_message()
+            .is_some_and(|message| message.contains("no semantic index"))
+    );
+    Ok(())
+}
+
 #[cfg(unix)]
 #[test]
 #[allow(clippy::too_many_lines)]
@@ -2514,11 +2844,18 @@ fn load_profiles_handles_prompt_statuses() -> Result<()> {
         "ALPHA".into(),
         ProfileConfig {
             name: "ALPHA".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: None,
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -2527,11 +2864,18 @@ fn load_profiles_handles_prompt_statuses() -> Result<()> {
         "alpha".into(),
         ProfileConfig {
             name: "alpha".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: None,
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -2546,6 +2890,8 @@ fn load_profiles_handles_prompt_statuses() -> Result<()> {
             bin: "echo".into(),
             flags: vec!["--alpha".into()],
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: vec![alpha_root],
             stdin: None,
         },
@@ -2634,11 +2980,18 @@ fn load_profiles_covers_conflicts_and_missing_prompt_message() -> Result<()> {
         "ALPHA".into(),
         ProfileConfig {
             name: "ALPHA".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: None,
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -2647,11 +3000,18 @@ fn load_profiles_covers_conflicts_and_missing_prompt_message() -> Result<()> {
         "alpha".into(),
         ProfileConfig {
             name: "alpha".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: None,
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -2660,11 +3020,18 @@ fn load_profiles_covers_conflicts_and_missing_prompt_message() -> Result<()> {
         "tests/demo".into(),
         ProfileConfig {
             name: "tests/demo".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: Some("conflicting key".into()),
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: None,
             prompt_assembler_args: Vec::new(),
         },
@@ -2673,11 +3040,18 @@ fn load_profiles_covers_conflicts_and_missing_prompt_message() -> Result<()> {
         "missing-prompt".into(),
         ProfileConfig {
             name: "missing-prompt".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: None,
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: Some("does-not-exist".into()),
             prompt_assembler_args: Vec::new(),
         },
@@ -2690,6 +3064,8 @@ fn load_profiles_covers_conflicts_and_missing_prompt_message() -> Result<()> {
             bin: "echo".into(),
             flags: vec!["--alpha".into()],
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: vec![temp.path().join("alpha-sessions")],
             stdin: None,
         },
@@ -2783,11 +3159,18 @@ fn refresh_entries_preserves_config_profile_order() -> Result<()> {
 
     let make_profile = |name: &str| ProfileConfig {
         name: name.into(),
+        extends: None,
+        vars: IndexMap::new(),
+        flags: None,
+        env: Vec::new(),
+        env_file: Vec::new(),
+        cwd: None,
+        hooks: HooksConfig::default(),
         provider: "codex".into(),
         description: None,
         pre: Vec::new(),
         post: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
         prompt_assembler: None,
         prompt_assembler_args: Vec::new(),
     };
@@ -3001,7 +3384,7 @@ fn preview_prefers_virtual_profile_contents() -> Result<()> {
         provider: "codex".into(),
         pre: Vec::new(),
         post: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
         description: Some("Original description".into()),
         tags: vec!["team".into()],
         inline_pre: Vec::new(),
@@ -3095,7 +3478,7 @@ fn build_markdown_profile_preview_handles_multiline_and_empty_description() {
         provider: "codex".into(),
         pre: Vec::new(),
         post: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
         description: Some("First line\nSecond line".into()),
         tags: Vec::new(),
         inline_pre: Vec::new(),
@@ -3180,6 +3563,8 @@ fn plan_for_session_handles_provider_without_resume_metadata() -> Result<()> {
             bin: "echo".into(),
             flags: vec!["hello".into()],
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: vec![temp.path().join("alt-sessions")],
             stdin: None,
         },
@@ -3303,6 +3688,85 @@ fn plan_for_profile_marks_stdin_prompt() -> Result<()> {
     Ok(())
 }
 
+#[cfg(unix)]
+#[test]
+fn launching_profile_prompts_for_required_vars() -> Result<()> {
+    let temp = TempDir::new()?;
+    let mut config = build_config(temp.path());
+    config.wrappers.insert(
+        "ticketed".into(),
+        WrapperConfig {
+            name: "ticketed".into(),
+            mode: WrapperMode::Exec {
+                argv: vec!["run".into(), "{{var:ticket}}".into()],
+            },
+        },
+    );
+    let default_profile = config.profiles.get_mut("default").expect("default profile");
+    default_profile.wrap = vec!["ticketed".into()];
+    default_profile.vars.insert(
+        "ticket".into(),
+        ProfileVar {
+            name: "ticket".into(),
+            kind: VarKind::Integer,
+            default: None,
+            description: None,
+            choices: Vec::new(),
+            required: true,
+        },
+    );
+    let directories = build_directories(&temp);
+    directories.ensure_all()?;
+    let mut db = Database::open(&directories.data_dir.join("tx.sqlite3"))?;
+    let mut ctx = UiContext {
+        config: &config,
+        directories: &directories,
+        db: &mut db,
+        prompt: None,
+    };
+    let mut state = AppState::new(&mut ctx)?;
+    let index = state
+        .entries
+        .iter()
+        .position(|entry| matches!(entry, Entry::Profile(profile) if profile.display == "default"))
+        .expect("default profile entry");
+    state.index = index;
+    state.list_state.select(Some(index));
+
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    state.handle_key(key(KeyCode::Enter))?;
+    assert!(state.outcome.is_none());
+    assert!(
+        state
+            .status_banner()
+            .starts_with("default needs ticket [integer]: ▏")
+    );
+
+    state.handle_key(key(KeyCode::Esc))?;
+    assert!(state.var_prompt.is_none());
+    assert!(state.outcome.is_none());
+
+    state.handle_key(key(KeyCode::Enter))?;
+    state.handle_key(key(KeyCode::Enter))?;
+    assert!(state.status_banner().starts_with("a value is required"));
+    state.handle_key(key(KeyCode::Char('x')))?;
+    state.handle_key(key(KeyCode::Enter))?;
+    assert!(
+        state
+            .status_banner()
+            .starts_with("variable 'ticket' expects an integer, got 'x'")
+    );
+    state.handle_key(key(KeyCode::Backspace))?;
+    state.handle_key(key(KeyCode::Char('7')))?;
+    state.handle_key(key(KeyCode::Enter))?;
+    assert!(state.var_prompt.is_none());
+    match &state.outcome {
+        Some(Outcome::Execute(plan)) => assert_eq!(plan.display, "run 7"),
+        other => panic!("expected execute outcome, got {other:?}"),
+    }
+    Ok(())
+}
+
 #[cfg(unix)]
 #[test]
 fn plan_for_virtual_profile_uses_virtual_request_fields() -> Result<()> {
@@ -3339,7 +3803,7 @@ fn plan_for_virtual_profile_uses_virtual_request_fields() -> Result<()> {
         provider: "codex".into(),
         pre: vec!["prepare".into()],
         post: vec!["finish".into()],
-        wrap: None,
+        wrap: Vec::new(),
         description: Some("Virtual profile".into()),
         tags: vec!["demo".into()],
         inline_pre: Vec::new(),
@@ -3409,11 +3873,18 @@ fn plan_for_profile_injects_prompt_assembler() -> Result<()> {
         "troubleshooting".into(),
         ProfileConfig {
             name: "troubleshooting".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: Some("Troubleshooting run".into()),
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: Some(wrapper.name.clone()),
+            wrap: vec![wrapper.name.clone()],
             prompt_assembler: Some("demo".into()),
             prompt_assembler_args: vec!["--limit".into(), "5".into()],
         },
@@ -3456,7 +3927,7 @@ fn plan_for_profile_injects_prompt_assembler() -> Result<()> {
         "pipeline should capture prompt: {}",
         plan.pipeline
     );
-    assert_eq!(plan.wrapper.as_deref(), Some(wrapper.name.as_str()));
+    assert_eq!(plan.wrappers, std::slice::from_ref(&wrapper.name));
     Ok(())
 }
 
@@ -3468,11 +3939,18 @@ fn plan_for_profile_errors_when_prompt_missing() -> Result<()> {
         "missing".into(),
         ProfileConfig {
             name: "missing".into(),
+            extends: None,
+            vars: IndexMap::new(),
+            flags: None,
+            env: Vec::new(),
+            env_file: Vec::new(),
+            cwd: None,
+            hooks: HooksConfig::default(),
             provider: "codex".into(),
             description: None,
             pre: Vec::new(),
             post: Vec::new(),
-            wrap: None,
+            wrap: Vec::new(),
             prompt_assembler: Some("absent".into()),
             prompt_assembler_args: Vec::new(),
         },
@@ -3551,7 +4029,7 @@ fn tui_draw_snapshots() -> Result<()> {
             provider: "codex".into(),
             pre: vec!["echo prepare".into()],
             post: vec!["echo cleanup".into()],
-            wrap: Some("shellwrap".into()),
+            wrap: vec!["shellwrap".into()],
             description: Some("Run codex with helpers".into()),
             tags: vec!["team".into(), "demo".into()],
             inline_pre: Vec::new(),
@@ -114,6 +114,43 @@ fn config_dump_outputs_merged_toml() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn config_dump_resolved_flattens_profile_extends() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    let config_dir = temp.child("config-root");
+    config_dir.create_dir_all()?;
+    std::fs::write(
+        config_dir.child("config.toml").path(),
+        r#"
+[providers.echo]
+bin = "echo"
+
+[snippets.pre]
+setup = "true"
+review = "true"
+
+[profiles.base]
+provider = "echo"
+pre = ["setup"]
+
+[profiles.review]
+extends = "base"
+"pre+" = ["review"]
+"#,
+    )?;
+
+    let mut cmd = base_command(&temp);
+    cmd.env("TX_CONFIG_DIR", config_dir.path())
+        .args(["config", "dump", "--resolved"])
+        .assert()
+        .success()
+        .stdout(contains("pre = [\n    \"setup\",\n    \"review\",\n]"))
+        .stdout(contains("\"pre+\"").not());
+
+    temp.close()?;
+    Ok(())
+}
+
 #[test]
 fn config_lint_reports_errors_for_bad_config() -> color_eyre::Result<()> {
     let temp = TempDir::new()?;
@@ -198,6 +235,185 @@ fn db_reset_reports_missing_database() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn db_maintenance_commands_require_existing_database() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    for args in [
+        vec!["db", "stats"],
+        vec!["db", "check"],
+        vec!["db", "vacuum"],
+    ] {
+        let mut cmd = base_command(&temp);
+        cmd.args(&args)
+            .assert()
+            .failure()
+            .stderr(contains("database not found"));
+    }
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn db_backup_check_stats_and_vacuum_roundtrip() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    seed_rag_session(&temp)?;
+    let backup = temp.child("backups/tx-copy.sqlite3");
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["db", "backup"])
+        .arg(backup.path())
+        .assert()
+        .success()
+        .stdout(contains("Backed up"));
+    assert!(backup.path().exists());
+    let copy = Database::open(backup.path())?;
+    assert_eq!(copy.check()?.problem_count(), 0);
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["db", "backup"])
+        .arg(backup.path())
+        .assert()
+        .failure()
+        .stderr(contains("--force"));
+
+    let live = temp.child("data-root/../data-root/tx.sqlite3");
+    for target in [
+        live.path().to_path_buf(),
+        temp.path().join("data-root/tx.sqlite3-wal"),
+    ] {
+        let mut cmd = base_command(&temp);
+        cmd.args(["db", "backup", "--force"])
+            .arg(&target)
+            .assert()
+            .failure()
+            .stderr(contains("live database"));
+    }
+    assert!(temp.child("data-root/tx.sqlite3").path().exists());
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["db", "check"])
+        .assert()
+        .success()
+        .stdout(contains("integrity: ok"))
+        .stdout(contains("fts: ok"));
+
+    let output = base_command(&temp)
+        .args(["db", "stats", "--json"])
+        .output()?;
+    assert!(output.status.success());
+    let parsed: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(parsed["providers"][0]["provider"], json!("codex"));
+    assert_eq!(parsed["providers"][0]["sessions"], json!(1));
+    let objects = parsed["objects"].as_array().expect("objects array");
+    assert!(
+        objects
+            .iter()
+            .any(|object| object["name"] == json!("message_store") && object["rows"] == json!(1))
+    );
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["db", "vacuum"])
+        .assert()
+        .success()
+        .stdout(contains("Vacuumed"));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn db_bundle_export_and_import_merge_read_only_sessions() -> color_eyre::Result<()> {
+    let remote = TempDir::new()?;
+    seed_rag_session(&remote)?;
+    let bundle = remote.child("devbox.txbundle");
+    let mut cmd = base_command(&remote);
+    cmd.args(["db", "export-bundle", "--machine", "devbox", "--output"])
+        .arg(bundle.path())
+        .assert()
+        .success()
+        .stdout(contains("Exported 1 session(s), 1 message(s)"));
+
+    let local = TempDir::new()?;
+    for expected in [
+        "1 added, 0 updated, 0 unchanged",
+        "0 added, 0 updated, 1 unchanged",
+    ] {
+        let mut cmd = base_command(&local);
+        cmd.args(["db", "import-bundle"])
+            .arg(bundle.path())
+            .assert()
+            .success()
+            .stdout(contains("Imported bundle from devbox"))
+            .stdout(contains(expected));
+    }
+
+    // Indexing must keep imported sessions even though their logs are not on disk.
+    let mut cmd = base_command(&local);
+    cmd.args(["search", "retries", "--full-text", "--format", "ids"])
+        .assert()
+        .success()
+        .stdout(contains("sess-rag@devbox"));
+
+    let mut cmd = base_command(&local);
+    cmd.args(["resume", "sess-rag@devbox"])
+        .assert()
+        .failure()
+        .stderr(contains("imported from devbox and is read-only"));
+
+    // Imported sessions stay searchable, so `related` only needs an index.
+    let mut cmd = base_command(&local);
+    cmd.env_remove("TX_RAG_EMBED_MODEL")
+        .args(["related", "sess-rag@devbox"])
+        .assert()
+        .failure()
+        .stderr(contains("no semantic index for model"));
+
+    remote.close()?;
+    local.close()?;
+    Ok(())
+}
+
+#[test]
+fn db_query_reads_views_in_each_format_and_rejects_writes() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    seed_rag_session(&temp)?;
+    let sql = "SELECT id, provider, message_count FROM v_sessions";
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["db", "query", sql])
+        .assert()
+        .success()
+        .stdout(contains("id        provider  message_count"))
+        .stdout(contains("sess-rag  codex     1"));
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["db", "query", sql, "--format", "csv"])
+        .assert()
+        .success()
+        .stdout("id,provider,message_count\nsess-rag,codex,1\n");
+
+    let output = base_command(&temp)
+        .args([
+            "db",
+            "query",
+            "--format",
+            "json",
+            "SELECT role, content FROM v_messages",
+        ])
+        .output()?;
+    assert!(output.status.success());
+    let rows: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(rows, json!([{ "role": "user", "content": "find retries" }]));
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["db", "query", "DELETE FROM sessions"])
+        .assert()
+        .failure()
+        .stderr(contains("only read-only statements"));
+
+    temp.close()?;
+    Ok(())
+}
+
 #[test]
 fn db_reset_quiet_suppresses_output() -> color_eyre::Result<()> {
     let temp = TempDir::new()?;
@@ -274,6 +490,15 @@ provider = "demo"
 pre = ["setup"]
 post = ["finish"]
 wrap = "wrap"
+
+[profiles.sample.vars.ticket]
+type = "integer"
+required = true
+description = "Tracker id"
+
+[profiles.sample.vars.effort]
+choices = ["low", "high"]
+default = "low"
 "#,
         sessions = toml_path(sessions_dir.path())
     );
@@ -286,7 +511,10 @@ wrap = "wrap"
         .success()
         .stdout(contains("demo (bin: echo"))
         .stdout(contains("wrap (shell)"))
-        .stdout(contains("sample (provider: demo"));
+        .stdout(contains("sample (provider: demo"))
+        .stdout(contains(
+            "      var ticket: integer, required — Tracker id\n      var effort: string, default: low, choices: [low, high]\n",
+        ));
 
     temp.close()?;
     Ok(())
@@ -554,6 +782,77 @@ fn search_without_term_lists_sessions() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn search_supports_ids_and_tsv_formats() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    let data_dir = temp.child("data-root");
+    data_dir.create_dir_all()?;
+    let db_path = data_dir.child("tx.sqlite3");
+    let mut db = Database::open(db_path.path())?;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let path = temp.child("recent.jsonl").path().to_path_buf();
+    let summary = SessionSummary {
+        id: "sess-recent".into(),
+        provider: "codex".into(),
+        wrapper: None,
+        model: None,
+        label: Some("Recent".into()),
+        thread_name: None,
+        path,
+        uuid: Some("uuid-recent".into()),
+        first_prompt: Some("recent\nprompt".into()),
+        actionable: true,
+        subagent: false,
+        created_at: Some(now - 10),
+        started_at: Some(now - 10),
+        last_active: Some(now - 5),
+        size: 1,
+        mtime: now - 5,
+    };
+    let mut message = MessageRecord::new(
+        summary.id.clone(),
+        0,
+        "user",
+        "recent\nprompt",
+        None,
+        Some(now - 10),
+    );
+    message.is_first = true;
+    db.upsert_session(&SessionIngest::new(summary, vec![message]))?;
+    drop(db);
+
+    let output = base_command(&temp)
+        .env("TX_SKIP_INDEX", "1")
+        .args(["search", "--format", "ids"])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "sess-recent\n");
+
+    let output = base_command(&temp)
+        .env("TX_SKIP_INDEX", "1")
+        .args([
+            "search",
+            "--format",
+            "tsv",
+            "--columns",
+            "id,provider,snippet",
+        ])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "sess-recent\tcodex\trecent prompt\n"
+    );
+
+    let output = base_command(&temp)
+        .env("TX_SKIP_INDEX", "1")
+        .args(["search", "--format", "yaml"])
+        .output()?;
+    assert!(!output.status.success());
+    temp.close()?;
+    Ok(())
+}
+
 #[test]
 fn search_without_term_zero_limit_returns_empty() -> color_eyre::Result<()> {
     let temp = TempDir::new()?;
@@ -816,6 +1115,261 @@ fn rag_search_requires_openai_api_key() -> color_eyre::Result<()> {
     Ok(())
 }
 
+fn seed_rag_session(temp: &TempDir) -> color_eyre::Result<()> {
+    let data_dir = temp.child("data-root");
+    data_dir.create_dir_all()?;
+    let mut db = Database::open(data_dir.child("tx.sqlite3").path())?;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let summary = SessionSummary {
+        id: "sess-rag".into(),
+        provider: "codex".into(),
+        wrapper: None,
+        model: None,
+        label: None,
+        thread_name: None,
+        path: temp.child("rag.jsonl").path().to_path_buf(),
+        uuid: None,
+        first_prompt: Some("find retries".into()),
+        actionable: true,
+        subagent: false,
+        created_at: Some(now),
+        started_at: Some(now),
+        last_active: Some(now),
+        size: 1,
+        mtime: now,
+    };
+    let message = MessageRecord::new(
+        summary.id.clone(),
+        0,
+        "user",
+        "find retries",
+        None,
+        Some(now),
+    );
+    db.upsert_session(&SessionIngest::new(summary, vec![message]))?;
+    Ok(())
+}
+
+#[test]
+fn rag_status_and_models_work_without_api_key() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    seed_rag_session(&temp)?;
+
+    let output = base_command(&temp)
+        .env_remove("OPENAI_API_KEY")
+        .env_remove("TX_RAG_EMBED_MODEL")
+        .env("TX_SKIP_INDEX", "1")
+        .args(["rag", "status", "--json"])
+        .output()?;
+    assert!(output.status.success());
+    let parsed: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(parsed["model"], json!("text-embedding-3-small"));
+    assert_eq!(parsed["totals"]["total"], json!(1));
+    assert_eq!(parsed["totals"]["missing"], json!(1));
+    assert_eq!(parsed["providers"][0]["provider"], json!("codex"));
+    assert_eq!(parsed["sessions"][0]["session_id"], json!("sess-rag"));
+    assert!(parsed["estimated_cost_usd"].as_f64().is_some());
+
+    let mut cmd = base_command(&temp);
+    cmd.env_remove("OPENAI_API_KEY")
+        .env("TX_SKIP_INDEX", "1")
+        .args(["rag", "models"])
+        .assert()
+        .success()
+        .stdout(contains("No embedding models indexed yet"));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn auto_index_records_failures_without_failing_the_command() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    seed_rag_session(&temp)?;
+    let config_dir = temp.child("config-root");
+    config_dir.create_dir_all()?;
+    std::fs::write(
+        config_dir.child("config.toml").path(),
+        "[providers.codex]\nbin = \"codex\"\n\n[features.rag]\nauto_index = true\n",
+    )?;
+
+    let auto_index_status = || -> color_eyre::Result<Value> {
+        let output = base_command(&temp)
+            .env_remove("OPENAI_API_KEY")
+            .env("TX_SKIP_INDEX", "1")
+            .args(["rag", "status", "--json"])
+            .output()?;
+        assert!(output.status.success());
+        let parsed: Value = serde_json::from_slice(&output.stdout)?;
+        Ok(parsed["auto_index"].clone())
+    };
+
+    // No session file was added or changed, so nothing is embedded.
+    base_command(&temp)
+        .env_remove("OPENAI_API_KEY")
+        .args(["rag", "models"])
+        .assert()
+        .success();
+    let status = auto_index_status()?;
+    assert_eq!(status["enabled"], json!(true));
+    assert_eq!(status["last_run"], Value::Null);
+
+    write_codex_session_with_uuid(&temp, "auto.jsonl", "auto-uuid")?;
+    base_command(&temp)
+        .env_remove("OPENAI_API_KEY")
+        .args(["rag", "models"])
+        .assert()
+        .success();
+    let status = auto_index_status()?;
+    let error = status["last_run"]["error"]
+        .as_str()
+        .expect("failed run is recorded");
+    assert!(error.contains("OPENAI_API_KEY is not set"));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn related_requires_semantic_index_for_session() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    seed_rag_session(&temp)?;
+
+    for session in ["sess-rag", "last"] {
+        let mut cmd = base_command(&temp);
+        cmd.env_remove("OPENAI_API_KEY")
+            .env_remove("TX_RAG_EMBED_MODEL")
+            .env("TX_SKIP_INDEX", "1")
+            .args(["related", session, "--k", "3"])
+            .assert()
+            .failure()
+            .stderr(contains(
+                "no semantic index for model text-embedding-3-small",
+            ));
+    }
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn rag_topics_requires_semantic_index() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    let mut cmd = base_command(&temp);
+    cmd.env_remove("TX_RAG_EMBED_MODEL")
+        .env("TX_SKIP_INDEX", "1")
+        .args(["rag", "topics", "--k", "3", "--json"])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "no semantic index for model text-embedding-3-small",
+        ));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn resume_with_context_requires_openai_api_key() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    seed_rag_session(&temp)?;
+
+    let mut cmd = base_command(&temp);
+    cmd.env_remove("OPENAI_API_KEY")
+        .env("TX_SKIP_INDEX", "1")
+        .args([
+            "resume",
+            "sess-rag",
+            "--with-context",
+            "retry logic",
+            "--preview-context",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("OPENAI_API_KEY"));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn indexing_waits_for_lease_held_by_another_process() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    let data_dir = temp.child("data-root");
+    data_dir.create_dir_all()?;
+    let db = Database::open(data_dir.child("tx.sqlite3").path())?;
+    assert!(db.try_acquire_lease(
+        tool_executor::db::INDEX_LEASE,
+        "tx search",
+        std::time::Duration::from_mins(1)
+    )?);
+    write_codex_session_with_uuid(&temp, "leased.jsonl", "lease-uuid")?;
+
+    let output = base_command(&temp)
+        .args(["search", "--full-text", "Ping"])
+        .output()?;
+    assert!(output.status.success());
+    let parsed: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(parsed, json!([]), "lease holder owns indexing");
+
+    let mut cmd = base_command(&temp);
+    cmd.env("TX_SKIP_INDEX", "1")
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "index held by pid {} (tx search)",
+            std::process::id()
+        )));
+
+    db.release_lease(tool_executor::db::INDEX_LEASE)?;
+    let output = base_command(&temp)
+        .args(["search", "--full-text", "Ping"])
+        .output()?;
+    assert!(output.status.success());
+    let parsed: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(parsed.as_array().map(Vec::len), Some(1));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn prune_deletes_index_rows_and_pruned_sessions_stay_pruned() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    write_codex_session_with_uuid(&temp, "a.jsonl", "uuid-a")?;
+    write_codex_session_with_uuid(&temp, "b.jsonl", "uuid-b")?;
+    let config_dir = temp.child("config-root");
+    config_dir.create_dir_all()?;
+    config_dir.child("config.toml").write_str(
+        "[providers.codex]\nbin = \"codex\"\n\n[retention]\nmax_sessions_per_provider = 1\n",
+    )?;
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["prune", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(contains("Would prune 1 session(s)"))
+        .stdout(contains("b.jsonl"))
+        .stdout(contains("max_sessions_per_provider"));
+
+    let mut cmd = base_command(&temp);
+    cmd.arg("prune")
+        .assert()
+        .success()
+        .stdout(contains("Deleted 1 session(s), 2 message(s)"));
+    assert!(temp.child("codex-home/session/b.jsonl").path().exists());
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["search", "--format", "ids"])
+        .assert()
+        .success()
+        .stdout(contains("a.jsonl"))
+        .stdout(contains("b.jsonl").not());
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["prune", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(contains("Nothing to prune."));
+    temp.close()?;
+    Ok(())
+}
+
 #[test]
 fn resume_accepts_uuid_identifier() -> color_eyre::Result<()> {
     let temp = TempDir::new()?;
@@ -874,6 +1428,241 @@ fn resume_dry_run_emit_json_outputs_payload() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[cfg(unix)]
+#[test]
+fn resume_masks_secrets_in_plans_and_resolves_them_at_launch() -> color_eyre::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new()?;
+    let uuid = "019a1e58-daad-7740-9a01-7a9527114dd9";
+    write_codex_session_with_uuid(&temp, "resume-secret.jsonl", uuid)?;
+    let config_dir = temp.child("config-root");
+    config_dir.create_dir_all()?;
+    config_dir.child("config.toml").write_str(
+        r#"
+[providers.codex]
+bin = "codex"
+env = ["API_TOKEN=Bearer ${secret:api}"]
+
+[secrets.api]
+file = "secrets/api"
+
+[wrappers.show-env]
+shell = true
+cmd = "echo token=$API_TOKEN"
+"#,
+    )?;
+    let secret_file = config_dir.child("secrets/api");
+    secret_file.write_str("hunter2\n")?;
+    std::fs::set_permissions(secret_file.path(), std::fs::Permissions::from_mode(0o644))?;
+
+    let mut cmd = base_command(&temp);
+    let output = cmd
+        .args(["resume", uuid, "--dry-run", "--emit-json"])
+        .output()?;
+    assert!(output.status.success());
+    let parsed: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(parsed["env"], json!([["API_TOKEN", "Bearer ***"]]));
+    assert_eq!(
+        parsed["env_origins"],
+        json!({ "API_TOKEN": "provider codex" })
+    );
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["resume", uuid, "--wrap", "show-env"])
+        .assert()
+        .failure()
+        .stderr(contains("failed to resolve secret 'api'"))
+        .stderr(contains("accessible by group or others"));
+
+    std::fs::set_permissions(secret_file.path(), std::fs::Permissions::from_mode(0o600))?;
+    let mut cmd = base_command(&temp);
+    cmd.args(["resume", uuid, "--wrap", "show-env"])
+        .assert()
+        .success()
+        .stdout(contains("token=Bearer hunter2"));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn resume_runs_hooks_and_keeps_provider_exit_status() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    let uuid = "019a1e58-daad-7740-9a01-7a9527114dd9";
+    write_codex_session_with_uuid(&temp, "resume-hooks.jsonl", uuid)?;
+    let log = temp.child("hooks.log");
+    temp.child("config-root/config.toml").write_str(&format!(
+        r#"
+[hooks]
+before = ['echo "$TX_HOOK $TX_SESSION_ID" >> "{log}"']
+
+[providers.codex]
+bin = "codex"
+hooks.on_failure = ['echo "$TX_HOOK $TX_EXIT_CODE" >> "{log}"']
+
+[wrappers.fail]
+shell = true
+cmd = "exit 5"
+
+[profiles.review]
+provider = "codex"
+wrap = "fail"
+hooks.after = ["exit 2", 'echo "$TX_HOOK $TX_PROVIDER" >> "{log}"']
+"#,
+        log = log.path().display()
+    ))?;
+
+    let mut cmd = base_command(&temp);
+    cmd.args(["resume", uuid, "--profile", "review"])
+        .assert()
+        .failure()
+        .stderr(contains("tx: after hook 'exit 2' exited with status"))
+        .stderr(contains("command exited with status exit status: 5"));
+    assert_eq!(
+        std::fs::read_to_string(log.path())?,
+        "before codex/resume-hooks.jsonl\non_failure 5\nafter codex\n"
+    );
+
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_plan_runs_emitted_json_and_rejects_invalid_plans() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    let uuid = "019a1e58-daad-7740-9a01-7a9527114dd9";
+    write_codex_session_with_uuid(&temp, "exec-plan.jsonl", uuid)?;
+    temp.child("config-root/config.toml").write_str(
+        r#"
+[providers.codex]
+bin = "echo"
+"#,
+    )?;
+
+    let output = base_command(&temp)
+        .args(["resume", uuid, "--dry-run", "--emit-json"])
+        .output()?;
+    assert!(output.status.success());
+    let mut document: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(document["schema_version"], json!(1));
+    assert_eq!(document["plan"]["programs"], json!(["echo"]));
+    let plan_file = temp.child("plan.json");
+    plan_file.write_binary(&output.stdout)?;
+
+    base_command(&temp)
+        .args(["exec", "--check", "--plan"])
+        .arg(plan_file.path())
+        .assert()
+        .success()
+        .stdout(contains("Plan OK: echo resume"));
+    base_command(&temp)
+        .args(["exec", "--plan"])
+        .arg(plan_file.path())
+        .assert()
+        .success()
+        .stdout(contains(format!("resume {uuid}")));
+    base_command(&temp)
+        .args(["exec", "--plan", "-"])
+        .write_stdin(output.stdout.clone())
+        .assert()
+        .success()
+        .stdout(contains(format!("resume {uuid}")));
+
+    let history = base_command(&temp).args(["history", "--json"]).output()?;
+    let runs: Value = serde_json::from_slice(&history.stdout)?;
+    assert_eq!(runs.as_array().map(Vec::len), Some(2));
+
+    document["plan"]["programs"] = json!(["tx-missing-program"]);
+    plan_file.write_str(&document.to_string())?;
+    base_command(&temp)
+        .args(["exec", "--plan"])
+        .arg(plan_file.path())
+        .assert()
+        .failure()
+        .stderr(contains(
+            "program 'tx-missing-program' required by the plan",
+        ));
+
+    document["schema_version"] = json!(99);
+    plan_file.write_str(&document.to_string())?;
+    base_command(&temp)
+        .args(["exec", "--plan"])
+        .arg(plan_file.path())
+        .assert()
+        .failure()
+        .stderr(contains("schema_version 99 is not supported"));
+
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn resume_emit_json_shows_layered_env_origins() -> color_eyre::Result<()> {
+    let temp = TempDir::new()?;
+    let uuid = "019a1e58-daad-7740-9a01-7a9527114dd9";
+    write_codex_session_with_uuid(&temp, "resume-env.jsonl", uuid)?;
+    let project = temp.child("project");
+    project
+        .child(".env")
+        .write_str("REGION=eu\nMODEL=from-dotenv\n")?;
+    temp.child("config-root/config.toml").write_str(
+        r#"
+[providers.codex]
+bin = "codex"
+flags = ["--search"]
+env = ["MODEL=from-provider"]
+
+[profiles.eu]
+provider = "codex"
+env_file = [".env"]
+env = ["TRACE=1"]
+flags = ["--quiet"]
+cwd = "work"
+"#,
+    )?;
+
+    let mut cmd = base_command(&temp);
+    let output = cmd
+        .current_dir(project.path())
+        .args([
+            "resume",
+            uuid,
+            "--profile",
+            "eu",
+            "--dry-run",
+            "--emit-json",
+        ])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let parsed: Value = serde_json::from_slice(&output.stdout)?;
+    let dotenv = format!("env_file {}", project.child(".env").path().display());
+    assert_eq!(
+        parsed["env"],
+        json!([["MODEL", "***"], ["REGION", "***"], ["TRACE", "1"]])
+    );
+    assert_eq!(
+        parsed["env_origins"],
+        json!({ "MODEL": dotenv, "REGION": dotenv, "TRACE": "profile eu" })
+    );
+    assert_eq!(
+        parsed["cwd"],
+        json!(project.child("work").path().display().to_string())
+    );
+    let command = parsed["command"].as_str().expect("command string");
+    assert!(command.contains("--quiet"));
+    assert!(!command.contains("--search"));
+
+    temp.close()?;
+    Ok(())
+}
+
 #[test]
 fn resume_last_launches_most_recent_actionable_session() -> color_eyre::Result<()> {
     let temp = TempDir::new()?;
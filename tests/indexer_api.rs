@@ -3,7 +3,8 @@ use assert_fs::prelude::*;
 use color_eyre::Result;
 use indexmap::IndexMap;
 use tool_executor::config::model::{
-    Config, Defaults, FeatureConfig, ProviderConfig, SearchMode, SnippetConfig,
+    Config, Defaults, FeatureConfig, HooksConfig, ProviderConfig, RagConfig, RetentionConfig,
+    SearchFormat, SearchMode, SnippetConfig,
 };
 use tool_executor::db::Database;
 use tool_executor::indexer::Indexer;
@@ -14,6 +15,8 @@ fn provider_with_root(root: &std::path::Path) -> ProviderConfig {
         bin: "codex".to_string(),
         flags: Vec::new(),
         env: Vec::new(),
+        env_file: Vec::new(),
+        hooks: HooksConfig::default(),
         session_roots: vec![root.to_path_buf()],
         stdin: None,
     }
@@ -27,6 +30,7 @@ fn config_from_provider(provider: ProviderConfig) -> Config {
             provider: Some("codex".into()),
             profile: None,
             search_mode: SearchMode::FirstPrompt,
+            search_format: SearchFormat::Json,
             terminal_title: None,
         },
         providers,
@@ -38,7 +42,11 @@ fn config_from_provider(provider: ProviderConfig) -> Config {
         profiles: IndexMap::new(),
         features: FeatureConfig {
             prompt_assembler: None,
+            rag: RagConfig::default(),
         },
+        retention: RetentionConfig::default(),
+        secrets: IndexMap::new(),
+        hooks: HooksConfig::default(),
     }
 }
 
@@ -3,8 +3,8 @@ use std::path::PathBuf;
 
 use indexmap::IndexMap;
 use tool_executor::config::model::{
-    Config, Defaults, FeatureConfig, ProviderConfig, SearchMode, Snippet, SnippetConfig,
-    StdinMapping, StdinMode,
+    Config, Defaults, FeatureConfig, HooksConfig, ProviderConfig, RagConfig, RetentionConfig,
+    SearchFormat, SearchMode, Snippet, SnippetConfig, StdinMapping, StdinMode,
 };
 use tool_executor::pipeline::{PipelineRequest, PromptInvocation, SessionContext, build_pipeline};
 
@@ -17,6 +17,8 @@ fn sample_config() -> Config {
             bin: "codex".to_string(),
             flags: vec!["--search".to_string()],
             env: Vec::new(),
+            env_file: Vec::new(),
+            hooks: HooksConfig::default(),
             session_roots: Vec::new(),
             stdin: Some(StdinMapping {
                 args: vec!["{prompt}".to_string()],
@@ -39,6 +41,7 @@ fn sample_config() -> Config {
             provider: Some("codex".to_string()),
             profile: None,
             search_mode: SearchMode::FirstPrompt,
+            search_format: SearchFormat::Json,
             terminal_title: Some("{{provider}} {{var:USER}}".to_string()),
         },
         providers,
@@ -50,7 +53,11 @@ fn sample_config() -> Config {
         profiles: IndexMap::new(),
         features: FeatureConfig {
             prompt_assembler: None,
+            rag: RagConfig::default(),
         },
+        retention: RetentionConfig::default(),
+        secrets: IndexMap::new(),
+        hooks: HooksConfig::default(),
     }
 }
 
@@ -67,7 +74,7 @@ fn build_pipeline_supports_capture_arg_with_prompt_assembler() -> color_eyre::Re
         additional_pre: vec!["prep".to_string()],
         additional_post: Vec::new(),
         inline_pre: vec!["printf 'inline'".to_string()],
-        wrap: None,
+        wrap: Vec::new(),
         provider_args: vec!["--mode".to_string(), "chat".to_string()],
         capture_prompt: true,
         prompt_assembler: Some(PromptInvocation {
@@ -80,6 +87,7 @@ fn build_pipeline_supports_capture_arg_with_prompt_assembler() -> color_eyre::Re
             label: Some("Session".to_string()),
             path: Some("/tmp/session.jsonl".to_string()),
             resume_token: Some("resume-token".to_string()),
+            thread_name: None,
         },
         cwd: PathBuf::from("/tmp"),
     };
@@ -103,7 +111,7 @@ fn build_pipeline_errors_for_missing_wrapper() {
         additional_pre: Vec::new(),
         additional_post: Vec::new(),
         inline_pre: Vec::new(),
-        wrap: Some("missing"),
+        wrap: vec!["missing".into()],
         provider_args: Vec::new(),
         capture_prompt: false,
         prompt_assembler: None,
@@ -126,7 +134,7 @@ fn build_pipeline_errors_for_missing_profile() {
         additional_pre: Vec::new(),
         additional_post: Vec::new(),
         inline_pre: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
         provider_args: Vec::new(),
         capture_prompt: false,
         prompt_assembler: None,
@@ -150,7 +158,7 @@ fn build_pipeline_errors_when_no_provider_selected() {
         additional_pre: Vec::new(),
         additional_post: Vec::new(),
         inline_pre: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
         provider_args: Vec::new(),
         capture_prompt: false,
         prompt_assembler: None,
@@ -177,7 +185,7 @@ fn build_pipeline_errors_for_unknown_provider_hint() {
         additional_pre: Vec::new(),
         additional_post: Vec::new(),
         inline_pre: Vec::new(),
-        wrap: None,
+        wrap: Vec::new(),
         provider_args: Vec::new(),
         capture_prompt: false,
         prompt_assembler: None,